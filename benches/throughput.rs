@@ -0,0 +1,107 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Throughput benchmarks. Run with `cargo bench`.
+
+#[macro_use]
+extern crate criterion;
+extern crate rolling_crc;
+
+use criterion::{black_box, BenchmarkId, Criterion, Throughput};
+use rolling_crc::*;
+
+/// Size of the buffer for the whole-stream benchmarks.
+const STREAM_SIZE: usize = 16 << 20;
+
+/// Window size for the rolling benchmarks.
+const WINDOW_SIZE: usize = 64;
+
+fn test_bytes(len: usize) -> Vec<u8> {
+    let mut state = 0x9e37_79b9u32;
+    (0..len)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect()
+}
+
+fn bench_crc(c: &mut Criterion) {
+    let context = RollingCRCContext::checksum_only();
+    let bytes = test_bytes(1 << 20);
+    let mut group = c.benchmark_group("crc");
+    for &len in &[16, 64, 256, 4096, 1 << 20] {
+        let _ = group.throughput(Throughput::Bytes(len as u64));
+        let _ = group.bench_with_input(
+            BenchmarkId::from_parameter(len),
+            &bytes[..len],
+            |b, bytes| b.iter(|| context.crc(black_box(bytes))),
+        );
+    }
+    group.finish();
+}
+
+fn bench_push(c: &mut Criterion) {
+    let bytes = test_bytes(STREAM_SIZE);
+    let plain = RollingCRCContext::new(WINDOW_SIZE).unwrap();
+    let big = RollingCRCContext::new(WINDOW_SIZE).unwrap().with_big_table();
+    let mut group = c.benchmark_group("push");
+    let _ = group.throughput(Throughput::Bytes(STREAM_SIZE as u64));
+    for &(name, context) in &[("plain", &plain), ("big_table", &big)] {
+        let mut primed = RollingCRC::new(context);
+        let _ = primed.prime(&bytes[..WINDOW_SIZE]);
+        let _ = group.bench_function(name, |b| {
+            b.iter(|| {
+                let mut rolling_crc = primed.clone();
+                let mut acc = 0;
+                for &byte in &bytes {
+                    acc ^= rolling_crc.push(byte).unwrap();
+                }
+                acc
+            })
+        });
+    }
+    group.finish();
+}
+
+fn bench_scan(c: &mut Criterion) {
+    let bytes = test_bytes(STREAM_SIZE);
+    let context = RollingCRCContext::new(WINDOW_SIZE).unwrap();
+    let mut group = c.benchmark_group("scan");
+    let _ = group.throughput(Throughput::Bytes(STREAM_SIZE as u64));
+    let _ = group.bench_function("iter", |b| {
+        b.iter(|| {
+            RollingCRC::new(&context)
+                .iter(bytes.iter().cloned())
+                .fold(0, |acc, (_, crc)| acc ^ crc)
+        })
+    });
+    let _ = group.bench_function("slice", |b| {
+        b.iter(|| {
+            context.rolling_crcs(&bytes)
+                .fold(0, |acc, (_, crc)| acc ^ crc)
+        })
+    });
+    group.finish();
+}
+
+fn bench_context(c: &mut Criterion) {
+    let mut group = c.benchmark_group("context");
+    for &window_size in &[16, 64, 4096, 1 << 20] {
+        let _ = group.bench_with_input(
+            BenchmarkId::from_parameter(window_size),
+            &window_size,
+            |b, &window_size| {
+                b.iter(|| RollingCRCContext::new(window_size).unwrap())
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_crc, bench_push, bench_scan, bench_context);
+criterion_main!(benches);
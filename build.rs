@@ -0,0 +1,28 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! With the `ffi` feature, compile the C smoke test of the
+//! C API for `tests/ffi.rs`.
+
+#[cfg(feature = "ffi")]
+extern crate cc;
+
+fn main() {
+    #[cfg(feature = "ffi")]
+    {
+        println!("cargo:rerun-if-changed=tests/ffi/smoke.c");
+        println!("cargo:rerun-if-changed=include/rolling_crc.h");
+        // Only the test links the smoke test, by its own
+        // `#[link]` attribute.
+        cc::Build::new()
+            .file("tests/ffi/smoke.c")
+            .include("include")
+            .cargo_metadata(false)
+            .compile("rcrc_smoke");
+        let out_dir = std::env::var("OUT_DIR").unwrap();
+        println!("cargo:rustc-link-search=native={}", out_dir);
+    }
+    println!("cargo:rerun-if-changed=build.rs");
+}
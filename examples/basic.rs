@@ -9,7 +9,7 @@ extern crate rolling_crc;
 use rolling_crc::*;
 
 fn main() {
-    let context = RollingCRCContext::new(3);
+    let context = RollingCRCContext::new(3).unwrap();
     let mut roll_crc = RollingCRC::new(&context);
     let bytes = "hello world".as_bytes();
     for i in 0..bytes.len() {
@@ -0,0 +1,17 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Compare the byte-iterator path against the slice fast
+//! path on arbitrary data and window sizes.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rolling_crc::*;
+
+fuzz_target!(|input: (u8, Vec<u8>)| {
+    let (window_size, bytes) = input;
+    check_iter_matches_slice(usize::from(window_size), &bytes);
+});
@@ -0,0 +1,17 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Run arbitrary scripts of push, clone, reset, prime and
+//! iterator operations, checking the rolling CRC against
+//! a direct CRC of the tracked window.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use rolling_crc::*;
+
+fuzz_target!(|script: RollingCRCScript| {
+    check_rolling_crc_script(&script);
+});
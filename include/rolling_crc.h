@@ -0,0 +1,78 @@
+#ifndef ROLLING_CRC_H
+#define ROLLING_CRC_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+// Success.
+#define RCRC_OK 0
+
+// A required pointer argument was null.
+#define RCRC_NULL_POINTER -1
+
+// The call panicked.
+#define RCRC_PANIC -2
+
+// A rolling CRC context, opaque to C.
+typedef struct RcrcContext RcrcContext;
+
+// A rolling CRC, opaque to C.
+typedef struct RcrcRoller RcrcRoller;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+// Make a new context for the given window size, or return
+// null on failure. Free it with `rcrc_context_free()`.
+RcrcContext *rcrc_context_new(size_t window_size);
+
+// Free a context. Null is ignored.
+//
+// # Safety
+//
+// `ctx` must be null or come from `rcrc_context_new()`,
+// and must not be used again, nor any roller made from it.
+void rcrc_context_free(RcrcContext *ctx);
+
+// Compute the CRC of the `len` bytes at `data` into
+// `*crc_out`. `data` may be null if `len` is 0.
+//
+// # Safety
+//
+// `ctx` must be a live context, `data` must point to `len`
+// readable bytes and `crc_out` must be writable.
+int rcrc_crc(const RcrcContext *ctx, const uint8_t *data, size_t len, uint32_t *crc_out);
+
+// Start a new rolling CRC in the given context, or return
+// null on failure. Free it with `rcrc_roller_free()`.
+//
+// # Safety
+//
+// `ctx` must be a live context, and must outlive the
+// roller.
+RcrcRoller *rcrc_roller_new(const RcrcContext *ctx);
+
+// Roll a byte through a rolling CRC. Returns 1 and sets
+// `*crc_out` to the CRC of the window it completes, if
+// any, and otherwise returns 0, or an error code.
+//
+// # Safety
+//
+// `roller` must be a live roller and `crc_out` must be
+// writable.
+int rcrc_push(RcrcRoller *roller, uint8_t byte, uint32_t *crc_out);
+
+// Free a rolling CRC. Null is ignored.
+//
+// # Safety
+//
+// `roller` must be null or come from `rcrc_roller_new()`,
+// and must not be used again.
+void rcrc_roller_free(RcrcRoller *roller);
+
+#ifdef __cplusplus
+} // extern "C"
+#endif // __cplusplus
+
+#endif // ROLLING_CRC_H
@@ -0,0 +1,37 @@
+# Copyright © 2018 Bart Massey
+# [This program is licensed under the "MIT License"]
+# Please see the file LICENSE in the source
+# distribution of this software for license terms.
+
+"""Tests of the Python bindings, against the vectors of the
+Rust tests. Run with `pytest` after `maturin develop`."""
+
+import zlib
+
+from rolling_crc import RollingCrcContext
+
+
+def test_crc():
+    ctx = RollingCrcContext(5)
+    assert ctx.window_size == 5
+    assert ctx.crc(b"123456789") == 0xCBF43926
+    assert ctx.crc(b"hello world") == 0x0D4A1185
+    assert ctx.crc(b"") == 0
+
+
+def test_scan():
+    data = bytes((3 + i * 29 + i // 7) & 0xFF for i in range(300))
+    for window_size in (1, 5, 16, 64):
+        ctx = RollingCrcContext(window_size)
+        expected = [
+            (i, zlib.crc32(data[i:i + window_size]))
+            for i in range(len(data) - window_size + 1)
+        ]
+        assert ctx.scan(data) == expected
+
+
+def test_find():
+    ctx = RollingCrcContext(5)
+    assert ctx.find(b"hello world", b"world") == [6]
+    assert ctx.find(b"aaab", b"aa") == [0, 1]
+    assert ctx.find(b"hello there", b"world") == []
@@ -0,0 +1,751 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Analysis of buffer contents using window CRCs.
+
+use super::RollingCRCContext;
+#[cfg(test)]
+use super::test_bytes;
+
+use std::collections::{BTreeSet, HashMap, HashSet, VecDeque};
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Find a longest run of bytes shared by the two buffers,
+/// returning its offset in `a`, its offset in `b` and its
+/// length. Returns `None` if the buffers share no bytes at
+/// all.
+///
+/// This does a binary search over the run length: for each
+/// candidate length the window CRCs of `a` are indexed and
+/// those of `b` looked up. Candidates are verified
+/// byte-for-byte, so CRC collisions cannot inflate the
+/// answer. Of the longest runs, the one earliest in `b`
+/// (and then in `a`) is returned.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let a = b"the quick brown fox";
+/// let b = b"a quick brown dog";
+/// assert_eq!(longest_common_window(a, b), Some((3, 1, 13)));
+/// ```
+pub fn longest_common_window(a: &[u8], b: &[u8])
+                             -> Option<(usize, usize, usize)>
+{
+    let mut best = None;
+    // Invariant: a common run of length `lo` exists (or
+    // `lo` is 0), and none of length `hi + 1` does.
+    let mut lo = 0;
+    let mut hi = a.len().min(b.len());
+    while lo < hi {
+        let mid = lo + (hi - lo).div_ceil(2);
+        match common_window(a, b, mid) {
+            Some((offset_a, offset_b)) => {
+                best = Some((offset_a, offset_b, mid));
+                lo = mid;
+            },
+            None => hi = mid - 1,
+        }
+    }
+    best
+}
+
+/// Find a run of `len` bytes shared by the two buffers,
+/// returning its offsets in `a` and `b`.
+fn common_window(a: &[u8], b: &[u8], len: usize) -> Option<(usize, usize)> {
+    let context = RollingCRCContext::build(len);
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (offset, crc) in context.rolling_crcs(a) {
+        index.entry(crc).or_default().push(offset);
+    }
+    for (offset_b, crc) in context.rolling_crcs(b) {
+        let window = &b[offset_b..offset_b + len];
+        if let Some(offsets) = index.get(&crc) {
+            let found = offsets.iter()
+                .find(|&&offset_a| &a[offset_a..offset_a + len] == window);
+            if let Some(&offset_a) = found {
+                return Some((offset_a, offset_b));
+            }
+        }
+    }
+    None
+}
+
+/// Similarity of the two buffers, measured as the Jaccard
+/// index of their sets of window CRCs in the given context:
+/// the number of distinct CRCs the buffers share divided by
+/// the number of distinct CRCs in either. Identical buffers
+/// have similarity 1.0, and buffers with no windows in
+/// common 0.0. If either buffer is shorter than the window
+/// the similarity is 0.0.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(4).unwrap();
+/// assert_eq!(similarity(b"abcdefgh", b"abcdefgh", &context), 1.0);
+/// assert_eq!(similarity(b"abcdefgh", b"efghijkl", &context), 1.0 / 9.0);
+/// ```
+pub fn similarity(a: &[u8], b: &[u8], context: &RollingCRCContext) -> f64 {
+    let a: HashSet<u32> = context.rolling_crcs(a).map(|(_, crc)| crc).collect();
+    let b: HashSet<u32> = context.rolling_crcs(b).map(|(_, crc)| crc).collect();
+    jaccard(&a, &b)
+}
+
+/// Similarity of the two buffers as with `similarity()`,
+/// but using only the CRCs selected by
+/// `RollingCRCContext::winnow()` with the given guarantee.
+/// This bounds the memory needed for large buffers at some
+/// cost in accuracy.
+pub fn winnowed_similarity(a: &[u8],
+                           b: &[u8],
+                           context: &RollingCRCContext,
+                           guarantee: usize) -> f64
+{
+    let a: HashSet<u32> = context.winnow(a, guarantee)
+        .into_iter()
+        .map(|(_, crc)| crc)
+        .collect();
+    let b: HashSet<u32> = context.winnow(b, guarantee)
+        .into_iter()
+        .map(|(_, crc)| crc)
+        .collect();
+    jaccard(&a, &b)
+}
+
+/// Jaccard index of two sets, taken to be 0.0 if both are
+/// empty.
+fn jaccard(a: &HashSet<u32>, b: &HashSet<u32>) -> f64 {
+    let shared = a.intersection(b).count();
+    let total = a.len() + b.len() - shared;
+    if total == 0 {
+        return 0.0;
+    }
+    shared as f64 / total as f64
+}
+
+/// A set of offsets in a buffer at which identical windows
+/// occur. Returned by `RollingCRCContext::duplicates()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DuplicateGroup {
+    /// CRC of the shared window.
+    pub crc: u32,
+    /// Offsets of the shared window, in increasing order,
+    /// with consecutive offsets coalesced into runs. A run
+    /// of more than one offset occurs only within a long
+    /// repeat of a single byte value.
+    pub runs: Vec<Range<usize>>,
+}
+
+impl DuplicateGroup {
+    /// Number of offsets in this group.
+    pub fn count(&self) -> usize {
+        self.runs.iter().map(|run| run.len()).sum()
+    }
+
+    /// The offsets in this group, in increasing order.
+    pub fn offsets<'a>(&'a self) -> impl Iterator<Item=usize> + 'a {
+        self.runs.iter().flat_map(|run| run.clone())
+    }
+}
+
+impl<'a> RollingCRCContext<'a> {
+
+    /// Find every window of the buffer whose exact bytes
+    /// occur at more than one offset, grouped by window
+    /// contents and ordered by first offset. CRCs are used
+    /// to find candidates, which are then compared
+    /// byte-for-byte.
+    ///
+    /// Runs of a repeated byte produce overlapping repeats
+    /// at every offset; these are coalesced into ranges, so
+    /// that memory use stays proportional to the number of
+    /// distinct repeats.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new(3).unwrap();
+    /// let groups = context.duplicates(b"abcxabcyzzzzz");
+    /// assert_eq!(groups.len(), 2);
+    /// assert_eq!(groups[0].runs, vec![0..1, 4..5]);
+    /// assert_eq!(groups[1].runs, vec![8..11]);
+    /// ```
+    pub fn duplicates(&self, buf: &[u8]) -> Vec<DuplicateGroup> {
+        let window_size = self.window_size;
+        let window = |offset: usize| &buf[offset..offset + window_size];
+
+        // Collect runs of consecutive identical windows by
+        // CRC.
+        let mut buckets: HashMap<u32, Vec<Range<usize>>> = HashMap::new();
+        for (offset, crc) in self.rolling_crcs(buf) {
+            let runs = buckets.entry(crc).or_default();
+            if let Some(run) = runs.last_mut() {
+                // A run of two or more identical windows
+                // is a run of one byte value, so only the
+                // newest byte need be checked.
+                let extends = run.end == offset && if run.len() == 1 {
+                    window(run.start) == window(offset)
+                } else {
+                    buf[offset + window_size - 1]
+                        == buf[offset + window_size - 2]
+                };
+                if extends {
+                    run.end += 1;
+                    continue;
+                }
+            }
+            runs.push(offset..offset + 1);
+        }
+
+        // Split each bucket into groups of identical
+        // windows.
+        let mut groups = Vec::new();
+        for (crc, runs) in buckets {
+            let mut bucket_groups: Vec<DuplicateGroup> = Vec::new();
+            for run in runs {
+                let found = bucket_groups.iter_mut().find(|group| {
+                    window(group.runs[0].start) == window(run.start)
+                });
+                match found {
+                    Some(group) => group.runs.push(run),
+                    None => bucket_groups.push(DuplicateGroup {
+                        crc,
+                        runs: vec![run],
+                    }),
+                }
+            }
+            groups.extend(bucket_groups.into_iter()
+                          .filter(|group| group.count() > 1));
+        }
+        groups.sort_by_key(|group| group.runs[0].start);
+        groups
+    }
+
+    /// Select a winnowed subset of the window CRCs of the
+    /// buffer, returning their offsets and CRCs in order.
+    /// From each span of `guarantee` consecutive windows the
+    /// minimum CRC is selected (the rightmost on ties), and
+    /// each selected window is reported once.
+    ///
+    /// This guarantees that every run of `window_size +
+    /// guarantee - 1` bytes of the buffer contains a
+    /// selected window. A buffer with fewer than
+    /// `guarantee` windows reports just its minimum. See
+    /// Schleimer, Wilkerson and Aiken, "Winnowing: Local
+    /// Algorithms for Document Fingerprinting" (2003).
+    ///
+    /// # Panics
+    ///
+    /// Panics if `guarantee` is 0.
+    pub fn winnow(&self, buf: &[u8], guarantee: usize) -> Vec<(usize, u32)> {
+        assert!(guarantee > 0, "winnow: guarantee must be positive");
+        let mut selected = Vec::new();
+        // Candidate minima for the current span, with
+        // increasing offsets and strictly increasing CRCs.
+        let mut candidates: VecDeque<(usize, u32)> = VecDeque::new();
+        let mut nwindows = 0;
+        for (offset, crc) in self.rolling_crcs(buf) {
+            while candidates.back().is_some_and(|&(_, c)| c >= crc) {
+                let _ = candidates.pop_back();
+            }
+            candidates.push_back((offset, crc));
+            if candidates[0].0 + guarantee <= offset {
+                let _ = candidates.pop_front();
+            }
+            nwindows += 1;
+            if nwindows >= guarantee {
+                let min = candidates[0];
+                if selected.last() != Some(&min) {
+                    selected.push(min);
+                }
+            }
+        }
+        if nwindows > 0 && nwindows < guarantee {
+            selected.push(candidates[0]);
+        }
+        selected
+    }
+
+    /// Count the window CRCs of the buffer by the value of
+    /// their low `bits` bits, as a check on how uniformly
+    /// they are distributed before choosing a chunking mask.
+    /// The returned vector has `1 << bits` entries.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `bits` is greater than 24.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new(4).unwrap();
+    /// let histogram = context.mask_histogram(b"some sample text", 2);
+    /// assert_eq!(histogram.len(), 4);
+    /// assert_eq!(histogram.iter().sum::<u64>(), 13);
+    /// ```
+    pub fn mask_histogram(&self, buf: &[u8], bits: u8) -> Vec<u64> {
+        assert!(bits <= 24, "mask_histogram: at most 24 bits");
+        let mask = ((1u64 << bits) - 1) as u32;
+        let mut histogram = vec![0; 1 << bits];
+        for (_, crc) in self.rolling_crcs(buf) {
+            histogram[(crc & mask) as usize] += 1;
+        }
+        histogram
+    }
+}
+
+/// Summary of a histogram from
+/// `RollingCRCContext::mask_histogram()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct MaskStats {
+    /// Mean distance between windows whose low bits are all
+    /// ones, that is, the expected chunk size when cutting
+    /// at `crc & mask == mask`. Infinite if there are no
+    /// such windows.
+    pub expected_chunk_size: f64,
+    /// Pearson's chi-squared statistic for the histogram
+    /// against a uniform distribution. This has
+    /// `histogram.len() - 1` degrees of freedom, so values
+    /// far above that indicate bias.
+    pub chi_squared: f64,
+}
+
+/// Compute the expected chunk size and deviation from
+/// uniform of a mask histogram.
+///
+/// # Panics
+///
+/// Panics if the histogram is empty.
+pub fn mask_stats(histogram: &[u64]) -> MaskStats {
+    let total: u64 = histogram.iter().sum();
+    let boundaries = *histogram.last().expect("mask_stats: empty histogram");
+    let expected_chunk_size = if boundaries == 0 {
+        f64::INFINITY
+    } else {
+        total as f64 / boundaries as f64
+    };
+    let expected = total as f64 / histogram.len() as f64;
+    let chi_squared = if total == 0 {
+        0.0
+    } else {
+        histogram.iter()
+            .map(|&observed| {
+                let d = observed as f64 - expected;
+                d * d / expected
+            })
+            .sum()
+    };
+    MaskStats { expected_chunk_size, chi_squared }
+}
+
+/// The (approximately) `n` most frequent window CRCs of the
+/// buffer in the given context, with their estimated
+/// counts, most frequent first (ties in order of CRC).
+///
+/// This uses the Space-Saving algorithm of Metwally,
+/// Agrawal and El Abbadi (2005), so memory use is O(`n`)
+/// regardless of the buffer size. Estimated counts are
+/// never less than the true counts, and exceed them by at
+/// most the number of windows divided by `n`; any CRC
+/// occurring more often than that is guaranteed to be
+/// reported.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(2).unwrap();
+/// let top = top_windows(b"abababxab", &context, 3);
+/// assert_eq!(top[0], (context.crc(b"ab"), 4));
+/// ```
+pub fn top_windows(buf: &[u8], context: &RollingCRCContext, n: usize)
+                   -> Vec<(u32, usize)>
+{
+    top_windows_with_offsets(buf, context, n)
+        .into_iter()
+        .map(|(crc, count, _)| (crc, count))
+        .collect()
+}
+
+/// The most frequent window CRCs of the buffer as with
+/// `top_windows()`, with the offset of one window having
+/// each CRC, for later verification.
+pub fn top_windows_with_offsets(buf: &[u8],
+                                context: &RollingCRCContext,
+                                n: usize)
+                                -> Vec<(u32, usize, usize)>
+{
+    if n == 0 {
+        return Vec::new();
+    }
+    // Counts and example offsets by CRC, and the CRCs
+    // ordered by count.
+    let mut counters: HashMap<u32, (usize, usize)> = HashMap::with_capacity(n);
+    let mut by_count: BTreeSet<(usize, u32)> = BTreeSet::new();
+    for (offset, crc) in context.rolling_crcs(buf) {
+        if let Some(counter) = counters.get_mut(&crc) {
+            let _ = by_count.remove(&(counter.0, crc));
+            counter.0 += 1;
+            let _ = by_count.insert((counter.0, crc));
+            continue;
+        }
+        // Evict the least frequent CRC if full, crediting
+        // its count to the newcomer.
+        let mut count = 1;
+        if counters.len() == n {
+            let (min_count, min_crc) = *by_count.iter().next().unwrap();
+            let _ = by_count.remove(&(min_count, min_crc));
+            let _ = counters.remove(&min_crc);
+            count += min_count;
+        }
+        let _ = counters.insert(crc, (count, offset));
+        let _ = by_count.insert((count, crc));
+    }
+    let mut top: Vec<(u32, usize, usize)> = counters.into_iter()
+        .map(|(crc, (count, offset))| (crc, count, offset))
+        .collect();
+    top.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    top
+}
+
+/// A run of bytes shared by two buffers, as found by
+/// `anchors()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(::defmt::Format))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct AnchorMatch {
+    /// Offset of the run in the first buffer.
+    pub a_off: usize,
+    /// Offset of the run in the second buffer.
+    pub b_off: usize,
+    /// Length of the run.
+    pub len: usize,
+}
+
+/// Find anchors between two buffers: maximal runs of bytes
+/// of `b` that also occur in `a`, such as the unchanged
+/// parts of two versions of a binary image. The runs
+/// returned do not overlap in `b` and are in order of
+/// `b_off`; each is at least `window` bytes long.
+///
+/// This is not a full diff, just the anchor layer of one.
+/// A winnowed selection of the windows of `a` is indexed,
+/// and `b` is scanned left to right for them. Each window
+/// found is verified byte-for-byte and extended forward and
+/// backward to a maximal common run, taking the longest if
+/// the window occurs more than once in `a`; backward
+/// extension stops at the end of the previous anchor, and
+/// scanning resumes at the end of each anchor. Every
+/// common run of at least `2 * window - 1` bytes is
+/// guaranteed to be found, at least in part.
+///
+/// # Panics
+///
+/// Panics if `window` is 0.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let a = b"header: the unchanged part. old middle. the tail part.";
+/// let b = b"header: the unchanged part. new middle! the tail part.";
+/// let found = anchors(a, b, 4);
+/// assert_eq!(found[0], AnchorMatch { a_off: 0, b_off: 0, len: 28 });
+/// assert_eq!(found.last().unwrap().len, 15);
+/// ```
+pub fn anchors(a: &[u8], b: &[u8], window: usize) -> Vec<AnchorMatch> {
+    assert!(window > 0, "anchors: window must be positive");
+    let context = RollingCRCContext::build(window);
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for (offset, crc) in context.winnow(a, window) {
+        index.entry(crc).or_default().push(offset);
+    }
+    let mut found: Vec<AnchorMatch> = Vec::new();
+    // End in `b` of the last anchor.
+    let mut covered = 0;
+    'scan: loop {
+        for (offset, crc) in context.rolling_crcs(&b[covered..]) {
+            let b_off = covered + offset;
+            let candidates = match index.get(&crc) {
+                Some(candidates) => candidates,
+                None => continue,
+            };
+            let mut best: Option<AnchorMatch> = None;
+            for &a_off in candidates {
+                if a[a_off..a_off + window] != b[b_off..b_off + window] {
+                    continue;
+                }
+                let forward = a[a_off + window..].iter()
+                    .zip(&b[b_off + window..])
+                    .take_while(|&(x, y)| x == y)
+                    .count();
+                let backward = a[..a_off].iter().rev()
+                    .zip(b[covered..b_off].iter().rev())
+                    .take_while(|&(x, y)| x == y)
+                    .count();
+                let len = backward + window + forward;
+                if best.is_none_or(|m| len > m.len) {
+                    best = Some(AnchorMatch {
+                        a_off: a_off - backward,
+                        b_off: b_off - backward,
+                        len,
+                    });
+                }
+            }
+            if let Some(m) = best {
+                covered = m.b_off + m.len;
+                found.push(m);
+                continue 'scan;
+            }
+        }
+        return found;
+    }
+}
+
+#[test]
+fn test_longest_common_window() {
+    // Disjoint buffers.
+    let a: Vec<u8> = (0..500usize).map(|i| (i % 128) as u8).collect();
+    let b: Vec<u8> = (0..500usize).map(|i| (128 + i % 128) as u8).collect();
+    assert_eq!(longest_common_window(&a, &b), None);
+    assert_eq!(longest_common_window(&a, b""), None);
+
+    // Identical buffers.
+    assert_eq!(longest_common_window(&a, &a), Some((0, 0, a.len())));
+
+    // A planted 1 KiB shared block.
+    let mut a = test_bytes(1, 5000);
+    let mut b = test_bytes(2, 3000);
+    let block = test_bytes(3, 1024);
+    a[3000..3000 + 1024].copy_from_slice(&block);
+    b[700..700 + 1024].copy_from_slice(&block);
+    let (offset_a, offset_b, len) = longest_common_window(&a, &b).unwrap();
+    // The random neighbors might extend the block a byte
+    // or two by chance.
+    assert!((1024..1030).contains(&len));
+    assert!(offset_a <= 3000 && offset_a + len >= 3000 + 1024);
+    assert_eq!(offset_a as isize - 3000, offset_b as isize - 700);
+    assert_eq!(&a[offset_a..offset_a + len], &b[offset_b..offset_b + len]);
+}
+
+#[test]
+fn test_duplicates() {
+    let window_size = 16;
+    let context = RollingCRCContext::new(window_size).unwrap();
+    let mut buf = test_bytes(4, 10_000);
+    let block = test_bytes(5, 40);
+    for &offset in &[100, 2000, 7000] {
+        buf[offset..offset + block.len()].copy_from_slice(&block);
+    }
+    for b in &mut buf[5000..6000] {
+        *b = 0;
+    }
+    let groups = context.duplicates(&buf);
+
+    // Each window of the planted block occurs three
+    // times, and the zero run is one group.
+    let nblock = block.len() - window_size + 1;
+    assert_eq!(groups.len(), nblock + 1);
+    for (i, group) in groups[..nblock].iter().enumerate() {
+        let offsets: Vec<usize> = group.offsets().collect();
+        assert_eq!(offsets, vec![100 + i, 2000 + i, 7000 + i]);
+        assert_eq!(group.crc, context.crc(&block[i..i + window_size]));
+    }
+    let zeros = &groups[nblock];
+    assert_eq!(zeros.runs, vec![5000..6000 - window_size + 1]);
+    assert_eq!(zeros.count(), 1000 - window_size + 1);
+
+    assert!(context.duplicates(&test_bytes(6, 1000)).is_empty());
+    assert!(RollingCRCContext::checksum_only().duplicates(&buf).is_empty());
+}
+
+#[test]
+fn test_winnow() {
+    for seed in 0..50 {
+        let window_size = 1 + seed as usize % 7;
+        let guarantee = 1 + seed as usize % 11;
+        let context = RollingCRCContext::new(window_size).unwrap();
+        // Small alphabets make for plenty of tied CRCs.
+        let buf: Vec<u8> = test_bytes(seed + 1, 300 + 17 * seed as usize)
+            .into_iter()
+            .map(|b| b % (1 + seed as u8 % 4))
+            .collect();
+        let fingerprints = context.winnow(&buf, guarantee);
+
+        // Compare against the brute-force definition.
+        let crcs: Vec<(usize, u32)> = context.rolling_crcs(&buf).collect();
+        let mut expected: Vec<(usize, u32)> = Vec::new();
+        for span in crcs.windows(guarantee) {
+            let mut min = span[0];
+            for &w in span {
+                if w.1 <= min.1 {
+                    min = w;
+                }
+            }
+            if expected.last() != Some(&min) {
+                expected.push(min);
+            }
+        }
+        assert_eq!(fingerprints, expected);
+
+        // Check the guarantee.
+        let threshold = window_size + guarantee - 1;
+        for start in 0..=buf.len() - threshold {
+            assert!(fingerprints.iter().any(|&(offset, _)| {
+                offset >= start && offset + window_size <= start + threshold
+            }));
+        }
+    }
+
+    let context = RollingCRCContext::new(4).unwrap();
+    let crcs: Vec<(usize, u32)> = context.rolling_crcs(b"abcdef").collect();
+    let min = *crcs.iter().min_by_key(|w| w.1).unwrap();
+    assert_eq!(context.winnow(b"abcdef", 10), vec![min]);
+    assert_eq!(context.winnow(b"abc", 10), vec![]);
+}
+
+#[test]
+fn test_similarity() {
+    let window_size = 8;
+    let context = RollingCRCContext::new(window_size).unwrap();
+    let x = test_bytes(7, 1000);
+    let y = test_bytes(8, 3000);
+    let z = test_bytes(9, 2000);
+    let a = [&x[..], &y[..]].concat();
+    let b = [&y[..], &z[..]].concat();
+
+    // The windows within `y` are shared; all others are
+    // (almost surely) distinct.
+    let shared = y.len() - window_size + 1;
+    let na = a.len() - window_size + 1;
+    let nb = b.len() - window_size + 1;
+    let expected = shared as f64 / (na + nb - shared) as f64;
+    assert_eq!(similarity(&a, &b, &context), expected);
+    assert_eq!(similarity(&b, &a, &context), expected);
+
+    // Winnowing should give a similar answer.
+    let winnowed = winnowed_similarity(&a, &b, &context, 4);
+    assert!((winnowed - expected).abs() < 0.1);
+
+    assert_eq!(similarity(&a, &a, &context), 1.0);
+    assert_eq!(winnowed_similarity(&a, &a, &context, 4), 1.0);
+    assert_eq!(similarity(&x, &z, &context), 0.0);
+    assert_eq!(similarity(&a, b"short", &context), 0.0);
+    assert_eq!(similarity(b"short", b"short", &context), 0.0);
+}
+
+#[test]
+fn test_top_windows() {
+    let window_size = 8;
+    let context = RollingCRCContext::new(window_size).unwrap();
+    let blocks = [b"AAAAaaaa", b"BBBBbbbb", b"CCCCcccc"];
+    let counts = [50, 30, 30];
+    let mut remaining = counts;
+    let mut buf = Vec::new();
+    let mut seed = 10;
+    while remaining.iter().any(|&r| r > 0) {
+        for (block, r) in blocks.iter().zip(remaining.iter_mut()) {
+            if *r > 0 {
+                buf.extend_from_slice(&block[..]);
+                buf.extend(test_bytes(seed, 4));
+                seed += 1;
+                *r -= 1;
+            }
+        }
+    }
+    let top = top_windows_with_offsets(&buf, &context, 64);
+    assert_eq!(top.len(), 64);
+    let crcs: Vec<u32> = blocks.iter().map(|b| context.crc(&b[..])).collect();
+    let (b, c) = if crcs[1] < crcs[2] { (1, 2) } else { (2, 1) };
+    let expected = [(crcs[0], 50), (crcs[b], 30), (crcs[c], 30)];
+    for (&(crc, count, offset), &(ecrc, ecount)) in top.iter().zip(&expected) {
+        assert_eq!((crc, count), (ecrc, ecount));
+        assert_eq!(context.crc(&buf[offset..offset + window_size]), crc);
+    }
+    assert!(top[3].1 < 30);
+    assert_eq!(top_windows(&buf, &context, 64)[..3],
+               [expected[0], expected[1], expected[2]]);
+    assert!(top_windows(&buf, &context, 0).is_empty());
+}
+
+#[test]
+fn test_mask_histogram() {
+    let context = RollingCRCContext::new(16).unwrap();
+    let bits = 6;
+    let buf = test_bytes(11, 200_000);
+    let histogram = context.mask_histogram(&buf, bits);
+    assert_eq!(histogram.len(), 64);
+    let nwindows = (buf.len() - 16 + 1) as u64;
+    assert_eq!(histogram.iter().sum::<u64>(), nwindows);
+    let stats = mask_stats(&histogram);
+    // With 63 degrees of freedom, chi-squared exceeds 120
+    // with probability well under 0.01%.
+    assert!(stats.chi_squared < 120.0, "{:?}", stats);
+    assert!((stats.expected_chunk_size - 64.0).abs() < 8.0, "{:?}", stats);
+
+    // Every window of a zero buffer has the same CRC.
+    let zeros = vec![0; 10_000];
+    let histogram = context.mask_histogram(&zeros, bits);
+    assert_eq!(histogram.iter().filter(|&&n| n > 0).count(), 1);
+    let stats = mask_stats(&histogram);
+    let nwindows = (zeros.len() - 16 + 1) as f64;
+    assert!((stats.chi_squared - 63.0 * nwindows).abs() < 1e-6 * nwindows);
+
+    let empty = context.mask_histogram(b"", bits);
+    let stats = mask_stats(&empty);
+    assert_eq!(stats.chi_squared, 0.0);
+    assert!(stats.expected_chunk_size.is_infinite());
+}
+
+#[test]
+fn test_anchors() {
+    let a = test_bytes(12, 20_000);
+    // Remove a[5000..5500] and insert 300 bytes before
+    // a[12000]. The inserted bytes are chosen to differ
+    // from their neighbors-to-be.
+    let mut b = a[..5000].to_vec();
+    b.extend_from_slice(&a[5500..12000]);
+    b.extend(a[11700..12000].iter().map(|&x| !x));
+    b.extend_from_slice(&a[12000..]);
+    assert_ne!(a[5000], a[5500]);
+    assert_ne!(a[4999], a[5499]);
+
+    for &window in &[8, 32, 100] {
+        let found = anchors(&a, &b, window);
+        assert_eq!(found, vec![
+            AnchorMatch { a_off: 0, b_off: 0, len: 5000 },
+            AnchorMatch { a_off: 5500, b_off: 5000, len: 6500 },
+            AnchorMatch { a_off: 12000, b_off: 11800, len: 8000 },
+        ]);
+    }
+
+    // A block of `a` repeated in `b` is found twice, and
+    // repeats within `a` do not cause overlaps.
+    let block = &a[1000..3000];
+    let mut b = block.to_vec();
+    b.extend_from_slice(block);
+    let found = anchors(&a, &b, 16);
+    assert_eq!(found, vec![
+        AnchorMatch { a_off: 1000, b_off: 0, len: 2000 },
+        AnchorMatch { a_off: 1000, b_off: 2000, len: 2000 },
+    ]);
+    let zeros = vec![0; 1000];
+    let found = anchors(&zeros, &zeros[..700], 10);
+    assert_eq!(found.len(), 1);
+    assert_eq!((found[0].b_off, found[0].len), (0, 700));
+
+    assert!(anchors(&a, &test_bytes(13, 5000), 8).is_empty());
+    assert!(anchors(b"", &a, 8).is_empty());
+}
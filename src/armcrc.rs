@@ -0,0 +1,41 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Bulk CRC-32 using the AArch64 CRC32 instructions, which
+//! compute the standard (IEEE) CRC directly. Like the
+//! table-driven code they work on "open" CRCs.
+
+use std::arch::aarch64::{__crc32b, __crc32d};
+
+lazy_static! {
+    static ref AVAILABLE: bool = is_aarch64_feature_detected!("crc");
+}
+
+/// Can the kernel run on this CPU?
+#[inline(always)]
+pub(crate) fn available() -> bool {
+    *AVAILABLE
+}
+
+/// Given the current "open" CRC, return the open CRC
+/// including the bytes of the buffer.
+///
+/// # Safety
+///
+/// The CPU must support the CRC32 instructions: see
+/// `available()`.
+#[target_feature(enable = "crc")]
+pub(crate) unsafe fn update(mut crc: u32, buf: &[u8]) -> u32 {
+    let mut words = buf.chunks_exact(8);
+    for word in &mut words {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(word);
+        crc = __crc32d(crc, u64::from_le_bytes(bytes));
+    }
+    for &b in words.remainder() {
+        crc = __crc32b(crc, b);
+    }
+    crc
+}
@@ -0,0 +1,187 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! `rcrc`: whole-file CRCs, rolling CRCs, verified search
+//! and content-defined chunking from the command line.
+//! CRCs are printed as eight hex digits, offsets in
+//! decimal. A file named `-` is standard input.
+
+extern crate clap;
+extern crate rolling_crc;
+
+use clap::{Parser, Subcommand};
+use rolling_crc::*;
+
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+
+#[derive(Parser)]
+#[command(name = "rcrc", version, about = "Rolling CRC-32 tool")]
+struct Args {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Print the CRC of each whole file.
+    Crc {
+        /// Files to CRC, or - for standard input.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+    /// Print the offset and rolling CRC of every window of
+    /// a file, or of every block with --blocks.
+    Scan {
+        /// Window (or block) size in bytes.
+        #[arg(short, long)]
+        window: usize,
+        /// CRC consecutive blocks instead of every window.
+        #[arg(short, long)]
+        blocks: bool,
+        /// File to scan, or - for standard input.
+        file: PathBuf,
+    },
+    /// Print each file name and offset at which the
+    /// contents of the needle file occur. Matches are
+    /// verified byte for byte.
+    Find {
+        /// File holding the bytes to look for.
+        needle: PathBuf,
+        /// Files to search, or - for standard input.
+        #[arg(required = true)]
+        haystacks: Vec<PathBuf>,
+    },
+    /// Print the offset, length and CRC of each
+    /// content-defined chunk of a file.
+    Chunk {
+        /// Rolling CRC window size in bytes.
+        #[arg(short, long, default_value_t = DEFAULT_CHUNK_WINDOW)]
+        window: usize,
+        /// Boundary mask, in decimal or 0x-prefixed hex. The
+        /// average chunk size is about the mask plus one.
+        #[arg(short, long, value_parser = parse_mask)]
+        mask: u32,
+        /// File to chunk, or - for standard input.
+        file: PathBuf,
+    },
+}
+
+/// Parse a mask in decimal or `0x`-prefixed hex.
+fn parse_mask(text: &str) -> Result<u32, String> {
+    let parsed = match text.strip_prefix("0x") {
+        Some(hex) => u32::from_str_radix(hex, 16),
+        None => text.parse(),
+    };
+    parsed.map_err(|e| e.to_string())
+}
+
+/// Is this path the name for standard input?
+fn is_stdin(path: &Path) -> bool {
+    path == Path::new("-")
+}
+
+/// The file, or standard input for `-`, with its name in
+/// any error.
+fn open(path: &Path) -> Result<Box<dyn Read>, Box<dyn Error>> {
+    if is_stdin(path) {
+        return Ok(Box::new(io::stdin()));
+    }
+    let file = File::open(path)
+        .map_err(|e| format!("{}: {}", path.display(), e))?;
+    Ok(Box::new(file))
+}
+
+fn run(command: Command) -> Result<(), Box<dyn Error>> {
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    match command {
+        Command::Crc { files } => {
+            for path in files {
+                let crc = if is_stdin(&path) {
+                    RollingCRCContext::checksum_only().crc_reader(io::stdin())
+                } else {
+                    crc_file_sparse(&path)
+                };
+                let crc =
+                    crc.map_err(|e| format!("{}: {}", path.display(), e))?;
+                writeln!(out, "{:08x}  {}", crc, path.display())?;
+            }
+        },
+        Command::Scan { window, blocks: true, file } => {
+            if window == 0 {
+                return Err("block size must be positive".into());
+            }
+            // One block in memory at a time.
+            let context = RollingCRCContext::checksum_only();
+            let mut reader = BufReader::new(open(&file)?);
+            let mut block = Vec::with_capacity(window);
+            let mut offset = 0;
+            loop {
+                block.clear();
+                let len = (&mut reader).take(window as u64)
+                    .read_to_end(&mut block)?;
+                if len == 0 {
+                    break;
+                }
+                writeln!(out, "{} {:08x}", offset, context.crc(&block))?;
+                offset += len as u64;
+            }
+        },
+        Command::Scan { window, blocks: false, file } => {
+            let context = RollingCRCContext::new(window)?;
+            let reader = BufReader::new(open(&file)?);
+            let rolling_crc = RollingCRC::new(&context);
+            for result in rolling_crc.iter_result(reader.bytes()) {
+                let (offset, crc) = result?;
+                writeln!(out, "{} {:08x}", offset, crc)?;
+            }
+        },
+        Command::Find { needle, haystacks } => {
+            let needle = fs::read(&needle)
+                .map_err(|e| format!("{}: {}", needle.display(), e))?;
+            for path in haystacks {
+                let found = if is_stdin(&path) {
+                    Finder::new(&needle).find_reader(io::stdin())
+                } else {
+                    find_in_file(&path, &needle)
+                };
+                let found =
+                    found.map_err(|e| format!("{}: {}", path.display(), e))?;
+                for offset in found {
+                    writeln!(out, "{}: {}", path.display(), offset)?;
+                }
+            }
+        },
+        Command::Chunk { window, mask, file } => {
+            let avg_size = (mask as usize).saturating_add(1);
+            let config = ChunkerConfig::new(avg_size)
+                .window_size(window)
+                .mask(mask);
+            let chunker = Chunker::new(config);
+            for chunk in chunker.chunk_read(open(&file)?) {
+                let chunk = chunk?;
+                writeln!(out, "{} {} {:08x}",
+                         chunk.offset, chunk.len, chunk.crc)?;
+            }
+        },
+    }
+    out.flush()?;
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let args = Args::parse();
+    match run(args.command) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("rcrc: {}", e);
+            ExitCode::FAILURE
+        },
+    }
+}
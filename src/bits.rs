@@ -0,0 +1,269 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Rolling CRCs of windows of bits, for data that is not
+//! byte-aligned.
+
+// The CRC of a bit string feeds the bits through one at a
+// time, each byte's bits lowest first as for the reflected
+// CRC-32, so that a whole number of bytes gives the usual
+// CRC. Write A for the linear operator taking an open CRC
+// through one zero bit, and I for `INIT_CRC`. Feeding bit
+// b takes the open CRC s to `A (s ^ b)`, and the open CRC
+// of an n-bit window x_1 ... x_n is
+//
+//        S == A^n I ^ A^n x_1 ^ A^(n-1) x_2 ^ ... ^ A x_n
+//
+// Sliding the window by one bit, dropping x_1 and feeding
+// x_(n+1), gives
+//
+//        S' == A (S ^ x_(n+1)) ^ A^(n+1) x_1 ^ (A^(n+1) ^ A^n) I
+//
+// so each step is a one-bit CRC update and an XOR with one
+// of two constants chosen by the departing bit: a rolling
+// table of two entries, where the byte rolling table has
+// 256. A^n is multiplication by x^n modulo the CRC
+// polynomial, so the constants take time logarithmic in n.
+
+use super::combine::x2n_mod_poly;
+use super::gf2::clmul;
+use super::{finish_crc, RollingCRCContext, WindowSizeError, INIT_CRC,
+            POLY_CRC};
+#[cfg(test)]
+use super::test_rng;
+
+use alloc::vec::Vec;
+
+/// The open CRC after feeding one bit (0 or 1).
+#[inline(always)]
+fn update_crc_bit(crc: u32, bit: u32) -> u32 {
+    let crc = crc ^ bit;
+    (crc >> 1) ^ (POLY_CRC & (crc & 1).wrapping_neg())
+}
+
+/// Data needed for rolling CRC calculation over windows of
+/// bits rather than bytes. This can be created using
+/// `RollingCRCContext::new_bits()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RollingBitCRCContext {
+    /// Size of the calculation window in bits.
+    window_bits: usize,
+    /// Constant to XOR in when rolling out a 0 bit and a
+    /// 1 bit.
+    rolling_table: [u32; 2],
+}
+
+impl<'a> RollingCRCContext<'a> {
+
+    /// Create a context for rolling CRCs over windows of
+    /// the given number of bits. The CRC of a bit window
+    /// is the CRC-32 of its bits, fed lowest bit of each
+    /// byte first, so that a window of whole bytes has the
+    /// same CRC as from `crc()`.
+    ///
+    /// # Errors
+    ///
+    /// A window size of 0 is an error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new_bits(52).unwrap();
+    /// assert_eq!(context.window_bits(), 52);
+    /// assert!(RollingCRCContext::new_bits(0).is_err());
+    /// ```
+    pub fn new_bits(window_bits: usize)
+                    -> Result<RollingBitCRCContext, WindowSizeError>
+    {
+        if window_bits == 0 {
+            return Err(WindowSizeError);
+        }
+        let n = window_bits as u64;
+        let shift = |k| clmul(x2n_mod_poly(n + k, 0), INIT_CRC, POLY_CRC);
+        let init = shift(0) ^ shift(1);
+        let one = clmul(x2n_mod_poly(n + 1, 0), 1, POLY_CRC);
+        Ok(RollingBitCRCContext {
+            window_bits,
+            rolling_table: [init, init ^ one],
+        })
+    }
+}
+
+impl RollingBitCRCContext {
+
+    /// Size of the calculation window in bits.
+    pub fn window_bits(&self) -> usize {
+        self.window_bits
+    }
+}
+
+/// A rolling CRC over windows of bits: see
+/// `RollingCRCContext::new_bits()`. Windows are reported
+/// with bit offsets counted from the first bit pushed.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new_bits(12).unwrap();
+/// let mut roller = RollingBitCRC::new(&context);
+/// let mut crcs = Vec::new();
+/// for &b in b"hello" {
+///     roller.push_bits(b, 8, |offset, crc| crcs.push((offset, crc)));
+/// }
+/// assert_eq!(crcs.len(), 40 - 12 + 1);
+///
+/// // Byte-aligned windows of whole bytes have the usual CRC.
+/// let bytes = RollingCRCContext::checksum_only();
+/// let context = RollingCRCContext::new_bits(16).unwrap();
+/// let mut roller = RollingBitCRC::new(&context);
+/// roller.push_bits(b'h', 8, |_, _| ());
+/// roller.push_bits(b'e', 8, |_, _| ());
+/// roller.push_bits(b'l', 8, |_, _| ());
+/// assert_eq!(roller.crc(), Some(bytes.crc(b"el")));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RollingBitCRC<'a> {
+    context: &'a RollingBitCRCContext,
+    /// Number of bits processed so far.
+    count: u64,
+    /// Bits in window, packed lowest first into words.
+    bits: Vec<u64>,
+    /// Index of next bit in window to be replaced.
+    index: usize,
+    /// Current open CRC: of all the bits so far until the
+    /// window fills, then of the window.
+    open: u32,
+}
+
+impl<'a> RollingBitCRC<'a> {
+
+    /// Start a new rolling CRC in the given context.
+    pub fn new(context: &'a RollingBitCRCContext) -> Self {
+        Self {
+            context,
+            count: 0,
+            bits: vec![0; context.window_bits.div_ceil(64)],
+            index: 0,
+            open: INIT_CRC,
+        }
+    }
+
+    /// Roll a bit through this rolling CRC, returning the
+    /// CRC of the window it completes, if any.
+    #[inline]
+    pub fn push_bit(&mut self, bit: bool) -> Option<u32> {
+        let window_bits = self.context.window_bits;
+        let (word, shift) = (self.index >> 6, self.index & 63);
+        let out = (self.bits[word] >> shift) & 1;
+        self.bits[word] ^= (out ^ bit as u64) << shift;
+        self.open = update_crc_bit(self.open, bit as u32);
+        if self.count >= window_bits as u64 {
+            self.open ^= self.context.rolling_table[out as usize];
+        }
+        self.count += 1;
+        self.index += 1;
+        if self.index == window_bits {
+            self.index = 0;
+        }
+        self.crc()
+    }
+
+    /// Roll the low `nbits` bits of the byte through this
+    /// rolling CRC, lowest first, passing each window
+    /// completed to `sink` with its bit offset.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `nbits` is greater than 8.
+    pub fn push_bits<F>(&mut self, byte: u8, nbits: u8, mut sink: F)
+        where F: FnMut(u64, u32)
+    {
+        assert!(nbits <= 8, "push_bits: more than 8 bits");
+        let window_bits = self.context.window_bits as u64;
+        for i in 0..nbits {
+            if let Some(crc) = self.push_bit((byte >> i) & 1 != 0) {
+                sink(self.count - window_bits, crc);
+            }
+        }
+    }
+
+    /// The CRC of the current window, or `None` if the
+    /// window has not yet filled.
+    pub fn crc(&self) -> Option<u32> {
+        if self.count < self.context.window_bits as u64 {
+            return None;
+        }
+        Some(finish_crc(self.open))
+    }
+
+    /// Number of bits pushed so far.
+    pub fn bit_count(&self) -> u64 {
+        self.count
+    }
+}
+
+#[test]
+fn test_rolling_bit_crc() {
+    use super::{calc_crc, CRC_TABLE};
+
+    let mut next = test_rng(0x2545_f491u32);
+    let bits: Vec<bool> = (0..1000).map(|_| next() & 1 != 0).collect();
+    let naive = |window: &[bool]| {
+        let open = window.iter()
+            .fold(INIT_CRC, |crc, &bit| update_crc_bit(crc, bit as u32));
+        finish_crc(open)
+    };
+    for &window_bits in &[1, 2, 7, 8, 13, 52, 63, 64, 65, 128, 300] {
+        let context = RollingCRCContext::new_bits(window_bits).unwrap();
+        let mut roller = RollingBitCRC::new(&context);
+        for (i, &bit) in bits.iter().enumerate() {
+            let crc = roller.push_bit(bit);
+            if i + 1 < window_bits {
+                assert_eq!(crc, None);
+            } else {
+                let window = &bits[i + 1 - window_bits..=i];
+                assert_eq!(crc, Some(naive(window)), "{} {}", window_bits, i);
+            }
+        }
+        assert_eq!(roller.bit_count(), bits.len() as u64);
+
+        // Pushed in pieces of up to a byte.
+        let mut roller = RollingBitCRC::new(&context);
+        let mut crcs = Vec::new();
+        let mut start = 0;
+        while start < bits.len() {
+            let nbits = (next() % 9) as usize;
+            let nbits = nbits.min(bits.len() - start);
+            let byte = bits[start..start + nbits].iter().rev()
+                .fold(0u8, |byte, &bit| (byte << 1) | bit as u8);
+            // Bits above the ones pushed are ignored.
+            let byte = byte | ((next() as u16) << nbits) as u8;
+            roller.push_bits(byte, nbits as u8, |offset, crc| {
+                crcs.push((offset, crc));
+            });
+            start += nbits;
+        }
+        assert_eq!(crcs.len(), bits.len() + 1 - window_bits);
+        for &(offset, crc) in &crcs {
+            let offset = offset as usize;
+            assert_eq!(crc, naive(&bits[offset..offset + window_bits]));
+        }
+    }
+
+    // Whole bytes agree with the byte CRC.
+    let bytes: Vec<u8> = (0..100).map(|_| next() as u8).collect();
+    let context = RollingCRCContext::new_bits(8 * 5).unwrap();
+    let mut roller = RollingBitCRC::new(&context);
+    for (i, &b) in bytes.iter().enumerate() {
+        roller.push_bits(b, 8, |_, _| ());
+        if i >= 4 {
+            assert_eq!(roller.crc(),
+                       Some(calc_crc(&bytes[i - 4..=i], &CRC_TABLE)));
+        }
+    }
+    assert!(RollingCRCContext::new_bits(0).is_err());
+}
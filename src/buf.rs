@@ -0,0 +1,111 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Rolling and whole-message CRCs of `bytes::Buf`s, which
+//! may be stored in several chunks.
+
+use super::{Crc32Digest, RollingCRC};
+#[cfg(test)]
+use super::test_bytes;
+
+use bytes::Buf;
+
+impl<'a> RollingCRC<'a> {
+
+    /// Roll all the remaining bytes of `buf` through this
+    /// rolling CRC, a chunk at a time, consuming them. The
+    /// rolling CRC of each window completed is passed to
+    /// `sink` along with the position of the window start,
+    /// counted from the first byte this rolling CRC saw, as
+    /// with `iter()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # extern crate bytes;
+    /// # extern crate rolling_crc;
+    /// # use rolling_crc::*;
+    /// use bytes::{Buf, Bytes};
+    ///
+    /// let context = RollingCRCContext::new(5).unwrap();
+    /// let mut roll_crc = RollingCRC::new(&context);
+    /// let mut buf = Bytes::from_static(b"hello ")
+    ///     .chain(Bytes::from_static(b"world"));
+    /// let mut crcs = Vec::new();
+    /// roll_crc.push_buf(&mut buf, |offset, crc| crcs.push((offset, crc)));
+    /// assert_eq!(crcs.len(), 7);
+    /// assert_eq!(crcs[6], (6, context.crc(b"world")));
+    /// assert!(!buf.has_remaining());
+    /// ```
+    pub fn push_buf<B, F>(&mut self, buf: &mut B, mut sink: F)
+        where B: Buf, F: FnMut(u64, u32)
+    {
+        while buf.has_remaining() {
+            let chunk = buf.chunk();
+            let n = chunk.len();
+            self.push_each(chunk, &mut sink);
+            buf.advance(n);
+        }
+    }
+}
+
+impl<'a> Crc32Digest<'a> {
+
+    /// Hash the remaining bytes of `buf`, a chunk at a
+    /// time, consuming them.
+    pub fn update_buf<B: Buf>(&mut self, buf: &mut B) {
+        while buf.has_remaining() {
+            let n = {
+                let chunk = buf.chunk();
+                self.update(chunk);
+                chunk.len()
+            };
+            buf.advance(n);
+        }
+    }
+}
+
+#[test]
+fn test_push_buf() {
+    use super::RollingCRCContext;
+    use bytes::Bytes;
+
+    let bytes = test_bytes(0x2545_f491, 1000);
+    // Segments of assorted small lengths.
+    let mut segments = Vec::new();
+    let mut start = 0;
+    for len in (0..).map(|i| [1, 3, 0, 7, 16, 2, 41][i % 7]) {
+        if start >= bytes.len() {
+            break;
+        }
+        let end = (start + len).min(bytes.len());
+        segments.push(Bytes::copy_from_slice(&bytes[start..end]));
+        start = end;
+    }
+    let segmented = || {
+        let mut buf: Box<dyn Buf> = Box::new(Bytes::new());
+        for segment in &segments {
+            buf = Box::new(buf.chain(segment.clone()));
+        }
+        buf
+    };
+
+    for &window_size in &[0, 1, 2, 5, 16, 64] {
+        let context = RollingCRCContext::build(window_size);
+        let expected: Vec<(u64, u32)> = context.rolling_crcs(&bytes)
+            .map(|(offset, crc)| (offset as u64, crc))
+            .collect();
+        let mut roll_crc = RollingCRC::new(&context);
+        let mut buf = segmented();
+        let mut crcs = Vec::new();
+        roll_crc.push_buf(&mut buf, |offset, crc| crcs.push((offset, crc)));
+        assert_eq!(crcs, expected);
+        assert!(!buf.has_remaining());
+
+        let mut digest = Crc32Digest::new(&context);
+        digest.update_buf(&mut segmented());
+        assert_eq!(digest.finalize(), context.crc(&bytes));
+    }
+}
@@ -0,0 +1,131 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Cancellation of long-running searches.
+
+use std::error;
+use std::fmt;
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Default number of windows scanned between checks for
+/// cancellation.
+pub const DEFAULT_CANCEL_INTERVAL: usize = 4096;
+
+/// A flag that can be used to cancel a long-running search
+/// from another thread. Clones of a token share the flag.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let token = CancelToken::new();
+/// let finder = Finder::new(b"needle");
+/// token.clone().cancel();
+/// match finder.find_all_with_cancel(b"haystack", &token) {
+///     Err(SearchError::Cancelled) => (),
+///     _ => panic!("search not cancelled"),
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct CancelToken {
+    /// Shared cancellation flag.
+    cancelled: Arc<AtomicBool>,
+    /// Number of windows scanned between checks.
+    interval: usize,
+}
+
+impl Default for CancelToken {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl CancelToken {
+
+    /// Make a new uncancelled token that is checked every
+    /// `DEFAULT_CANCEL_INTERVAL` windows.
+    pub fn new() -> Self {
+        Self::with_interval(DEFAULT_CANCEL_INTERVAL)
+    }
+
+    /// Make a new uncancelled token that is checked every
+    /// `interval` windows (every window if 0).
+    pub fn with_interval(interval: usize) -> Self {
+        Self {
+            cancelled: Arc::new(AtomicBool::new(false)),
+            interval: interval.max(1),
+        }
+    }
+
+    /// Number of windows scanned between checks.
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Cancel any searches using this token or its clones.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    /// Has this token been cancelled?
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Fail if this token has been cancelled.
+    pub(crate) fn check(&self) -> Result<(), SearchError> {
+        if self.is_cancelled() {
+            return Err(SearchError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Reasons a cancellable search may fail.
+#[derive(Debug)]
+pub enum SearchError {
+    /// The search was cancelled.
+    Cancelled,
+    /// Reading the input failed.
+    Io(io::Error),
+}
+
+impl fmt::Display for SearchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SearchError::Cancelled => write!(f, "search cancelled"),
+            SearchError::Io(ref e) => write!(f, "search failed: {}", e),
+        }
+    }
+}
+
+impl error::Error for SearchError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            SearchError::Cancelled => None,
+            SearchError::Io(ref e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for SearchError {
+    fn from(e: io::Error) -> Self {
+        SearchError::Io(e)
+    }
+}
+
+impl SearchError {
+    /// Convert the error from a search with a token that
+    /// is never cancelled.
+    pub(crate) fn into_io(self) -> io::Error {
+        match self {
+            SearchError::Io(e) => e,
+            SearchError::Cancelled =>
+                panic!("internal error: uncancellable search cancelled"),
+        }
+    }
+}
@@ -0,0 +1,612 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Content-defined chunking using rolling CRCs.
+
+use super::{update_crc, finish_crc, RollingCRC, RollingCRCContext,
+            INIT_CRC};
+#[cfg(test)]
+use super::test_bytes;
+
+use std::collections::VecDeque;
+use std::io::{self, Read};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Size of the buffer used to read streams for chunking.
+const CHUNK_READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// Default window size for content-defined chunking.
+pub const DEFAULT_CHUNK_WINDOW: usize = 48;
+
+/// Parameters for a `Chunker`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChunkerConfig {
+    /// Size of the rolling CRC window.
+    window_size: usize,
+    /// Boundary mask.
+    mask: u32,
+    /// Minimum chunk size.
+    min_size: usize,
+    /// Target average chunk size.
+    avg_size: usize,
+    /// Maximum chunk size.
+    max_size: usize,
+    /// Normalization level.
+    normalization: u32,
+}
+
+impl ChunkerConfig {
+
+    /// Parameters for chunks averaging about `avg_size`
+    /// bytes: the mask has `avg_size` rounded up to a power
+    /// of two as its modulus, the minimum chunk size is
+    /// `avg_size / 4` and the maximum is `avg_size * 4`. The
+    /// window size is `DEFAULT_CHUNK_WINDOW`.
+    pub fn new(avg_size: usize) -> Self {
+        let avg_size = avg_size.max(1);
+        let mask = (avg_size.next_power_of_two() - 1) as u32;
+        Self {
+            window_size: DEFAULT_CHUNK_WINDOW,
+            mask,
+            min_size: avg_size / 4,
+            avg_size,
+            max_size: avg_size.saturating_mul(4),
+            normalization: 0,
+        }
+    }
+
+    /// Use the given rolling CRC window size.
+    pub fn window_size(mut self, window_size: usize) -> Self {
+        self.window_size = window_size;
+        self
+    }
+
+    /// Cut after a window whose CRC has all the bits of
+    /// `mask` set.
+    pub fn mask(mut self, mask: u32) -> Self {
+        self.mask = mask;
+        self
+    }
+
+    /// Make no chunk shorter than `min_size` bytes, except
+    /// possibly the last.
+    pub fn min_size(mut self, min_size: usize) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Make no chunk longer than `max_size` bytes, cutting
+    /// there if no boundary has been found.
+    pub fn max_size(mut self, max_size: usize) -> Self {
+        self.max_size = max_size;
+        self
+    }
+
+    /// Use normalized chunking as in FastCDC (Xia *et al*,
+    /// "FastCDC: a Fast and Efficient Content-Defined
+    /// Chunking Approach for Data Deduplication", 2016).
+    /// Below the average chunk size the mask gains `level`
+    /// more bits, making a cut less likely; at or above it
+    /// the mask loses `level` bits, making a cut more
+    /// likely. This narrows the chunk size distribution
+    /// around the average. Level 0, the default, is plain
+    /// mask chunking; FastCDC recommends level 2.
+    pub fn normalization(mut self, level: u32) -> Self {
+        self.normalization = level;
+        self
+    }
+}
+
+/// A chunk found by a `Chunker`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct Chunk {
+    /// Offset of the chunk in the input.
+    pub offset: u64,
+    /// Length of the chunk.
+    pub len: usize,
+    /// CRC of the whole chunk.
+    pub crc: u32,
+}
+
+/// A chunk found in a stream by `Chunker::chunk_read()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ChunkBoundary {
+    /// Offset of the chunk in the stream.
+    pub offset: u64,
+    /// Length of the chunk.
+    pub len: u64,
+    /// CRC of the whole chunk.
+    pub crc: u32,
+}
+
+#[cfg(feature = "defmt")]
+impl ::defmt::Format for Chunk {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "Chunk {{ offset: {=u64}, len: {=usize}, \
+                            crc: {=u32:#010x} }}",
+                        self.offset, self.len, self.crc)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl ::defmt::Format for ChunkBoundary {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "ChunkBoundary {{ offset: {=u64}, len: {=u64}, \
+                            crc: {=u32:#010x} }}",
+                        self.offset, self.len, self.crc)
+    }
+}
+
+impl From<Chunk> for ChunkBoundary {
+    fn from(chunk: Chunk) -> Self {
+        ChunkBoundary {
+            offset: chunk.offset,
+            len: chunk.len as u64,
+            crc: chunk.crc,
+        }
+    }
+}
+
+/// A content-defined chunker. Chunk boundaries are placed
+/// after windows whose CRC matches a mask, so that they
+/// depend only on nearby content: an edit moves only the
+/// boundaries near it, and data is chunked identically
+/// however it is delivered.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let data: Vec<u8> = (0..100_000u32)
+///     .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+///     .collect();
+/// let chunker = Chunker::new(ChunkerConfig::new(1024));
+/// let chunks: Vec<Chunk> = chunker.chunk_slice(&data).collect();
+/// assert_eq!(chunks[0].offset, 0);
+/// let total: usize = chunks.iter().map(|c| c.len).sum();
+/// assert_eq!(total, data.len());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Chunker {
+    /// Chunking parameters.
+    config: ChunkerConfig,
+    /// Context for the window size.
+    context: RollingCRCContext<'static>,
+    /// Boundary mask for chunks shorter than the average.
+    small_mask: u32,
+    /// Boundary mask for chunks at least the average.
+    large_mask: u32,
+}
+
+/// The mask with its `n` lowest clear bits set.
+fn add_mask_bits(mut mask: u32, n: u32) -> u32 {
+    for _ in 0..n {
+        mask |= !mask & (!mask).wrapping_neg();
+    }
+    mask
+}
+
+/// The mask with its `n` highest set bits cleared.
+fn remove_mask_bits(mut mask: u32, n: u32) -> u32 {
+    for _ in 0..n {
+        if mask == 0 {
+            break;
+        }
+        mask &= !(1 << (31 - mask.leading_zeros()));
+    }
+    mask
+}
+
+impl Chunker {
+
+    /// Make a new chunker with the given parameters.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the maximum chunk size is 0 or less than
+    /// the minimum.
+    pub fn new(config: ChunkerConfig) -> Self {
+        assert!(config.max_size > 0, "Chunker: max_size must be positive");
+        assert!(config.min_size <= config.max_size,
+                "Chunker: min_size exceeds max_size");
+        let context = RollingCRCContext::build(config.window_size);
+        let small_mask = add_mask_bits(config.mask, config.normalization);
+        let large_mask = remove_mask_bits(config.mask, config.normalization);
+        Self { config, context, small_mask, large_mask }
+    }
+
+    /// Parameters of this chunker.
+    pub fn config(&self) -> &ChunkerConfig {
+        &self.config
+    }
+
+    /// Is the end of a chunk of length `len` whose last
+    /// window has the given CRC a boundary?
+    #[inline(always)]
+    fn is_boundary(&self, len: usize, crc: u32) -> bool {
+        let mask = if len < self.config.avg_size {
+            self.small_mask
+        } else {
+            self.large_mask
+        };
+        len >= self.config.min_size && crc & mask == mask
+    }
+
+    /// An iterator over the chunks of the given slice.
+    pub fn chunk_slice<'c, 'b>(&'c self, buf: &'b [u8])
+                               -> ChunkSlice<'c, 'b>
+    {
+        ChunkSlice { chunker: self, buf, offset: 0 }
+    }
+
+    /// Start chunking a stream delivered in pieces. The
+    /// chunks are the same as those of `chunk_slice()` on
+    /// the concatenated pieces.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let data: Vec<u8> = (0..50_000u32)
+    ///     .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+    ///     .collect();
+    /// let chunker = Chunker::new(ChunkerConfig::new(512));
+    /// let mut stream = chunker.stream();
+    /// let mut chunks = Vec::new();
+    /// for piece in data.chunks(1000) {
+    ///     stream.push(piece, |chunk| chunks.push(chunk));
+    /// }
+    /// stream.finish(|chunk| chunks.push(chunk));
+    /// let expected: Vec<Chunk> = chunker.chunk_slice(&data).collect();
+    /// assert_eq!(chunks, expected);
+    /// ```
+    pub fn stream<'c>(&'c self) -> StreamChunker<'c> {
+        StreamChunker {
+            chunker: self,
+            rolling_crc: RollingCRC::new(&self.context),
+            offset: 0,
+            len: 0,
+            crc: INIT_CRC,
+        }
+    }
+
+    /// An iterator over the chunks of the bytes of the
+    /// given reader. The stream is read through a
+    /// fixed-size buffer, so memory use is independent of
+    /// the stream length, and the chunks are the same as
+    /// those of `chunk_slice()` on the whole stream. A read
+    /// error ends the iteration after it is returned.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let data: Vec<u8> = (0..50_000u32)
+    ///     .map(|i| (i.wrapping_mul(2654435761) >> 24) as u8)
+    ///     .collect();
+    /// let chunker = Chunker::new(ChunkerConfig::new(512));
+    /// let chunks: Vec<ChunkBoundary> = chunker.chunk_read(&data[..])
+    ///     .collect::<Result<_, _>>()
+    ///     .unwrap();
+    /// let expected: Vec<ChunkBoundary> = chunker.chunk_slice(&data)
+    ///     .map(ChunkBoundary::from)
+    ///     .collect();
+    /// assert_eq!(chunks, expected);
+    /// ```
+    pub fn chunk_read<'c, R: Read>(&'c self, reader: R) -> ChunkRead<'c, R> {
+        ChunkRead {
+            stream: Some(self.stream()),
+            reader,
+            buf: vec![0; CHUNK_READ_BUFFER_SIZE],
+            pending: VecDeque::new(),
+        }
+    }
+}
+
+/// An iterator over the chunks of a slice. This iterator
+/// can be created using `Chunker::chunk_slice()`.
+#[derive(Debug, Clone)]
+pub struct ChunkSlice<'c, 'b> {
+    chunker: &'c Chunker,
+    buf: &'b [u8],
+    /// Start of the next chunk.
+    offset: usize,
+}
+
+impl<'c, 'b> Iterator for ChunkSlice<'c, 'b> {
+    type Item = Chunk;
+
+    fn next(&mut self) -> Option<Chunk> {
+        let start = self.offset;
+        if start >= self.buf.len() {
+            return None;
+        }
+        let chunker = self.chunker;
+        let window_size = chunker.config.window_size;
+        let limit = self.buf.len()
+            .min(start.saturating_add(chunker.config.max_size));
+        // Window CRCs depend only on the window contents, so
+        // rolling can start at the first window that could
+        // end a chunk.
+        let mut end = limit;
+        if window_size > 0 {
+            let first_end = (start + chunker.config.min_size.max(1))
+                .max(window_size);
+            if first_end < limit {
+                let windows = &self.buf[first_end - window_size..limit];
+                for (i, crc) in chunker.context.rolling_crcs(windows) {
+                    let len = first_end + i - start;
+                    if chunker.is_boundary(len, crc) {
+                        end = first_end + i;
+                        break;
+                    }
+                }
+            }
+        }
+        self.offset = end;
+        let chunk = &self.buf[start..end];
+        Some(Chunk {
+            offset: start as u64,
+            len: chunk.len(),
+            crc: chunker.context.crc(chunk),
+        })
+    }
+}
+
+/// Chunking of a stream delivered in pieces. This can be
+/// created using `Chunker::stream()`.
+#[derive(Debug, Clone)]
+pub struct StreamChunker<'c> {
+    chunker: &'c Chunker,
+    /// Rolling CRC of the stream.
+    rolling_crc: RollingCRC<'c>,
+    /// Offset of the current chunk.
+    offset: u64,
+    /// Length of the current chunk so far.
+    len: usize,
+    /// Open CRC of the current chunk so far.
+    crc: u32,
+}
+
+impl<'c> StreamChunker<'c> {
+
+    /// Chunk the next piece of the stream, calling `sink`
+    /// with each chunk completed in it.
+    pub fn push<F>(&mut self, bytes: &[u8], mut sink: F)
+        where F: FnMut(Chunk)
+    {
+        let table = self.chunker.context.crc_table;
+        let max_size = self.chunker.config.max_size;
+        for &byte in bytes {
+            self.len += 1;
+            self.crc = update_crc(self.crc, table, byte);
+            let boundary = match self.rolling_crc.push(byte) {
+                Some(crc) => self.chunker.is_boundary(self.len, crc),
+                None => false,
+            };
+            if boundary || self.len == max_size {
+                sink(self.take());
+            }
+        }
+    }
+
+    /// End the stream, calling `sink` with the final chunk
+    /// if the stream does not end on a boundary.
+    pub fn finish<F>(mut self, mut sink: F)
+        where F: FnMut(Chunk)
+    {
+        if self.len > 0 {
+            sink(self.take());
+        }
+    }
+
+    /// Complete the current chunk and start another.
+    fn take(&mut self) -> Chunk {
+        let chunk = Chunk {
+            offset: self.offset,
+            len: self.len,
+            crc: finish_crc(self.crc),
+        };
+        self.offset += self.len as u64;
+        self.len = 0;
+        self.crc = INIT_CRC;
+        chunk
+    }
+}
+
+/// An iterator over the chunks of a stream. This iterator
+/// can be created using `Chunker::chunk_read()`.
+#[derive(Debug)]
+pub struct ChunkRead<'c, R> {
+    /// Chunking state, or `None` once the stream has ended.
+    stream: Option<StreamChunker<'c>>,
+    reader: R,
+    buf: Vec<u8>,
+    /// Chunks found but not yet returned.
+    pending: VecDeque<Chunk>,
+}
+
+impl<'c, R: Read> Iterator for ChunkRead<'c, R> {
+    type Item = io::Result<ChunkBoundary>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let Some(chunk) = self.pending.pop_front() {
+                return Some(Ok(chunk.into()));
+            }
+            let stream = self.stream.as_mut()?;
+            let nread = match self.reader.read(&mut self.buf) {
+                Ok(nread) => nread,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted =>
+                    continue,
+                Err(e) => {
+                    self.stream = None;
+                    return Some(Err(e));
+                },
+            };
+            let pending = &mut self.pending;
+            if nread == 0 {
+                let stream = self.stream.take()?;
+                stream.finish(|chunk| pending.push_back(chunk));
+            } else {
+                stream.push(&self.buf[..nread], |c| pending.push_back(c));
+            }
+        }
+    }
+}
+
+#[test]
+fn test_chunker() {
+    let data = test_bytes(1, 200_000);
+    let configs = [
+        ChunkerConfig::new(1024),
+        ChunkerConfig::new(256).window_size(16).min_size(0),
+        ChunkerConfig::new(4096).max_size(5000),
+        ChunkerConfig::new(64).window_size(0),
+        ChunkerConfig::new(100).window_size(300).min_size(10),
+        ChunkerConfig::new(1024).normalization(2),
+        ChunkerConfig::new(512).normalization(3).min_size(0).window_size(5),
+        ChunkerConfig::new(512).mask(0x5555).normalization(40),
+    ];
+    for config in configs.iter() {
+        let chunker = Chunker::new(config.clone());
+        let chunks: Vec<Chunk> = chunker.chunk_slice(&data).collect();
+        let mut offset = 0;
+        for (i, chunk) in chunks.iter().enumerate() {
+            assert_eq!(chunk.offset, offset as u64);
+            assert!(chunk.len <= config.max_size);
+            assert!(chunk.len >= config.min_size || i == chunks.len() - 1);
+            let bytes = &data[offset..offset + chunk.len];
+            assert_eq!(chunk.crc,
+                       RollingCRCContext::checksum_only().crc(bytes));
+            offset += chunk.len;
+        }
+        assert_eq!(offset, data.len());
+
+        // Streamed in uneven pieces, including empty ones.
+        let mut stream = chunker.stream();
+        let mut streamed = Vec::new();
+        let mut start = 0;
+        let mut step = 0;
+        while start < data.len() {
+            let len = (step * step % 7919).min(data.len() - start);
+            stream.push(&data[start..start + len], |c| streamed.push(c));
+            start += len;
+            step += 1;
+        }
+        stream.finish(|c| streamed.push(c));
+        assert_eq!(streamed, chunks);
+    }
+    let chunker = Chunker::new(ChunkerConfig::new(1024));
+    assert_eq!(chunker.chunk_slice(b"").next(), None);
+    let mut chunks = Vec::new();
+    chunker.stream().finish(|c| chunks.push(c));
+    assert!(chunks.is_empty());
+}
+
+#[test]
+fn test_chunker_shift_resistance() {
+    let data = test_bytes(2, 1 << 20);
+    let chunker = Chunker::new(ChunkerConfig::new(4096));
+    let ends = |buf: &[u8]| -> Vec<u64> {
+        chunker.chunk_slice(buf)
+            .map(|c| c.offset + c.len as u64)
+            .collect()
+    };
+    let original = ends(&data);
+    let mut edited = data.clone();
+    edited.insert(100, 0x5a);
+    // Shift the edited boundaries back to compare.
+    let shifted: Vec<u64> = ends(&edited).iter()
+        .filter(|&&end| end > 200_000)
+        .map(|&end| end - 1)
+        .collect();
+    let downstream: Vec<u64> = original.iter()
+        .cloned()
+        .filter(|&end| end > 200_000)
+        .collect();
+    assert!(downstream.len() > 100);
+    let kept = downstream.iter()
+        .filter(|end| shifted.binary_search(end).is_ok())
+        .count();
+    assert!(kept * 100 >= downstream.len() * 95,
+            "only {} of {} boundaries kept", kept, downstream.len());
+}
+
+#[test]
+fn test_mask_bits() {
+    assert_eq!(add_mask_bits(0xff, 2), 0x3ff);
+    assert_eq!(add_mask_bits(0b1010, 2), 0b1111);
+    assert_eq!(add_mask_bits(!0, 3), !0);
+    assert_eq!(remove_mask_bits(0xff, 2), 0x3f);
+    assert_eq!(remove_mask_bits(0b1011, 2), 0b0001);
+    assert_eq!(remove_mask_bits(0b1, 5), 0);
+}
+
+#[test]
+fn test_chunker_normalization() {
+    let data = test_bytes(3, 4 << 20);
+    let std_dev = |config: ChunkerConfig| -> f64 {
+        let chunker = Chunker::new(config);
+        let sizes: Vec<f64> = chunker.chunk_slice(&data)
+            .map(|c| c.len as f64)
+            .collect();
+        let n = sizes.len() as f64;
+        let mean = sizes.iter().sum::<f64>() / n;
+        let var = sizes.iter().map(|s| (s - mean) * (s - mean)).sum::<f64>() / n;
+        var.sqrt()
+    };
+    let plain = std_dev(ChunkerConfig::new(4096));
+    let normalized = std_dev(ChunkerConfig::new(4096).normalization(2));
+    assert!(normalized < 0.6 * plain,
+            "normalized {} vs plain {}", normalized, plain);
+}
+
+#[test]
+fn test_chunk_read() {
+    // Deliver reads of varying short lengths.
+    struct ShortReads<'a>(&'a [u8], usize);
+
+    impl<'a> Read for ShortReads<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.1 = (self.1 * 31 + 7) % 1000 + 1;
+            let n = buf.len().min(self.0.len()).min(self.1);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let data = test_bytes(4, 3 << 20);
+    for config in [ChunkerConfig::new(8192), ChunkerConfig::new(2048)
+                   .normalization(2)].iter() {
+        let chunker = Chunker::new(config.clone());
+        let expected: Vec<ChunkBoundary> = chunker.chunk_slice(&data)
+            .map(ChunkBoundary::from)
+            .collect();
+        let chunks: Vec<ChunkBoundary> = chunker.chunk_read(ShortReads(&data, 0))
+            .collect::<io::Result<_>>()
+            .unwrap();
+        assert_eq!(chunks, expected);
+    }
+
+    struct Failing;
+
+    impl Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("broken"))
+        }
+    }
+
+    let chunker = Chunker::new(ChunkerConfig::new(1024));
+    let mut chunks = chunker.chunk_read(Failing);
+    assert!(chunks.next().unwrap().is_err());
+    assert!(chunks.next().is_none());
+    assert!(chunker.chunk_read(&b""[..]).next().is_none());
+}
@@ -0,0 +1,382 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! CRC arithmetic: combining the CRCs of consecutive
+//! messages, after zlib's `crc32_combine()`, extending CRCs
+//! over runs of zeros, and other manipulations that follow
+//! from the linearity of CRCs.
+//!
+//! CRCs here are bit-reflected polynomials over GF(2): bit
+//! 31 is the coefficient of x^0 and bit 0 that of x^31.
+//! Appending n zero bits to a message multiplies its
+//! (open) CRC by x^n modulo the CRC polynomial.
+
+use super::gf2::clmul;
+use super::{unupdate_crc, update_crc, RollingCRCContext, INIT_CRC,
+            INVERSE_CRC_TABLE, POLY_CRC};
+#[cfg(test)]
+use super::{test_bytes, test_rng};
+
+/// Entry k is x^(2^k) modulo the CRC polynomial. The
+/// powers cycle with a period dividing 2^32 - 1, so 32
+/// entries cover any exponent.
+static X2N_TABLE: [u32; 32] = {
+    let mut table = [0; 32];
+    table[0] = 1 << 30;
+    let mut k = 1;
+    while k < 32 {
+        table[k] = clmul(table[k - 1], table[k - 1], POLY_CRC);
+        k += 1;
+    }
+    table
+};
+
+/// x^(n * 2^k) modulo the CRC polynomial.
+pub(crate) fn x2n_mod_poly(mut n: u64, mut k: usize) -> u32 {
+    let mut p = 1 << 31;
+    while n != 0 {
+        if n & 1 != 0 {
+            p = clmul(X2N_TABLE[k & 31], p, POLY_CRC);
+        }
+        n >>= 1;
+        k += 1;
+    }
+    p
+}
+
+/// Divide a polynomial by x modulo the CRC polynomial,
+/// undoing one step of multiplication by x.
+fn div_x_mod_poly(p: u32) -> u32 {
+    if p & (1 << 31) != 0 {
+        ((p ^ POLY_CRC) << 1) | 1
+    } else {
+        p << 1
+    }
+}
+
+/// x^(-8n) modulo the CRC polynomial: the inverse of
+/// `x2n_mod_poly(n, 3)`.
+pub(crate) fn x8n_inv_mod_poly(mut n: u64) -> u32 {
+    let mut base = (0..8).fold(1 << 31, |p, _| div_x_mod_poly(p));
+    let mut p = 1 << 31;
+    while n != 0 {
+        if n & 1 != 0 {
+            p = clmul(base, p, POLY_CRC);
+        }
+        base = clmul(base, base, POLY_CRC);
+        n >>= 1;
+    }
+    p
+}
+
+/// The open CRC after appending `n` zero bytes to the
+/// message with the given open CRC.
+pub(crate) fn append_zeros(open_crc: u32, n: u64) -> u32 {
+    clmul(x2n_mod_poly(n, 3), open_crc, POLY_CRC)
+}
+
+/// The open CRC before appending `n` zero bytes, given the
+/// open CRC after: the inverse of
+/// `RollingCRCContext::extend_zeros()`.
+pub(crate) fn shrink_zeros(open_crc: u32, n: u64) -> u32 {
+    clmul(x8n_inv_mod_poly(n), open_crc, POLY_CRC)
+}
+
+/// The CRC of the concatenation of two messages, given the
+/// CRC of each and the length of the second. This makes it
+/// possible to CRC the parts of a message separately, in
+/// parallel say, and then stitch the results together. It
+/// takes time logarithmic in `len_b`.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::checksum_only();
+/// let (a, b) = (b"hello ", b"world");
+/// assert_eq!(combine(context.crc(a), context.crc(b), b.len() as u64),
+///            context.crc(b"hello world"));
+/// ```
+pub fn combine(crc_a: u32, crc_b: u32, len_b: u64) -> u32 {
+    clmul(x2n_mod_poly(len_b, 3), crc_a, POLY_CRC) ^ crc_b
+}
+
+impl<'a> RollingCRCContext<'a> {
+
+    /// The open CRC after appending `n` zero bytes to the
+    /// message with the given open CRC, computed in time
+    /// logarithmic in `n`. The zeros are not translated by
+    /// any byte map.
+    ///
+    /// An "open" CRC is the state of a CRC computation in
+    /// progress: a finished CRC XORed with `INIT_CRC`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// let open = context.crc(b"abc") ^ INIT_CRC;
+    /// let crc = context.extend_zeros(open, 3) ^ INIT_CRC;
+    /// assert_eq!(crc, context.crc(b"abc\0\0\0"));
+    /// ```
+    pub fn extend_zeros(&self, open_crc: u32, n: u64) -> u32 {
+        append_zeros(open_crc, n)
+    }
+
+    /// The CRC of a message after an in-place edit, given
+    /// its CRC before the edit, its length, and the old and
+    /// new bytes at `offset`. This takes time linear in the
+    /// length of the edit but only logarithmic in the
+    /// length of the message.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `old` and `new` differ in length or the
+    /// edit extends past `message_len`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// let crc = context.crc(b"the cat sat on the mat");
+    /// let crc = context.update_edit(crc, 22, 4, b"cat", b"dog");
+    /// assert_eq!(crc, context.crc(b"the dog sat on the mat"));
+    /// ```
+    pub fn update_edit(&self,
+                       crc: u32,
+                       message_len: u64,
+                       offset: u64,
+                       old: &[u8],
+                       new: &[u8])
+                       -> u32
+    {
+        assert_eq!(old.len(), new.len(),
+                   "update_edit: old and new must be the same length");
+        let end = offset.checked_add(old.len() as u64)
+            .filter(|&end| end <= message_len)
+            .expect("update_edit: edit past end of message");
+        // By linearity the CRC changes by the contribution
+        // of the XOR of the old and new bytes followed by
+        // the rest of the message as zeros.
+        let contribution = old.iter().zip(new).fold(0, |crc, (&o, &n)| {
+            let diff = self.map_byte(o) ^ self.map_byte(n);
+            update_crc(crc, self.crc_table, diff)
+        });
+        crc ^ self.extend_zeros(contribution, message_len - end)
+    }
+
+    /// The CRC of a message with its last bytes removed,
+    /// given the CRC of the whole message and those last
+    /// bytes. Each byte is removed by undoing its update
+    /// step, so this takes time linear in the number of
+    /// bytes removed. If `trailing` is not in fact the end
+    /// of the message, the result is meaningless.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// let crc = context.crc(b"payload+TRAILER");
+    /// assert_eq!(context.rewind(crc, b"+TRAILER"), context.crc(b"payload"));
+    /// ```
+    pub fn rewind(&self, crc: u32, trailing: &[u8]) -> u32 {
+        let open = trailing.iter().rev().fold(crc ^ INIT_CRC, |open, &b| {
+            unupdate_crc(open,
+                         self.crc_table,
+                         &INVERSE_CRC_TABLE,
+                         self.map_byte(b))
+        });
+        open ^ INIT_CRC
+    }
+
+    /// The CRC of the suffix of a message, given the CRC of
+    /// the whole message, its prefix, and the length of the
+    /// suffix.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// let whole = context.crc(b"HEADERpayload");
+    /// assert_eq!(context.strip_prefix(whole, b"HEADER", 7),
+    ///            context.crc(b"payload"));
+    /// ```
+    pub fn strip_prefix(&self, crc_of_whole: u32, prefix: &[u8], suffix_len: u64)
+                        -> u32
+    {
+        // The open CRC of the whole is that of the prefix
+        // extended by the suffix length, XOR the
+        // contribution of the suffix bytes themselves; the
+        // open CRC of the suffix is that contribution XOR
+        // INIT_CRC extended by the suffix length.
+        let prefix_crc = self.crc(prefix) ^ INIT_CRC;
+        let contribution = (crc_of_whole ^ INIT_CRC)
+            ^ self.extend_zeros(prefix_crc, suffix_len);
+        contribution ^ self.extend_zeros(INIT_CRC, suffix_len) ^ INIT_CRC
+    }
+}
+
+#[test]
+fn test_extend_zeros() {
+    use super::CRC_TABLE;
+
+    let context = RollingCRCContext::checksum_only();
+    for &open in &[INIT_CRC, 0, 0x1234_5678, 0x8000_0001] {
+        let mut crc = open;
+        for n in 0..5000 {
+            assert_eq!(context.extend_zeros(open, n), crc, "{:08x} {}", open, n);
+            crc = update_crc(crc, &CRC_TABLE, 0);
+        }
+    }
+
+    // A billion zeros, as a thousand literal millions.
+    let open = 0xdead_beef;
+    let million = (0..1_000_000)
+        .fold(open, |crc, _| update_crc(crc, &CRC_TABLE, 0));
+    assert_eq!(context.extend_zeros(open, 1_000_000), million);
+    let billion = (0..1000)
+        .fold(open, |crc, _| context.extend_zeros(crc, 1_000_000));
+    assert_eq!(context.extend_zeros(open, 1_000_000_000), billion);
+
+    // CRC(a ++ zeros) by extension and by combination.
+    let n = 1_000_000_000;
+    let crc_a = context.crc(b"some prefix");
+    let crc_zeros = context.extend_zeros(INIT_CRC, n) ^ INIT_CRC;
+    assert_eq!(context.extend_zeros(crc_a ^ INIT_CRC, n) ^ INIT_CRC,
+               combine(crc_a, crc_zeros, n));
+}
+
+#[test]
+fn test_strip_prefix() {
+    let context = RollingCRCContext::checksum_only();
+    let bytes = test_bytes(7, 5000);
+    for &(split, len) in &[(0, 0), (0, 100), (100, 100), (1, 5000),
+                           (3000, 3001), (4999, 5000)] {
+        let (prefix, suffix) = bytes[..len].split_at(split);
+        let whole = context.crc(&bytes[..len]);
+        assert_eq!(context.strip_prefix(whole, prefix, suffix.len() as u64),
+                   context.crc(suffix), "{} {}", split, len);
+    }
+
+    // With a byte map, the prefix is mapped as usual.
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8).to_ascii_uppercase();
+    }
+    let context = RollingCRCContext::checksum_only_with_byte_map(fold);
+    let whole = context.crc(b"Header: payload");
+    assert_eq!(context.strip_prefix(whole, b"HEADER: ", 7),
+               context.crc(b"payload"));
+}
+
+#[test]
+fn test_update_edit() {
+    let mut bytes = test_bytes(8, 10_000);
+    let edits = test_bytes(9, 10_000);
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8) >> 1;
+    }
+    let contexts = [
+        RollingCRCContext::checksum_only(),
+        RollingCRCContext::checksum_only_with_byte_map(fold),
+    ];
+    for context in &contexts {
+        let mut crc = context.crc(&bytes);
+        let mut next = test_rng(5);
+        for _ in 0..200 {
+            let state = next();
+            let len = state as usize % 20;
+            let offset = (state >> 8) as usize % (bytes.len() - len + 1);
+            let new = &edits[offset..offset + len];
+            let old = bytes[offset..offset + len].to_vec();
+            crc = context.update_edit(crc,
+                                      bytes.len() as u64,
+                                      offset as u64,
+                                      &old,
+                                      new);
+            bytes[offset..offset + len].copy_from_slice(new);
+            assert_eq!(crc, context.crc(&bytes));
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "update_edit: edit past end of message")]
+fn test_update_edit_wrapped() {
+    // The end of the edit wraps to 1, within the message.
+    let context = RollingCRCContext::checksum_only();
+    context.update_edit(0, 10, u64::MAX, b"ab", b"cd");
+}
+
+#[test]
+fn test_shrink_zeros() {
+    let context = RollingCRCContext::checksum_only();
+    for &n in &[0, 1, 2, 3, 4, 100, 12345, 1 << 40] {
+        for &open in &[INIT_CRC, 1, 0x8000_0000, 0x1234_5678] {
+            assert_eq!(shrink_zeros(context.extend_zeros(open, n), n), open);
+            assert_eq!(context.extend_zeros(shrink_zeros(open, n), n), open);
+        }
+    }
+}
+
+#[test]
+fn test_rewind() {
+    let bytes = test_bytes(10, 2000);
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8).rotate_left(3);
+    }
+    let contexts = [
+        RollingCRCContext::checksum_only(),
+        RollingCRCContext::checksum_only_with_byte_map(fold),
+    ];
+    for context in &contexts {
+        let mut next = test_rng(3);
+        for _ in 0..100 {
+            let state = next();
+            let len = state as usize % bytes.len();
+            let k = (state >> 12) as usize % (len + 1);
+            let buf = &bytes[..len];
+            assert_eq!(context.rewind(context.crc(buf), &buf[len - k..]),
+                       context.crc(&buf[..len - k]));
+        }
+        assert_eq!(context.rewind(context.crc(&bytes), b""),
+                   context.crc(&bytes));
+        assert_eq!(context.rewind(context.crc(&bytes), &bytes),
+                   context.crc(b""));
+    }
+}
+
+#[test]
+fn test_combine() {
+    use super::{calc_crc, CRC_TABLE};
+
+    let mut next = test_rng(0x1234_5678u32);
+    let bytes: Vec<u8> = (0..100_000).map(|_| next() as u8).collect();
+    let crc = |buf: &[u8]| calc_crc(buf, &CRC_TABLE);
+
+    for _ in 0..200 {
+        let len = next() as usize % bytes.len();
+        let split = next() as usize % (len + 1);
+        let (a, b) = bytes[..len].split_at(split);
+        assert_eq!(combine(crc(a), crc(b), b.len() as u64), crc(&bytes[..len]));
+    }
+    for &len in &[0, 1, 7, 1000] {
+        let buf = &bytes[..len];
+        assert_eq!(combine(crc(b""), crc(buf), len as u64), crc(buf));
+        assert_eq!(combine(crc(buf), crc(b""), 0), crc(buf));
+    }
+
+    // Many parts.
+    let parts: Vec<&[u8]> = bytes.chunks(777).collect();
+    let combined = parts.iter()
+        .fold(crc(b""), |acc, part| combine(acc, crc(part), part.len() as u64));
+    assert_eq!(combined, crc(&bytes));
+}
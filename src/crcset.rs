@@ -0,0 +1,167 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Fast membership tests of window CRCs against a fixed set
+//! of target CRCs.
+
+use alloc::vec::Vec;
+
+/// A fixed set of target CRCs, built for checking every
+/// window of a stream against many patterns at once. In
+/// that use nearly every probe misses, so a probe first
+/// checks one bit of a filter, two instructions or so, and
+/// only consults the sorted targets if the bit is set. With
+/// the filter sized at 16 bits per target, about one miss
+/// in sixteen gets past it.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(5).unwrap();
+/// let targets = CrcSet::new(vec![context.crc(b"hello"), context.crc(b"world")]);
+/// let found: Vec<usize> = context.rolling_crcs(b"hello, world!")
+///     .filter(|&(_, crc)| targets.contains(crc))
+///     .map(|(offset, _)| offset)
+///     .collect();
+/// assert_eq!(found, vec![0, 7]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrcSet {
+    /// Filter bits, indexed by the high bits of a mixed
+    /// CRC.
+    filter: Vec<u64>,
+    /// Shift taking a mixed CRC to its filter bit index.
+    shift: u32,
+    /// The targets, sorted and deduplicated.
+    crcs: Vec<u32>,
+}
+
+impl CrcSet {
+
+    /// Build a set of the given target CRCs.
+    pub fn new<I>(crcs: I) -> Self
+        where I: IntoIterator<Item=u32>
+    {
+        let mut crcs: Vec<u32> = crcs.into_iter().collect();
+        crcs.sort_unstable();
+        crcs.dedup();
+        let nbits = Self::filter_bits(crcs.len());
+        let shift = 32 - nbits.trailing_zeros();
+        let mut filter = vec![0u64; (nbits / 64) as usize];
+        for &crc in &crcs {
+            let bit = Self::mix(crc) >> shift;
+            filter[bit as usize >> 6] |= 1 << (bit & 63);
+        }
+        Self { filter, shift, crcs }
+    }
+
+    /// Number of filter bits for a set of `ncrcs` CRCs:
+    /// about 16 per CRC, as a power of two no smaller than
+    /// a word and no larger than 2^32, one per mixed CRC.
+    fn filter_bits(ncrcs: usize) -> u64 {
+        (ncrcs as u64 * 16).next_power_of_two().clamp(64, 1 << 32)
+    }
+
+    /// Scramble a CRC so that its high bits depend on all of
+    /// its bits.
+    #[inline(always)]
+    fn mix(crc: u32) -> u32 {
+        crc.wrapping_mul(0x9e37_79b9)
+    }
+
+    /// Is the given CRC in the set?
+    #[inline]
+    pub fn contains(&self, crc: u32) -> bool {
+        let bit = Self::mix(crc) >> self.shift;
+        let word = self.filter[bit as usize >> 6];
+        word & (1 << (bit & 63)) != 0 && self.crcs.binary_search(&crc).is_ok()
+    }
+
+    /// Number of CRCs in the set.
+    pub fn len(&self) -> usize {
+        self.crcs.len()
+    }
+
+    /// Is the set empty?
+    pub fn is_empty(&self) -> bool {
+        self.crcs.is_empty()
+    }
+
+    /// The CRCs in the set, in ascending order.
+    pub fn crcs(&self) -> &[u32] {
+        &self.crcs
+    }
+}
+
+#[cfg(test)]
+fn test_crcs(seed: u32, n: usize) -> Vec<u32> {
+    let mut next = super::test_rng(seed);
+    (0..n).map(|_| next()).collect()
+}
+
+#[test]
+fn test_crc_set() {
+    for &n in &[0, 1, 3, 64, 1000, 100_000] {
+        let targets = test_crcs(1, n);
+        let set = CrcSet::new(targets.iter().cloned());
+        assert_eq!(set.len(), n);
+        assert!(targets.iter().all(|&crc| set.contains(crc)));
+
+        let others = test_crcs(2, 100_000);
+        let exact: std::collections::HashSet<u32> =
+            targets.iter().cloned().collect();
+        for &crc in &others {
+            assert_eq!(set.contains(crc), exact.contains(&crc));
+        }
+    }
+
+    // Structured CRCs, differing only in their high or low
+    // bits, are still spread across the filter.
+    let low: Vec<u32> = (0..1000).collect();
+    let high: Vec<u32> = (0..1000).map(|i| i << 22).collect();
+    for targets in [low, high].iter() {
+        let set = CrcSet::new(targets.iter().cloned());
+        assert!(targets.iter().all(|&crc| set.contains(crc)));
+        let filled: u32 = set.filter.iter().map(|w| w.count_ones()).sum();
+        assert!(filled > 900);
+    }
+
+    let set = CrcSet::new(vec![5, 3, 5, 1]);
+    assert_eq!(set.crcs(), &[1, 3, 5]);
+}
+
+#[test]
+fn test_filter_bits() {
+    assert_eq!(CrcSet::filter_bits(0), 64);
+    assert_eq!(CrcSet::filter_bits(5), 128);
+    assert_eq!(CrcSet::filter_bits(1 << 20), 1 << 24);
+    // Past 2^28 CRCs the filter stops growing, with a shift
+    // of 0.
+    assert_eq!(CrcSet::filter_bits((1 << 28) + 1), 1 << 32);
+    assert_eq!(CrcSet::filter_bits(u32::MAX as usize), 1 << 32);
+}
+
+#[test]
+#[ignore]
+fn bench_crc_set() {
+    use std::collections::HashSet;
+    use std::time::Instant;
+
+    let targets = test_crcs(3, 1000);
+    let probes = test_crcs(4, 50_000_000);
+    let set = CrcSet::new(targets.iter().cloned());
+    let exact: HashSet<u32> = targets.iter().cloned().collect();
+
+    let start = Instant::now();
+    let hits = probes.iter().filter(|&&crc| exact.contains(&crc)).count();
+    let hash_time = start.elapsed();
+    let start = Instant::now();
+    assert_eq!(probes.iter().filter(|&&crc| set.contains(crc)).count(), hits);
+    let filter_time = start.elapsed();
+    println!("{} probes: HashSet {:?}, CrcSet {:?}",
+             probes.len(), hash_time, filter_time);
+    assert!(filter_time < hash_time);
+}
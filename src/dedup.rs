@@ -0,0 +1,378 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Detection of windows repeated across many inputs.
+
+use super::{RollingCRC, RollingCRCContext};
+#[cfg(test)]
+use super::test_bytes;
+
+use std::collections::{BTreeMap, HashMap};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Default maximum number of windows remembered by a
+/// `DedupDetector`.
+pub const DEFAULT_DEDUP_CAPACITY: usize = 1 << 20;
+
+/// A window seen again by a `DedupDetector`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(::defmt::Format))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct DupHit {
+    /// Source in which the window was seen again.
+    pub source_id: u32,
+    /// Offset of the window in that source.
+    pub offset: usize,
+    /// Source in which the window was first seen.
+    pub prior_source: u32,
+    /// Offset of the window in that source.
+    pub prior_offset: usize,
+}
+
+/// A remembered window.
+#[derive(Debug, Clone)]
+struct DedupEntry {
+    /// Source in which the window was first seen.
+    source_id: u32,
+    /// Offset of the window in that source.
+    offset: usize,
+    /// Copy of the window, for verification.
+    window: Box<[u8]>,
+    /// Time of last use, as a key into the LRU queue.
+    last_used: u64,
+}
+
+/// A detector of windows that recur across a sequence of
+/// inputs. Windows are indexed by CRC, and a copy of each
+/// is retained so that hits are verified byte-for-byte: CRC
+/// collisions never produce false hits. Memory is bounded by
+/// a capacity on the number of windows remembered, evicting
+/// the least recently seen window when it is reached.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(6).unwrap();
+/// let mut detector = DedupDetector::new(&context);
+/// assert!(detector.scan(1, b"first:shared!").is_empty());
+/// let hits = detector.scan(2, b"the second:shared!");
+/// assert_eq!(hits, vec![
+///     DupHit { source_id: 2, offset: 10, prior_source: 1, prior_offset: 5 },
+///     DupHit { source_id: 2, offset: 11, prior_source: 1, prior_offset: 6 },
+///     DupHit { source_id: 2, offset: 12, prior_source: 1, prior_offset: 7 },
+/// ]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DedupDetector<'a> {
+    /// Context for the window size.
+    context: &'a RollingCRCContext<'a>,
+    /// Maximum number of windows remembered.
+    capacity: usize,
+    /// Remembered windows by CRC.
+    entries: HashMap<u32, DedupEntry>,
+    /// CRCs of remembered windows by time of last use.
+    lru: BTreeMap<u64, u32>,
+    /// Current time.
+    now: u64,
+}
+
+impl<'a> DedupDetector<'a> {
+
+    /// Make a new detector in the given context,
+    /// remembering up to `DEFAULT_DEDUP_CAPACITY` windows.
+    pub fn new(context: &'a RollingCRCContext<'a>) -> Self {
+        Self {
+            context,
+            capacity: DEFAULT_DEDUP_CAPACITY,
+            entries: HashMap::new(),
+            lru: BTreeMap::new(),
+            now: 0,
+        }
+    }
+
+    /// Remember at most `capacity` windows (at least one).
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self.evict();
+        self
+    }
+
+    /// Number of windows currently remembered.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Are no windows remembered?
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Scan the bytes of the given source, returning a hit
+    /// for each window that has been seen before, in this
+    /// or an earlier source, in order of offset. Each hit
+    /// refers to the first remembered occurrence of its
+    /// window. Windows not seen before are remembered.
+    pub fn scan(&mut self, source_id: u32, bytes: &[u8]) -> Vec<DupHit> {
+        let window_size = self.context.window_size();
+        let mut hits = Vec::new();
+        for (offset, crc) in self.context.rolling_crcs(bytes) {
+            let window = &bytes[offset..offset + window_size];
+            self.now += 1;
+            if let Some(entry) = self.entries.get_mut(&crc) {
+                let _ = self.lru.remove(&entry.last_used);
+                let _ = self.lru.insert(self.now, crc);
+                entry.last_used = self.now;
+                if *entry.window == *window {
+                    hits.push(DupHit {
+                        source_id,
+                        offset,
+                        prior_source: entry.source_id,
+                        prior_offset: entry.offset,
+                    });
+                } else {
+                    // A CRC collision: remember the newer
+                    // window instead.
+                    entry.source_id = source_id;
+                    entry.offset = offset;
+                    entry.window = window.into();
+                }
+                continue;
+            }
+            let _ = self.entries.insert(crc, DedupEntry {
+                source_id,
+                offset,
+                window: window.into(),
+                last_used: self.now,
+            });
+            let _ = self.lru.insert(self.now, crc);
+            self.evict();
+        }
+        hits
+    }
+
+    /// Forget least recently used windows until within
+    /// capacity.
+    fn evict(&mut self) {
+        while self.entries.len() > self.capacity {
+            let (&time, &crc) = self.lru.iter().next()
+                .expect("internal error: LRU queue empty");
+            let _ = self.lru.remove(&time);
+            let _ = self.entries.remove(&crc);
+        }
+    }
+}
+
+/// A window seen again by a `RecentWindowCache`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(::defmt::Format))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecentHit {
+    /// Stream offset of the window just seen.
+    pub current_offset: u64,
+    /// Stream offset of its most recent earlier occurrence.
+    pub earlier_offset: u64,
+}
+
+/// A cache of the CRCs of recently seen windows of a byte
+/// stream, for detecting repeats within recent traffic, as
+/// for on-the-wire redundancy elimination. Up to `capacity`
+/// distinct window CRCs are remembered with the offset at
+/// which each was last seen; the least recently seen is
+/// forgotten first.
+///
+/// No window bytes are retained, so matches are by CRC
+/// alone unless verified with `push_verified()`.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(4).unwrap();
+/// let mut cache = RecentWindowCache::new(&context, 100);
+/// let hits: Vec<RecentHit> = b"abcdXabcd".iter()
+///     .filter_map(|&b| cache.push(b))
+///     .collect();
+/// assert_eq!(hits, vec![RecentHit { current_offset: 5, earlier_offset: 0 }]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RecentWindowCache<'a> {
+    /// Rolling CRC of the stream.
+    rolling_crc: RollingCRC<'a>,
+    /// Window size of the context.
+    window_size: u64,
+    /// Maximum number of windows remembered.
+    capacity: usize,
+    /// Offset of the last occurrence of each remembered
+    /// window, by CRC.
+    last_seen: HashMap<u32, u64>,
+    /// CRCs of remembered windows by offset of last
+    /// occurrence.
+    lru: BTreeMap<u64, u32>,
+    /// Number of bytes pushed so far.
+    position: u64,
+}
+
+impl<'a> RecentWindowCache<'a> {
+
+    /// Make a new cache in the given context remembering
+    /// up to `capacity` windows (at least one).
+    pub fn new(context: &'a RollingCRCContext<'a>, capacity: usize) -> Self {
+        Self {
+            rolling_crc: RollingCRC::new(context),
+            window_size: context.window_size() as u64,
+            capacity: capacity.max(1),
+            last_seen: HashMap::new(),
+            lru: BTreeMap::new(),
+            position: 0,
+        }
+    }
+
+    /// Number of windows currently remembered.
+    pub fn len(&self) -> usize {
+        self.last_seen.len()
+    }
+
+    /// Are no windows remembered?
+    pub fn is_empty(&self) -> bool {
+        self.last_seen.is_empty()
+    }
+
+    /// Roll the next stream byte, returning a hit if the
+    /// window ending with it has a remembered CRC.
+    pub fn push(&mut self, byte: u8) -> Option<RecentHit> {
+        self.push_verified(byte, |_| true)
+    }
+
+    /// Roll the next stream byte as with `push()`, but
+    /// report a hit only if `verify` accepts it, for
+    /// example after comparing the two windows in a buffer
+    /// of recent traffic.
+    pub fn push_verified<F>(&mut self, byte: u8, verify: F) -> Option<RecentHit>
+        where F: FnOnce(RecentHit) -> bool
+    {
+        self.position += 1;
+        let crc = self.rolling_crc.push(byte)?;
+        let current_offset = self.position - self.window_size;
+        let earlier = self.last_seen.insert(crc, current_offset);
+        let _ = self.lru.insert(current_offset, crc);
+        match earlier {
+            Some(earlier_offset) => {
+                let _ = self.lru.remove(&earlier_offset);
+                let hit = RecentHit { current_offset, earlier_offset };
+                if verify(hit) {
+                    return Some(hit);
+                }
+            },
+            None => {
+                if self.last_seen.len() > self.capacity {
+                    let (&offset, &crc) = self.lru.iter().next()
+                        .expect("internal error: LRU queue empty");
+                    let _ = self.lru.remove(&offset);
+                    let _ = self.last_seen.remove(&crc);
+                }
+            },
+        }
+        None
+    }
+}
+
+#[test]
+fn test_dedup_detector() {
+    let random = |seed: u32, len| {
+        test_bytes(seed.wrapping_mul(2654435761), len)
+    };
+    let window_size = 24;
+    let context = RollingCRCContext::new(window_size).unwrap();
+    let (x, y, z) = (random(1, 200), random(2, 300), random(3, 100));
+    let mut bufs = [random(4, 5000), random(5, 6000), random(6, 7000)];
+    bufs[0][100..300].copy_from_slice(&x);
+    bufs[2][6000..6200].copy_from_slice(&x);
+    bufs[1][4000..4300].copy_from_slice(&y);
+    bufs[2][50..350].copy_from_slice(&y);
+    bufs[0][2000..2100].copy_from_slice(&z);
+    bufs[1][10..110].copy_from_slice(&z);
+    bufs[2][3000..3100].copy_from_slice(&z);
+
+    // Hits for each window of a `len`-byte block at
+    // `offset` in `source_id`, first seen at `prior_offset`
+    // in `prior_source`.
+    let hits_for = |source_id, offset, prior_source, prior_offset, len| {
+        (0..len - window_size + 1).map(move |i| DupHit {
+            source_id,
+            offset: offset + i,
+            prior_source,
+            prior_offset: prior_offset + i,
+        })
+    };
+
+    let mut detector = DedupDetector::new(&context);
+    assert!(detector.scan(0, &bufs[0]).is_empty());
+    let hits = detector.scan(1, &bufs[1]);
+    let expected: Vec<DupHit> = hits_for(1, 10, 0, 2000, 100).collect();
+    assert_eq!(hits, expected);
+    let hits = detector.scan(2, &bufs[2]);
+    let expected: Vec<DupHit> = hits_for(2, 50, 1, 4000, 300)
+        .chain(hits_for(2, 3000, 0, 2000, 100))
+        .chain(hits_for(2, 6000, 0, 100, 200))
+        .collect();
+    assert_eq!(hits, expected);
+
+    // With room for little more than one source, the
+    // first is mostly forgotten, though its recently seen
+    // windows may survive.
+    let mut detector = DedupDetector::new(&context).capacity(6000);
+    let _ = detector.scan(0, &bufs[0]);
+    let _ = detector.scan(1, &bufs[1]);
+    assert_eq!(detector.len(), 6000);
+    let hits = detector.scan(2, &bufs[2]);
+    let y_hits: Vec<DupHit> = hits_for(2, 50, 1, 4000, 300).collect();
+    assert_eq!(hits[..y_hits.len()], y_hits[..]);
+    assert!(hits.iter().all(|hit| hit.offset < 6000));
+}
+
+#[test]
+fn test_recent_window_cache() {
+    let window_size = 16;
+    let context = RollingCRCContext::new(window_size).unwrap();
+    let capacity = 1000;
+    let pattern = |period| test_bytes(0x1234_5678, period);
+
+    // A repeat within capacity is always reported.
+    let period = 900;
+    let bytes = pattern(period).repeat(5);
+    let mut cache = RecentWindowCache::new(&context, capacity);
+    for (i, &b) in bytes.iter().enumerate() {
+        let hit = cache.push(b);
+        if i + 1 < period + window_size {
+            assert_eq!(hit, None);
+        } else {
+            let current_offset = (i + 1 - window_size) as u64;
+            assert_eq!(hit, Some(RecentHit {
+                current_offset,
+                earlier_offset: current_offset - period as u64,
+            }));
+        }
+    }
+    assert_eq!(cache.len(), period);
+
+    // A longer repeat has been evicted by the time it
+    // recurs.
+    let bytes = pattern(1100).repeat(5);
+    let mut cache = RecentWindowCache::new(&context, capacity);
+    assert!(bytes.iter().all(|&b| cache.push(b).is_none()));
+    assert_eq!(cache.len(), capacity);
+
+    // Rejected hits are not reported but still refresh
+    // the cache.
+    let bytes = pattern(100).repeat(3);
+    let mut cache = RecentWindowCache::new(&context, capacity);
+    let hits = bytes.iter()
+        .filter_map(|&b| cache.push_verified(b, |hit| hit.current_offset >= 200))
+        .count();
+    assert_eq!(hits, 300 - 200 - window_size + 1);
+}
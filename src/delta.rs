@@ -0,0 +1,200 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Delta encoding of a buffer against a reference buffer.
+
+use super::RollingCRCContext;
+#[cfg(test)]
+use super::test_bytes;
+
+use std::collections::HashMap;
+
+/// An operation of a delta produced by `encode_delta()`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum DeltaOp {
+    /// Copy bytes from the reference.
+    Copy {
+        /// Offset of the bytes in the reference.
+        ref_off: usize,
+        /// Number of bytes.
+        len: usize,
+    },
+    /// Insert these bytes.
+    Literal(Vec<u8>),
+}
+
+/// Encode `target` as a delta against `reference`: a
+/// sequence of operations that `apply_delta()` will turn
+/// back into `target`.
+///
+/// The reference is indexed by the CRCs of its consecutive
+/// `window`-byte blocks, and the rolling CRC of `target` is
+/// looked up in the index. Each block found is verified
+/// byte-for-byte and extended forward and backward into as
+/// long a copy as possible, taking the longest if the block
+/// occurs more than once in the reference; scanning
+/// resumes after each copy. Every run of at least
+/// `2 * window - 1` bytes shared with the reference is
+/// copied, at least in part. The encoding is greedy, not
+/// optimal.
+///
+/// # Panics
+///
+/// Panics if `window` is 0.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let reference = b"the quick brown fox jumps over the lazy dog";
+/// let target = b"the quick red fox jumps over the lazy dog";
+/// let delta = encode_delta(reference, target, 4);
+/// assert_eq!(delta, vec![
+///     DeltaOp::Copy { ref_off: 0, len: 10 },
+///     DeltaOp::Literal(b"red".to_vec()),
+///     DeltaOp::Copy { ref_off: 15, len: 28 },
+/// ]);
+/// assert_eq!(apply_delta(reference, &delta), target.to_vec());
+/// ```
+pub fn encode_delta(reference: &[u8], target: &[u8], window: usize)
+                    -> Vec<DeltaOp>
+{
+    assert!(window > 0, "encode_delta: window must be positive");
+    let context = RollingCRCContext::build(window);
+    let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+    for ref_off in (0..reference.len() / window).map(|i| i * window) {
+        let crc = context.crc(&reference[ref_off..ref_off + window]);
+        index.entry(crc).or_default().push(ref_off);
+    }
+    let mut ops = Vec::new();
+    // Start in `target` of the bytes not yet encoded.
+    let mut pending = 0;
+    'scan: loop {
+        for (offset, crc) in context.rolling_crcs(&target[pending..]) {
+            let t_off = pending + offset;
+            let candidates = match index.get(&crc) {
+                Some(candidates) => candidates,
+                None => continue,
+            };
+            // Best match as (ref_off, t_off, len).
+            let mut best: Option<(usize, usize, usize)> = None;
+            for &ref_off in candidates {
+                let block = &reference[ref_off..ref_off + window];
+                if block != &target[t_off..t_off + window] {
+                    continue;
+                }
+                let forward = reference[ref_off + window..].iter()
+                    .zip(&target[t_off + window..])
+                    .take_while(|&(x, y)| x == y)
+                    .count();
+                let backward = reference[..ref_off].iter().rev()
+                    .zip(target[pending..t_off].iter().rev())
+                    .take_while(|&(x, y)| x == y)
+                    .count();
+                let len = backward + window + forward;
+                if best.is_none_or(|(_, _, best_len)| len > best_len) {
+                    best = Some((ref_off - backward, t_off - backward, len));
+                }
+            }
+            if let Some((ref_off, t_off, len)) = best {
+                if t_off > pending {
+                    ops.push(DeltaOp::Literal(target[pending..t_off].to_vec()));
+                }
+                ops.push(DeltaOp::Copy { ref_off, len });
+                pending = t_off + len;
+                continue 'scan;
+            }
+        }
+        if pending < target.len() {
+            ops.push(DeltaOp::Literal(target[pending..].to_vec()));
+        }
+        return ops;
+    }
+}
+
+/// Apply a delta produced by `encode_delta()` to the
+/// reference it was encoded against.
+///
+/// # Panics
+///
+/// Panics if a copy is outside the reference.
+pub fn apply_delta(reference: &[u8], ops: &[DeltaOp]) -> Vec<u8> {
+    let mut result = Vec::new();
+    for op in ops {
+        match *op {
+            DeltaOp::Copy { ref_off, len } => {
+                result.extend_from_slice(&reference[ref_off..ref_off + len]);
+            },
+            DeltaOp::Literal(ref data) => {
+                result.extend_from_slice(data);
+            },
+        }
+    }
+    result
+}
+
+#[test]
+fn test_delta() {
+    let window = 16;
+    let reference = test_bytes(1, 20_000);
+
+    let check = |target: &[u8]| -> Vec<DeltaOp> {
+        let delta = encode_delta(&reference, target, window);
+        assert_eq!(apply_delta(&reference, &delta), target);
+        delta
+    };
+    let literal_len = |delta: &[DeltaOp]| -> usize {
+        delta.iter()
+            .map(|op| match *op {
+                DeltaOp::Literal(ref data) => data.len(),
+                DeltaOp::Copy { .. } => 0,
+            })
+            .sum()
+    };
+
+    // Identical and disjoint targets.
+    assert_eq!(check(&reference),
+               vec![DeltaOp::Copy { ref_off: 0, len: reference.len() }]);
+    assert_eq!(check(b""), vec![]);
+    let other = test_bytes(2, 5000);
+    assert_eq!(check(&other), vec![DeltaOp::Literal(other.clone())]);
+    assert_eq!(encode_delta(b"", &other, window),
+               vec![DeltaOp::Literal(other.clone())]);
+
+    // An insertion and a deletion.
+    let mut target = reference[..3000].to_vec();
+    target.extend_from_slice(&other[..700]);
+    target.extend_from_slice(&reference[3000..12_000]);
+    target.extend_from_slice(&reference[12_500..]);
+    let delta = check(&target);
+    assert_eq!(literal_len(&delta), 700);
+    assert_eq!(delta.len(), 4);
+
+    // Repeated and reordered sections, with a short tail.
+    let mut target = Vec::new();
+    for _ in 0..3 {
+        target.extend_from_slice(&reference[5000..6000]);
+    }
+    target.extend_from_slice(&reference[15_000..16_000]);
+    target.extend_from_slice(&reference[100..1100]);
+    target.extend_from_slice(&other[..5]);
+    let delta = check(&target);
+    assert_eq!(literal_len(&delta), 5);
+    assert_eq!(delta[0], DeltaOp::Copy { ref_off: 5000, len: 1000 });
+
+    // Every edit of a small target round trips.
+    let target = test_bytes(3, 300);
+    let reference = {
+        let mut r = target.clone();
+        r.splice(100..110, other[..25].iter().cloned());
+        r
+    };
+    for t in [&target[..], &target[1..], &target[..299], &reference[..]].iter() {
+        for w in 1..20 {
+            let delta = encode_delta(&reference, t, w);
+            assert_eq!(apply_delta(&reference, &delta), t.to_vec());
+        }
+    }
+}
@@ -0,0 +1,195 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Incremental computation of whole-message CRCs.
+
+use super::{finish_crc, RollingCRCContext, INIT_CRC};
+#[cfg(test)]
+use super::{test_bytes, test_rng};
+
+/// The state of a CRC computation in progress: an "open"
+/// CRC, which is a finished CRC XORed with `INIT_CRC`. This
+/// is the continuation token for `resume_open()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct OpenCrc(u32);
+
+impl OpenCrc {
+
+    /// The state before any bytes have been hashed.
+    pub fn new() -> Self {
+        OpenCrc(INIT_CRC)
+    }
+
+    /// The state after hashing a message with the given
+    /// finished CRC.
+    pub fn reopen(finished_crc: u32) -> Self {
+        OpenCrc(finish_crc(finished_crc))
+    }
+
+    /// The finished CRC of the bytes hashed so far.
+    pub fn finish(self) -> u32 {
+        finish_crc(self.0)
+    }
+
+    /// The open CRC value.
+    pub fn value(self) -> u32 {
+        self.0
+    }
+}
+
+impl Default for OpenCrc {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl ::defmt::Format for OpenCrc {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "OpenCrc({=u32:#010x})", self.0)
+    }
+}
+
+impl<'a> RollingCRCContext<'a> {
+
+    /// The CRC of a message with the given finished CRC
+    /// followed by `more`: the same as `crc()` of the
+    /// concatenation, without rehashing the start.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// let stored = context.crc(b"log line 1\n");
+    /// assert_eq!(context.resume(stored, b"log line 2\n"),
+    ///            context.crc(b"log line 1\nlog line 2\n"));
+    /// ```
+    pub fn resume(&self, finished_crc: u32, more: &[u8]) -> u32 {
+        self.resume_open(OpenCrc::reopen(finished_crc), more).finish()
+    }
+
+    /// Continue a CRC computation in progress over more
+    /// bytes.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// let state = context.resume_open(OpenCrc::new(), b"hello ");
+    /// let state = context.resume_open(state, b"world");
+    /// assert_eq!(state.finish(), context.crc(b"hello world"));
+    /// ```
+    pub fn resume_open(&self, state: OpenCrc, more: &[u8]) -> OpenCrc {
+        OpenCrc(self.update_open(state.0, more))
+    }
+}
+
+/// An incremental CRC of a whole message, fed in pieces.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::checksum_only();
+/// let mut digest = Crc32Digest::new(&context);
+/// digest.update(b"hello ");
+/// digest.update(b"world");
+/// assert_eq!(digest.finalize(), context.crc(b"hello world"));
+/// ```
+#[derive(Debug, Clone)]
+pub struct Crc32Digest<'a> {
+    /// Context giving the CRC parameters.
+    context: &'a RollingCRCContext<'a>,
+    /// CRC of the bytes so far.
+    state: OpenCrc,
+}
+
+impl<'a> Crc32Digest<'a> {
+
+    /// Start a new CRC in the given context. Only the
+    /// context's CRC parameters and byte map matter: its
+    /// window size is ignored.
+    pub fn new(context: &'a RollingCRCContext<'a>) -> Self {
+        Self { context, state: OpenCrc::new() }
+    }
+
+    /// Hash the next bytes of the message.
+    pub fn update(&mut self, bytes: &[u8]) {
+        self.state = self.context.resume_open(self.state, bytes);
+    }
+
+    /// The CRC of the message so far. Hashing may
+    /// continue afterward.
+    pub fn finalize(&self) -> u32 {
+        self.state.finish()
+    }
+
+    /// The CRC of the message so far, starting a new
+    /// message.
+    pub fn finalize_reset(&mut self) -> u32 {
+        let crc = self.finalize();
+        self.state = OpenCrc::new();
+        crc
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<'a> ::defmt::Format for Crc32Digest<'a> {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "Crc32Digest {{ crc: {=u32:#010x} }}",
+                        self.finalize())
+    }
+}
+
+#[test]
+fn test_resume() {
+    let bytes = test_bytes(11, 3000);
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8) | 0x80;
+    }
+    let contexts = [
+        RollingCRCContext::checksum_only(),
+        RollingCRCContext::checksum_only_with_byte_map(fold),
+    ];
+    for context in &contexts {
+        let whole = context.crc(&bytes);
+        for split in (0..=bytes.len()).step_by(37).chain(Some(bytes.len())) {
+            let (a, b) = bytes.split_at(split);
+            assert_eq!(context.resume(context.crc(a), b), whole);
+            let state = context.resume_open(OpenCrc::new(), a);
+            assert_eq!(state.finish(), context.crc(a));
+            assert_eq!(context.resume_open(state, b).finish(), whole);
+        }
+        assert_eq!(context.resume(context.crc(b""), b""), context.crc(b""));
+    }
+    assert_eq!(OpenCrc::reopen(0x1234_5678).finish(), 0x1234_5678);
+    assert_eq!(OpenCrc::default().value(), INIT_CRC);
+}
+
+#[test]
+fn test_crc32_digest() {
+    let bytes = test_bytes(12, 20_000);
+    let context = RollingCRCContext::checksum_only();
+    let whole = context.crc(&bytes);
+    let mut next = test_rng(99);
+    for _ in 0..50 {
+        let mut digest = Crc32Digest::new(&context);
+        let mut rest = &bytes[..];
+        while !rest.is_empty() {
+            let n = (next() as usize % 3000).min(rest.len());
+            digest.update(&rest[..n]);
+            rest = &rest[n..];
+        }
+        assert_eq!(digest.finalize(), whole);
+        assert_eq!(digest.finalize(), whole);
+        assert_eq!(digest.finalize_reset(), whole);
+        assert_eq!(digest.finalize(), context.crc(b""));
+        digest.update(b"abc");
+        assert_eq!(digest.finalize(), context.crc(b"abc"));
+    }
+}
@@ -0,0 +1,140 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Rolling CRCs of `embedded_io::Read` streams, for
+//! `no_std` targets.
+
+use super::{RollingCRCBuf, RollingCRCContext};
+#[cfg(test)]
+use super::test_bytes;
+
+use embedded_io::{Error, ErrorKind, Read};
+
+/// Pass the rolling CRC of every window of the stream from
+/// `reader` to `sink`, along with the stream offset of the
+/// window start. The first `context.window_size()` bytes
+/// of `buf` hold the window; the rest receive reads. The
+/// rolling state carries across reads, so each byte is
+/// rolled just once. Interrupted reads are retried.
+///
+/// # Panics
+///
+/// Panics if `buf` is not longer than the window size.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rolling_crc;
+/// # use rolling_crc::*;
+/// # fn main() {
+/// let context = RollingCRCContext::new(5).unwrap();
+/// let mut buf = [0; 64];
+/// let mut last = None;
+/// scan_embedded(&context, &b"hello world"[..], &mut buf, |offset, crc| {
+///     last = Some((offset, crc));
+/// }).unwrap();
+/// assert_eq!(last, Some((6, context.crc(b"world"))));
+/// # }
+/// ```
+pub fn scan_embedded<R, F>(context: &RollingCRCContext,
+                           mut reader: R,
+                           buf: &mut [u8],
+                           mut sink: F)
+                           -> Result<(), R::Error>
+    where R: Read, F: FnMut(u64, u32)
+{
+    let window_size = context.window_size();
+    assert!(buf.len() > window_size,
+            "scan_embedded: buffer must be longer than the window");
+    let (window, scratch) = buf.split_at_mut(window_size);
+    let mut roller = RollingCRCBuf::with_buffer(context, window);
+    // Number of bytes of the stream rolled so far.
+    let mut count = 0u64;
+    loop {
+        let nread = match reader.read(scratch) {
+            Ok(0) => return Ok(()),
+            Ok(nread) => nread,
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        for &b in &scratch[..nread] {
+            count += 1;
+            if let Some(crc) = roller.push(b) {
+                sink(count - window_size as u64, crc);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_scan_embedded() {
+    use embedded_io::ErrorType;
+
+    /// A reader returning at most `max` bytes per read, and
+    /// an interruption before every other read.
+    struct Trickle<'a> {
+        bytes: &'a [u8],
+        max: usize,
+        interrupt: bool,
+    }
+
+    #[derive(Debug)]
+    struct Interrupted;
+
+    impl Error for Interrupted {
+        fn kind(&self) -> ErrorKind {
+            ErrorKind::Interrupted
+        }
+    }
+
+    impl<'a> ErrorType for Trickle<'a> {
+        type Error = Interrupted;
+    }
+
+    impl<'a> Read for Trickle<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize, Interrupted> {
+            self.interrupt = !self.interrupt;
+            if self.interrupt {
+                return Err(Interrupted);
+            }
+            let n = self.max.min(buf.len()).min(self.bytes.len());
+            buf[..n].copy_from_slice(&self.bytes[..n]);
+            self.bytes = &self.bytes[n..];
+            Ok(n)
+        }
+    }
+
+    let bytes = test_bytes(0x9e37_79b9, 2000);
+    let mut buf = [0; 200];
+    for &window_size in &[0, 1, 2, 7, 64, 199] {
+        let context = RollingCRCContext::build(window_size);
+        let expected: Vec<(u64, u32)> = context.rolling_crcs(&bytes)
+            .map(|(offset, crc)| (offset as u64, crc))
+            .collect();
+
+        let mut crcs = Vec::new();
+        scan_embedded(&context, &bytes[..], &mut buf, |offset, crc| {
+            crcs.push((offset, crc));
+        }).unwrap();
+        assert_eq!(crcs, expected);
+
+        for &max in &[1, 3, 50] {
+            let reader = Trickle { bytes: &bytes, max, interrupt: false };
+            let mut crcs = Vec::new();
+            scan_embedded(&context, reader, &mut buf, |offset, crc| {
+                crcs.push((offset, crc));
+            }).unwrap();
+            assert_eq!(crcs, expected);
+        }
+    }
+}
+
+#[test]
+#[should_panic(expected = "buffer must be longer than the window")]
+fn test_scan_embedded_short_buffer() {
+    let context = RollingCRCContext::new(8).unwrap();
+    let mut buf = [0; 8];
+    let _ = scan_embedded(&context, &b"0123456789"[..], &mut buf, |_, _| ());
+}
@@ -0,0 +1,214 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! A crate-wide error type, into which each of the more
+//! specific error types converts.
+
+use super::{CompareError, FingerprintError, FollowError, ForgeError,
+            SearchError, SelfTestError, WindowSizeError};
+#[cfg(feature = "vectors")]
+use super::vectors::VectorError;
+
+use std::error;
+use std::fmt;
+use std::io;
+
+/// Any error from this crate. Each of the more specific
+/// error types returned by individual functions converts
+/// into this one, so that `?` can gather them into one
+/// type.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum Error {
+    /// A window size was zero.
+    InvalidWindowSize,
+    /// Window sizes that must agree differ.
+    WindowSizeMismatch {
+        /// Window size needed.
+        expected: u64,
+        /// Window size found.
+        found: u64,
+    },
+    /// A pattern's length differs from the window size.
+    PatternLengthMismatch {
+        /// Window size.
+        expected: usize,
+        /// Length of the pattern.
+        found: usize,
+    },
+    /// CRC polynomials that must agree differ.
+    PolynomialMismatch {
+        /// Polynomial needed.
+        expected: u32,
+        /// Polynomial found.
+        found: u32,
+    },
+    /// A file or serialized table is malformed or of an
+    /// unsupported format.
+    TableFormat(String),
+    /// Reference CRCs differ from those of this crate.
+    Mismatch(String),
+    /// A CRC could not be forged.
+    Forge(ForgeError),
+    /// Reading or writing failed.
+    Io(io::Error),
+    /// A search was cancelled.
+    Cancelled,
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::InvalidWindowSize =>
+                write!(f, "window size must be positive"),
+            Error::WindowSizeMismatch { expected, found } =>
+                write!(f, "window size {} does not match {}",
+                       found, expected),
+            Error::PatternLengthMismatch { expected, found } =>
+                write!(f, "pattern length {} does not match window size {}",
+                       found, expected),
+            Error::PolynomialMismatch { expected, found } =>
+                write!(f, "polynomial {:08x} does not match {:08x}",
+                       found, expected),
+            Error::TableFormat(ref message) =>
+                write!(f, "bad table format: {}", message),
+            Error::Mismatch(ref message) =>
+                write!(f, "CRC mismatch: {}", message),
+            Error::Forge(ref e) => write!(f, "{}", e),
+            Error::Io(ref e) => write!(f, "I/O error: {}", e),
+            Error::Cancelled => write!(f, "search cancelled"),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            Error::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<WindowSizeError> for Error {
+    fn from(_: WindowSizeError) -> Self {
+        Error::InvalidWindowSize
+    }
+}
+
+impl From<SearchError> for Error {
+    fn from(e: SearchError) -> Self {
+        match e {
+            SearchError::Cancelled => Error::Cancelled,
+            SearchError::Io(e) => Error::Io(e),
+        }
+    }
+}
+
+impl From<ForgeError> for Error {
+    fn from(e: ForgeError) -> Self {
+        Error::Forge(e)
+    }
+}
+
+impl From<SelfTestError> for Error {
+    fn from(e: SelfTestError) -> Self {
+        Error::Mismatch(e.to_string())
+    }
+}
+
+impl From<FingerprintError> for Error {
+    fn from(e: FingerprintError) -> Self {
+        match e {
+            FingerprintError::Io(e) => Error::Io(e),
+            FingerprintError::WindowSizeMismatch { expected, found } =>
+                Error::WindowSizeMismatch { expected: expected as u64, found },
+            e => Error::TableFormat(e.to_string()),
+        }
+    }
+}
+
+impl From<FollowError> for Error {
+    fn from(e: FollowError) -> Self {
+        match e {
+            FollowError::PatternLength { expected, found, .. } =>
+                Error::PatternLengthMismatch { expected, found },
+            FollowError::Io(e) => Error::Io(e),
+        }
+    }
+}
+
+impl From<CompareError> for Error {
+    fn from(e: CompareError) -> Self {
+        match e {
+            CompareError::WindowSizeMismatch(expected, found) =>
+                Error::WindowSizeMismatch { expected, found },
+            CompareError::PolynomialMismatch(expected, found) =>
+                Error::PolynomialMismatch { expected, found },
+        }
+    }
+}
+
+#[cfg(feature = "vectors")]
+impl From<VectorError> for Error {
+    fn from(e: VectorError) -> Self {
+        match e {
+            VectorError::Mismatch { .. } => Error::Mismatch(e.to_string()),
+            e => Error::TableFormat(e.to_string()),
+        }
+    }
+}
+
+#[test]
+fn test_error() {
+    use std::error::Error as _;
+
+    fn window(size: usize) -> Result<usize, Error> {
+        Ok(super::RollingCRCContext::new(size)?.window_size())
+    }
+    assert_eq!(window(3).unwrap(), 3);
+    let e = window(0).unwrap_err();
+    assert!(matches!(e, Error::InvalidWindowSize));
+    assert_eq!(e.to_string(), "window size must be positive");
+    assert!(e.source().is_none());
+
+    let e = Error::from(io::Error::other("disk on fire"));
+    assert_eq!(e.to_string(), "I/O error: disk on fire");
+    let source = e.source().expect("no source");
+    assert_eq!(source.to_string(), "disk on fire");
+    let e = Error::from(SearchError::Io(io::ErrorKind::NotFound.into()));
+    assert_eq!(e.source().unwrap().to_string(), "entity not found");
+    assert_eq!(Error::from(SearchError::Cancelled).to_string(),
+               "search cancelled");
+
+    assert_eq!(Error::from(CompareError::WindowSizeMismatch(8, 16))
+               .to_string(),
+               "window size 16 does not match 8");
+    assert_eq!(Error::from(CompareError::PolynomialMismatch(1, 2))
+               .to_string(),
+               "polynomial 00000002 does not match 00000001");
+    assert_eq!(Error::from(FingerprintError::BadMagic).to_string(),
+               "bad table format: not a fingerprint file");
+    let e = FollowError::PatternLength { index: 2, expected: 8, found: 5 };
+    let e = Error::from(e);
+    assert!(matches!(e,
+                     Error::PatternLengthMismatch { expected: 8, found: 5 }));
+    assert_eq!(e.to_string(), "pattern length 5 does not match window size 8");
+    let e = FollowError::Io(io::ErrorKind::NotFound.into());
+    assert_eq!(Error::from(e).source().unwrap().to_string(),
+               "entity not found");
+    assert_eq!(Error::from(ForgeError::ByteMap).to_string(),
+               "cannot forge CRCs through a byte map");
+    let e = SelfTestError::Rolling { window_size: 4, offset: 2 };
+    assert_eq!(Error::from(e).to_string(),
+               "CRC mismatch: rolling CRC at offset 2 is wrong \
+                for window size 4");
+}
@@ -0,0 +1,188 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! A C API for contexts and rolling CRCs, declared in
+//! `include/rolling_crc.h`. Build a shared library with
+//!
+//! ```text
+//! cargo rustc --release --features ffi --crate-type cdylib
+//! ```
+//!
+//! No panic crosses the C boundary: a function that
+//! panics returns `RCRC_PANIC`, or a null pointer if it
+//! returns a pointer.
+
+use super::{RollingCRC, RollingCRCContext};
+
+use std::os::raw::c_int;
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+
+/// Success.
+pub const RCRC_OK: c_int = 0;
+/// A required pointer argument was null.
+pub const RCRC_NULL_POINTER: c_int = -1;
+/// The call panicked.
+pub const RCRC_PANIC: c_int = -2;
+
+/// A rolling CRC context, opaque to C.
+pub struct RcrcContext(RollingCRCContext<'static>);
+
+/// A rolling CRC, opaque to C.
+pub struct RcrcRoller(RollingCRC<'static>);
+
+/// Run the given function, turning a panic into the given
+/// error value.
+fn guard<T, F: FnOnce() -> T>(on_panic: T, f: F) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or(on_panic)
+}
+
+/// Make a new context for the given window size, or return
+/// null on failure. Free it with `rcrc_context_free()`.
+#[no_mangle]
+pub extern "C" fn rcrc_context_new(window_size: usize) -> *mut RcrcContext {
+    guard(ptr::null_mut(), || {
+        let context = RcrcContext(RollingCRCContext::build(window_size));
+        Box::into_raw(Box::new(context))
+    })
+}
+
+/// Free a context. Null is ignored.
+///
+/// # Safety
+///
+/// `ctx` must be null or come from `rcrc_context_new()`,
+/// and must not be used again, nor any roller made from it.
+#[no_mangle]
+pub unsafe extern "C" fn rcrc_context_free(ctx: *mut RcrcContext) {
+    if !ctx.is_null() {
+        guard((), || drop(Box::from_raw(ctx)));
+    }
+}
+
+/// Compute the CRC of the `len` bytes at `data` into
+/// `*crc_out`. `data` may be null if `len` is 0.
+///
+/// # Safety
+///
+/// `ctx` must be a live context, `data` must point to `len`
+/// readable bytes and `crc_out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn rcrc_crc(ctx: *const RcrcContext,
+                                  data: *const u8,
+                                  len: usize,
+                                  crc_out: *mut u32)
+                                  -> c_int
+{
+    if ctx.is_null() || crc_out.is_null() || (data.is_null() && len > 0) {
+        return RCRC_NULL_POINTER;
+    }
+    guard(RCRC_PANIC, || {
+        let bytes = if len == 0 {
+            &[][..]
+        } else {
+            slice::from_raw_parts(data, len)
+        };
+        *crc_out = (*ctx).0.crc(bytes);
+        RCRC_OK
+    })
+}
+
+/// Start a new rolling CRC in the given context, or return
+/// null on failure. Free it with `rcrc_roller_free()`.
+///
+/// # Safety
+///
+/// `ctx` must be a live context, and must outlive the
+/// roller.
+#[no_mangle]
+pub unsafe extern "C" fn rcrc_roller_new(ctx: *const RcrcContext)
+                                         -> *mut RcrcRoller
+{
+    if ctx.is_null() {
+        return ptr::null_mut();
+    }
+    guard(ptr::null_mut(), || {
+        let context: &'static RollingCRCContext<'static> = &(*ctx).0;
+        Box::into_raw(Box::new(RcrcRoller(RollingCRC::new(context))))
+    })
+}
+
+/// Roll a byte through a rolling CRC. Returns 1 and sets
+/// `*crc_out` to the CRC of the window it completes, if
+/// any, and otherwise returns 0, or an error code.
+///
+/// # Safety
+///
+/// `roller` must be a live roller and `crc_out` must be
+/// writable.
+#[no_mangle]
+pub unsafe extern "C" fn rcrc_push(roller: *mut RcrcRoller,
+                                   byte: u8,
+                                   crc_out: *mut u32)
+                                   -> c_int
+{
+    if roller.is_null() || crc_out.is_null() {
+        return RCRC_NULL_POINTER;
+    }
+    guard(RCRC_PANIC, || {
+        match (*roller).0.push(byte) {
+            Some(crc) => {
+                *crc_out = crc;
+                1
+            },
+            None => 0,
+        }
+    })
+}
+
+/// Free a rolling CRC. Null is ignored.
+///
+/// # Safety
+///
+/// `roller` must be null or come from `rcrc_roller_new()`,
+/// and must not be used again.
+#[no_mangle]
+pub unsafe extern "C" fn rcrc_roller_free(roller: *mut RcrcRoller) {
+    if !roller.is_null() {
+        guard((), || drop(Box::from_raw(roller)));
+    }
+}
+
+#[test]
+fn test_ffi() {
+    let bytes = b"hello world";
+    unsafe {
+        let ctx = rcrc_context_new(5);
+        assert!(!ctx.is_null());
+        let mut crc = 0;
+        let status = rcrc_crc(ctx, bytes.as_ptr(), bytes.len(), &mut crc);
+        assert_eq!(status, RCRC_OK);
+        assert_eq!(crc, 0x0d4a1185);
+        assert_eq!(rcrc_crc(ctx, ptr::null(), 0, &mut crc), RCRC_OK);
+        assert_eq!(crc, 0);
+        let status = rcrc_crc(ctx, ptr::null(), 1, &mut crc);
+        assert_eq!(status, RCRC_NULL_POINTER);
+
+        let roller = rcrc_roller_new(ctx);
+        assert!(!roller.is_null());
+        for (i, &b) in bytes.iter().enumerate() {
+            let status = rcrc_push(roller, b, &mut crc);
+            if i < 4 {
+                assert_eq!(status, 0);
+            } else {
+                assert_eq!(status, 1);
+                assert_eq!(crc, (*ctx).0.crc(&bytes[i - 4..=i]));
+            }
+        }
+        let status = rcrc_push(ptr::null_mut(), 0, &mut crc);
+        assert_eq!(status, RCRC_NULL_POINTER);
+        rcrc_roller_free(roller);
+        rcrc_roller_free(ptr::null_mut());
+        rcrc_context_free(ctx);
+        rcrc_context_free(ptr::null_mut());
+    }
+}
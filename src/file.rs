@@ -0,0 +1,485 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! CRCs of files, and searching files using rolling CRCs.
+
+use super::{CancelToken, Finder, RollingCRCContext, RollingCrcReader,
+            ScanMonitor, ScanProgress, ScanStats, SearchError};
+#[cfg(test)]
+use super::test_bytes;
+
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+#[cfg(feature = "mmap")]
+use filebuffer::FileBuffer;
+
+/// CRC of the file at the given path, as `calc_crc()` of
+/// its contents.
+///
+/// With the `mmap` feature enabled, regular files are
+/// memory-mapped and checksummed in one pass; files that
+/// cannot be mapped (pipes, devices, files too large for
+/// the address space) are read as a stream with
+/// `RollingCRCContext::crc_reader()`. Without the feature,
+/// all files are read as a stream.
+pub fn crc_file(path: &Path) -> io::Result<u32> {
+    let context = RollingCRCContext::checksum_only();
+    #[cfg(feature = "mmap")]
+    {
+        if let Some(mapped) = map_file(path)? {
+            return Ok(context.crc(&mapped));
+        }
+    }
+    context.crc_reader(File::open(path)?)
+}
+
+/// Rolling CRCs of every window of the given size in the
+/// file at the given path, with the absolute file offset
+/// of each window start, as with
+/// `RollingCRCContext::rolling_crcs()` of its contents.
+/// Files are memory-mapped or read as a stream as with
+/// `crc_file()`.
+///
+/// # Errors
+///
+/// A window size of 0 is an `InvalidInput` error.
+pub fn rolling_crcs_file(path: &Path, window_size: usize)
+                         -> io::Result<Vec<(u64, u32)>>
+{
+    let context = RollingCRCContext::new(window_size)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    #[cfg(feature = "mmap")]
+    {
+        if let Some(mapped) = map_file(path)? {
+            return Ok(context.rolling_crcs(&mapped)
+                      .map(|(offset, crc)| (offset as u64, crc))
+                      .collect());
+        }
+    }
+    let mut reader = RollingCrcReader::new(File::open(path)?, &context);
+    let _ = io::copy(&mut reader, &mut io::sink())?;
+    Ok(reader.take_windows())
+}
+
+/// The file at the given path memory-mapped, or `None` if
+/// it is not a regular file or cannot be mapped and so
+/// must be read as a stream.
+#[cfg(feature = "mmap")]
+fn map_file(path: &Path) -> io::Result<Option<FileBuffer>> {
+    if !fs::metadata(path)?.is_file() {
+        return Ok(None);
+    }
+    Ok(FileBuffer::open(path).ok())
+}
+
+/// Positions of all occurrences of the needle in the file
+/// at the given path, as absolute file offsets.
+///
+/// With the `mmap` feature enabled, regular files are
+/// memory-mapped and searched with the slice fast path;
+/// files that cannot be mapped (pipes, devices, files too
+/// large for the address space) are searched as a stream.
+/// Without the feature, all files are searched as a
+/// stream.
+pub fn find_in_file(path: &Path, needle: &[u8]) -> io::Result<Vec<u64>> {
+    let mut monitor = ScanMonitor::new();
+    find_in_file_with(path, &Finder::new(needle), &mut monitor)
+        .map_err(SearchError::into_io)
+}
+
+/// Positions of all occurrences of the needle in the file
+/// at the given path, as with `find_in_file()`, checking
+/// the given token for cancellation every
+/// `token.interval()` windows.
+pub fn find_in_file_with_cancel(path: &Path,
+                                needle: &[u8],
+                                token: &CancelToken)
+                                -> Result<Vec<u64>, SearchError>
+{
+    let mut monitor = ScanMonitor::with_cancel(token);
+    find_in_file_with(path, &Finder::new(needle), &mut monitor)
+}
+
+/// Positions of all occurrences of the needle in the file
+/// at the given path, as with `find_in_file()`, together
+/// with statistics for the search. The `progress` callback
+/// is called at most every `interval` bytes, and once more
+/// at the end of the search.
+pub fn find_in_file_with_progress<P>(path: &Path,
+                                     needle: &[u8],
+                                     interval: usize,
+                                     mut progress: P)
+                                     -> io::Result<(Vec<u64>, ScanStats)>
+    where P: FnMut(ScanProgress)
+{
+    let mut monitor = ScanMonitor::with_progress(interval, &mut progress);
+    let found = find_in_file_with(path, &Finder::new(needle), &mut monitor)
+        .map_err(SearchError::into_io)?;
+    let bytes = monitor.current_bytes();
+    Ok((found, monitor.finish(bytes)))
+}
+
+/// Search the file at the given path using the given
+/// finder, reporting to the given monitor.
+#[cfg_attr(feature = "tracing", ::tracing::instrument(
+    level = "debug",
+    name = "find_in_file",
+    skip_all,
+    fields(path = %path.display(), needle_len = finder.needle().len()),
+))]
+fn find_in_file_with(path: &Path, finder: &Finder, monitor: &mut ScanMonitor)
+                     -> Result<Vec<u64>, SearchError>
+{
+    #[cfg(feature = "mmap")]
+    {
+        if let Some(mapped) = map_file(path)? {
+            let mut found = Vec::new();
+            finder.find_each(&mapped, 0, monitor, |index| {
+                found.push(index as u64);
+            })?;
+            #[cfg(feature = "tracing")]
+            monitor.trace_finished();
+            return Ok(found);
+        }
+    }
+    let file = File::open(path)?;
+    finder.find_reader_monitored(io::BufReader::new(file), monitor)
+}
+
+/// Result of searching one file with `search_paths()`.
+#[derive(Debug)]
+pub struct FileMatches {
+    /// Path of the file searched.
+    pub path: PathBuf,
+    /// Positions of the needle in the file, or the error
+    /// encountered while searching it.
+    pub offsets: io::Result<Vec<u64>>,
+}
+
+/// Search the given files for the needle in parallel,
+/// returning the positions found in each file in order of
+/// path. Directories are searched recursively; as with
+/// `grep -r`, symbolic links found while walking a
+/// directory are not followed.
+///
+/// Files are handed out to `threads` worker threads (all
+/// available parallelism if 0) that share a single
+/// `Finder`. An error opening, reading or walking a path is
+/// reported in its result rather than aborting the search.
+pub fn search_paths<I>(paths: I, needle: &[u8], threads: usize)
+                       -> Vec<FileMatches>
+    where I: IntoIterator<Item=PathBuf>
+{
+    search_paths_with_cancel(paths, needle, threads, &CancelToken::new())
+        .expect("internal error: uncancellable search cancelled")
+}
+
+/// Search the given files for the needle in parallel, as
+/// with `search_paths()`, checking the given token for
+/// cancellation between files and every `token.interval()`
+/// windows. Once cancelled, no new files are started, and
+/// the result is `Err(SearchError::Cancelled)`.
+pub fn search_paths_with_cancel<I>(paths: I,
+                                   needle: &[u8],
+                                   threads: usize,
+                                   token: &CancelToken)
+                                   -> Result<Vec<FileMatches>, SearchError>
+    where I: IntoIterator<Item=PathBuf>
+{
+    let finder = Finder::new(needle);
+    let mut results = Vec::new();
+    let mut files = Vec::new();
+    for path in paths {
+        match fs::metadata(&path) {
+            Ok(ref metadata) if metadata.is_dir() =>
+                walk_dir(path, &mut files, &mut results),
+            _ => files.push(path),
+        }
+    }
+
+    let threads = if threads == 0 {
+        thread::available_parallelism().map(|n| n.get()).unwrap_or(1)
+    } else {
+        threads
+    };
+    let next = AtomicUsize::new(0);
+    thread::scope(|scope| -> Result<(), SearchError> {
+        let workers: Vec<_> = (0..threads.min(files.len()))
+            .map(|_| scope.spawn(|| {
+                let mut found = Vec::new();
+                loop {
+                    token.check()?;
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let path = match files.get(i) {
+                        Some(path) => path,
+                        None => return Ok(found),
+                    };
+                    let mut monitor = ScanMonitor::with_cancel(token);
+                    let offsets = find_in_file_with(path, &finder, &mut monitor);
+                    let offsets = match offsets {
+                        Ok(offsets) => Ok(offsets),
+                        Err(SearchError::Io(e)) => Err(e),
+                        Err(SearchError::Cancelled) =>
+                            return Err(SearchError::Cancelled),
+                    };
+                    found.push(FileMatches { path: path.clone(), offsets });
+                }
+            }))
+            .collect();
+        for worker in workers {
+            results.extend(worker.join().expect("search worker panicked")?);
+        }
+        Ok(())
+    })?;
+    results.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(results)
+}
+
+/// Collect the files under the given directory, recording
+/// any errors encountered while walking it.
+fn walk_dir(dir: PathBuf,
+            files: &mut Vec<PathBuf>,
+            errors: &mut Vec<FileMatches>)
+{
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            errors.push(FileMatches { path: dir, offsets: Err(e) });
+            return;
+        },
+    };
+    for entry in entries {
+        let file_type = entry.and_then(|entry| {
+            let file_type = entry.file_type()?;
+            Ok((entry.path(), file_type))
+        });
+        match file_type {
+            Ok((path, ref file_type)) if file_type.is_dir() =>
+                walk_dir(path, files, errors),
+            Ok((_, ref file_type)) if file_type.is_symlink() => (),
+            Ok((path, _)) => files.push(path),
+            Err(e) => errors.push(FileMatches {
+                path: dir.clone(),
+                offsets: Err(e),
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+fn make_test_file(contents: &[u8]) -> tempfile::NamedTempFile {
+    use std::io::Write;
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(contents).unwrap();
+    file.flush().unwrap();
+    file
+}
+
+#[test]
+fn test_crc_file() {
+    use super::{calc_crc, CRC_TABLE};
+
+    let contents = test_bytes(0x1234_5678, 200_000);
+    let file = make_test_file(&contents);
+    assert_eq!(crc_file(file.path()).unwrap(), calc_crc(&contents, &CRC_TABLE));
+    let context = RollingCRCContext::new(64).unwrap();
+    let expected: Vec<(u64, u32)> = context.rolling_crcs(&contents)
+        .map(|(offset, crc)| (offset as u64, crc))
+        .collect();
+    assert_eq!(rolling_crcs_file(file.path(), 64).unwrap(), expected);
+    let e = rolling_crcs_file(file.path(), 0).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+
+    let empty = make_test_file(b"");
+    assert_eq!(crc_file(empty.path()).unwrap(), 0);
+    assert!(rolling_crcs_file(empty.path(), 1).unwrap().is_empty());
+
+    let missing = Path::new("/nonexistent/file");
+    assert!(crc_file(missing).is_err());
+    assert!(rolling_crcs_file(missing, 1).is_err());
+}
+
+// A fifo cannot be mapped, so is read as a stream.
+#[cfg(unix)]
+#[test]
+fn test_crc_file_fifo() {
+    use super::{calc_crc, CRC_TABLE};
+    use std::process::Command;
+
+    let contents = b"hello world".repeat(10_000);
+    let dir = tempfile::tempdir().unwrap();
+    let fifo = dir.path().join("fifo");
+    let status = Command::new("mkfifo").arg(&fifo).status().unwrap();
+    assert!(status.success());
+    let write = || {
+        let fifo = fifo.clone();
+        let contents = contents.clone();
+        thread::spawn(move || fs::write(fifo, contents).unwrap())
+    };
+
+    let writer = write();
+    assert_eq!(crc_file(&fifo).unwrap(), calc_crc(&contents, &CRC_TABLE));
+    writer.join().unwrap();
+
+    let writer = write();
+    let windows = rolling_crcs_file(&fifo, 5).unwrap();
+    writer.join().unwrap();
+    let context = RollingCRCContext::new(5).unwrap();
+    assert_eq!(windows.len(), contents.len() - 4);
+    assert_eq!(windows[6], (6, context.crc(b"world")));
+}
+
+#[test]
+fn test_find_in_file() {
+    let mut contents: Vec<u8> = test_bytes(0x9e37_79b9, 200_000)
+        .into_iter()
+        .map(|b| b & 0x7f)
+        .collect();
+    let needle = b"\xffneedle\xff";
+    let expected = vec![0u64, 65_530, 131_070, 199_990];
+    for &index in &expected {
+        let index = index as usize;
+        contents[index..index + needle.len()].copy_from_slice(needle);
+    }
+    let file = make_test_file(&contents);
+    assert_eq!(find_in_file(file.path(), needle).unwrap(), expected);
+    assert_eq!(find_in_file(file.path(), b"\xfe").unwrap(), Vec::<u64>::new());
+
+    let empty = make_test_file(b"");
+    assert_eq!(find_in_file(empty.path(), needle).unwrap(), Vec::<u64>::new());
+
+    assert!(find_in_file(Path::new("/nonexistent/file"), needle).is_err());
+}
+
+#[test]
+fn test_search_paths() {
+    let needle = b"needle";
+    let dir = tempfile::tempdir().unwrap();
+    let sub = dir.path().join("sub");
+    fs::create_dir(&sub).unwrap();
+    fs::write(dir.path().join("a"), b"a needle, another needle").unwrap();
+    fs::write(dir.path().join("b"), b"no match here").unwrap();
+    fs::write(sub.join("c"), b"needle").unwrap();
+    fs::write(sub.join("d"), b"").unwrap();
+    let missing = dir.path().join("missing");
+
+    for &threads in &[0, 1, 3] {
+        let paths = vec![sub.clone(), missing.clone(), dir.path().join("b"),
+                         dir.path().join("a")];
+        let results = search_paths(paths, needle, threads);
+        let paths: Vec<&Path> = results.iter()
+            .map(|result| result.path.as_path())
+            .collect();
+        assert_eq!(paths, vec![dir.path().join("a").as_path(),
+                               dir.path().join("b").as_path(),
+                               missing.as_path(),
+                               sub.join("c").as_path(),
+                               sub.join("d").as_path()]);
+        assert_eq!(results[0].offsets.as_ref().unwrap(), &[2, 18]);
+        assert!(results[1].offsets.as_ref().unwrap().is_empty());
+        assert!(results[2].offsets.is_err());
+        assert_eq!(results[3].offsets.as_ref().unwrap(), &[0]);
+        assert!(results[4].offsets.as_ref().unwrap().is_empty());
+    }
+
+    // Walking the whole tree finds the same files.
+    let results = search_paths(vec![dir.path().to_path_buf()], needle, 2);
+    assert_eq!(results.len(), 4);
+    assert!(results.iter().all(|result| result.offsets.is_ok()));
+}
+
+#[cfg(unix)]
+#[test]
+fn test_search_paths_unreadable() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let locked = dir.path().join("locked");
+    fs::write(&locked, b"a needle").unwrap();
+    fs::set_permissions(&locked, fs::Permissions::from_mode(0o000)).unwrap();
+    // Root reads the file regardless of its mode.
+    if File::open(&locked).is_ok() {
+        return;
+    }
+    let open = dir.path().join("open");
+    fs::write(&open, b"needle").unwrap();
+
+    let results = search_paths(vec![dir.path().to_path_buf()], b"needle", 2);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].path, locked);
+    match results[0].offsets {
+        Err(ref e) => assert_eq!(e.kind(), io::ErrorKind::PermissionDenied),
+        ref r => panic!("unexpected search result {:?}", r),
+    }
+    assert_eq!(results[1].path, open);
+    assert_eq!(results[1].offsets.as_ref().unwrap(), &[0]);
+}
+
+#[test]
+fn test_search_paths_cancel() {
+    let dir = tempfile::tempdir().unwrap();
+    fs::write(dir.path().join("a"), b"a needle").unwrap();
+    let token = CancelToken::new();
+    let paths = vec![dir.path().to_path_buf()];
+    let results = search_paths_with_cancel(paths.clone(), b"needle", 2, &token);
+    assert_eq!(results.unwrap()[0].offsets.as_ref().unwrap(), &[2]);
+    let path = dir.path().join("a");
+    assert_eq!(find_in_file_with_cancel(&path, b"needle", &token).unwrap(), &[2]);
+
+    token.cancel();
+    match search_paths_with_cancel(paths, b"needle", 2, &token) {
+        Err(SearchError::Cancelled) => (),
+        r => panic!("unexpected search result {:?}", r),
+    }
+    match find_in_file_with_cancel(&path, b"needle", &token) {
+        Err(SearchError::Cancelled) => (),
+        r => panic!("unexpected search result {:?}", r),
+    }
+}
+
+#[test]
+fn test_find_in_file_progress() {
+    let mut contents = vec![b'x'; 300_000];
+    contents[250_000..250_006].copy_from_slice(b"needle");
+    let file = make_test_file(&contents);
+    let mut reports: Vec<ScanProgress> = Vec::new();
+    let (found, stats) = find_in_file_with_progress(
+        file.path(),
+        b"needle",
+        50_000,
+        |p| reports.push(p),
+    ).unwrap();
+    assert_eq!(found, vec![250_000]);
+    assert_eq!(stats.bytes, contents.len() as u64);
+    assert_eq!(stats.matches, 1);
+    assert!(!reports.is_empty());
+    for pair in reports.windows(2) {
+        assert!(pair[1].bytes > pair[0].bytes);
+    }
+    assert_eq!(reports.last().unwrap().bytes, contents.len() as u64);
+}
+
+// Creates a sparse file of several GiB with a needle
+// planted near the end. Scanning it takes a while, so run
+// this explicitly with `--features mmap -- --ignored`.
+#[cfg(all(feature = "mmap", target_pointer_width = "64"))]
+#[test]
+#[ignore]
+fn test_find_in_large_file() {
+    use std::io::{Seek, SeekFrom, Write};
+
+    let needle = b"needle in a haystack";
+    let index = (5u64 << 30) - 1000;
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.as_file().set_len(5 << 30).unwrap();
+    file.seek(SeekFrom::Start(index)).unwrap();
+    file.write_all(needle).unwrap();
+    file.flush().unwrap();
+    assert_eq!(find_in_file(file.path(), needle).unwrap(), vec![index]);
+}
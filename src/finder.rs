@@ -0,0 +1,659 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Substring search using rolling CRCs.
+
+use super::{CancelToken, RollingCRCContext, RollingCRCSlice, ScanMonitor,
+            ScanProgress, ScanStats, SearchError};
+#[cfg(test)]
+use super::{test_bytes, test_rng};
+
+use memchr::{memchr_iter, memrchr_iter, Memchr};
+
+use std::io::{self, Read};
+
+/// Size of the buffer used when searching a byte stream.
+const READ_BUFFER_SIZE: usize = 64 * 1024;
+
+/// How a `Finder` locates candidate occurrences of its
+/// needle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindStrategy {
+    /// Compare the rolling CRC of every haystack window
+    /// with the needle CRC.
+    Rolling,
+    /// Use `memchr` to skip to occurrences of an "anchor"
+    /// byte of the needle, and compare only the windows
+    /// positioned around them. This is much faster when
+    /// the anchor byte is uncommon in the haystack, and
+    /// much slower when it is common.
+    Memchr,
+}
+
+/// A reusable search for a fixed needle. The rolling CRC
+/// context for the needle length, the needle CRC and a copy
+/// of the needle are computed once at construction; the
+/// `Finder` can then be run against any number of
+/// haystacks, including from several threads at once.
+///
+/// Candidate windows are found by CRC and then verified
+/// byte-for-byte, so CRC collisions will never produce a
+/// false match.
+#[derive(Debug, Clone)]
+pub struct Finder {
+    /// Context for the needle length.
+    context: RollingCRCContext<'static>,
+    /// Copy of the needle for verification.
+    needle: Vec<u8>,
+    /// CRC of the needle.
+    target: u32,
+    /// Candidate search strategy.
+    strategy: FindStrategy,
+    /// Position in the needle of the byte used by the
+    /// `Memchr` strategy.
+    anchor: usize,
+}
+
+/// Is this byte likely to be common in typical data?
+fn is_common_byte(b: u8) -> bool {
+    b == 0 || b == 0xff || b.is_ascii_alphanumeric() || b.is_ascii_whitespace()
+}
+
+impl Finder {
+
+    /// Make a new finder for the given needle, using the
+    /// `Rolling` strategy.
+    pub fn new(needle: &[u8]) -> Self {
+        Self::with_strategy(needle, FindStrategy::Rolling)
+    }
+
+    /// Make a new finder for the given needle, using the
+    /// given strategy. The anchor byte for the `Memchr`
+    /// strategy is the first needle byte that is not
+    /// usually common (NUL, 0xff, ASCII whitespace, letters
+    /// and digits), or the first byte if all are.
+    pub fn with_strategy(needle: &[u8], strategy: FindStrategy) -> Self {
+        let context = RollingCRCContext::build(needle.len());
+        let target = context.crc(needle);
+        let anchor = needle.iter()
+            .position(|&b| !is_common_byte(b))
+            .unwrap_or(0);
+        Self { context, needle: needle.to_vec(), target, strategy, anchor }
+    }
+
+    /// The candidate search strategy of this finder.
+    pub fn strategy(&self) -> FindStrategy {
+        self.strategy
+    }
+
+    /// The needle being searched for.
+    pub fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    /// Position of the first occurrence of the needle in
+    /// the haystack, if any. An empty needle is found at
+    /// position 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let finder = Finder::new(b"world");
+    /// assert_eq!(finder.find(b"hello world"), Some(6));
+    /// assert_eq!(finder.find(b"hello there"), None);
+    /// ```
+    pub fn find(&self, haystack: &[u8]) -> Option<usize> {
+        self.find_iter(haystack).next()
+    }
+
+    /// Position of the last occurrence of the needle in the
+    /// haystack, if any, found by scanning backward from
+    /// the end of the haystack. An empty needle is found at
+    /// the end of the haystack.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let finder = Finder::new(b"o");
+    /// assert_eq!(finder.rfind(b"hello world"), Some(7));
+    /// assert_eq!(finder.rfind(b"hi there"), None);
+    /// ```
+    pub fn rfind(&self, haystack: &[u8]) -> Option<usize> {
+        let needle = &self.needle;
+        if needle.is_empty() {
+            return Some(haystack.len());
+        }
+        let is_match = |index: usize| {
+            haystack.get(index..index + needle.len()) == Some(needle)
+        };
+        match self.strategy {
+            FindStrategy::Rolling => self.context.rolling_crcs_rev(haystack)
+                .find(|&(index, crc)| crc == self.target && is_match(index))
+                .map(|(index, _)| index),
+            FindStrategy::Memchr => {
+                let anchor = self.anchor;
+                memrchr_iter(needle[anchor], haystack)
+                    .filter(|&position| position >= anchor)
+                    .map(|position| position - anchor)
+                    .find(|&index| is_match(index))
+            },
+        }
+    }
+
+    /// An iterator over the positions of all occurrences
+    /// of the needle in the haystack, in increasing order.
+    /// Occurrences may overlap. An empty needle is found at
+    /// every position from 0 through the haystack length.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let finder = Finder::new(b"aa");
+    /// let found: Vec<usize> = finder.find_iter(b"aaab").collect();
+    /// assert_eq!(found, vec![0, 1]);
+    /// ```
+    pub fn find_iter<'f, 'h>(&'f self, haystack: &'h [u8])
+                             -> FindIter<'f, 'h>
+    {
+        let candidates = match self.strategy {
+            FindStrategy::Rolling =>
+                Candidates::Rolling(self.context.rolling_crcs(haystack)),
+            FindStrategy::Memchr => {
+                let anchor = self.needle.get(self.anchor).cloned().unwrap_or(0);
+                Candidates::Memchr(memchr_iter(anchor, haystack))
+            },
+        };
+        FindIter {
+            finder: self,
+            haystack,
+            candidates,
+            empty_index: 0,
+            examined: 0,
+        }
+    }
+
+    /// Positions of all occurrences of the needle in the
+    /// haystack, as with `find_iter()`, checking the given
+    /// token for cancellation every `token.interval()`
+    /// windows.
+    #[cfg_attr(feature = "tracing", ::tracing::instrument(
+        level = "debug",
+        name = "find_all",
+        skip_all,
+        fields(len = haystack.len(), needle_len = self.needle.len()),
+    ))]
+    pub fn find_all_with_cancel(&self,
+                                haystack: &[u8],
+                                token: &CancelToken)
+                                -> Result<Vec<usize>, SearchError>
+    {
+        let mut monitor = ScanMonitor::with_cancel(token);
+        let mut found = Vec::new();
+        self.find_each(haystack, 0, &mut monitor, |index| found.push(index))?;
+        #[cfg(feature = "tracing")]
+        monitor.trace_finished();
+        Ok(found)
+    }
+
+    /// Positions of all occurrences of the needle in the
+    /// haystack, as with `find_iter()`, together with
+    /// statistics for the search. The `progress` callback
+    /// is called at most every `interval` bytes, and once
+    /// more at the end of the search.
+    #[cfg_attr(feature = "tracing", ::tracing::instrument(
+        level = "debug",
+        name = "find_all",
+        skip_all,
+        fields(len = haystack.len(), needle_len = self.needle.len()),
+    ))]
+    pub fn find_all_with_progress<P>(&self,
+                                     haystack: &[u8],
+                                     interval: usize,
+                                     mut progress: P)
+                                     -> (Vec<usize>, ScanStats)
+        where P: FnMut(ScanProgress)
+    {
+        let mut monitor = ScanMonitor::with_progress(interval, &mut progress);
+        let mut found = Vec::new();
+        self.find_each(haystack, 0, &mut monitor, |index| found.push(index))
+            .expect("internal error: uncancellable search cancelled");
+        #[cfg(feature = "tracing")]
+        monitor.trace_finished();
+        (found, monitor.finish(haystack.len() as u64))
+    }
+
+    /// Report the positions of all occurrences of the
+    /// needle in the haystack, which starts at position
+    /// `base` of the input, to the given monitor every
+    /// `monitor.interval()` windows.
+    pub(crate) fn find_each<F>(&self,
+                               haystack: &[u8],
+                               base: u64,
+                               monitor: &mut ScanMonitor,
+                               mut report: F)
+                               -> Result<(), SearchError>
+        where F: FnMut(usize)
+    {
+        let mut found = self.find_iter(haystack);
+        loop {
+            let interval = monitor.interval();
+            let mut budget = interval;
+            let mut matches = 0;
+            while let Some(index) = found.next_limited(&mut budget) {
+                report(index);
+                matches += 1;
+            }
+            let bytes = base + found.examined as u64;
+            monitor.checkpoint(bytes, interval - budget, matches)?;
+            if budget > 0 {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Positions of all occurrences of the needle in the
+    /// bytes of the given reader, as with `find_iter()`.
+    /// The stream is read through a fixed-size buffer that
+    /// retains enough bytes to find occurrences spanning
+    /// successive reads, so memory use is independent of
+    /// the stream length.
+    pub fn find_reader<R: Read>(&self, reader: R) -> io::Result<Vec<u64>> {
+        self.find_reader_monitored(reader, &mut ScanMonitor::new())
+            .map_err(SearchError::into_io)
+    }
+
+    /// Positions of all occurrences of the needle in the
+    /// bytes of the given reader, as with `find_reader()`,
+    /// checking the given token for cancellation every
+    /// `token.interval()` windows and after every read.
+    pub fn find_reader_with_cancel<R: Read>(&self,
+                                            reader: R,
+                                            token: &CancelToken)
+                                            -> Result<Vec<u64>, SearchError>
+    {
+        self.find_reader_monitored(reader, &mut ScanMonitor::with_cancel(token))
+    }
+
+    /// Positions of all occurrences of the needle in the
+    /// bytes of the given reader, as with `find_reader()`,
+    /// together with statistics for the search. The
+    /// `progress` callback is called at most every
+    /// `interval` bytes, and once more at the end of the
+    /// search.
+    pub fn find_reader_with_progress<R, P>(&self,
+                                           reader: R,
+                                           interval: usize,
+                                           mut progress: P)
+                                           -> io::Result<(Vec<u64>, ScanStats)>
+        where R: Read, P: FnMut(ScanProgress)
+    {
+        let mut monitor = ScanMonitor::with_progress(interval, &mut progress);
+        let found = self.find_reader_monitored(reader, &mut monitor)
+            .map_err(SearchError::into_io)?;
+        let bytes = monitor.current_bytes();
+        Ok((found, monitor.finish(bytes)))
+    }
+
+    /// Search the bytes of the given reader, reporting to
+    /// the given monitor.
+    #[cfg_attr(feature = "tracing", ::tracing::instrument(
+        level = "debug",
+        name = "find_reader",
+        skip_all,
+        fields(needle_len = self.needle.len()),
+    ))]
+    pub(crate) fn find_reader_monitored<R: Read>(&self,
+                                                 mut reader: R,
+                                                 monitor: &mut ScanMonitor)
+                                                 -> Result<Vec<u64>, SearchError>
+    {
+        let overlap = self.needle.len().saturating_sub(1);
+        let mut buf = vec![0; overlap + READ_BUFFER_SIZE];
+        // Number of bytes retained from the previous read.
+        let mut held = 0;
+        // Stream position of the start of the buffer.
+        let mut base = 0u64;
+        let mut found = Vec::new();
+        loop {
+            monitor.check()?;
+            let nread = match reader.read(&mut buf[held..]) {
+                Ok(0) => break,
+                Ok(nread) => nread,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted =>
+                    continue,
+                Err(e) => return Err(e.into()),
+            };
+            let end = held + nread;
+            if self.needle.is_empty() {
+                monitor.checkpoint(base + end as u64, 0, 0)?;
+            } else {
+                // The retained bytes are shorter than the
+                // needle, so every occurrence found here is
+                // new.
+                self.find_each(&buf[..end], base, monitor, |index| {
+                    found.push(base + index as u64);
+                })?;
+            }
+            let keep = overlap.min(end);
+            buf.copy_within(end - keep..end, 0);
+            base += (end - keep) as u64;
+            held = keep;
+        }
+        if self.needle.is_empty() {
+            found.extend(0..=base + held as u64);
+        }
+        #[cfg(feature = "tracing")]
+        monitor.trace_finished();
+        Ok(found)
+    }
+}
+
+/// An iterator over the positions of needle occurrences in
+/// a haystack. This iterator can be created using
+/// `Finder::find_iter()`.
+#[derive(Debug, Clone)]
+pub struct FindIter<'f, 'h> {
+    finder: &'f Finder,
+    haystack: &'h [u8],
+    candidates: Candidates<'f, 'h>,
+    /// Next position to report for an empty needle.
+    empty_index: usize,
+    /// Number of haystack positions that have been
+    /// examined as occurrence starts.
+    examined: usize,
+}
+
+/// Source of candidate occurrences for a `FindIter`.
+#[derive(Debug, Clone)]
+enum Candidates<'f, 'h> {
+    /// Window positions and CRCs.
+    Rolling(RollingCRCSlice<'f, 'h>),
+    /// Anchor byte positions.
+    Memchr(Memchr<'h>),
+}
+
+impl<'f, 'h> Iterator for FindIter<'f, 'h> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        let mut budget = usize::MAX;
+        self.next_limited(&mut budget)
+    }
+}
+
+impl<'f, 'h> FindIter<'f, 'h> {
+    /// Find the next occurrence, examining at most
+    /// `*budget` candidates. The budget is reduced by the
+    /// number of candidates examined: if it is still
+    /// positive after returning `None` the haystack has been
+    /// exhausted.
+    fn next_limited(&mut self, budget: &mut usize) -> Option<usize> {
+        let needle = &self.finder.needle;
+        let haystack = self.haystack;
+        if needle.is_empty() {
+            if *budget == 0 || self.empty_index > haystack.len() {
+                return None;
+            }
+            *budget -= 1;
+            self.empty_index += 1;
+            self.examined = self.empty_index.min(haystack.len());
+            return Some(self.empty_index - 1);
+        }
+        let is_match = |index: usize| {
+            haystack.get(index..index + needle.len()) == Some(needle)
+        };
+        match self.candidates {
+            Candidates::Rolling(ref mut crcs) => {
+                let target = self.finder.target;
+                while *budget > 0 {
+                    let (index, crc) = match crcs.next() {
+                        Some(window) => window,
+                        None => break,
+                    };
+                    *budget -= 1;
+                    self.examined = index + 1;
+                    if crc == target && is_match(index) {
+                        return Some(index);
+                    }
+                }
+            },
+            Candidates::Memchr(ref mut positions) => {
+                let anchor = self.finder.anchor;
+                while *budget > 0 {
+                    let position = match positions.next() {
+                        Some(position) => position,
+                        None => break,
+                    };
+                    *budget -= 1;
+                    self.examined = (position + 1).saturating_sub(anchor);
+                    if position >= anchor && is_match(position - anchor) {
+                        return Some(position - anchor);
+                    }
+                }
+            },
+        }
+        if *budget > 0 {
+            self.examined = haystack.len();
+        }
+        None
+    }
+}
+
+#[test]
+fn test_finder_empty_needle() {
+    let finder = Finder::new(b"");
+    assert_eq!(finder.find(b""), Some(0));
+    assert_eq!(finder.rfind(b"abc"), Some(3));
+    let found: Vec<usize> = finder.find_iter(b"abc").collect();
+    assert_eq!(found, vec![0, 1, 2, 3]);
+}
+
+#[test]
+fn test_finder_strategies() {
+    let mut random = test_rng(17u32);
+    for round in 0..100 {
+        // Mostly the anchor byte, to make the memchr
+        // strategy work hard.
+        let alphabet: &[u8] = if round % 2 == 0 {
+            b"\x80\x80\x80a"
+        } else {
+            b"\x80ab"
+        };
+        let choose = |r: u32| alphabet[r as usize % alphabet.len()];
+        let needle: Vec<u8> = (0..1 + round % 6)
+            .map(|_| choose(random()))
+            .collect();
+        let haystack: Vec<u8> = (0..200 + round)
+            .map(|_| choose(random()))
+            .collect();
+        let rolling = Finder::with_strategy(&needle, FindStrategy::Rolling);
+        let memchr = Finder::with_strategy(&needle, FindStrategy::Memchr);
+        assert_eq!(memchr.strategy(), FindStrategy::Memchr);
+        let expected: Vec<usize> = (0..=haystack.len() - needle.len())
+            .filter(|&i| haystack[i..].starts_with(&needle))
+            .collect();
+        assert_eq!(rolling.find_iter(&haystack).collect::<Vec<_>>(), expected);
+        assert_eq!(memchr.find_iter(&haystack).collect::<Vec<_>>(), expected);
+        assert_eq!(memchr.find(&haystack), expected.first().cloned());
+        assert_eq!(rolling.rfind(&haystack), expected.last().cloned());
+        assert_eq!(memchr.rfind(&haystack), expected.last().cloned());
+    }
+}
+
+#[test]
+fn test_finder_cancel() {
+    /// A reader of up to `len` zeros that cancels the
+    /// token once it has supplied `cancel_at` bytes,
+    /// counting the bytes read.
+    struct Cancelling<'t> {
+        token: &'t CancelToken,
+        cancel_at: usize,
+        len: usize,
+        read: usize,
+    }
+
+    impl<'t> Read for Cancelling<'t> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.len - self.read);
+            for b in &mut buf[..n] {
+                *b = 0;
+            }
+            self.read += n;
+            if self.read >= self.cancel_at {
+                self.token.cancel();
+            }
+            Ok(n)
+        }
+    }
+
+    let finder = Finder::new(b"needle");
+    let token = CancelToken::with_interval(1000);
+    let haystack = b"a needle in a haystack";
+    let found = finder.find_all_with_cancel(haystack, &token).unwrap();
+    assert_eq!(found, vec![2]);
+
+    // Cancel a reader scan partway through: it stops within
+    // a buffer of the cancellation.
+    let mut reader = Cancelling {
+        token: &token,
+        cancel_at: 1 << 20,
+        len: 128 << 20,
+        read: 0,
+    };
+    match finder.find_reader_with_cancel(&mut reader, &token) {
+        Err(SearchError::Cancelled) => (),
+        r => panic!("unexpected search result {:?}", r),
+    }
+    assert!(token.is_cancelled());
+    assert!(reader.read <= reader.cancel_at + READ_BUFFER_SIZE,
+            "read {} bytes", reader.read);
+
+    // A cancelled token stops scans before they start.
+    let haystack = vec![0u8; 1 << 20];
+    let result = finder.find_all_with_cancel(&haystack, &token);
+    match result {
+        Err(SearchError::Cancelled) => (),
+        r => panic!("unexpected search result {:?}", r),
+    }
+    let result = finder.find_reader_with_cancel(&haystack[..], &token);
+    match result {
+        Err(SearchError::Cancelled) => (),
+        r => panic!("unexpected search result {:?}", r),
+    }
+}
+
+#[test]
+fn test_finder_progress() {
+    let mut haystack = vec![0u8; 100_000];
+    for i in 0..10 {
+        haystack[i * 9_000..i * 9_000 + 6].copy_from_slice(b"needle");
+    }
+    let finder = Finder::new(b"needle");
+    let mut reports: Vec<ScanProgress> = Vec::new();
+    let (found, stats) = finder.find_all_with_progress(&haystack, 10_000, |p| {
+        reports.push(p);
+    });
+    assert_eq!(found.len(), 10);
+    assert_eq!(stats.bytes, haystack.len() as u64);
+    assert_eq!(stats.windows, (haystack.len() - 5) as u64);
+    assert_eq!(stats.matches, 10);
+    assert!(reports.len() >= 9 && reports.len() <= 11);
+    for pair in reports.windows(2) {
+        assert!(pair[1].bytes >= pair[0].bytes + 10_000
+                || pair[1].bytes == haystack.len() as u64);
+        assert!(pair[1].windows > pair[0].windows);
+        assert!(pair[1].matches >= pair[0].matches);
+    }
+    assert_eq!(reports.last().unwrap().bytes, stats.bytes);
+
+    // The memchr strategy examines only the windows at the
+    // anchor byte, here the first of each needle.
+    let memchr = Finder::with_strategy(b"needle", FindStrategy::Memchr);
+    let (found, stats) = memchr.find_all_with_progress(&haystack, 0, |_| ());
+    assert_eq!(found.len(), 10);
+    assert_eq!(stats.bytes, haystack.len() as u64);
+    assert_eq!(stats.windows, 10);
+
+    let mut reports: Vec<ScanProgress> = Vec::new();
+    let (offsets, stats) = finder
+        .find_reader_with_progress(&haystack[..], 1000, |p| reports.push(p))
+        .unwrap();
+    assert_eq!(offsets.len(), 10);
+    assert_eq!(stats.bytes, haystack.len() as u64);
+    assert_eq!(stats.matches, 10);
+    for pair in reports.windows(2) {
+        assert!(pair[1].bytes > pair[0].bytes);
+    }
+    assert_eq!(reports.last().unwrap().bytes, stats.bytes);
+}
+
+#[test]
+fn test_finder_reader() {
+    // Deliver a few bytes at a time, so that occurrences
+    // straddle reads.
+    struct Dribble<'a>(&'a [u8]);
+
+    impl<'a> Read for Dribble<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(3);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let haystack = b"abcabcabdabcabd";
+    for needle in [&b""[..], b"a", b"abcabd", b"abd", b"xyz"].iter() {
+        let finder = Finder::new(needle);
+        let expected: Vec<u64> = finder.find_iter(haystack)
+            .map(|index| index as u64)
+            .collect();
+        let found = finder.find_reader(Dribble(haystack)).unwrap();
+        assert_eq!(found, expected);
+        let found = finder.find_reader(&haystack[..]).unwrap();
+        assert_eq!(found, expected);
+    }
+}
+
+#[test]
+fn test_finder_threads() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Finder>();
+
+    let needle = b"needle";
+    let finder = Finder::new(needle);
+    let haystacks: Vec<(Vec<u8>, Vec<usize>)> = (0..8usize)
+        .map(|t| {
+            let mut haystack: Vec<u8> =
+                test_bytes(t as u32 + 1, 1000 + 37 * t)
+                    .into_iter()
+                    .map(|b| b & 0x7f)
+                    .collect();
+            let mut expected = Vec::new();
+            for k in 0..t {
+                let index = 17 + k * 101;
+                haystack[index..index + needle.len()]
+                    .copy_from_slice(needle);
+                expected.push(index);
+            }
+            (haystack, expected)
+        })
+        .collect();
+
+    std::thread::scope(|scope| {
+        for (haystack, expected) in &haystacks {
+            let finder = &finder;
+            scope.spawn(move || {
+                let found: Vec<usize> =
+                    finder.find_iter(haystack).collect();
+                assert_eq!(&found, expected);
+                assert_eq!(finder.find(haystack), expected.first().cloned());
+            });
+        }
+    });
+}
@@ -0,0 +1,630 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Storage of window fingerprints in files.
+
+// The format is a header followed by fixed-size records,
+// all integers little-endian:
+//
+//     magic        4 bytes  "RCFP"
+//     version      u32      FINGERPRINT_VERSION
+//     window size  u64
+//     polynomial   u32      reflected CRC polynomial
+//     entry count  u64
+//
+// then for each entry
+//
+//     offset       u64
+//     crc          u32
+
+use super::{RollingCRCContext, POLY_CRC};
+#[cfg(test)]
+use super::test_bytes;
+
+use std::convert::TryFrom;
+use std::error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Magic number at the start of a fingerprint file.
+pub const FINGERPRINT_MAGIC: [u8; 4] = *b"RCFP";
+
+/// Version of the fingerprint file format written by this
+/// crate.
+pub const FINGERPRINT_VERSION: u32 = 1;
+
+/// A window fingerprint: the offset of a window in some
+/// data and its CRC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct FingerprintEntry {
+    /// Offset of the window.
+    pub offset: u64,
+    /// CRC of the window.
+    pub crc: u32,
+}
+
+#[cfg(feature = "defmt")]
+impl ::defmt::Format for FingerprintEntry {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "FingerprintEntry {{ offset: {=u64}, \
+                            crc: {=u32:#010x} }}",
+                        self.offset, self.crc)
+    }
+}
+
+/// Reasons a fingerprint file may be unusable.
+#[derive(Debug)]
+pub enum FingerprintError {
+    /// Reading the file failed, or it was truncated.
+    Io(io::Error),
+    /// The file does not start with `FINGERPRINT_MAGIC`.
+    BadMagic,
+    /// The file has an unsupported format version.
+    UnsupportedVersion(u32),
+    /// The file's window size is 0 or too large for this
+    /// platform.
+    BadWindowSize(u64),
+    /// The file's window size differs from the one needed.
+    WindowSizeMismatch {
+        /// Window size needed.
+        expected: usize,
+        /// Window size of the file.
+        found: u64,
+    },
+}
+
+impl fmt::Display for FingerprintError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FingerprintError::Io(ref e) =>
+                write!(f, "fingerprint file read failed: {}", e),
+            FingerprintError::BadMagic =>
+                write!(f, "not a fingerprint file"),
+            FingerprintError::UnsupportedVersion(version) =>
+                write!(f, "unsupported fingerprint file version {}", version),
+            FingerprintError::BadWindowSize(window_size) =>
+                write!(f, "invalid fingerprint window size {}", window_size),
+            FingerprintError::WindowSizeMismatch { expected, found } =>
+                write!(f, "fingerprint window size {} does not match {}",
+                       found, expected),
+        }
+    }
+}
+
+impl error::Error for FingerprintError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            FingerprintError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FingerprintError {
+    fn from(e: io::Error) -> Self {
+        FingerprintError::Io(e)
+    }
+}
+
+/// Writer of a fingerprint file. Entries are held in
+/// memory until `finish()`, since the header records their
+/// number.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(4).unwrap();
+/// let mut writer = FingerprintWriter::new(Vec::new(), &context);
+/// for (offset, crc) in context.rolling_crcs(b"some data") {
+///     writer.add(offset as u64, crc);
+/// }
+/// let file = writer.finish().unwrap();
+/// let fingerprints = FingerprintReader::open(&file[..]).unwrap();
+/// assert_eq!(fingerprints.window_size(), 4);
+/// assert_eq!(fingerprints.len(), 6);
+/// ```
+#[derive(Debug)]
+pub struct FingerprintWriter<W: Write> {
+    writer: W,
+    /// Window size of the fingerprints.
+    window_size: usize,
+    /// Entries added so far.
+    entries: Vec<FingerprintEntry>,
+}
+
+impl<W: Write> FingerprintWriter<W> {
+
+    /// Start a fingerprint file for windows of the given
+    /// context.
+    pub fn new(writer: W, context: &RollingCRCContext) -> Self {
+        Self { writer, window_size: context.window_size(), entries: Vec::new() }
+    }
+
+    /// Add the fingerprint of the window at `offset`.
+    pub fn add(&mut self, offset: u64, crc: u32) {
+        self.entries.push(FingerprintEntry { offset, crc });
+    }
+
+    /// Write the file, returning the underlying writer.
+    pub fn finish(mut self) -> io::Result<W> {
+        let mut header = Vec::with_capacity(28);
+        header.extend_from_slice(&FINGERPRINT_MAGIC);
+        header.extend_from_slice(&FINGERPRINT_VERSION.to_le_bytes());
+        header.extend_from_slice(&(self.window_size as u64).to_le_bytes());
+        header.extend_from_slice(&POLY_CRC.to_le_bytes());
+        header.extend_from_slice(&(self.entries.len() as u64).to_le_bytes());
+        self.writer.write_all(&header)?;
+        let mut record = [0; 12];
+        for entry in &self.entries {
+            record[..8].copy_from_slice(&entry.offset.to_le_bytes());
+            record[8..].copy_from_slice(&entry.crc.to_le_bytes());
+            self.writer.write_all(&record)?;
+        }
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+/// The contents of a fingerprint file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FingerprintFile {
+    /// Window size of the fingerprints.
+    window_size: u64,
+    /// CRC polynomial of the fingerprints.
+    polynomial: u32,
+    /// The fingerprints, in file order.
+    entries: Vec<FingerprintEntry>,
+}
+
+impl FingerprintFile {
+
+    /// Window size of the fingerprints.
+    pub fn window_size(&self) -> u64 {
+        self.window_size
+    }
+
+    /// Reflected CRC polynomial of the fingerprints.
+    pub fn polynomial(&self) -> u32 {
+        self.polynomial
+    }
+
+    /// Number of fingerprints.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Are there no fingerprints?
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// The fingerprints, in file order.
+    pub fn entries(&self) -> &[FingerprintEntry] {
+        &self.entries
+    }
+
+    /// The fingerprints, for comparison with windows of
+    /// the given context. Fails if the window sizes differ.
+    pub fn entries_for(&self, context: &RollingCRCContext)
+                       -> Result<&[FingerprintEntry], FingerprintError>
+    {
+        let expected = context.window_size();
+        if self.window_size != expected as u64 {
+            return Err(FingerprintError::WindowSizeMismatch {
+                expected,
+                found: self.window_size,
+            });
+        }
+        Ok(&self.entries)
+    }
+}
+
+/// Reader of fingerprint files.
+#[derive(Debug, Clone, Copy)]
+pub struct FingerprintReader;
+
+impl FingerprintReader {
+
+    /// Read a fingerprint file, checking its header.
+    pub fn open<R: Read>(mut reader: R)
+                         -> Result<FingerprintFile, FingerprintError>
+    {
+        let mut header = [0; 28];
+        reader.read_exact(&mut header)?;
+        if header[..4] != FINGERPRINT_MAGIC {
+            return Err(FingerprintError::BadMagic);
+        }
+        let version = u32::from_le_bytes(le_bytes(&header[4..8]));
+        if version != FINGERPRINT_VERSION {
+            return Err(FingerprintError::UnsupportedVersion(version));
+        }
+        let window_size = u64::from_le_bytes(le_bytes(&header[8..16]));
+        if window_size == 0 || usize::try_from(window_size).is_err() {
+            return Err(FingerprintError::BadWindowSize(window_size));
+        }
+        let polynomial = u32::from_le_bytes(le_bytes(&header[16..20]));
+        let count = u64::from_le_bytes(le_bytes(&header[20..28]));
+        // Don't trust the count for preallocation.
+        let mut entries = Vec::with_capacity(count.min(1 << 16) as usize);
+        let mut record = [0; 12];
+        for _ in 0..count {
+            reader.read_exact(&mut record)?;
+            entries.push(FingerprintEntry {
+                offset: u64::from_le_bytes(le_bytes(&record[..8])),
+                crc: u32::from_le_bytes(le_bytes(&record[8..])),
+            });
+        }
+        Ok(FingerprintFile { window_size, polynomial, entries })
+    }
+}
+
+/// Reasons two fingerprint files cannot be compared.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompareError {
+    /// The files have different window sizes.
+    WindowSizeMismatch(u64, u64),
+    /// The files use different CRC polynomials.
+    PolynomialMismatch(u32, u32),
+}
+
+impl fmt::Display for CompareError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CompareError::WindowSizeMismatch(a, b) =>
+                write!(f, "fingerprint window sizes {} and {} differ", a, b),
+            CompareError::PolynomialMismatch(a, b) =>
+                write!(f, "fingerprint polynomials {:08x} and {:08x} differ",
+                       a, b),
+        }
+    }
+}
+
+impl error::Error for CompareError {}
+
+/// Find the windows common to the data sets of two
+/// fingerprint files, returning for each pair of entries
+/// with the same CRC the offset in `a`, the offset in `b`
+/// and the CRC, in order of offset in `a` and then in `b`.
+/// Since the original data is not at hand, matches are not
+/// verified: with enough entries, some CRC collisions are
+/// to be expected.
+///
+/// The entries are joined by sorting copies of both by CRC
+/// and merging, so no hash table is needed.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(5).unwrap();
+/// let fingerprint = |data: &[u8]| {
+///     let mut writer = FingerprintWriter::new(Vec::new(), &context);
+///     for (offset, crc) in context.rolling_crcs(data) {
+///         writer.add(offset as u64, crc);
+///     }
+///     let file = writer.finish().unwrap();
+///     FingerprintReader::open(&file[..]).unwrap()
+/// };
+/// let a = fingerprint(b"the quick brown fox");
+/// let b = fingerprint(b"a brown dog");
+/// let common = compare_fingerprints(&a, &b).unwrap();
+/// assert_eq!(common, vec![(9, 1, context.crc(b" brow")),
+///                         (10, 2, context.crc(b"brown")),
+///                         (11, 3, context.crc(b"rown "))]);
+/// ```
+pub fn compare_fingerprints(a: &FingerprintFile, b: &FingerprintFile)
+                            -> Result<Vec<(u64, u64, u32)>, CompareError>
+{
+    if a.window_size != b.window_size {
+        return Err(CompareError::WindowSizeMismatch(a.window_size,
+                                                    b.window_size));
+    }
+    if a.polynomial != b.polynomial {
+        return Err(CompareError::PolynomialMismatch(a.polynomial,
+                                                    b.polynomial));
+    }
+    let by_crc = |file: &FingerprintFile| {
+        let mut entries = file.entries.clone();
+        entries.sort_unstable_by_key(|e| (e.crc, e.offset));
+        entries
+    };
+    let (a, b) = (by_crc(a), by_crc(b));
+    let mut common = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let crc = a[i].crc;
+        if crc < b[j].crc {
+            i += 1;
+            continue;
+        }
+        if crc > b[j].crc {
+            j += 1;
+            continue;
+        }
+        let i_end = i + a[i..].iter().take_while(|e| e.crc == crc).count();
+        let j_end = j + b[j..].iter().take_while(|e| e.crc == crc).count();
+        for ea in &a[i..i_end] {
+            for eb in &b[j..j_end] {
+                common.push((ea.offset, eb.offset, crc));
+            }
+        }
+        i = i_end;
+        j = j_end;
+    }
+    common.sort_unstable();
+    Ok(common)
+}
+
+/// Regions of `new_data` that no longer match anything in
+/// the data set of the `old` fingerprints, as when finding
+/// what to back up incrementally. Windows of the new data
+/// whose CRC occurs in `old` are taken to be unchanged;
+/// bytes covered by no such window are changed. Changed
+/// bytes are rounded out to the enclosing blocks of
+/// `granularity` bytes (1 if 0), and adjacent blocks
+/// merged, giving ranges in increasing order.
+///
+/// The window size is that of `old`. Since the old data is
+/// not at hand, a CRC collision can hide a change; see
+/// `changed_regions_verified()`.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(8).unwrap();
+/// let old = b"an example of some data to back up";
+/// let mut writer = FingerprintWriter::new(Vec::new(), &context);
+/// for (offset, crc) in context.rolling_crcs(old) {
+///     writer.add(offset as u64, crc);
+/// }
+/// let file = writer.finish().unwrap();
+/// let fingerprints = FingerprintReader::open(&file[..]).unwrap();
+/// let new = b"an example of SOME data to back up";
+/// assert_eq!(changed_regions(&fingerprints, new, 1), vec![14..18]);
+/// assert_eq!(changed_regions(&fingerprints, new, 16), vec![0..32]);
+/// ```
+pub fn changed_regions(old: &FingerprintFile,
+                       new_data: &[u8],
+                       granularity: usize)
+                       -> Vec<Range<u64>>
+{
+    changed_regions_verified(old, new_data, granularity, |_, _| true)
+}
+
+/// Regions of `new_data` that no longer match anything in
+/// the data set of the `old` fingerprints, as with
+/// `changed_regions()`. Each window whose CRC occurs in
+/// `old` is passed to `verify` with each old offset having
+/// that CRC, and is taken to be unchanged only if `verify`
+/// confirms that the old data there matches.
+pub fn changed_regions_verified<F>(old: &FingerprintFile,
+                                   new_data: &[u8],
+                                   granularity: usize,
+                                   mut verify: F)
+                                   -> Vec<Range<u64>>
+    where F: FnMut(u64, &[u8]) -> bool
+{
+    let granularity = granularity.max(1) as u64;
+    // When no window fits everything has changed. Then use
+    // a window size of 0, which has no windows, rather than
+    // build a context for a window size that may be huge.
+    let window_size = if old.window_size > new_data.len() as u64 {
+        0
+    } else {
+        old.window_size as usize
+    };
+    let mut known = old.entries.clone();
+    known.sort_unstable_by_key(|e| (e.crc, e.offset));
+    let context = RollingCRCContext::build(window_size);
+    let mut regions: Vec<Range<u64>> = Vec::new();
+    let mut mark_changed = |start: usize, end: usize| {
+        let start = start as u64 / granularity * granularity;
+        let end = (end as u64).div_ceil(granularity) * granularity;
+        let end = end.min(new_data.len() as u64);
+        if let Some(last) = regions.last_mut() {
+            if last.end >= start {
+                last.end = last.end.max(end);
+                return;
+            }
+        }
+        regions.push(start..end);
+    };
+    // End of the bytes covered by unchanged windows so far.
+    let mut covered = 0;
+    for (offset, crc) in context.rolling_crcs(new_data) {
+        let first = known.partition_point(|e| e.crc < crc);
+        let window = &new_data[offset..offset + window_size];
+        let unchanged = known[first..].iter()
+            .take_while(|e| e.crc == crc)
+            .any(|e| verify(e.offset, window));
+        if unchanged {
+            if covered < offset {
+                mark_changed(covered, offset);
+            }
+            covered = offset + window_size;
+        }
+    }
+    if covered < new_data.len() {
+        mark_changed(covered, new_data.len());
+    }
+    regions
+}
+
+/// Copy a slice into an array for integer decoding.
+fn le_bytes<A: Default + AsMut<[u8]>>(bytes: &[u8]) -> A {
+    let mut array = A::default();
+    array.as_mut().copy_from_slice(bytes);
+    array
+}
+
+#[test]
+fn test_fingerprint_round_trip() {
+    let data = test_bytes(0xdead_beef, 5000);
+    let context = RollingCRCContext::new(32).unwrap();
+    let mut writer = FingerprintWriter::new(Vec::new(), &context);
+    let mut expected = Vec::new();
+    for (offset, crc) in context.winnow(&data, 16) {
+        writer.add(offset as u64, crc);
+        expected.push(FingerprintEntry { offset: offset as u64, crc });
+    }
+    let file = writer.finish().unwrap();
+    assert_eq!(file.len(), 28 + 12 * expected.len());
+    assert_eq!(&file[..4], b"RCFP");
+
+    let fingerprints = FingerprintReader::open(&file[..]).unwrap();
+    assert_eq!(fingerprints.window_size(), 32);
+    assert_eq!(fingerprints.polynomial(), POLY_CRC);
+    assert_eq!(fingerprints.entries(), &expected[..]);
+    assert_eq!(fingerprints.entries_for(&context).unwrap(), &expected[..]);
+    match fingerprints.entries_for(&RollingCRCContext::new(16).unwrap()) {
+        Err(FingerprintError::WindowSizeMismatch { expected: 16, found: 32 }) =>
+            (),
+        r => panic!("window size mismatch not detected: {:?}", r),
+    }
+
+    let empty = FingerprintWriter::new(Vec::new(), &context).finish().unwrap();
+    assert!(FingerprintReader::open(&empty[..]).unwrap().is_empty());
+}
+
+#[test]
+fn test_fingerprint_corrupt() {
+    let context = RollingCRCContext::new(8).unwrap();
+    let mut writer = FingerprintWriter::new(Vec::new(), &context);
+    writer.add(0, 0x1234_5678);
+    writer.add(1, 0x9abc_def0);
+    let file = writer.finish().unwrap();
+
+    let mut bad_magic = file.clone();
+    bad_magic[0] = b'X';
+    match FingerprintReader::open(&bad_magic[..]) {
+        Err(FingerprintError::BadMagic) => (),
+        r => panic!("bad magic accepted: {:?}", r),
+    }
+    let mut bad_version = file.clone();
+    bad_version[4] = 99;
+    match FingerprintReader::open(&bad_version[..]) {
+        Err(FingerprintError::UnsupportedVersion(99)) => (),
+        r => panic!("bad version accepted: {:?}", r),
+    }
+    let mut zero_window = file.clone();
+    zero_window[8..16].copy_from_slice(&0u64.to_le_bytes());
+    match FingerprintReader::open(&zero_window[..]) {
+        Err(FingerprintError::BadWindowSize(0)) => (),
+        r => panic!("zero window size accepted: {:?}", r),
+    }
+    // A huge window size can't be used on 32-bit targets,
+    // and costs nothing when no window fits.
+    let mut huge_window = file.clone();
+    huge_window[8..16].copy_from_slice(&(1u64 << 40).to_le_bytes());
+    match FingerprintReader::open(&huge_window[..]) {
+        Ok(old) => assert_eq!(changed_regions(&old, b"new data", 1),
+                              vec![0..8]),
+        Err(FingerprintError::BadWindowSize(_))
+            if usize::try_from(1u64 << 40).is_err() => (),
+        r => panic!("huge window size mishandled: {:?}", r),
+    }
+    for &len in &[0, 10, 27, 28, 39, file.len() - 1] {
+        match FingerprintReader::open(&file[..len]) {
+            Err(FingerprintError::Io(ref e))
+                if e.kind() == io::ErrorKind::UnexpectedEof => (),
+            r => panic!("truncation to {} accepted: {:?}", len, r),
+        }
+    }
+}
+
+#[test]
+fn test_compare_fingerprints() {
+    let window_size = 16;
+    let context = RollingCRCContext::new(window_size).unwrap();
+    let fingerprint = |data: &[u8], context: &RollingCRCContext| {
+        let mut writer = FingerprintWriter::new(Vec::new(), context);
+        for (offset, crc) in context.rolling_crcs(data) {
+            writer.add(offset as u64, crc);
+        }
+        let file = writer.finish().unwrap();
+        FingerprintReader::open(&file[..]).unwrap()
+    };
+    let mut a = test_bytes(1, 8000);
+    let mut b = test_bytes(2, 6000);
+    let shared = test_bytes(3, 100);
+    a[1000..1100].copy_from_slice(&shared);
+    b[5000..5100].copy_from_slice(&shared);
+    a[7000..7050].copy_from_slice(&shared[..50]);
+    b[200..250].copy_from_slice(&shared[50..]);
+
+    let common = compare_fingerprints(&fingerprint(&a, &context),
+                                      &fingerprint(&b, &context))
+        .unwrap();
+    let mut expected = Vec::new();
+    for i in 0..=100 - window_size {
+        let crc = context.crc(&shared[i..i + window_size]);
+        expected.push((1000 + i as u64, 5000 + i as u64, crc));
+        if i >= 50 && i <= 100 - window_size {
+            expected.push((1000 + i as u64, 200 + i as u64 - 50, crc));
+        }
+        if i <= 50 - window_size {
+            expected.push((7000 + i as u64, 5000 + i as u64, crc));
+        }
+    }
+    expected.sort();
+    assert_eq!(common, expected);
+
+    let other = fingerprint(&b, &RollingCRCContext::new(8).unwrap());
+    assert_eq!(compare_fingerprints(&fingerprint(&a, &context), &other),
+               Err(CompareError::WindowSizeMismatch(16, 8)));
+    let mut file = FingerprintWriter::new(Vec::new(), &context)
+        .finish()
+        .unwrap();
+    file[16] ^= 1;
+    let other = FingerprintReader::open(&file[..]).unwrap();
+    assert_eq!(compare_fingerprints(&other, &fingerprint(&a, &context)),
+               Err(CompareError::PolynomialMismatch(POLY_CRC ^ 1, POLY_CRC)));
+}
+
+#[test]
+fn test_changed_regions() {
+    let data = test_bytes(0x2545_f491, 100_000);
+    let window_size = 32;
+    let context = RollingCRCContext::new(window_size).unwrap();
+    let mut writer = FingerprintWriter::new(Vec::new(), &context);
+    for (offset, crc) in context.rolling_crcs(&data) {
+        writer.add(offset as u64, crc);
+    }
+    let file = writer.finish().unwrap();
+    let old = FingerprintReader::open(&file[..]).unwrap();
+
+    assert!(changed_regions(&old, &data, 4096).is_empty());
+
+    let mut new = data.clone();
+    for &offset in &[0usize, 5000, 5010, 40_000, 99_999] {
+        new[offset] ^= 0x40;
+    }
+    // Bytes between nearby changes are covered by no
+    // unchanged window.
+    assert_eq!(changed_regions(&old, &new, 1),
+               vec![0..1, 5000..5011, 40_000..40_001, 99_999..100_000]);
+    assert_eq!(changed_regions(&old, &new, 64),
+               vec![0..64, 4992..5056, 40_000..40_064, 99_968..100_000]);
+    assert_eq!(changed_regions(&old, &new, 4096),
+               vec![0..8192, 36_864..40_960, 98_304..100_000]);
+
+    // Verification against the old data catches what the
+    // CRCs alone cannot.
+    let verified = changed_regions_verified(&old, &new, 1, |offset, window| {
+        let offset = offset as usize;
+        data[offset..offset + window_size] == *window
+    });
+    assert_eq!(verified, changed_regions(&old, &new, 1));
+    let rejected = changed_regions_verified(&old, &data, 1, |_, _| false);
+    assert_eq!(rejected, vec![0..data.len() as u64]);
+    assert_eq!(changed_regions(&old, &data[..10], 1), vec![0..10]);
+}
@@ -0,0 +1,248 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Rolling CRCs that never allocate, for bare-metal
+//! targets.
+
+use super::{calc_crc, finish_crc, RollingCRCContext};
+#[cfg(test)]
+use super::test_bytes;
+
+/// An in-progress rolling CRC whose window is kept in a
+/// buffer of type `B`, supplied by the caller, instead of
+/// on the heap. Use `RollingCRCFixed` for a window of size
+/// fixed at compile time, or `with_buffer()` for a window
+/// in a borrowed slice.
+///
+/// Memory use is small and fixed. The standard CRC table
+/// (1 KiB) is a static shared by every context, as are the
+/// tables for processing several bytes at a time (16 KiB)
+/// and for undoing a CRC step (256 bytes); all of these
+/// are computed at compile time and can live in read-only
+/// memory. Each context holds its own 1 KiB rolling CRC
+/// table, plus 256 bytes for a byte map if it has one. A
+/// rolling CRC holds its window of bytes and a few words.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(5).unwrap();
+/// let mut buffer = [0; 64];
+/// let mut roll_crc = RollingCRCBuf::with_buffer(&context, &mut buffer);
+/// let bytes = b"hello world";
+/// let last = bytes.iter().fold(None, |_, &b| roll_crc.push(b));
+/// assert_eq!(last, Some(context.crc(b"world")));
+/// ```
+#[derive(Debug, Clone)]
+pub struct RollingCRCBuf<'a, B> {
+    /// Needed context information.
+    context: &'a RollingCRCContext<'a>,
+    /// Bytes in window, exactly a window long.
+    window: B,
+    /// Number of bytes in the window while it is still
+    /// filling.
+    filled: usize,
+    /// Index of next byte in window to be replaced.
+    index: usize,
+    /// Last "open" rolling CRC, to continue rolling.
+    last_crc: Option<u32>,
+}
+
+/// An in-progress rolling CRC with a window of `W` bytes
+/// held inline.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(2).unwrap();
+/// let mut roll_crc = RollingCRCFixed::<2>::new(&context);
+/// let bytes = "hello world".as_bytes();
+/// for i in 0..bytes.len() {
+///     let crc = roll_crc.push(bytes[i]);
+///     if i == 0 {
+///         assert_eq!(crc, None);
+///     } else {
+///         assert_eq!(crc, Some(context.crc(&bytes[i-1..=i])));
+///     }
+/// }
+/// ```
+pub type RollingCRCFixed<'a, const W: usize> = RollingCRCBuf<'a, [u8; W]>;
+
+impl<'a, const W: usize> RollingCRCBuf<'a, [u8; W]> {
+
+    /// Start a new rolling CRC in the given context.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the window size of `context` is not `W`.
+    pub fn new(context: &'a RollingCRCContext<'a>) -> Self {
+        assert_eq!(context.window_size(), W,
+                   "RollingCRCFixed: context window size must be W");
+        Self {
+            context,
+            window: [0; W],
+            filled: 0,
+            index: 0,
+            last_crc: None,
+        }
+    }
+}
+
+impl<'a, 'b> RollingCRCBuf<'a, &'b mut [u8]> {
+
+    /// Start a new rolling CRC in the given context,
+    /// keeping the window in the start of the given buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is shorter than the window size
+    /// of `context`.
+    pub fn with_buffer(context: &'a RollingCRCContext<'a>,
+                       buffer: &'b mut [u8])
+                       -> Self
+    {
+        let window_size = context.window_size();
+        assert!(buffer.len() >= window_size,
+                "RollingCRCBuf: buffer shorter than window");
+        Self {
+            context,
+            window: &mut buffer[..window_size],
+            filled: 0,
+            index: 0,
+            last_crc: None,
+        }
+    }
+}
+
+impl<'a, B> RollingCRCBuf<'a, B> where B: AsRef<[u8]> + AsMut<[u8]> {
+
+    /// The bytes currently in the window, oldest first, as
+    /// a pair of slices to be read in order. Until the
+    /// window has filled this holds the bytes seen so far.
+    /// Bytes are reported after any translation by the
+    /// context's byte map.
+    pub fn window(&self) -> (&[u8], &[u8]) {
+        let window = self.window.as_ref();
+        match self.last_crc {
+            Some(_) => (&window[self.index..], &window[..self.index]),
+            None => (&window[..self.filled], &[]),
+        }
+    }
+
+    /// Roll a byte through this rolling CRC, returning the
+    /// CRC of the window it completes, if any, as with
+    /// `RollingCRC::push()`.
+    #[inline(always)]
+    pub fn push(&mut self, byte: u8) -> Option<u32> {
+        let byte = self.context.map_byte(byte);
+        let last_crc = match self.last_crc {
+            Some(last_crc) => last_crc,
+            None => return self.warm_up(byte),
+        };
+        let window = self.window.as_mut();
+        let roll_out = window[self.index];
+        let crc = self.context.roll(last_crc, byte, roll_out);
+        window[self.index] = byte;
+        // Wrap the index without a branch, as in
+        // `RollingCRC::push()`.
+        let next = self.index + 1;
+        let at_end = (next == window.len()) as usize;
+        self.index = next & at_end.wrapping_sub(1);
+        self.last_crc = Some(crc);
+        Some(finish_crc(crc))
+    }
+
+    /// Push a byte (already mapped) while the window is
+    /// still filling, returning the CRC of the first full
+    /// window.
+    #[cold]
+    #[inline(never)]
+    fn warm_up(&mut self, byte: u8) -> Option<u32> {
+        let window = self.window.as_mut();
+        if window.is_empty() {
+            return None;
+        }
+        window[self.filled] = byte;
+        self.filled += 1;
+        if self.filled < window.len() {
+            return None;
+        }
+        let crc = calc_crc(window, self.context.crc_table);
+        self.last_crc = Some(finish_crc(crc));
+        Some(crc)
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl<'a, B> ::defmt::Format for RollingCRCBuf<'a, B> {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "RollingCRCBuf {{ filled: {=usize}, primed: {=bool}",
+                        self.filled, self.last_crc.is_some());
+        if let Some(crc) = self.last_crc {
+            ::defmt::write!(f, ", last_crc: {=u32:#010x}", finish_crc(crc));
+        }
+        ::defmt::write!(f, " }}")
+    }
+}
+
+#[test]
+fn test_rolling_crc_fixed() {
+    let bytes = test_bytes(0x0bad_cafe, 300);
+
+    fn check<B>(context: &RollingCRCContext,
+                mut roll_crc: RollingCRCBuf<B>,
+                bytes: &[u8])
+        where B: AsRef<[u8]> + AsMut<[u8]>
+    {
+        let window_size = context.window_size();
+        let mut expected = context.rolling_crcs(bytes);
+        for (i, &b) in bytes.iter().enumerate() {
+            let crc = roll_crc.push(b);
+            if i + 1 < window_size || window_size == 0 {
+                assert_eq!(crc, None);
+                let (first, second) = roll_crc.window();
+                let seen = if window_size == 0 { 0 } else { i + 1 };
+                assert_eq!(first.len() + second.len(), seen);
+            } else {
+                assert_eq!(crc, expected.next().map(|(_, crc)| crc), "{}", i);
+                let (first, second) = roll_crc.window();
+                let mut window = [0; 64];
+                window[..first.len()].copy_from_slice(first);
+                window[first.len()..window_size].copy_from_slice(second);
+                assert_eq!(context.crc(&window[..window_size]), crc.unwrap());
+            }
+        }
+    }
+
+    let context = RollingCRCContext::checksum_only();
+    check(&context, RollingCRCFixed::<0>::new(&context), &bytes);
+    let context = RollingCRCContext::new(1).unwrap();
+    check(&context, RollingCRCFixed::<1>::new(&context), &bytes);
+    let context = RollingCRCContext::new(16).unwrap();
+    check(&context, RollingCRCFixed::<16>::new(&context), &bytes);
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8) & 0x3f;
+    }
+    let context = RollingCRCContext::with_byte_map(7, fold).unwrap();
+    check(&context, RollingCRCFixed::<7>::new(&context), &bytes);
+
+    let mut buffer = [0xff; 64];
+    for window_size in 0..=64 {
+        let context = RollingCRCContext::build(window_size);
+        check(&context,
+              RollingCRCBuf::with_buffer(&context, &mut buffer),
+              &bytes);
+    }
+}
+
+#[test]
+#[should_panic(expected = "RollingCRCFixed: context window size must be W")]
+fn test_rolling_crc_fixed_size_mismatch() {
+    let context = RollingCRCContext::new(8).unwrap();
+    let _ = RollingCRCFixed::<4>::new(&context);
+}
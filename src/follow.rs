@@ -0,0 +1,426 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Following a growing file, such as an append-only log,
+//! for known byte signatures.
+
+use super::{CrcSet, RollingCRC, RollingCRCContext};
+
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+
+/// Size of the buffer used to read newly appended data.
+const FOLLOW_BUF_SIZE: usize = 64 * 1024;
+
+/// An occurrence of a signature found by `FollowScanner`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(::defmt::Format))]
+pub struct FollowMatch {
+    /// Absolute file offset of the occurrence.
+    pub offset: u64,
+    /// Index of the signature found, in the order given to
+    /// `FollowScanner::new()`.
+    pub signature: usize,
+}
+
+/// Reasons a `FollowScanner` may fail to start.
+#[derive(Debug)]
+pub enum FollowError {
+    /// A signature length is not the context window size.
+    PatternLength {
+        /// Index of the signature.
+        index: usize,
+        /// Window size of the context.
+        expected: usize,
+        /// Length of the signature.
+        found: usize,
+    },
+    /// Reading the file failed, or it has been truncated.
+    Io(io::Error),
+}
+
+impl fmt::Display for FollowError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FollowError::PatternLength { index, expected, found } =>
+                write!(f, "signature {} length {} is not the \
+                           window size {}",
+                       index, found, expected),
+            FollowError::Io(ref e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl error::Error for FollowError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match *self {
+            FollowError::Io(ref e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for FollowError {
+    fn from(e: io::Error) -> Self {
+        FollowError::Io(e)
+    }
+}
+
+/// A scan of a file that is being appended to, such as a
+/// log, for occurrences of a set of signatures of the
+/// context window size. Each `poll()` reads whatever has
+/// been appended since the last one, carrying the rolling
+/// state over, so that occurrences straddling the data of
+/// two polls are found just as in a single scan of the
+/// whole file. Candidates are found by CRC and then
+/// verified byte-for-byte.
+///
+/// The scan position can be saved with `position()` and the
+/// scan picked up again later with `resume()`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate rolling_crc;
+/// # extern crate tempfile;
+/// # use rolling_crc::*;
+/// use std::fs::File;
+/// use std::io::Write;
+///
+/// # fn main() {
+/// let mut log = tempfile::NamedTempFile::new().unwrap();
+/// let context = RollingCRCContext::new(5).unwrap();
+/// let file = File::open(log.path()).unwrap();
+/// let mut scanner = FollowScanner::new(&context, &[b"panic"], file);
+/// log.write_all(b"ok\nkernel pa").unwrap();
+/// assert!(scanner.poll().unwrap().is_empty());
+/// log.write_all(b"nic\nok\n").unwrap();
+/// let found = scanner.poll().unwrap();
+/// assert_eq!(found, vec![FollowMatch { offset: 10, signature: 0 }]);
+/// assert_eq!(scanner.position(), 19);
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct FollowScanner<'a> {
+    file: File,
+    /// Rolling state carried across polls.
+    rolling_crc: RollingCRC<'a>,
+    /// The signatures sought.
+    signatures: Vec<Vec<u8>>,
+    /// CRC of each signature.
+    crcs: Vec<u32>,
+    /// Set of the signature CRCs.
+    targets: CrcSet,
+    /// File offset of the next byte to be read.
+    position: u64,
+}
+
+impl<'a> FollowScanner<'a> {
+
+    /// Start following the given file from its beginning
+    /// for the given signatures.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a signature length is not the context
+    /// window size; `try_new()` returns an error instead.
+    pub fn new<S>(context: &'a RollingCRCContext<'a>,
+                  signatures: &[S],
+                  file: File)
+                  -> Self
+        where S: AsRef<[u8]>
+    {
+        match Self::try_new(context, signatures, file) {
+            Ok(scanner) => scanner,
+            Err(e) => panic!("FollowScanner: {}", e),
+        }
+    }
+
+    /// Start following the given file from its beginning
+    /// for the given signatures, as with `new()`.
+    ///
+    /// # Errors
+    ///
+    /// It is an error if a signature length is not the
+    /// context window size.
+    pub fn try_new<S>(context: &'a RollingCRCContext<'a>,
+                      signatures: &[S],
+                      file: File)
+                      -> Result<Self, FollowError>
+        where S: AsRef<[u8]>
+    {
+        let window_size = context.window_size();
+        let mut owned = Vec::with_capacity(signatures.len());
+        for (index, signature) in signatures.iter().enumerate() {
+            let signature = signature.as_ref();
+            if signature.len() != window_size {
+                return Err(FollowError::PatternLength {
+                    index,
+                    expected: window_size,
+                    found: signature.len(),
+                });
+            }
+            owned.push(signature.to_vec());
+        }
+        let crcs: Vec<u32> = owned.iter()
+            .map(|signature| context.crc(signature))
+            .collect();
+        Ok(Self {
+            file,
+            rolling_crc: RollingCRC::new(context),
+            targets: CrcSet::new(crcs.iter().cloned()),
+            signatures: owned,
+            crcs,
+            position: 0,
+        })
+    }
+
+    /// Pick up following the given file at the given
+    /// position, as saved from `position()`, for the given
+    /// signatures. The bytes of the window before the
+    /// position are read again to restore the rolling
+    /// state, so that occurrences straddling the position
+    /// are found.
+    ///
+    /// # Errors
+    ///
+    /// It is an error of kind `InvalidData` if the file is
+    /// now shorter than the position.
+    ///
+    /// # Panics
+    ///
+    /// Panics if a signature length is not the context
+    /// window size; `try_resume()` returns an error
+    /// instead.
+    pub fn resume<S>(context: &'a RollingCRCContext<'a>,
+                     signatures: &[S],
+                     file: File,
+                     position: u64)
+                     -> io::Result<Self>
+        where S: AsRef<[u8]>
+    {
+        Self::new(context, signatures, file).resume_at(position)
+    }
+
+    /// Pick up following the given file at the given
+    /// position, as with `resume()`.
+    ///
+    /// # Errors
+    ///
+    /// It is an error if a signature length is not the
+    /// context window size, or as for `resume()`.
+    pub fn try_resume<S>(context: &'a RollingCRCContext<'a>,
+                         signatures: &[S],
+                         file: File,
+                         position: u64)
+                         -> Result<Self, FollowError>
+        where S: AsRef<[u8]>
+    {
+        let scanner = Self::try_new(context, signatures, file)?;
+        Ok(scanner.resume_at(position)?)
+    }
+
+    /// Restore the rolling state of a new scanner to the
+    /// given position.
+    fn resume_at(mut self, position: u64) -> io::Result<Self> {
+        self.check_length(position)?;
+        let context = self.rolling_crc.context;
+        let history = context.window_size().saturating_sub(1) as u64;
+        let start = position.saturating_sub(history);
+        let _ = self.file.seek(SeekFrom::Start(start))?;
+        let mut prefix = Vec::new();
+        let _ = (&mut self.file)
+            .take(position - start)
+            .read_to_end(&mut prefix)?;
+        if (prefix.len() as u64) < position - start {
+            return Err(truncated(position, start + prefix.len() as u64));
+        }
+        for &b in &prefix {
+            let _ = self.rolling_crc.push(b);
+        }
+        self.position = position;
+        Ok(self)
+    }
+
+    /// Read everything appended to the file since the last
+    /// poll, returning the occurrences of the signatures
+    /// ending in the new data, in order of offset.
+    ///
+    /// # Errors
+    ///
+    /// It is an error of kind `InvalidData` if the file has
+    /// been truncated to shorter than the scan position.
+    /// The scanner is left unchanged; call `reset()` to
+    /// start again from the beginning of the file.
+    pub fn poll(&mut self) -> io::Result<Vec<FollowMatch>> {
+        self.check_length(self.position)?;
+        let _ = self.file.seek(SeekFrom::Start(self.position))?;
+        let window_size = self.rolling_crc.context.window_size() as u64;
+        let mut found = Vec::new();
+        let mut buf = vec![0; FOLLOW_BUF_SIZE];
+        loop {
+            let n = match self.file.read(&mut buf) {
+                Ok(0) => return Ok(found),
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted =>
+                    continue,
+                Err(e) => return Err(e),
+            };
+            for &b in &buf[..n] {
+                self.position += 1;
+                let crc = match self.rolling_crc.push(b) {
+                    Some(crc) if self.targets.contains(crc) => crc,
+                    _ => continue,
+                };
+                let (first, second) = self.rolling_crc.window();
+                let signature = (0..self.signatures.len()).find(|&i| {
+                    let signature = &self.signatures[i];
+                    self.crcs[i] == crc
+                        && signature[..first.len()] == *first
+                        && signature[first.len()..] == *second
+                });
+                if let Some(signature) = signature {
+                    let offset = self.position - window_size;
+                    found.push(FollowMatch { offset, signature });
+                }
+            }
+        }
+    }
+
+    /// File offset up to which the file has been scanned.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Start the scan again from the beginning of the
+    /// file, as after truncation or rotation in place.
+    pub fn reset(&mut self) {
+        self.rolling_crc = RollingCRC::new(self.rolling_crc.context);
+        self.position = 0;
+    }
+
+    /// The file followed.
+    pub fn get_ref(&self) -> &File {
+        &self.file
+    }
+
+    /// Stop following, returning the file.
+    pub fn into_inner(self) -> File {
+        self.file
+    }
+
+    /// Check that the file is at least `position` bytes
+    /// long.
+    fn check_length(&self, position: u64) -> io::Result<()> {
+        let len = self.file.metadata()?.len();
+        if len < position {
+            return Err(truncated(position, len));
+        }
+        Ok(())
+    }
+}
+
+/// Error for a file found to be shorter than the scan
+/// position.
+fn truncated(position: u64, len: u64) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData,
+                   format!("file truncated to {} bytes \
+                            from scan position {}",
+                           len, position))
+}
+
+#[test]
+fn test_follow_scanner() {
+    use std::io::Write;
+
+    let context = RollingCRCContext::new(6).unwrap();
+    let signatures = [b"ERROR!", b"FATAL!"];
+    let mut log = tempfile::NamedTempFile::new().unwrap();
+    let path = log.path().to_path_buf();
+    let open = || File::open(&path).unwrap();
+    let mut scanner = FollowScanner::new(&context, &signatures, open());
+    assert!(scanner.poll().unwrap().is_empty());
+
+    // Appends splitting signatures at every point,
+    // including a stage longer than the read buffer.
+    let mut contents = Vec::new();
+    let mut expected = Vec::new();
+    let mut found = Vec::new();
+    let mut stages: Vec<Vec<u8>> = Vec::new();
+    for split in 0..=6 {
+        let signature = split % 2;
+        expected.push(FollowMatch {
+            offset: contents.len() as u64 + 3,
+            signature,
+        });
+        let mut line = b"ok ".to_vec();
+        line.extend_from_slice(signatures[signature]);
+        line.extend_from_slice(b" ERROR FATAL\n");
+        let (old, new) = line.split_at(3 + split);
+        stages.push(old.to_vec());
+        stages.push(new.to_vec());
+        contents.extend_from_slice(&line);
+    }
+    let mut big = vec![b'.'; FOLLOW_BUF_SIZE + 100];
+    big[FOLLOW_BUF_SIZE - 3..FOLLOW_BUF_SIZE + 3].copy_from_slice(b"FATAL!");
+    expected.push(FollowMatch {
+        offset: (contents.len() + FOLLOW_BUF_SIZE - 3) as u64,
+        signature: 1,
+    });
+    contents.extend_from_slice(&big);
+    stages.push(big);
+    for stage in &stages {
+        log.write_all(stage).unwrap();
+        log.flush().unwrap();
+        found.extend(scanner.poll().unwrap());
+        assert_eq!(scanner.position(), log.as_file().metadata().unwrap().len());
+    }
+    assert_eq!(found, expected);
+    assert!(scanner.poll().unwrap().is_empty());
+
+    // Resuming partway through a signature finds it.
+    let position = expected[3].offset + 2;
+    let mut resumed =
+        FollowScanner::resume(&context, &signatures, open(), position)
+        .unwrap();
+    assert_eq!(resumed.poll().unwrap(), &expected[3..]);
+    assert_eq!(resumed.position(), contents.len() as u64);
+    let resumed = FollowScanner::resume(&context, &signatures, open(), 2);
+    assert_eq!(resumed.unwrap().poll().unwrap(), expected);
+    let e = FollowScanner::resume(&context, &signatures, open(), 1 << 40)
+        .unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+
+    // Truncation is reported until reset.
+    log.as_file().set_len(0).unwrap();
+    let _ = log.seek(SeekFrom::Start(0)).unwrap();
+    let position = scanner.position();
+    let e = scanner.poll().unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+    assert_eq!(scanner.position(), position);
+    scanner.reset();
+    log.write_all(b"FATAL!").unwrap();
+    log.flush().unwrap();
+    assert_eq!(scanner.poll().unwrap(),
+               vec![FollowMatch { offset: 0, signature: 1 }]);
+
+    // Signatures of the wrong length are errors on request.
+    let signatures: [&[u8]; 2] = [b"ERROR!", b"FATAL"];
+    match FollowScanner::try_new(&context, &signatures, open()) {
+        Err(FollowError::PatternLength { index, expected, found }) =>
+            assert_eq!((index, expected, found), (1, 6, 5)),
+        e => panic!("unexpected {:?}", e),
+    }
+    let e = FollowScanner::try_resume(&context, &signatures, open(), 0)
+        .unwrap_err();
+    assert_eq!(e.to_string(),
+               "signature 1 length 5 is not the window size 6");
+    let signatures = [b"ERROR!"];
+    let e = FollowScanner::try_resume(&context, &signatures, open(), 1 << 40)
+        .unwrap_err();
+    assert!(matches!(e, FollowError::Io(_)));
+}
@@ -0,0 +1,137 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Forging CRCs: choosing four bytes of a message to give
+//! it any desired CRC.
+
+use super::{finish_crc, shrink_zeros, OpenCrc, RollingCRCContext};
+#[cfg(test)]
+use super::test_rng;
+
+use std::error;
+use std::fmt;
+
+/// Error in forging a CRC.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ForgeError {
+    /// The four-byte field is not within the data.
+    FieldOutOfBounds {
+        /// Offset of the field.
+        offset: usize,
+        /// Length of the data.
+        len: usize,
+    },
+    /// The context has a byte map, through which the
+    /// forged bytes might not be reachable.
+    ByteMap,
+}
+
+impl fmt::Display for ForgeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ForgeError::FieldOutOfBounds { offset, len } =>
+                write!(f, "four-byte field at {} is not within {} bytes",
+                       offset, len),
+            ForgeError::ByteMap =>
+                write!(f, "cannot forge CRCs through a byte map"),
+        }
+    }
+}
+
+impl error::Error for ForgeError {}
+
+impl<'a> RollingCRCContext<'a> {
+
+    /// Four bytes which when appended to `data` give a
+    /// message with CRC `target`. Fails if the context has
+    /// a byte map.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// let mut data = b"attack at dawn".to_vec();
+    /// let suffix = context.forge_suffix(&data, 0xdeadbeef).unwrap();
+    /// data.extend_from_slice(&suffix);
+    /// assert_eq!(context.crc(&data), 0xdeadbeef);
+    /// ```
+    pub fn forge_suffix(&self, data: &[u8], target: u32)
+                        -> Result<[u8; 4], ForgeError>
+    {
+        if self.byte_map().is_some() {
+            return Err(ForgeError::ByteMap);
+        }
+        // Hashing four bytes w from open CRC r is hashing
+        // four zeros from r ^ w.
+        let open = self.resume_open(OpenCrc::new(), data).value();
+        let w = shrink_zeros(finish_crc(target), 4) ^ open;
+        Ok(w.to_le_bytes())
+    }
+
+    /// Overwrite the four bytes of `data` at `offset` so
+    /// that its CRC is `target`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// let mut packet = *b"HDR:....:payload";
+    /// context.forge_at(&mut packet, 4, 0x01234567).unwrap();
+    /// assert_eq!(context.crc(&packet), 0x01234567);
+    /// assert_eq!(context.forge_at(&mut packet, 13, 0),
+    ///            Err(ForgeError::FieldOutOfBounds { offset: 13, len: 16 }));
+    /// ```
+    pub fn forge_at(&self, data: &mut [u8], offset: usize, target: u32)
+                    -> Result<(), ForgeError>
+    {
+        let len = data.len();
+        if offset > len || len - offset < 4 {
+            return Err(ForgeError::FieldOutOfBounds { offset, len });
+        }
+        if self.byte_map().is_some() {
+            return Err(ForgeError::ByteMap);
+        }
+        // With the field zeroed, the CRC is off by the
+        // contribution of the field's value followed by the
+        // rest of the data as zeros.
+        data[offset..offset + 4].copy_from_slice(&[0; 4]);
+        let contribution = self.crc(data) ^ target;
+        let tail = (len - offset) as u64;
+        let w = shrink_zeros(contribution, tail);
+        data[offset..offset + 4].copy_from_slice(&w.to_le_bytes());
+        Ok(())
+    }
+}
+
+#[test]
+fn test_forge() {
+    let mut next = test_rng(77u32);
+    let context = RollingCRCContext::checksum_only();
+    for _ in 0..200 {
+        let len = next() as usize % 300;
+        let mut data: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+        let target = next();
+
+        let suffix = context.forge_suffix(&data, target).unwrap();
+        let mut forged = data.clone();
+        forged.extend_from_slice(&suffix);
+        assert_eq!(context.crc(&forged), target);
+
+        if len >= 4 {
+            let offset = next() as usize % (len - 3);
+            context.forge_at(&mut data, offset, target).unwrap();
+            assert_eq!(context.crc(&data), target);
+        } else {
+            assert!(context.forge_at(&mut data, 0, target).is_err());
+        }
+    }
+    assert_eq!(context.crc(&context.forge_suffix(b"", 0).unwrap()), 0);
+
+    let context = RollingCRCContext::checksum_only_with_byte_map([0; 256]);
+    assert_eq!(context.forge_suffix(b"", 0), Err(ForgeError::ByteMap));
+    assert_eq!(context.forge_at(&mut [0; 8], 0, 0), Err(ForgeError::ByteMap));
+}
@@ -0,0 +1,285 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Arithmetic on polynomials over GF(2) modulo a CRC
+//! polynomial, the machinery beneath `combine()`,
+//! `RollingCRCContext::extend_zeros()` and friends.
+//!
+//! Everything here uses the bit-reflected order of this
+//! crate's CRCs: a polynomial of degree less than 32 is a
+//! `u32` whose bit 31 is the coefficient of x^0 and whose
+//! bit 0 is that of x^31. So 1 is `0x8000_0000` and x is
+//! `0x4000_0000`. A CRC polynomial is given the same way
+//! with its implicit x^32 term left off, as in `POLY_CRC`.
+//!
+//! An open CRC is such a polynomial, and appending n zero
+//! bits to a message multiplies its open CRC by x^n modulo
+//! the CRC polynomial.
+
+use std::fmt;
+use std::ops::Mul;
+
+/// Polynomial 1 in reflected order.
+const ONE: u32 = 1 << 31;
+
+/// Multiply a polynomial by x modulo `poly`.
+const fn mul_x(p: u32, poly: u32) -> u32 {
+    if p & 1 != 0 {
+        (p >> 1) ^ poly
+    } else {
+        p >> 1
+    }
+}
+
+/// Carry-less product of `a` and `b` modulo `poly`.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// // x * x^31 = x^32, which is the polynomial itself.
+/// assert_eq!(gf2::clmul(0x4000_0000, 1, POLY_CRC), POLY_CRC);
+/// ```
+pub const fn clmul(a: u32, mut b: u32, poly: u32) -> u32 {
+    let mut product = 0;
+    let mut m = ONE;
+    while m != 0 {
+        if a & m != 0 {
+            product ^= b;
+        }
+        b = mul_x(b, poly);
+        m >>= 1;
+    }
+    product
+}
+
+/// x^n modulo `poly`, by repeated squaring.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// assert_eq!(gf2::xpow_mod(0, POLY_CRC), 0x8000_0000);
+/// assert_eq!(gf2::xpow_mod(31, POLY_CRC), 1);
+/// assert_eq!(gf2::xpow_mod(32, POLY_CRC), POLY_CRC);
+/// ```
+pub const fn xpow_mod(mut n: u64, poly: u32) -> u32 {
+    let mut p = ONE;
+    let mut base = ONE >> 1;
+    while n != 0 {
+        if n & 1 != 0 {
+            p = clmul(p, base, poly);
+        }
+        base = clmul(base, base, poly);
+        n >>= 1;
+    }
+    p
+}
+
+/// A 32×32 matrix over GF(2): a linear map on 32-bit
+/// vectors such as the step of a CRC. Column j is the image
+/// of the vector with only bit j set; bits are numbered as
+/// in a `u32`, so in reflected order bit j holds the
+/// coefficient of x^(31-j).
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// // Eight zero bits, or one zero byte.
+/// let zero_byte = gf2::Gf2Matrix::mul_x(POLY_CRC).pow(8);
+/// let context = RollingCRCContext::checksum_only();
+/// let open = context.crc(b"abc") ^ INIT_CRC;
+/// assert_eq!(zero_byte.apply(open) ^ INIT_CRC, context.crc(b"abc\0"));
+/// ```
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct Gf2Matrix {
+    columns: [u32; 32],
+}
+
+impl Gf2Matrix {
+
+    /// Make a matrix with the given columns.
+    pub fn from_columns(columns: [u32; 32]) -> Self {
+        Self { columns }
+    }
+
+    /// The columns of this matrix.
+    pub fn columns(&self) -> &[u32; 32] {
+        &self.columns
+    }
+
+    /// The identity matrix.
+    pub fn identity() -> Self {
+        let mut columns = [0; 32];
+        for (j, c) in columns.iter_mut().enumerate() {
+            *c = 1 << j;
+        }
+        Self { columns }
+    }
+
+    /// The matrix multiplying a polynomial by x modulo
+    /// `poly`: one step of a bitwise CRC over a zero bit.
+    pub fn mul_x(poly: u32) -> Self {
+        let mut columns = [0; 32];
+        for (j, c) in columns.iter_mut().enumerate() {
+            *c = mul_x(1 << j, poly);
+        }
+        Self { columns }
+    }
+
+    /// Apply this matrix to a vector.
+    pub fn apply(&self, v: u32) -> u32 {
+        let mut result = 0;
+        let mut v = v;
+        let mut j = 0;
+        while v != 0 {
+            if v & 1 != 0 {
+                result ^= self.columns[j];
+            }
+            v >>= 1;
+            j += 1;
+        }
+        result
+    }
+
+    /// The product of this matrix and `other`: the map
+    /// applying `other` and then this matrix.
+    pub fn multiply(&self, other: &Gf2Matrix) -> Self {
+        let mut columns = [0; 32];
+        for (c, &o) in columns.iter_mut().zip(&other.columns) {
+            *c = self.apply(o);
+        }
+        Self { columns }
+    }
+
+    /// This matrix raised to the power `n`, by repeated
+    /// squaring.
+    pub fn pow(&self, mut n: u64) -> Self {
+        let mut result = Self::identity();
+        let mut base = *self;
+        while n != 0 {
+            if n & 1 != 0 {
+                result = result.multiply(&base);
+            }
+            base = base.multiply(&base);
+            n >>= 1;
+        }
+        result
+    }
+}
+
+impl<'a> Mul<&'a Gf2Matrix> for &'a Gf2Matrix {
+    type Output = Gf2Matrix;
+
+    fn mul(self, other: &'a Gf2Matrix) -> Gf2Matrix {
+        self.multiply(other)
+    }
+}
+
+impl fmt::Debug for Gf2Matrix {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Gf2Matrix({:08x?})", self.columns)
+    }
+}
+
+#[cfg(test)]
+const TEST_POLYS: [u32; 4] = [
+    super::POLY_CRC,
+    // CRC-32C (Castagnoli).
+    0x82f6_3b78,
+    // Reducible: x^32 + 1.
+    0x8000_0000,
+    // x^32 + x^31.
+    0x0000_0001,
+];
+
+/// Reverse the bits of a reflected polynomial to get the
+/// usual order, with bit i the coefficient of x^i.
+#[cfg(test)]
+fn unreflect(p: u32) -> u64 {
+    u64::from(p.reverse_bits())
+}
+
+/// Reduce a polynomial in the usual order modulo the full
+/// polynomial `x^32 + poly`.
+#[cfg(test)]
+fn brute_reduce(mut p: u64, poly: u32) -> u32 {
+    let full = (1 << 32) | unreflect(poly);
+    for i in (32..64).rev() {
+        if p & (1 << i) != 0 {
+            p ^= full << (i - 32);
+        }
+    }
+    (p as u32).reverse_bits()
+}
+
+#[test]
+fn test_clmul() {
+    let brute = |a: u32, b: u32, poly: u32| {
+        let (a, b) = (unreflect(a), unreflect(b));
+        let product = (0..32)
+            .filter(|i| a & (1 << i) != 0)
+            .fold(0, |p, i| p ^ (b << i));
+        brute_reduce(product, poly)
+    };
+    let mut next = super::test_rng(0x9e37_79b9u32);
+    for &poly in &TEST_POLYS {
+        for _ in 0..1000 {
+            let (a, b) = (next(), next());
+            assert_eq!(clmul(a, b, poly), brute(a, b, poly));
+            assert_eq!(clmul(a, b, poly), clmul(b, a, poly));
+        }
+        assert_eq!(clmul(ONE, 0x1234_5678, poly), 0x1234_5678);
+        assert_eq!(clmul(0, 0x1234_5678, poly), 0);
+    }
+}
+
+#[test]
+fn test_xpow_mod() {
+    for &poly in &TEST_POLYS {
+        let full = (1 << 32) | unreflect(poly);
+        let mut p: u64 = 1;
+        for n in 0..300 {
+            assert_eq!(xpow_mod(n, poly), (p as u32).reverse_bits(),
+                       "{:08x} {}", poly, n);
+            p <<= 1;
+            if p & (1 << 32) != 0 {
+                p ^= full;
+            }
+        }
+        let big = 1 << 40;
+        assert_eq!(xpow_mod(big + 7, poly),
+                   clmul(xpow_mod(big, poly), xpow_mod(7, poly), poly));
+    }
+}
+
+#[test]
+fn test_gf2_matrix() {
+    use super::{update_crc, CRC_TABLE, POLY_CRC};
+
+    let id = Gf2Matrix::identity();
+    for &v in &[0, 1, 0x8000_0000, 0xdead_beef] {
+        assert_eq!(id.apply(v), v);
+    }
+    for &poly in &TEST_POLYS {
+        let m = Gf2Matrix::mul_x(poly);
+        for n in 0..100 {
+            let mn = m.pow(n);
+            for &v in &[ONE, 0x1234_5678, 0xffff_ffff] {
+                assert_eq!(mn.apply(v), clmul(xpow_mod(n, poly), v, poly));
+            }
+        }
+        let (a, b) = (m.pow(5), m.pow(17));
+        assert_eq!(&a * &b, m.pow(22));
+        assert_eq!(a.multiply(&id), a);
+    }
+
+    // Eight steps over a zero bit are a byte update.
+    let zero_byte = Gf2Matrix::mul_x(POLY_CRC).pow(8);
+    for &v in &[0, 1, 0x8000_0000, 0xdead_beef] {
+        assert_eq!(zero_byte.apply(v), update_crc(v, &CRC_TABLE, 0));
+    }
+}
@@ -0,0 +1,126 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Searching gzip-compressed streams using rolling CRCs.
+
+use super::{Finder, RollingCRC, RollingCRCMapResult};
+#[cfg(test)]
+use super::test_bytes;
+
+use std::io::{self, BufReader, Read};
+
+use flate2::read::MultiGzDecoder;
+
+/// Positions of all occurrences of the needle in the
+/// decompressed contents of the given gzip stream, as
+/// offsets in the decompressed stream. Multi-member gzip
+/// streams (as produced by concatenating gzip files) are
+/// treated as the concatenation of their contents.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate flate2;
+/// # extern crate rolling_crc;
+/// # use rolling_crc::*;
+/// # use std::io::Write;
+/// # fn main() {
+/// let mut encoder = flate2::write::GzEncoder::new(
+///     Vec::new(),
+///     flate2::Compression::default(),
+/// );
+/// encoder.write_all(b"find the needle in the haystack").unwrap();
+/// let gzipped = encoder.finish().unwrap();
+/// let found = find_in_gzip(&gzipped[..], b"needle").unwrap();
+/// assert_eq!(found, vec![9]);
+/// # }
+/// ```
+pub fn find_in_gzip<R: Read>(reader: R, needle: &[u8])
+                             -> io::Result<Vec<u64>>
+{
+    Finder::new(needle).find_reader(MultiGzDecoder::new(reader))
+}
+
+/// An iterator over the rolling CRCs of the decompressed
+/// contents of a gzip stream. This iterator can be created
+/// using `gzip_rolling_crcs()`.
+pub type GzipRollingCRCs<'a, R> =
+    RollingCRCMapResult<'a,
+                        io::Bytes<BufReader<MultiGzDecoder<R>>>,
+                        io::Error>;
+
+/// An iterator over the decompressed contents of the given
+/// gzip stream, returning successive rolling CRCs resulting
+/// from operating on the given state, as with
+/// `RollingCRC::iter_result()`. Positions are offsets in
+/// the decompressed stream.
+pub fn gzip_rolling_crcs<R: Read>(reader: R, rolling_crc: RollingCRC)
+                                  -> GzipRollingCRCs<R>
+{
+    let bytes = BufReader::new(MultiGzDecoder::new(reader)).bytes();
+    rolling_crc.iter_result(bytes)
+}
+
+#[cfg(test)]
+fn gzip_member(parts: &[&[u8]]) -> Vec<u8> {
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    for part in parts {
+        encoder.write_all(part).unwrap();
+        // A sync flush ends the current deflate block.
+        encoder.flush().unwrap();
+    }
+    encoder.finish().unwrap()
+}
+
+#[test]
+fn test_find_in_gzip() {
+    use super::RollingCRCContext;
+
+    let needle = b"NEEDLE-in-the-gzip";
+    let filler: Vec<u8> = test_bytes(0x2545_f491, 100_000)
+        .into_iter()
+        .map(|b| b"abcdefgh"[b as usize % 8])
+        .collect();
+    let mut data = filler.clone();
+    let mut expected = Vec::new();
+    for &offset in &[0usize, 70_000, 99_000] {
+        data[offset..offset + needle.len()].copy_from_slice(needle);
+        expected.push(offset as u64);
+    }
+
+    // The first member ends a deflate block in the middle
+    // of a needle, and the second member starts with the
+    // rest of another.
+    let split = 40_000;
+    data[split - 5..split - 5 + needle.len()].copy_from_slice(needle);
+    expected.insert(1, (split - 5) as u64);
+    let member_split = 60_000;
+    data[member_split - 7..member_split - 7 + needle.len()]
+        .copy_from_slice(needle);
+    expected.insert(2, (member_split - 7) as u64);
+    let mut gzipped = gzip_member(&[&data[..split], &data[split..member_split]]);
+    gzipped.extend(gzip_member(&[&data[member_split..]]));
+
+    assert_eq!(find_in_gzip(&gzipped[..], needle).unwrap(), expected);
+
+    let context = RollingCRCContext::new(needle.len()).unwrap();
+    let crcs: Vec<(u64, u32)> =
+        gzip_rolling_crcs(&gzipped[..], RollingCRC::new(&context))
+        .collect::<io::Result<_>>()
+        .unwrap();
+    let reference: Vec<(u64, u32)> = context.rolling_crcs(&data)
+        .map(|(offset, crc)| (offset as u64, crc))
+        .collect();
+    assert_eq!(crcs, reference);
+
+    // A corrupt stream is an error, not a short result.
+    let mut corrupt = gzipped.clone();
+    corrupt.truncate(gzipped.len() / 2);
+    assert!(find_in_gzip(&corrupt[..], needle).is_err());
+}
@@ -0,0 +1,83 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Serialization of CRCs as hex strings, for use with
+//! `#[serde(with = "rolling_crc::hex_crc")]` on a `u32`
+//! field. A CRC is written as eight lowercase hex digits,
+//! as in `"cbf43926"`; reading also accepts uppercase
+//! digits, a `0x` prefix and fewer digits.
+//!
+//! The result types of this crate serialize their CRCs as
+//! plain numbers.
+//!
+//! # Examples
+//!
+//! ```
+//! # extern crate rolling_crc;
+//! # extern crate serde;
+//! # extern crate serde_json;
+//! #[derive(serde::Serialize, serde::Deserialize)]
+//! struct Record {
+//!     #[serde(with = "rolling_crc::hex_crc")]
+//!     crc: u32,
+//! }
+//!
+//! # fn main() {
+//! let json = serde_json::to_string(&Record { crc: 0xcbf4_3926 }).unwrap();
+//! assert_eq!(json, r#"{"crc":"cbf43926"}"#);
+//! let record: Record = serde_json::from_str(r#"{"crc":"0xCBF43926"}"#)
+//!     .unwrap();
+//! assert_eq!(record.crc, 0xcbf4_3926);
+//! # }
+//! ```
+
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::Serializer;
+
+/// Serialize a CRC as a hex string.
+pub fn serialize<S: Serializer>(crc: &u32, serializer: S)
+                                -> Result<S::Ok, S::Error>
+{
+    serializer.collect_str(&format_args!("{:08x}", crc))
+}
+
+/// Deserialize a CRC from a hex string.
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D)
+                                              -> Result<u32, D::Error>
+{
+    let text = String::deserialize(deserializer)?;
+    let digits = text.strip_prefix("0x").unwrap_or(&text);
+    if digits.is_empty() || digits.len() > 8 {
+        return Err(de::Error::invalid_length(digits.len(),
+                                             &"1 to 8 hex digits"));
+    }
+    if !digits.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return Err(de::Error::invalid_value(de::Unexpected::Str(&text),
+                                            &"a hex CRC"));
+    }
+    Ok(u32::from_str_radix(digits, 16).expect("hex digits"))
+}
+
+#[test]
+fn test_hex_crc() {
+    let mut serialized = Vec::new();
+    serialize(&0x0d4a_1185, &mut serde_json::Serializer::new(&mut serialized))
+        .unwrap();
+    assert_eq!(serialized, b"\"0d4a1185\"");
+
+    let parse = |json: &str| {
+        deserialize(&mut serde_json::Deserializer::from_str(json))
+    };
+    assert_eq!(parse("\"0d4a1185\"").unwrap(), 0x0d4a_1185);
+    assert_eq!(parse("\"0xD4A1185\"").unwrap(), 0x0d4a_1185);
+    assert_eq!(parse("\"0\"").unwrap(), 0);
+    assert!(parse("\"\"").is_err());
+    assert!(parse("\"0x\"").is_err());
+    assert!(parse("\"123456789\"").is_err());
+    assert!(parse("\"-1\"").is_err());
+    assert!(parse("\"+1\"").is_err());
+    assert!(parse("\"xyz\"").is_err());
+    assert!(parse("17").is_err());
+}
@@ -0,0 +1,224 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Approximate counting of the distinct windows of a stream
+//! with a HyperLogLog sketch.
+
+// HyperLogLog (Flajolet, Fusy, Gandouet and Meunier 2007)
+// hashes each item, takes the top p bits of the hash as a
+// register index and the position of the first 1 bit in
+// the rest as a rank, and keeps the largest rank seen in
+// each of the m == 2^p registers. The harmonic mean of
+// 2^rank over the registers estimates n/m, with relative
+// standard error about 1.04/sqrt(m); below 5m/2 the count
+// of empty registers gives a better "linear counting"
+// estimate. CRCs of structured data are far from uniform,
+// so each window CRC is mixed into 64 bits first. The
+// mixer is a bijection, so distinct CRCs stay distinct,
+// and 64 bits leave no need for the usual large-range
+// correction.
+
+use super::{RollingCRC, RollingCRCContext};
+#[cfg(test)]
+use super::test_rng;
+
+/// Smallest sketch precision.
+const MIN_PRECISION: u8 = 4;
+/// Largest sketch precision.
+const MAX_PRECISION: u8 = 18;
+
+/// Mix a CRC into a well-distributed 64-bit hash: the
+/// SplitMix64 finalizer.
+fn mix(crc: u32) -> u64 {
+    let mut h = crc as u64;
+    h = (h ^ (h >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    h = (h ^ (h >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    h ^ (h >> 31)
+}
+
+/// An estimate of the number of distinct window CRCs of a
+/// stream, in memory fixed by the precision rather than by
+/// the size of the stream: 2^precision bytes. Windows are
+/// counted by CRC, so windows whose CRCs collide count
+/// once.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(4).unwrap();
+/// let mut distinct = DistinctWindows::new(&context, 12);
+/// for _ in 0..1000 {
+///     distinct.feed(b"abcdefgh");
+/// }
+/// // The stream repeats with period 8, so has 8 distinct
+/// // windows.
+/// assert!((distinct.estimate() - 8.0).abs() < 0.5);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DistinctWindows<'a> {
+    /// Rolling CRC of the stream so far.
+    rolling_crc: RollingCRC<'a>,
+    /// Number of index bits of a hash.
+    precision: u8,
+    /// Largest rank seen for each index.
+    registers: Vec<u8>,
+}
+
+impl<'a> DistinctWindows<'a> {
+
+    /// Start an empty sketch of the windows of a stream in
+    /// the given context, with `2^precision` registers.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `precision` is less than 4 or greater than
+    /// 18.
+    pub fn new(context: &'a RollingCRCContext<'a>, precision: u8) -> Self {
+        assert!((MIN_PRECISION..=MAX_PRECISION).contains(&precision),
+                "DistinctWindows: precision {} out of range {}..={}",
+                precision, MIN_PRECISION, MAX_PRECISION);
+        Self {
+            rolling_crc: RollingCRC::new(context),
+            precision,
+            registers: vec![0; 1 << precision],
+        }
+    }
+
+    /// Number of index bits of the sketch.
+    pub fn precision(&self) -> u8 {
+        self.precision
+    }
+
+    /// Roll the bytes through the sketch as the next part
+    /// of the stream, adding the CRC of each window
+    /// completed. Windows span calls, so feeding a stream
+    /// in pieces counts the same windows as feeding it
+    /// whole.
+    pub fn feed(&mut self, buf: &[u8]) {
+        let precision = self.precision;
+        let registers = &mut self.registers;
+        self.rolling_crc.push_each(buf, |_, crc| {
+            let hash = mix(crc);
+            let index = (hash >> (64 - precision)) as usize;
+            // The guard bit bounds the rank when the rest of
+            // the hash is 0.
+            let rest = (hash << precision) | (1 << (precision - 1));
+            let rank = rest.leading_zeros() as u8 + 1;
+            if rank > registers[index] {
+                registers[index] = rank;
+            }
+        });
+    }
+
+    /// Estimated number of distinct window CRCs fed so far,
+    /// with relative standard error about
+    /// `1.04 / 2^(precision / 2)`.
+    pub fn estimate(&self) -> f64 {
+        let m = self.registers.len() as f64;
+        let alpha = match self.registers.len() {
+            16 => 0.673,
+            32 => 0.697,
+            64 => 0.709,
+            _ => 0.7213 / (1.0 + 1.079 / m),
+        };
+        let mut sum = 0.0;
+        let mut zeros = 0;
+        for &rank in &self.registers {
+            sum += 1.0 / (1u64 << rank) as f64;
+            zeros += (rank == 0) as usize;
+        }
+        let raw = alpha * m * m / sum;
+        if raw <= 2.5 * m && zeros > 0 {
+            m * (m / zeros as f64).ln()
+        } else {
+            raw
+        }
+    }
+
+    /// Fold the windows counted by another sketch, such as
+    /// of another shard of a dataset, into this one. The
+    /// estimate is then of the distinct windows of both:
+    /// windows spanning the boundary between two shards are
+    /// not counted.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the sketches have different precisions.
+    pub fn merge(&mut self, other: &DistinctWindows) {
+        assert_eq!(self.precision, other.precision,
+                   "DistinctWindows: merging different precisions");
+        for (rank, &other_rank) in
+            self.registers.iter_mut().zip(&other.registers)
+        {
+            if other_rank > *rank {
+                *rank = other_rank;
+            }
+        }
+    }
+}
+
+#[test]
+fn test_distinct_windows() {
+    use std::collections::HashSet;
+
+    let mut next = test_rng(0x1234_5678u32);
+    let context = RollingCRCContext::new(8).unwrap();
+    let distinct = |bytes: &[u8]| {
+        context.rolling_crcs(bytes)
+            .map(|(_, crc)| crc)
+            .collect::<HashSet<u32>>()
+            .len() as f64
+    };
+    // Within four standard errors of the true count.
+    let check = |sketch: &DistinctWindows, expected: f64| {
+        let m = (1u64 << sketch.precision()) as f64;
+        let bound = 4.0 * 1.04 / m.sqrt() * expected;
+        let estimate = sketch.estimate();
+        assert!((estimate - expected).abs() <= bound.max(1.0),
+                "{} {} {}", sketch.precision(), estimate, expected);
+    };
+
+    // A random block repeated has as many distinct windows
+    // as the block has bytes. Feed it in uneven pieces.
+    for &period in &[10, 300, 5000, 50_000] {
+        let block: Vec<u8> = (0..period).map(|_| next() as u8).collect();
+        let stream: Vec<u8> = block.iter().cycle()
+            .take(3 * period + 7)
+            .cloned()
+            .collect();
+        let expected = distinct(&stream);
+        for &precision in &[4, 10, 14] {
+            let mut sketch = DistinctWindows::new(&context, precision);
+            let mut start = 0;
+            while start < stream.len() {
+                let end = start + (next() % 1000) as usize;
+                let end = end.min(stream.len());
+                sketch.feed(&stream[start..end]);
+                start = end;
+            }
+            check(&sketch, expected);
+        }
+    }
+
+    // Merged shards estimate the distinct windows of both.
+    let a: Vec<u8> = (0..40_000).map(|_| next() as u8).collect();
+    let b: Vec<u8> = a[20_000..].iter().cloned()
+        .chain((0..20_000).map(|_| next() as u8))
+        .collect();
+    let mut sketch_a = DistinctWindows::new(&context, 12);
+    sketch_a.feed(&a);
+    let mut sketch_b = DistinctWindows::new(&context, 12);
+    sketch_b.feed(&b);
+    let union = distinct(&[&a[..], &b[..]].concat()) as usize;
+    let estimate_a = sketch_a.estimate();
+    sketch_a.merge(&sketch_a.clone());
+    assert_eq!(sketch_a.estimate(), estimate_a);
+    sketch_a.merge(&sketch_b);
+    check(&sketch_a, union as f64);
+
+    let empty = DistinctWindows::new(&context, 4);
+    assert_eq!(empty.estimate(), 0.0);
+}
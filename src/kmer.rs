@@ -0,0 +1,261 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Strand-independent rolling CRCs of DNA k-mers.
+
+// The canonical hash of a k-mer is the smaller of the CRC
+// of the k-mer and the CRC of its reverse complement. The
+// forward CRC rolls as usual. When the forward window
+// slides right, the reverse complement window slides
+// *left*: the complement of the new byte is prepended, and
+// the complement of the departing byte is dropped from the
+// end. So the reverse strand must be rolled backward.
+//
+// Write A for the linear operator taking an open CRC
+// through one zero byte, T(b) for `crc_table[b]`, and I for
+// `INIT_CRC`. Then `update_crc(s, T, b) == A s ^ T(b)`, and
+// the open CRC of a message M is
+//
+//        S(M) == A^|M| I ^ L(M)
+//
+// where L is linear with L(M ++ b) == A L(M) ^ T(b).
+//
+// Dropping the last byte v of a message is just undoing
+// `update_crc`: `S(U) == A^-1 (S(U ++ v) ^ T(v))`. The CRC-32
+// table entries have distinct top bytes, so A^-1 can be
+// computed byte-at-a-time with an inverse table; see
+// `unupdate_crc()` in `rollcrc`.
+//
+// Prepending a byte c to a message U of length k-1 gives
+//
+//        S(c ++ U) == A^k I ^ A^(k-1) T(c) ^ L(U)
+//                  == S(U) ^ A^(k-1) (I ^ A I ^ T(c))
+//
+// The last term depends only on c, so it is precomputed
+// into a "prepend table" for the k-mer size.
+
+use super::{finish_crc, unupdate_crc, update_crc, CRCTable, RollingCRC,
+            RollingCRCContext, INIT_CRC, INVERSE_CRC_TABLE};
+#[cfg(test)]
+use super::{test_bytes, test_rng};
+
+use alloc::vec::Vec;
+
+/// Complement of a nucleotide byte: `A`/`T` and `C`/`G` are
+/// exchanged (in either case); other bytes are unchanged.
+pub fn complement_nucleotide(b: u8) -> u8 {
+    match b {
+        b'A' => b'T',
+        b'T' => b'A',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'a' => b't',
+        b't' => b'a',
+        b'c' => b'g',
+        b'g' => b'c',
+        b => b,
+    }
+}
+
+/// Nucleotides represented by the 2-bit symbols 0 through 3
+/// of packed input.
+pub const PACKED_NUCLEOTIDES: [u8; 4] = *b"ACGT";
+
+impl<'a> RollingCRC<'a> {
+
+    /// Roll four 2-bit packed nucleotides through this
+    /// rolling CRC, most significant bits first. Each symbol
+    /// is rolled as its byte from `PACKED_NUCLEOTIDES`, and
+    /// the results of the four pushes are returned in order.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new(3).unwrap();
+    /// let mut roll_crc = RollingCRC::new(&context);
+    /// // 0b00_01_10_11 is ACGT.
+    /// let crcs = roll_crc.push_packed(0b00_01_10_11);
+    /// assert_eq!(crcs, [None, None,
+    ///                   Some(context.crc(b"ACG")),
+    ///                   Some(context.crc(b"CGT"))]);
+    /// ```
+    pub fn push_packed(&mut self, word: u8) -> [Option<u32>; 4] {
+        let symbol = |i: u8| {
+            PACKED_NUCLEOTIDES[(word >> (6 - 2 * i)) as usize & 3]
+        };
+        [
+            self.push(symbol(0)),
+            self.push(symbol(1)),
+            self.push(symbol(2)),
+            self.push(symbol(3)),
+        ]
+    }
+}
+
+/// Data needed for canonical rolling k-mer CRC calculation.
+#[derive(Debug, Clone)]
+pub struct CanonicalKmerContext {
+    /// Context for the forward strand.
+    context: RollingCRCContext<'static>,
+    /// Contribution of a byte prepended to a k-1 byte
+    /// message.
+    prepend_table: CRCTable,
+}
+
+impl CanonicalKmerContext {
+
+    /// Make a new context for k-mers of size `k`.
+    pub fn new(k: usize) -> Self {
+        let context = RollingCRCContext::build(k);
+        let crc_table = context.crc_table;
+        let mut prepend_table = [0; 256];
+        if k >= 1 {
+            let init = INIT_CRC ^ update_crc(INIT_CRC, crc_table, 0);
+            for (c, entry) in prepend_table.iter_mut().enumerate() {
+                let mut crc = init ^ crc_table[c];
+                for _ in 0..k - 1 {
+                    crc = update_crc(crc, crc_table, 0);
+                }
+                *entry = crc;
+            }
+        }
+        Self { context, prepend_table }
+    }
+
+    /// Size of the k-mers.
+    pub fn k(&self) -> usize {
+        self.context.window_size()
+    }
+
+    /// Canonical CRC of the given k-mer: the smaller of its
+    /// CRC and the CRC of its reverse complement.
+    pub fn crc(&self, kmer: &[u8]) -> u32 {
+        let rc: Vec<u8> = kmer.iter()
+            .rev()
+            .map(|&b| complement_nucleotide(b))
+            .collect();
+        self.context.crc(kmer).min(self.context.crc(&rc))
+    }
+
+    /// Undo `update_crc()` of the given byte on an open CRC.
+    #[inline(always)]
+    fn unupdate_crc(&self, crc: u32, b: u8) -> u32 {
+        unupdate_crc(crc, self.context.crc_table, &INVERSE_CRC_TABLE, b)
+    }
+}
+
+/// An in-progress canonical rolling k-mer CRC.
+#[derive(Debug, Clone)]
+pub struct CanonicalKmerCRC<'a> {
+    /// Needed context information.
+    context: &'a CanonicalKmerContext,
+    /// Rolling CRC of the forward strand.
+    forward: RollingCRC<'a>,
+    /// Open CRC of the reverse complement of the window.
+    reverse: Option<u32>,
+}
+
+impl<'a> CanonicalKmerCRC<'a> {
+
+    /// Start a new canonical rolling k-mer CRC in the given
+    /// context.
+    pub fn new(context: &'a CanonicalKmerContext) -> Self {
+        Self {
+            context,
+            forward: RollingCRC::new(&context.context),
+            reverse: None,
+        }
+    }
+
+    /// Roll a nucleotide through this rolling CRC,
+    /// returning the canonical CRC of the k-mer ending with
+    /// it once k nucleotides have been seen.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = CanonicalKmerContext::new(4);
+    /// let mut kmer_crc = CanonicalKmerCRC::new(&context);
+    /// let mut crcs = b"GGCATGCC".iter().filter_map(|&b| kmer_crc.push(b));
+    /// // GGCA and its reverse complement TGCC hash alike.
+    /// let first = crcs.next().unwrap();
+    /// assert_eq!(crcs.last(), Some(first));
+    /// ```
+    pub fn push(&mut self, byte: u8) -> Option<u32> {
+        let roll_out = match self.reverse {
+            Some(_) => {
+                let (first, second) = self.forward.window();
+                first.first().or_else(|| second.first()).cloned()
+            },
+            None => None,
+        };
+        let forward = self.forward.push(byte)?;
+        let reverse = match (self.reverse, roll_out) {
+            (Some(reverse), Some(roll_out)) => {
+                let dropped = self.context.unupdate_crc(
+                    reverse,
+                    complement_nucleotide(roll_out),
+                );
+                let added = complement_nucleotide(byte) as usize;
+                dropped ^ self.context.prepend_table[added]
+            },
+            _ => {
+                let (first, second) = self.forward.window();
+                let mut crc = INIT_CRC;
+                let table = self.context.context.crc_table;
+                for &b in first.iter().chain(second).rev() {
+                    crc = update_crc(crc, table, complement_nucleotide(b));
+                }
+                debug_assert!(first.len() + second.len() == self.context.k());
+                crc
+            },
+        };
+        self.reverse = Some(reverse);
+        Some(forward.min(finish_crc(reverse)))
+    }
+}
+
+#[test]
+fn test_canonical_kmer() {
+    let mut next = test_rng(0x1234_5678);
+    for k in 1..40 {
+        let bases: Vec<u8> = (0..300)
+            .map(|_| b"ACGT"[(next() >> 7) as usize % 4])
+            .collect();
+        let context = CanonicalKmerContext::new(k);
+        let mut kmer_crc = CanonicalKmerCRC::new(&context);
+        let crcs: Vec<u32> = bases.iter()
+            .filter_map(|&b| kmer_crc.push(b))
+            .collect();
+        let expected: Vec<u32> = bases.windows(k)
+            .map(|kmer| context.crc(kmer))
+            .collect();
+        assert_eq!(crcs, expected);
+    }
+}
+
+#[test]
+fn test_push_packed() {
+    let words = test_bytes(0x5eed_1234, 200);
+    let bases: Vec<u8> = words.iter()
+        .flat_map(|&w| (0..4).map(move |i| {
+            PACKED_NUCLEOTIDES[(w >> (6 - 2 * i)) as usize & 3]
+        }))
+        .collect();
+    for window_size in 1..12 {
+        let context = RollingCRCContext::new(window_size).unwrap();
+        let mut packed = RollingCRC::new(&context);
+        let crcs: Vec<Option<u32>> = words.iter()
+            .flat_map(|&w| packed.push_packed(w).to_vec())
+            .collect();
+        let mut unpacked = RollingCRC::new(&context);
+        let expected: Vec<Option<u32>> = bases.iter()
+            .map(|&b| unpacked.push(b))
+            .collect();
+        assert_eq!(crcs, expected);
+    }
+}
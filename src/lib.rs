@@ -15,27 +15,277 @@
 //! public domain code. See the source for full attribution;
 //! it is also available as
 //! <http://github.com/BartMassey/rolling-crc>.
+//!
+//! The default `std` feature may be turned off for use in
+//! `no_std` programs, keeping the `alloc` feature for
+//! programs with an allocator. This leaves the core rolling
+//! CRC machinery and whole-message CRC arithmetic; the I/O,
+//! file and threaded helpers need `std`.
+//!
+//! With `alloc` off as well, only the allocation-free core
+//! remains: `RollingCRCContext` (by `new()` or
+//! `with_byte_map()`) with its whole-message CRCs and
+//! `rolling_crcs()`, `RollingCRCFixed` and `RollingCRCBuf`,
+//! `OpenCrc` and `Crc32Digest`, `combine()` and friends,
+//! the `gf2` arithmetic and the forging functions. None of
+//! these allocate, with or without `alloc`.
+//!
+//! With the `serde` feature, the result types (`Chunk`,
+//! `ChunkBoundary`, `DupHit`, `RecentHit`, `AnchorMatch`,
+//! `DuplicateGroup`, `MaskStats`, `FingerprintEntry`,
+//! `ScanProgress`, `ScanStats`, `BadPiece`, `BadPage`,
+//! `PieceLength`, `PieceReport`) and `Signature` can be
+//! serialized. Their field names are serialized as
+//! written and are a stable wire format: fields may be
+//! added, but not renamed or removed, without a major
+//! version change. CRCs are serialized as numbers; the
+//! `hex_crc` module can serialize CRCs in types of your own
+//! as hex strings instead.
+
+#![cfg_attr(not(any(feature = "std", test)), no_std)]
 
 mod rollcrc;
 pub use self::rollcrc::*;
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+mod pclmul;
+#[cfg(all(target_arch = "aarch64", feature = "std"))]
+mod armcrc;
+#[cfg(feature = "alloc")]
+mod kmer;
+#[cfg(feature = "alloc")]
+pub use self::kmer::*;
+#[cfg(feature = "alloc")]
+mod bits;
+#[cfg(feature = "alloc")]
+pub use self::bits::*;
+#[cfg(feature = "alloc")]
+mod tiles;
+#[cfg(feature = "alloc")]
+pub use self::tiles::*;
+#[cfg(feature = "alloc")]
+mod range;
+#[cfg(feature = "alloc")]
+pub use self::range::*;
+#[cfg(feature = "alloc")]
+mod crcset;
+#[cfg(feature = "alloc")]
+pub use self::crcset::*;
+pub mod gf2;
+#[cfg(feature = "serde")]
+pub mod hex_crc;
+#[cfg(feature = "vectors")]
+pub mod vectors;
+mod combine;
+pub use self::combine::*;
+mod forge;
+pub use self::forge::*;
+mod digest;
+pub use self::digest::*;
+mod fixed;
+pub use self::fixed::*;
+mod reverse;
+pub use self::reverse::*;
+mod selftest;
+pub use self::selftest::*;
+pub mod test_vectors;
+#[cfg(feature = "alloc")]
+mod multi;
+#[cfg(feature = "alloc")]
+pub use self::multi::*;
+#[cfg(all(feature = "alloc", any(test, feature = "testing")))]
+mod testing;
+#[cfg(all(feature = "alloc", any(test, feature = "testing")))]
+pub use self::testing::*;
+#[cfg(feature = "bytes")]
+mod buf;
+#[cfg(feature = "embedded-io")]
+mod embedded;
+#[cfg(feature = "embedded-io")]
+pub use self::embedded::*;
+#[cfg(feature = "std")]
+mod error;
+#[cfg(feature = "std")]
+pub use self::error::*;
+#[cfg(feature = "std")]
+mod writer;
+#[cfg(feature = "std")]
+pub use self::writer::*;
+#[cfg(feature = "std")]
+mod reader;
+#[cfg(feature = "std")]
+pub use self::reader::*;
+#[cfg(feature = "std")]
+mod cancel;
+#[cfg(feature = "std")]
+pub use self::cancel::*;
+#[cfg(feature = "std")]
+mod progress;
+#[cfg(feature = "std")]
+pub use self::progress::*;
+#[cfg(feature = "std")]
+mod finder;
+#[cfg(feature = "std")]
+pub use self::finder::*;
+#[cfg(feature = "std")]
+mod file;
+#[cfg(feature = "std")]
+pub use self::file::*;
+#[cfg(feature = "std")]
+mod sparse;
+#[cfg(feature = "std")]
+pub use self::sparse::*;
+#[cfg(feature = "std")]
+mod analysis;
+#[cfg(feature = "std")]
+pub use self::analysis::*;
+#[cfg(feature = "std")]
+mod scanner;
+#[cfg(feature = "std")]
+pub use self::scanner::*;
+#[cfg(feature = "std")]
+mod follow;
+#[cfg(feature = "std")]
+pub use self::follow::*;
+#[cfg(feature = "std")]
+mod records;
+#[cfg(feature = "std")]
+pub use self::records::*;
+#[cfg(feature = "std")]
+mod parallel;
+#[cfg(feature = "std")]
+pub use self::parallel::*;
+#[cfg(feature = "std")]
+mod pool;
+#[cfg(feature = "std")]
+pub use self::pool::*;
+#[cfg(feature = "std")]
+mod chunker;
+#[cfg(feature = "std")]
+pub use self::chunker::*;
+#[cfg(feature = "std")]
+mod signature;
+#[cfg(feature = "std")]
+pub use self::signature::*;
+#[cfg(feature = "std")]
+mod delta;
+#[cfg(feature = "std")]
+pub use self::delta::*;
+#[cfg(feature = "std")]
+mod dedup;
+#[cfg(feature = "std")]
+pub use self::dedup::*;
+#[cfg(feature = "std")]
+mod fingerprint;
+#[cfg(feature = "std")]
+pub use self::fingerprint::*;
+#[cfg(feature = "std")]
+mod pieces;
+#[cfg(feature = "std")]
+pub use self::pieces::*;
+#[cfg(feature = "hll")]
+mod hll;
+#[cfg(feature = "hll")]
+pub use self::hll::*;
+#[cfg(feature = "flate2")]
+mod gzip;
+#[cfg(feature = "flate2")]
+pub use self::gzip::*;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "wasm")]
+mod wasm;
+#[cfg(feature = "wasm")]
+pub use self::wasm::*;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "python")]
+pub use self::python::*;
+#[cfg(feature = "digest")]
+mod rustcrypto;
+#[cfg(feature = "digest")]
+pub use self::rustcrypto::*;
 
+#[cfg(not(any(feature = "std", test)))]
+extern crate core as std;
+#[cfg(feature = "alloc")]
+#[cfg_attr(not(any(feature = "std", test)), macro_use)]
+extern crate alloc;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate lazy_static;
+#[cfg(feature = "std")]
+extern crate memchr;
+#[cfg(feature = "mmap")]
+extern crate filebuffer;
+#[cfg(feature = "flate2")]
+extern crate flate2;
+#[cfg(feature = "crc32fast")]
+extern crate crc32fast;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "digest")]
+extern crate digest as digest_traits;
+#[cfg(feature = "serde")]
+extern crate serde;
+#[cfg(feature = "wasm")]
+extern crate wasm_bindgen;
+#[cfg(feature = "arbitrary")]
+extern crate arbitrary;
+#[cfg(feature = "defmt")]
+extern crate defmt;
+#[cfg(feature = "bytes")]
+extern crate bytes;
+#[cfg(feature = "embedded-io")]
+extern crate embedded_io;
+#[cfg(feature = "python")]
+extern crate pyo3;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+// PyO3's macros name `::core`, which in this edition means
+// the crate root.
+#[cfg(feature = "python")]
+extern crate core;
+#[cfg(all(feature = "std", any(target_os = "linux", target_os = "android")))]
+extern crate libc;
+#[cfg(any(feature = "vectors", all(test, feature = "serde")))]
+extern crate serde_json;
+#[cfg(test)]
+extern crate tempfile;
+#[cfg(all(test, feature = "wasm", target_arch = "wasm32"))]
+extern crate wasm_bindgen_test;
 
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+#[cfg(feature = "alloc")]
+use alloc::string::String;
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
 use std::fmt;
+#[cfg(feature = "std")]
+use std::io;
+#[cfg(feature = "std")]
+use std::ops;
 
-// Build the CRC table just once at first use.  It is not
-// clear to me where the performance penalty for referencing
-// this lives.
-lazy_static! {
-    static ref CRC_TABLE: CRCTable = {
-        let mut crc_table = [0;256];
-        make_crc_table(&mut crc_table, POLY_CRC);
-        crc_table
-    };
-}
+// Build the CRC tables at compile time, so that they need
+// no lazy initialization and are available without `std`.
+static CRC_TABLE: CRCTable = {
+    let mut crc_table = [0;256];
+    make_crc_table(&mut crc_table, POLY_CRC);
+    crc_table
+};
+static SLICE_TABLES: SliceTables = make_slice_tables(&CRC_TABLE);
+static INVERSE_CRC_TABLE: InverseCRCTable =
+    make_inverse_crc_table(&CRC_TABLE);
+
+/// Size of the buffer used by
+/// `RollingCRCContext::crc_reader()`.
+#[cfg(feature = "std")]
+pub const CRC_READER_BUFFER_SIZE: usize = 1 << 16;
 
-/// Data needed for rolling CRC calculation.
+/// Data needed for rolling CRC calculation. A context is
+/// immutable once made, and is `Send` and `Sync`: one
+/// context may be shared by any number of threads, each
+/// with its own rolling CRCs.
 #[derive(Clone)]
 pub struct RollingCRCContext<'a> {
     /// Size of calculation window.
@@ -44,8 +294,19 @@ pub struct RollingCRCContext<'a> {
     crc_table: &'a CRCTable,
     /// Rolling CRC table for this window size.
     rolling_crc_table: CRCTable,
+    /// Translation applied to each byte before hashing.
+    byte_map: Option<ByteMap>,
+    /// Optional combined roll table: entry
+    /// `(i << 8) | out` is `crc_table[i] ^
+    /// rolling_crc_table[out]`.
+    #[cfg(feature = "alloc")]
+    big_table: Option<Box<[u32]>>,
 }
 
+/// A translation table giving the replacement for each
+/// possible byte value.
+pub type ByteMap = [u8; 256];
+
 impl<'a> fmt::Debug for RollingCRCContext<'a> {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         write!(f, "RollingCRCContext {{ \
@@ -55,27 +316,281 @@ impl<'a> fmt::Debug for RollingCRCContext<'a> {
         self.crc_table[..].fmt(f)?;
         write!(f, ", rolling_crc_table: ")?;
         self.rolling_crc_table[..].fmt(f)?;
+        if let Some(ref byte_map) = self.byte_map {
+            write!(f, ", byte_map: ")?;
+            byte_map[..].fmt(f)?;
+        }
+        #[cfg(feature = "alloc")]
+        {
+            if self.big_table.is_some() {
+                write!(f, ", big_table")?;
+            }
+        }
         write!(f, " }}")
     }
 }
 
+/// Just the parameters, leaving out the tables.
+#[cfg(feature = "defmt")]
+impl<'a> ::defmt::Format for RollingCRCContext<'a> {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "RollingCRCContext {{ window_size: {=usize}",
+                        self.window_size);
+        if self.byte_map.is_some() {
+            ::defmt::write!(f, ", byte_map");
+        }
+        #[cfg(feature = "alloc")]
+        {
+            if self.big_table.is_some() {
+                ::defmt::write!(f, ", big_table");
+            }
+        }
+        ::defmt::write!(f, " }}")
+    }
+}
+
+/// Error returned by `RollingCRCContext::new()` for a zero
+/// window size.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowSizeError;
+
+impl fmt::Display for WindowSizeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "window size must be positive")
+    }
+}
+
+impl std::error::Error for WindowSizeError {}
+
 impl<'a> RollingCRCContext<'a> {
 
     /// Make a new rolling CRC context for this window size.
-    /// The first call will incur the overhead of CRC table
-    /// calculation. Subsequent calls will incur the
-    /// overhead of rolling CRC table calculation.
-    pub fn new(window_size: usize) -> Self {
+    /// Each call incurs the overhead of rolling CRC table
+    /// calculation. A zero window size has no windows to
+    /// roll, and is rejected: use `checksum_only()` for a
+    /// context that only computes CRCs.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new(5)?;
+    /// assert_eq!(context.window_size(), 5);
+    /// assert_eq!(RollingCRCContext::new(0).unwrap_err(), WindowSizeError);
+    /// # Ok::<(), WindowSizeError>(())
+    /// ```
+    pub fn new(window_size: usize) -> Result<Self, WindowSizeError> {
+        if window_size == 0 {
+            return Err(WindowSizeError);
+        }
+        Ok(Self::build(window_size))
+    }
+
+    /// Make a context with no window, for computing CRCs
+    /// with `crc()` and friends. Its `window_size()` is 0,
+    /// and a `RollingCRC` made from it never reports a CRC.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// assert_eq!(context.crc(b"123456789"), 0xcbf43926);
+    /// ```
+    pub fn checksum_only() -> Self {
+        Self::build(0)
+    }
+
+    /// Make a new rolling CRC context for this window size,
+    /// or a checksum-only context if the window size is 0,
+    /// as `new()` did before it rejected a zero window
+    /// size.
+    #[deprecated(since = "0.2.0",
+                 note = "use `new()`, which rejects a zero window \
+                         size, or `checksum_only()`")]
+    pub fn new_or_checksum_only(window_size: usize) -> Self {
+        Self::build(window_size)
+    }
+
+    /// Make a context for this window size, which may be 0.
+    pub(crate) fn build(window_size: usize) -> Self {
         let crc_table = &CRC_TABLE;
         let mut rolling_crc_table = [0; 256];
         if window_size >= 1 {
+            #[cfg(feature = "tracing")]
+            let _span = ::tracing::debug_span!("rolling_table", window_size)
+                .entered();
+            #[cfg(feature = "tracing")]
+            let start = ::std::time::Instant::now();
             make_rolling_crc_table(
                 window_size,
-                &crc_table,
+                crc_table,
                 &mut rolling_crc_table,
                 );
+            #[cfg(feature = "tracing")]
+            ::tracing::debug!(elapsed = ?start.elapsed(),
+                              "built rolling CRC table");
+        }
+        Self {
+            window_size,
+            crc_table,
+            rolling_crc_table,
+            byte_map: None,
+            #[cfg(feature = "alloc")]
+            big_table: None,
+        }
+    }
+
+    /// Make new rolling CRC contexts for each of the given
+    /// window sizes, in the same order. This is much faster
+    /// than making each context separately when there are
+    /// many sizes: the rolling CRC tables are built
+    /// together, at the cost of building the largest. As
+    /// with `new()`, a zero window size is rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let contexts = RollingCRCContext::with_window_sizes(&[64, 16, 32])?;
+    /// let bytes = [7; 100];
+    /// for context in &contexts {
+    ///     let single = RollingCRCContext::new(context.window_size())?;
+    ///     assert!(context.rolling_crcs(&bytes).eq(single.rolling_crcs(&bytes)));
+    /// }
+    /// assert!(RollingCRCContext::with_window_sizes(&[8, 0]).is_err());
+    /// # Ok::<(), WindowSizeError>(())
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn with_window_sizes(window_sizes: &[usize])
+                             -> Result<Vec<Self>, WindowSizeError>
+    {
+        if window_sizes.contains(&0) {
+            return Err(WindowSizeError);
+        }
+        let mut order: Vec<usize> = (0..window_sizes.len()).collect();
+        order.sort_by_key(|&i| window_sizes[i]);
+        let sorted: Vec<usize> =
+            order.iter().map(|&i| window_sizes[i]).collect();
+        let crc_table = &CRC_TABLE;
+        #[cfg(feature = "tracing")]
+        let _span = ::tracing::debug_span!(
+            "rolling_tables",
+            count = sorted.len(),
+            max_window_size = sorted.last().cloned().unwrap_or(0),
+        ).entered();
+        #[cfg(feature = "tracing")]
+        let start = ::std::time::Instant::now();
+        let tables = make_rolling_crc_tables(&sorted, crc_table);
+        #[cfg(feature = "tracing")]
+        ::tracing::debug!(elapsed = ?start.elapsed(),
+                          "built rolling CRC tables");
+        let mut contexts: Vec<Option<Self>> =
+            window_sizes.iter().map(|_| None).collect();
+        for (&i, rolling_crc_table) in order.iter().zip(tables) {
+            contexts[i] = Some(Self {
+                window_size: window_sizes[i],
+                crc_table,
+                rolling_crc_table,
+                byte_map: None,
+                big_table: None,
+            });
+        }
+        Ok(contexts.into_iter()
+            .map(|context| context.expect("internal error: lost context"))
+            .collect())
+    }
+
+    /// Build a combined 256 KiB table for this context that
+    /// lets each roll step of a `RollingCRC` do one table
+    /// lookup instead of two. This is worthwhile only for
+    /// long-lived contexts that roll a lot of data: the
+    /// table is expensive to build and hard on the cache.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new(5).unwrap();
+    /// let big = RollingCRCContext::new(5).unwrap().with_big_table();
+    /// let bytes = b"hello world";
+    /// let crcs: Vec<(u64, u32)> = RollingCRC::new(&big)
+    ///     .iter(bytes.iter().cloned())
+    ///     .collect();
+    /// assert_eq!(crcs[6], (6, context.crc(b"world")));
+    /// ```
+    #[cfg(feature = "alloc")]
+    pub fn with_big_table(mut self) -> Self {
+        let mut big_table = vec![0; 256 * 256];
+        for (i, row) in big_table.chunks_mut(256).enumerate() {
+            let entry = self.crc_table[i];
+            for (e, &r) in row.iter_mut().zip(&self.rolling_crc_table[..]) {
+                *e = entry ^ r;
+            }
+        }
+        self.big_table = Some(big_table.into_boxed_slice());
+        self
+    }
+
+    /// Roll the open CRC of a window forward by one byte,
+    /// given the (mapped) bytes entering and leaving the
+    /// window, with the standard tables.
+    #[inline(always)]
+    fn roll(&self, crc: u32, roll_in: u8, roll_out: u8) -> u32 {
+        update_crc(crc, self.crc_table, roll_in)
+            ^ table_entry(&self.rolling_crc_table, roll_out)
+    }
+
+    /// Make a new rolling CRC context for this window size
+    /// in which every byte is translated through the given
+    /// map before being hashed. Positions reported by
+    /// rolling CRCs in this context still refer to the
+    /// original bytes. As with `new()`, a zero window size
+    /// is rejected.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let mut fold = [0; 256];
+    /// for (b, f) in fold.iter_mut().enumerate() {
+    ///     *f = (b as u8).to_ascii_lowercase();
+    /// }
+    /// let context = RollingCRCContext::with_byte_map(5, fold)?;
+    /// assert_eq!(context.crc(b"HeLLo"), context.crc(b"hello"));
+    /// # Ok::<(), WindowSizeError>(())
+    /// ```
+    pub fn with_byte_map(window_size: usize, byte_map: ByteMap)
+                         -> Result<Self, WindowSizeError>
+    {
+        let mut context = Self::new(window_size)?;
+        context.byte_map = Some(byte_map);
+        Ok(context)
+    }
+
+    /// Make a context with no window in which every byte is
+    /// translated through the given map before being
+    /// hashed, as with `checksum_only()` and
+    /// `with_byte_map()`.
+    pub fn checksum_only_with_byte_map(byte_map: ByteMap) -> Self {
+        let mut context = Self::checksum_only();
+        context.byte_map = Some(byte_map);
+        context
+    }
+
+    /// The byte translation applied by this context, if
+    /// any.
+    pub fn byte_map(&self) -> Option<&ByteMap> {
+        self.byte_map.as_ref()
+    }
+
+    /// Translate a byte through the byte map, if any.
+    #[inline(always)]
+    fn map_byte(&self, byte: u8) -> u8 {
+        match self.byte_map {
+            Some(ref byte_map) => byte_map[byte as usize],
+            None => byte,
         }
-        Self { window_size, crc_table, rolling_crc_table }
     }
 
     /// Compute the CRC of the given bytes.
@@ -84,23 +599,256 @@ impl<'a> RollingCRCContext<'a> {
     ///
     /// ```
     /// # use rolling_crc::*;
-    /// let context = RollingCRCContext::new(0);
+    /// let context = RollingCRCContext::checksum_only();
     /// let bytes = "hello world".as_bytes();
     /// assert_eq!(context.crc(bytes), 0x0d4a1185);
     /// ```
     pub fn crc(&self, bytes: &[u8]) -> u32 {
-        calc_crc(bytes, &self.crc_table)
+        finish_crc(self.update_open(INIT_CRC, bytes))
+    }
+
+    /// Compute the CRC of the concatenation of the given
+    /// buffers, without copying them together.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// let bufs: [&[u8]; 3] = [b"hello", b"", b" world"];
+    /// assert_eq!(context.crc_vectored(&bufs), context.crc(b"hello world"));
+    /// ```
+    pub fn crc_vectored(&self, bufs: &[&[u8]]) -> u32 {
+        let open = bufs.iter()
+            .fold(INIT_CRC, |open, buf| self.update_open(open, buf));
+        finish_crc(open)
+    }
+
+    /// Compute the CRC of the concatenation of two slices,
+    /// such as the two halves of a `VecDeque<u8>` from
+    /// `as_slices()`, without copying them together.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// use std::collections::VecDeque;
+    ///
+    /// let context = RollingCRCContext::checksum_only();
+    /// let mut deque: VecDeque<u8> = b"world".iter().cloned().collect();
+    /// for &b in b"hello ".iter().rev() {
+    ///     deque.push_front(b);
+    /// }
+    /// let (a, b) = deque.as_slices();
+    /// assert_eq!(context.crc_slices(a, b), context.crc(b"hello world"));
+    /// ```
+    pub fn crc_slices(&self, a: &[u8], b: &[u8]) -> u32 {
+        finish_crc(self.update_open(self.update_open(INIT_CRC, a), b))
+    }
+
+    /// Compute the CRC of the concatenation of the given
+    /// I/O slices, as with `crc_vectored()`.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// use std::io::IoSlice;
+    ///
+    /// let context = RollingCRCContext::checksum_only();
+    /// let bufs = [IoSlice::new(b"hello"), IoSlice::new(b" world")];
+    /// assert_eq!(context.crc_io_slices(&bufs), context.crc(b"hello world"));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn crc_io_slices(&self, bufs: &[io::IoSlice]) -> u32 {
+        let open = bufs.iter()
+            .fold(INIT_CRC, |open, buf| self.update_open(open, buf));
+        finish_crc(open)
+    }
+
+    /// Compute the CRC of all the bytes of the given
+    /// reader, read through a `CRC_READER_BUFFER_SIZE`
+    /// buffer.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::checksum_only();
+    /// let crc = context.crc_reader(&b"hello world"[..]).unwrap();
+    /// assert_eq!(crc, context.crc(b"hello world"));
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn crc_reader<R: io::Read>(&self, reader: R) -> io::Result<u32> {
+        let mut buf = vec![0; CRC_READER_BUFFER_SIZE];
+        self.crc_reader_with_buffer(reader, &mut buf)
+    }
+
+    /// Compute the CRC of all the bytes of the given
+    /// reader, as with `crc_reader()`, read through the
+    /// given buffer.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the buffer is empty.
+    #[cfg(feature = "std")]
+    pub fn crc_reader_with_buffer<R>(&self, mut reader: R, buf: &mut [u8])
+                                     -> io::Result<u32>
+        where R: io::Read
+    {
+        assert!(!buf.is_empty(), "crc_reader_with_buffer: empty buffer");
+        let mut open = INIT_CRC;
+        loop {
+            match reader.read(buf) {
+                Ok(0) => return Ok(finish_crc(open)),
+                Ok(n) => open = self.update_open(open, &buf[..n]),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Given an open CRC, return the open CRC including the
+    /// given bytes.
+    #[inline]
+    fn update_open(&self, mut crc: u32, bytes: &[u8]) -> u32 {
+        match self.byte_map {
+            Some(ref byte_map) => {
+                for &b in bytes {
+                    crc = update_crc(crc, self.crc_table, byte_map[b as usize]);
+                }
+                crc
+            },
+            None => update_crc_buf(crc, self.crc_table, bytes),
+        }
+    }
+
+    /// Size of the calculation window.
+    pub fn window_size(&self) -> usize {
+        self.window_size
+    }
+
+    /// An iterator over the rolling CRCs of every window of
+    /// the given slice, returning the position of each
+    /// window start along with its CRC. Since the whole
+    /// input is at hand, no window buffer needs to be
+    /// maintained: this is the fast path for in-memory
+    /// data.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new(5).unwrap();
+    /// let bytes = "hello world".as_bytes();
+    /// let crcs: Vec<(usize, u32)> =
+    ///     context.rolling_crcs(bytes).collect();
+    /// assert_eq!(crcs.len(), bytes.len() - 4);
+    /// assert_eq!(crcs[6], (6, context.crc(b"world")));
+    /// ```
+    pub fn rolling_crcs<'b>(&'a self, bytes: &'b [u8])
+                            -> RollingCRCSlice<'a, 'b>
+    {
+        RollingCRCSlice{ context: self, bytes, index: 0, last_crc: 0 }
+    }
+
+    /// An iterator over the rolling CRCs of every window of
+    /// the strided subsequence of the given slice at
+    /// positions `start`, `start + stride`,
+    /// `start + 2 * stride`, ..., such as one channel of
+    /// interleaved samples or one column of a row-major
+    /// matrix. Each window start is reported as its index
+    /// in the subsequence, along with its CRC. The
+    /// subsequence is not copied.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `stride` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new(3).unwrap();
+    /// let samples = b"a1b2c3d4e5";
+    /// let crcs: Vec<(usize, u32)> =
+    ///     context.rolling_crcs_strided(samples, 1, 2).collect();
+    /// assert_eq!(crcs.len(), 3);
+    /// assert_eq!(crcs[2], (2, context.crc(b"345")));
+    /// ```
+    pub fn rolling_crcs_strided<'b>(&'a self,
+                                    bytes: &'b [u8],
+                                    start: usize,
+                                    stride: usize)
+                                    -> RollingCRCStrided<'a, 'b>
+    {
+        assert!(stride > 0, "rolling_crcs_strided: stride must be positive");
+        let len = if start < bytes.len() {
+            (bytes.len() - start - 1) / stride + 1
+        } else {
+            0
+        };
+        RollingCRCStrided {
+            context: self,
+            bytes,
+            start,
+            stride,
+            len,
+            index: 0,
+            last_crc: 0,
+        }
     }
 
 }
 
-/// An in-progress rolling CRC.
-#[derive(Debug, Clone)]
+/// A way of rolling an open CRC forward by one byte. A
+/// `RollingCRC` chooses one once per call, so that its
+/// steady-state loop does not branch on whether the context
+/// has a big table.
+#[cfg(feature = "alloc")]
+trait RollStep {
+    /// Roll the open CRC of a window forward by one byte,
+    /// given the (mapped) bytes entering and leaving the
+    /// window.
+    fn roll(&self, crc: u32, roll_in: u8, roll_out: u8) -> u32;
+}
+
+/// The standard roll step: two table lookups.
+#[cfg(feature = "alloc")]
+impl<'a> RollStep for RollingCRCContext<'a> {
+    #[inline(always)]
+    fn roll(&self, crc: u32, roll_in: u8, roll_out: u8) -> u32 {
+        RollingCRCContext::roll(self, crc, roll_in, roll_out)
+    }
+}
+
+/// The roll step of `RollingCRCContext::with_big_table()`:
+/// one lookup in the combined table.
+#[cfg(feature = "alloc")]
+struct BigTableStep<'t>(&'t [u32]);
+
+#[cfg(feature = "alloc")]
+impl<'t> RollStep for BigTableStep<'t> {
+    #[inline(always)]
+    fn roll(&self, crc: u32, roll_in: u8, roll_out: u8) -> u32 {
+        let i = (((crc ^ roll_in as u32) & 0xff) << 8) as usize
+            | roll_out as usize;
+        self.0[i] ^ (crc >> 8)
+    }
+}
+
+/// An in-progress rolling CRC. This allocates its window
+/// buffer on the heap; `RollingCRCFixed` does not. It is
+/// `Send` and `Sync`, so it may be handed from thread to
+/// thread mid-stream.
+#[cfg(feature = "alloc")]
+#[derive(Clone)]
 pub struct RollingCRC<'a> {
     /// Needed context information.
     context: &'a RollingCRCContext<'a>,
-    /// Number of bytes processed so far.
-    count: usize,
+    /// Number of bytes processed so far: 64 bits even on
+    /// 32-bit targets, so long streams do not wrap.
+    count: u64,
     /// Bytes in window.
     bytes: Vec<u8>,
     /// Index of next byte in window to be replaced. We
@@ -109,8 +857,17 @@ pub struct RollingCRC<'a> {
     index: usize,
     /// Last "open" rolling CRC, to continue rolling.
     last_crc: Option<u32>,
+    /// Number of emitted CRCs between self-verifications,
+    /// or 0 for none.
+    #[cfg(debug_assertions)]
+    verify_every: usize,
+    /// Number of CRCs emitted since the last
+    /// self-verification.
+    #[cfg(debug_assertions)]
+    unverified: usize,
 }
 
+#[cfg(feature = "alloc")]
 impl<'a> RollingCRC<'a> {
 
     /// Start a new rolling CRC in the given context. If the
@@ -123,6 +880,129 @@ impl<'a> RollingCRC<'a> {
             bytes: Vec::new(),
             index: 0,
             last_crc: None,
+            #[cfg(debug_assertions)]
+            verify_every: 0,
+            #[cfg(debug_assertions)]
+            unverified: 0,
+        }
+    }
+
+    /// Check every `n`th emitted CRC (none if `n` is 0) by
+    /// recomputing it directly from the window bytes,
+    /// panicking with a detailed message on mismatch. This
+    /// is a debugging aid for suspected rolling state
+    /// corruption: in builds without debug assertions it
+    /// does nothing and costs nothing.
+    pub fn verify_every(self, n: usize) -> Self {
+        #[cfg(debug_assertions)]
+        {
+            let mut rolling_crc = self;
+            rolling_crc.verify_every = n;
+            rolling_crc.unverified = 0;
+            rolling_crc
+        }
+        #[cfg(not(debug_assertions))]
+        {
+            let _ = n;
+            self
+        }
+    }
+
+    /// The bytes currently in the window, oldest first, as
+    /// a pair of slices to be read in order. Until the
+    /// window has filled this holds the bytes seen so far.
+    /// Bytes are reported after any translation by the
+    /// context's byte map.
+    pub fn window(&self) -> (&[u8], &[u8]) {
+        (&self.bytes[self.index..], &self.bytes[..self.index])
+    }
+
+    /// Roll the bytes through this rolling CRC, passing
+    /// each window completed to `sink` with its position
+    /// counted from the first byte this rolling CRC saw.
+    #[inline]
+    pub(crate) fn push_each<F>(&mut self, bytes: &[u8], sink: F)
+        where F: FnMut(u64, u32)
+    {
+        let context = self.context;
+        match context.big_table {
+            Some(ref big_table) =>
+                self.push_each_with(&BigTableStep(big_table), bytes, sink),
+            None => self.push_each_with(context, bytes, sink),
+        }
+    }
+
+    /// `push_each()` with the given roll step.
+    #[inline(always)]
+    fn push_each_with<S, F>(&mut self, step: &S, bytes: &[u8], mut sink: F)
+        where S: RollStep, F: FnMut(u64, u32)
+    {
+        let window_size = self.context.window_size as u64;
+        for &b in bytes {
+            if let Some(crc) = self.push_with(step, b) {
+                sink(self.count - window_size, crc);
+            }
+        }
+    }
+
+    /// Roll the bytes of `a` and then of `b` through this
+    /// rolling CRC, as one stream, passing each window
+    /// completed to `sink` with its position counted from
+    /// the first byte this rolling CRC saw. This takes the
+    /// two halves of a ring buffer, such as a `VecDeque<u8>`
+    /// from `as_slices()`, without copying them together.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new(5).unwrap();
+    /// let mut roll_crc = RollingCRC::new(&context);
+    /// let mut crcs = Vec::new();
+    /// roll_crc.push_slices(b"hello w", b"orld", |offset, crc| {
+    ///     crcs.push((offset, crc));
+    /// });
+    /// assert_eq!(crcs.len(), 7);
+    /// assert_eq!(crcs[4], (4, context.crc(b"o wor")));
+    /// ```
+    pub fn push_slices<F>(&mut self, a: &[u8], b: &[u8], mut sink: F)
+        where F: FnMut(u64, u32)
+    {
+        self.push_each(a, &mut sink);
+        self.push_each(b, sink);
+    }
+
+    /// Count an emitted CRC, verifying it if it is due.
+    #[cfg(debug_assertions)]
+    #[inline(always)]
+    fn check(&mut self, crc: u32) {
+        if self.verify_every == 0 {
+            return;
+        }
+        self.unverified += 1;
+        if self.unverified >= self.verify_every {
+            self.unverified = 0;
+            self.verify(crc);
+        }
+    }
+
+    /// Recompute the CRC of the current window directly and
+    /// panic if it differs from the given rolling CRC.
+    #[cfg(debug_assertions)]
+    #[cold]
+    #[inline(never)]
+    fn verify(&self, crc: u32) {
+        let (first, second) = self.window();
+        let window: Vec<u8> = first.iter().chain(second).cloned().collect();
+        let expected = calc_crc(&window, self.context.crc_table);
+        if crc != expected {
+            let hex: Vec<String> = window.iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            panic!("rolling CRC verification failed at offset {}: \
+                    expected {:08x}, got {:08x}, window [{}]",
+                   self.count - self.context.window_size as u64,
+                   expected, crc, hex.join(" "));
         }
     }
 
@@ -134,7 +1014,7 @@ impl<'a> RollingCRC<'a> {
     ///
     /// ```
     /// # use rolling_crc::*;
-    /// let context = RollingCRCContext::new(2);
+    /// let context = RollingCRCContext::new(2).unwrap();
     /// let mut roll_crc = RollingCRC::new(&context);
     /// let bytes = "hello world".as_bytes();
     /// for i in 0..bytes.len() {
@@ -148,33 +1028,206 @@ impl<'a> RollingCRC<'a> {
     /// ```
     #[inline(always)]
     pub fn push(&mut self, byte: u8) -> Option<u32> {
+        let context = self.context;
+        match context.big_table {
+            Some(ref big_table) =>
+                self.push_with(&BigTableStep(big_table), byte),
+            None => self.push_with(context, byte),
+        }
+    }
+
+    /// `push()` with the given roll step.
+    #[inline(always)]
+    fn push_with<S: RollStep>(&mut self, step: &S, byte: u8) -> Option<u32> {
+        let byte = self.context.map_byte(byte);
+        let last_crc = match self.last_crc {
+            Some(last_crc) => last_crc,
+            None => return self.warm_up(byte),
+        };
+        self.count += 1;
+        let roll_out = self.bytes[self.index];
+        let crc = step.roll(last_crc, byte, roll_out);
+        self.bytes[self.index] = byte;
+        // Wrap the index without a branch: the mask is all
+        // zeros at the end of the window, all ones otherwise.
+        let next = self.index + 1;
+        let at_end = (next == self.context.window_size) as usize;
+        self.index = next & at_end.wrapping_sub(1);
+        self.last_crc=Some(crc);
+        #[cfg(debug_assertions)]
+        self.check(finish_crc(crc));
+        Some(finish_crc(crc))
+    }
+
+    /// Roll all of the given bytes through this rolling
+    /// CRC, returning the CRC of the last window if there
+    /// is one, as repeated `push()` would. A fresh rolling
+    /// CRC given at least a window of bytes skips straight
+    /// to computing the CRC of the last window.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new(5).unwrap();
+    /// let mut roll_crc = RollingCRC::new(&context);
+    /// assert_eq!(roll_crc.prime(b"hello "), Some(context.crc(b"ello ")));
+    /// assert_eq!(roll_crc.push(b'w'), Some(context.crc(b"llo w")));
+    /// ```
+    pub fn prime(&mut self, bytes: &[u8]) -> Option<u32> {
+        let window_size = self.context.window_size;
+        if self.count > 0 || window_size == 0 || bytes.len() < window_size {
+            return bytes.iter().fold(None, |_, &b| self.push(b));
+        }
+        let window = &bytes[bytes.len() - window_size..];
+        self.count = bytes.len() as u64;
+        self.bytes = window.iter()
+            .map(|&b| self.context.map_byte(b))
+            .collect();
+        self.index = 0;
+        let crc = calc_crc(&self.bytes, self.context.crc_table);
+        self.last_crc = Some(finish_crc(crc));
+        Some(crc)
+    }
+
+    /// Start this rolling CRC over at the given offset of
+    /// the source: seek there and read a window of bytes,
+    /// returning the CRC of that window. Rolling then
+    /// continues from the end of the window, with offsets
+    /// reported counted from the start of the source.
+    ///
+    /// # Errors
+    ///
+    /// It is an error of kind `UnexpectedEof` if there is
+    /// not a whole window of bytes at the offset, and of
+    /// kind `InvalidInput` if the window size is 0. The
+    /// state of this rolling CRC is unspecified after an
+    /// error.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// use std::io::Cursor;
+    ///
+    /// let context = RollingCRCContext::new(5).unwrap();
+    /// let mut roll_crc = RollingCRC::new(&context);
+    /// let mut src = Cursor::new(b"hello world");
+    /// let crc = roll_crc.prime_at(&mut src, 4).unwrap();
+    /// assert_eq!(crc, context.crc(b"o wor"));
+    /// let crcs: Vec<(u64, u32)> = roll_crc.iter(b"ld".iter().cloned())
+    ///     .collect();
+    /// assert_eq!(crcs, vec![(5, context.crc(b" worl")),
+    ///                       (6, context.crc(b"world"))]);
+    /// assert!(RollingCRC::new(&context).prime_at(&mut src, 7).is_err());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn prime_at<R>(&mut self, src: &mut R, offset: u64) -> io::Result<u32>
+        where R: io::Read + io::Seek
+    {
+        let window_size = self.context.window_size;
+        if window_size == 0 {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                      "window size must be positive"));
+        }
+        let _ = src.seek(io::SeekFrom::Start(offset))?;
+        let mut window = vec![0; window_size];
+        src.read_exact(&mut window)?;
+        for b in window.iter_mut() {
+            *b = self.context.map_byte(*b);
+        }
+        let crc = calc_crc(&window, self.context.crc_table);
+        self.count = offset + window_size as u64;
+        self.bytes = window;
+        self.index = 0;
+        self.last_crc = Some(finish_crc(crc));
+        Ok(crc)
+    }
+
+    /// The CRCs of the windows lying wholly in the given
+    /// range of offsets of the source, with their offsets
+    /// counted from the start of the source, as
+    /// `RollingCRCContext::rolling_crcs()` of that region.
+    /// The source is read from `range.start` with
+    /// `prime_at()`, so nothing before the range is read.
+    /// A range, or the part of it before the end of the
+    /// source, too short to hold a window yields no CRCs.
+    /// Rolling continues from the last byte read.
+    ///
+    /// # Errors
+    ///
+    /// It is an error of kind `InvalidInput` if the window
+    /// size is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// use std::io::Cursor;
+    ///
+    /// let context = RollingCRCContext::new(5).unwrap();
+    /// let mut roll_crc = RollingCRC::new(&context);
+    /// let mut src = Cursor::new(b"hello world");
+    /// let crcs = roll_crc.scan_range(&mut src, 3..9).unwrap();
+    /// assert_eq!(crcs, vec![(3, context.crc(b"lo wo")),
+    ///                       (4, context.crc(b"o wor"))]);
+    /// assert!(roll_crc.scan_range(&mut src, 8..20).unwrap().is_empty());
+    /// ```
+    #[cfg(feature = "std")]
+    pub fn scan_range<R>(&mut self, src: &mut R, range: ops::Range<u64>)
+                         -> io::Result<Vec<(u64, u32)>>
+        where R: io::Read + io::Seek
+    {
+        let window_size = self.context.window_size as u64;
+        let mut crcs = Vec::new();
+        if range.end < range.start.saturating_add(window_size) {
+            if window_size == 0 {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput,
+                                          "window size must be positive"));
+            }
+            return Ok(crcs);
+        }
+        match self.prime_at(src, range.start) {
+            Ok(crc) => crcs.push((range.start, crc)),
+            Err(ref e) if e.kind() == io::ErrorKind::UnexpectedEof =>
+                return Ok(crcs),
+            Err(e) => return Err(e),
+        }
+        let rest = range.end - range.start - window_size;
+        let mut rest = io::Read::take(src, rest);
+        let mut buf = vec![0; CRC_READER_BUFFER_SIZE];
+        loop {
+            match io::Read::read(&mut rest, &mut buf) {
+                Ok(0) => return Ok(crcs),
+                Ok(n) => self.push_each(&buf[..n], |offset, crc| {
+                    crcs.push((offset, crc));
+                }),
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    /// Push a byte (already mapped) while the window is
+    /// still filling, returning the CRC of the first full
+    /// window. Kept out of line so that `push()` inlines
+    /// just the steady state.
+    #[cold]
+    #[inline(never)]
+    fn warm_up(&mut self, byte: u8) -> Option<u32> {
         self.count += 1;
         if self.context.window_size == 0 {
             return None;
         }
-        if self.count < self.context.window_size {
-            self.bytes.push(byte);
+        self.bytes.push(byte);
+        if self.bytes.len() < self.context.window_size {
             return None;
         }
-        if self.count == self.context.window_size {
-            self.bytes.push(byte);
-            let crc = self.context.crc(&self.bytes);
-            self.last_crc = Some(finish_crc(crc));
-            return Some(crc);
-        }
-        assert!(self.context.window_size == self.bytes.len());
-        let roll_out = self.bytes[self.index] as usize;
-        let last_crc = self.last_crc.expect("internal error: lost CRC");
-        let table = self.context.crc_table;
-        let rolling_table = self.context.rolling_crc_table;
-        let crc = update_crc(last_crc, &table, byte) ^ rolling_table[roll_out];
-        self.bytes[self.index] = byte;
-        self.index += 1;
-        if self.index >= self.context.window_size {
-            self.index = 0;
-        }
-        self.last_crc=Some(crc);
-        Some(finish_crc(crc))
+        let crc = calc_crc(&self.bytes, self.context.crc_table);
+        self.last_crc = Some(finish_crc(crc));
+        #[cfg(debug_assertions)]
+        self.check(crc);
+        Some(crc)
     }
 
     /// An iterator over the bytes from the given byte
@@ -189,16 +1242,16 @@ impl<'a> RollingCRC<'a> {
     /// let s = "hello world";
     /// let ns = s.len();
     /// // Set up the collection context.
-    /// let context = RollingCRCContext::new(5);
+    /// let context = RollingCRCContext::new(5).unwrap();
     /// let rolling_crc = RollingCRC::new(&context);
     /// // Collect the rolling CRCs.
-    /// let mut crcs: Vec<(usize, u32)> =
+    /// let mut crcs: Vec<(u64, u32)> =
     ///     rolling_crc.iter(s.bytes()).collect();
     /// // Check the result.
     /// assert_eq!(ns - 4, crcs.len());
     /// let bytes = s.as_bytes();
     /// let crc = context.crc(&bytes[ns-5..ns]);
-    /// assert_eq!(Some((ns - 5, crc)), crcs.pop());
+    /// assert_eq!(Some(((ns - 5) as u64, crc)), crcs.pop());
     /// ```
     pub fn iter<T>(self, bytes: T) -> RollingCRCMap<'a, T>
         where T: Iterator<Item=u8>
@@ -216,11 +1269,98 @@ impl<'a> RollingCRC<'a> {
     }
 }
 
+#[cfg(all(feature = "alloc", feature = "defmt"))]
+impl<'a> ::defmt::Format for RollingCRC<'a> {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "RollingCRC {{ count: {=u64}, primed: {=bool}",
+                        self.count, self.last_crc.is_some());
+        if let Some(crc) = self.last_crc {
+            ::defmt::write!(f, ", last_crc: {=u32:#010x}", finish_crc(crc));
+        }
+        ::defmt::write!(f, " }}")
+    }
+}
+
+/// Number of window bytes shown by `RollingCRC`'s `Debug`
+/// before the rest are elided.
+#[cfg(feature = "alloc")]
+const DEBUG_WINDOW_BYTES: usize = 32;
+
+/// Window bytes in hex, oldest first, for `Debug`.
+#[cfg(feature = "alloc")]
+struct DebugWindow<'r>(&'r [u8], &'r [u8]);
+
+#[cfg(feature = "alloc")]
+impl<'r> fmt::Debug for DebugWindow<'r> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bytes = self.0.iter().chain(self.1);
+        write!(f, "[")?;
+        for (i, b) in bytes.clone().take(DEBUG_WINDOW_BYTES).enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02x}", b)?;
+        }
+        if self.0.len() + self.1.len() > DEBUG_WINDOW_BYTES {
+            write!(f, " ...")?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// A CRC in hex, for `Debug`.
+#[cfg(feature = "alloc")]
+struct DebugCrc(u32);
+
+#[cfg(feature = "alloc")]
+impl fmt::Debug for DebugCrc {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:#010x}", self.0)
+    }
+}
+
+/// Just the parameters of the context, leaving out the
+/// tables, for `Debug`.
+#[cfg(feature = "alloc")]
+struct DebugContext<'r, 'a: 'r>(&'r RollingCRCContext<'a>);
+
+#[cfg(feature = "alloc")]
+impl<'r, 'a> fmt::Debug for DebugContext<'r, 'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RollingCRCContext")
+            .field("window_size", &self.0.window_size)
+            .finish_non_exhaustive()
+    }
+}
+
+/// The count, whether the window has filled, the window
+/// bytes oldest first (in hex, elided past the first 32),
+/// the last open and finished CRCs, and the context's
+/// window size: not the context's tables.
+#[cfg(feature = "alloc")]
+impl<'a> fmt::Debug for RollingCRC<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (first, second) = self.window();
+        let last_crc = self.last_crc.map(|crc| DebugCrc(finish_crc(crc)));
+        f.debug_struct("RollingCRC")
+            .field("count", &self.count)
+            .field("primed", &self.last_crc.is_some())
+            .field("window", &DebugWindow(first, second))
+            .field("last_open_crc", &self.last_crc.map(DebugCrc))
+            .field("last_crc", &last_crc)
+            .field("context", &DebugContext(self.context))
+            .finish()
+    }
+}
+
 /// An iterator that maps the stream of input bytes from the
 /// given byte iterator to a stream of positions relative to
 /// the start of a given `RollingCRC` and their
 /// corresponding rolling CRCs. This iterator can be created
-/// using `RollingCRC::iter()`.
+/// using `RollingCRC::iter()`. Positions are `u64`, so that
+/// they do not wrap on streams longer than 4 GiB on 32-bit
+/// targets.
+#[cfg(feature = "alloc")]
 #[derive(Debug, Clone)]
 pub struct RollingCRCMap<'a, T>
     where T: Iterator<Item=u8>
@@ -229,13 +1369,14 @@ pub struct RollingCRCMap<'a, T>
     bytes: T,
 }
 
+#[cfg(feature = "alloc")]
 impl<'a, T> Iterator for RollingCRCMap<'a, T>
     where T: Iterator<Item=u8>
 {
-    type Item = (usize, u32);
+    type Item = (u64, u32);
 
     fn next(&mut self) -> Option<Self::Item> {
-        let window_size = self.rolling_crc.context.window_size;
+        let window_size = self.rolling_crc.context.window_size as u64;
         loop {
             let byte = self.bytes.next()?;
             let crc = self.rolling_crc.push(byte);
@@ -247,16 +1388,38 @@ impl<'a, T> Iterator for RollingCRCMap<'a, T>
     }
 }
 
+/// A xorshift pseudo-random number generator for tests,
+/// started from the given nonzero seed.
+#[cfg(test)]
+pub(crate) fn test_rng(seed: u32) -> impl FnMut() -> u32 {
+    let mut state = seed;
+    move || {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state
+    }
+}
+
+/// Pseudo-random test data: `len` bytes from `test_rng()`
+/// with the given seed.
+#[cfg(test)]
+pub(crate) fn test_bytes(seed: u32, len: usize) -> Vec<u8> {
+    let mut next = test_rng(seed);
+    (0..len).map(|_| next() as u8).collect()
+}
+
+#[cfg(feature = "alloc")]
 #[test]
 fn test_iterator_index() {
     // Set up the byte source.
     let s = b"llllollllollll";
     // Set up the collection context.
-    let context = RollingCRCContext::new(4);
+    let context = RollingCRCContext::new(4).unwrap();
     let target = context.crc(b"llll");
     let rolling_crc = RollingCRC::new(&context);
     // Collect the rolling CRCs.
-    for (index, crc) in rolling_crc.iter(s.into_iter().cloned()) {
+    for (index, crc) in rolling_crc.iter(s.iter().cloned()) {
         if index == 0 || index == 5 || index == 10 {
             assert_eq!(crc, target);
         } else {
@@ -269,7 +1432,9 @@ fn test_iterator_index() {
 /// from the given byte iterator to a stream of position
 /// results relative to the start of a given `RollingCRC`
 /// and their corresponding rolling CRCs. This iterator can
-/// be created using `RollingCRC::iter_result()`.
+/// be created using `RollingCRC::iter_result()`. Positions
+/// are `u64`, as for `RollingCRCMap`.
+#[cfg(feature = "alloc")]
 pub struct RollingCRCMapResult<'a, T, E>
     where T: Iterator<Item=Result<u8, E>>
 {
@@ -277,13 +1442,14 @@ pub struct RollingCRCMapResult<'a, T, E>
     bytes: T,
 }
 
+#[cfg(feature = "alloc")]
 impl<'a, T, E> Iterator for RollingCRCMapResult<'a, T, E>
     where T: Iterator<Item=Result<u8, E>>
 {
-    type Item = Result<(usize, u32), E>;
+    type Item = Result<(u64, u32), E>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let window_size = self.rolling_crc.context.window_size;
+        let window_size = self.rolling_crc.context.window_size as u64;
         loop {
             let byte = self.bytes.next()?;
             let byte = match byte {
@@ -298,3 +1464,623 @@ impl<'a, T, E> Iterator for RollingCRCMapResult<'a, T, E>
         }
     }
 }
+
+/// An iterator over the positions and rolling CRCs of
+/// successive windows of a byte slice. This iterator can be
+/// created using `RollingCRCContext::rolling_crcs()`.
+#[derive(Debug, Clone)]
+pub struct RollingCRCSlice<'a, 'b> {
+    context: &'a RollingCRCContext<'a>,
+    bytes: &'b [u8],
+    /// Start of the next window to be reported.
+    index: usize,
+    /// Last "open" rolling CRC, to continue rolling.
+    last_crc: u32,
+}
+
+impl<'a, 'b> Iterator for RollingCRCSlice<'a, 'b> {
+    type Item = (usize, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let window_size = self.context.window_size;
+        let index = self.index;
+        if window_size == 0 || index + window_size > self.bytes.len() {
+            return None;
+        }
+        let crc = if index == 0 {
+            let crc = self.context.crc(&self.bytes[..window_size]);
+            finish_crc(crc)
+        } else {
+            let context = self.context;
+            let roll_out = context.map_byte(self.bytes[index - 1]);
+            let roll_in = context.map_byte(self.bytes[index + window_size - 1]);
+            context.roll(self.last_crc, roll_in, roll_out)
+        };
+        self.last_crc = crc;
+        self.index += 1;
+        Some((index, finish_crc(crc)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let window_size = self.context.window_size;
+        let end = self.bytes.len() + 1;
+        let n = if window_size == 0 || self.index + window_size > end {
+            0
+        } else {
+            end - window_size - self.index
+        };
+        (n, Some(n))
+    }
+}
+
+/// An iterator over the positions and rolling CRCs of
+/// successive windows of a strided subsequence of a byte
+/// slice. This iterator can be created using
+/// `RollingCRCContext::rolling_crcs_strided()`.
+#[derive(Debug, Clone)]
+pub struct RollingCRCStrided<'a, 'b> {
+    context: &'a RollingCRCContext<'a>,
+    bytes: &'b [u8],
+    /// Position of the first element in `bytes`.
+    start: usize,
+    /// Distance between elements in `bytes`.
+    stride: usize,
+    /// Number of elements.
+    len: usize,
+    /// Element index of the next window to be reported.
+    index: usize,
+    /// Last "open" rolling CRC, to continue rolling.
+    last_crc: u32,
+}
+
+impl<'a, 'b> RollingCRCStrided<'a, 'b> {
+
+    /// The element with the given index, after any byte
+    /// map.
+    #[inline(always)]
+    fn element(&self, index: usize) -> u8 {
+        self.context.map_byte(self.bytes[self.start + index * self.stride])
+    }
+}
+
+impl<'a, 'b> Iterator for RollingCRCStrided<'a, 'b> {
+    type Item = (usize, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let window_size = self.context.window_size;
+        let index = self.index;
+        if window_size == 0 || index + window_size > self.len {
+            return None;
+        }
+        let context = self.context;
+        let crc = if index == 0 {
+            (0..window_size).fold(INIT_CRC, |crc, i| {
+                update_crc(crc, context.crc_table, self.element(i))
+            })
+        } else {
+            let roll_out = self.element(index - 1);
+            let roll_in = self.element(index + window_size - 1);
+            context.roll(self.last_crc, roll_in, roll_out)
+        };
+        self.last_crc = crc;
+        self.index += 1;
+        Some((index, finish_crc(crc)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let window_size = self.context.window_size;
+        let end = self.len + 1;
+        let n = if window_size == 0 || self.index + window_size > end {
+            0
+        } else {
+            end - window_size - self.index
+        };
+        (n, Some(n))
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_push_slices() {
+    let bytes = test_bytes(0x7f4a_7c15, 150);
+    let plain = RollingCRCContext::checksum_only();
+    for split in 0..=bytes.len() {
+        let (a, b) = bytes.split_at(split);
+        assert_eq!(plain.crc_slices(a, b), plain.crc(&bytes));
+    }
+    for &window_size in &[1, 2, 7, 16, 64] {
+        let context = RollingCRCContext::new(window_size).unwrap();
+        let expected: Vec<(u64, u32)> = context.rolling_crcs(&bytes)
+            .map(|(offset, crc)| (offset as u64, crc))
+            .collect();
+        let splits = (0..=2 * window_size + 1)
+            .chain(bytes.len() - window_size - 1..=bytes.len());
+        for split in splits {
+            let (a, b) = bytes.split_at(split);
+            let mut rolling_crc = RollingCRC::new(&context);
+            let mut crcs = Vec::new();
+            rolling_crc.push_slices(a, b, |offset, crc| {
+                crcs.push((offset, crc));
+            });
+            assert_eq!(crcs, expected, "{} {}", window_size, split);
+            assert_eq!(context.crc_slices(a, b), context.crc(&bytes));
+        }
+    }
+}
+
+#[test]
+fn test_rolling_crcs_strided() {
+    let bytes = test_bytes(0x3c6e_f372, 301);
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8) & 0xf0;
+    }
+    for window_size in 0..12 {
+        let plain = RollingCRCContext::build(window_size);
+        let mut mapped = RollingCRCContext::build(window_size);
+        mapped.byte_map = Some(fold);
+        for context in [plain, mapped].iter() {
+            for &stride in &[1, 2, 4, 7, 300, 400] {
+                for &start in &[0, 1, 3, 6, 299, 300, 301, 500] {
+                    let channel: Vec<u8> = bytes.iter()
+                        .skip(start)
+                        .step_by(stride)
+                        .cloned()
+                        .collect();
+                    let expected: Vec<(usize, u32)> =
+                        context.rolling_crcs(&channel).collect();
+                    let strided =
+                        context.rolling_crcs_strided(&bytes, start, stride);
+                    assert_eq!(strided.size_hint().0, expected.len());
+                    assert_eq!(strided.collect::<Vec<_>>(), expected,
+                               "{} {} {}", window_size, stride, start);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_window_size_zero() {
+    assert_eq!(RollingCRCContext::new(0).unwrap_err(), WindowSizeError);
+    assert!(RollingCRCContext::with_byte_map(0, [0; 256]).is_err());
+    #[cfg(feature = "alloc")]
+    assert_eq!(RollingCRCContext::with_window_sizes(&[3, 0, 5]).unwrap_err(),
+               WindowSizeError);
+    #[cfg(feature = "std")]
+    assert_eq!(WindowSizeError.to_string(), "window size must be positive");
+
+    let context = RollingCRCContext::checksum_only();
+    assert_eq!(context.window_size(), 0);
+    assert_eq!(context.crc(b"123456789"), 0xcbf4_3926);
+    assert_eq!(context.rolling_crcs(b"hello").next(), None);
+    let mut roller = RollingCRC::new(&context);
+    assert!(b"hello".iter().all(|&b| roller.push(b).is_none()));
+    let zeros = RollingCRCContext::checksum_only_with_byte_map([0; 256]);
+    assert_eq!(zeros.crc(b"abc"), context.crc(&[0; 3]));
+
+    #[allow(deprecated)]
+    let shimmed = RollingCRCContext::new_or_checksum_only(0);
+    assert_eq!(shimmed.window_size(), 0);
+    assert_eq!(shimmed.crc(b"123456789"), 0xcbf4_3926);
+    #[allow(deprecated)]
+    let shimmed = RollingCRCContext::new_or_checksum_only(5);
+    let context = RollingCRCContext::new(5).unwrap();
+    assert!(shimmed.rolling_crcs(b"hello world")
+            .eq(context.rolling_crcs(b"hello world")));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_window_size_one() {
+    let bytes = b"hello, world";
+    let context = RollingCRCContext::new(1).unwrap();
+    let mut slow: CRCTable = [0; 256];
+    make_rolling_crc_table_slow(1, &CRC_TABLE, &mut slow);
+    assert_eq!(&context.rolling_crc_table[..], &slow[..]);
+    let expected: Vec<(usize, u32)> = bytes.iter()
+        .enumerate()
+        .map(|(i, &b)| (i, context.crc(&[b])))
+        .collect();
+    assert_eq!(context.rolling_crcs(bytes).collect::<Vec<_>>(), expected);
+    // Each push both fills and rolls the window.
+    let mut roller = RollingCRC::new(&context);
+    for &(i, crc) in &expected {
+        assert_eq!(roller.push(bytes[i]), Some(crc));
+        assert_eq!(roller.window(), (&bytes[i..=i], &[][..]));
+    }
+    let mut roller = RollingCRC::new(&context);
+    assert_eq!(roller.prime(b"hello"), Some(context.crc(b"o")));
+    assert_eq!(roller.push(b'!'), Some(context.crc(b"!")));
+    let mut fixed = RollingCRCFixed::<1>::new(&context);
+    for &(i, crc) in &expected {
+        assert_eq!(fixed.push(bytes[i]), Some(crc));
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_window_size_input_length() {
+    let bytes = b"hello, world";
+    let n = bytes.len();
+    for &window_size in &[n, n + 1, 2 * n] {
+        let context = RollingCRCContext::new(window_size).unwrap();
+        let expected = if window_size == n {
+            vec![(0, context.crc(bytes))]
+        } else {
+            Vec::new()
+        };
+        assert_eq!(context.rolling_crcs(bytes).size_hint(),
+                   (expected.len(), Some(expected.len())));
+        assert_eq!(context.rolling_crcs(bytes).collect::<Vec<_>>(), expected);
+        let crcs: Vec<(usize, u32)> = RollingCRC::new(&context)
+            .iter(bytes.iter().cloned())
+            .map(|(offset, crc)| (offset as usize, crc))
+            .collect();
+        assert_eq!(crcs, expected);
+        let mut roller = RollingCRC::new(&context);
+        assert_eq!(roller.prime(bytes), expected.last().map(|&(_, crc)| crc));
+        assert_eq!(roller.window(), (&bytes[..], &[][..]));
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_crc_reader() {
+    let context = RollingCRCContext::checksum_only();
+    assert_eq!(context.crc_reader(io::empty()).unwrap(), 0);
+    let bytes = test_bytes(0xa54f_f53a, 3 * CRC_READER_BUFFER_SIZE + 17);
+    for &len in &[0, 1, 1000, CRC_READER_BUFFER_SIZE, bytes.len()] {
+        let expected = calc_crc(&bytes[..len], &CRC_TABLE);
+        let cursor = io::Cursor::new(&bytes[..len]);
+        assert_eq!(context.crc_reader(cursor).unwrap(), expected);
+        let mut buf = [0; 7];
+        let cursor = io::Cursor::new(&bytes[..len]);
+        assert_eq!(context.crc_reader_with_buffer(cursor, &mut buf).unwrap(),
+                   expected);
+    }
+
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8).to_ascii_lowercase();
+    }
+    let folded = RollingCRCContext::checksum_only_with_byte_map(fold);
+    assert_eq!(folded.crc_reader(&b"HeLLo"[..]).unwrap(), folded.crc(b"hello"));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_large_count() {
+    let context = RollingCRCContext::new(4).unwrap();
+    let mut rolling_crc = RollingCRC::new(&context);
+    rolling_crc.prime(b"abcd");
+    // Pretend that 5 GiB more have gone by, as would wrap a
+    // 32-bit count.
+    let skipped = 5 << 30;
+    rolling_crc.count += skipped;
+    let crcs: Vec<(u64, u32)> = rolling_crc.clone()
+        .iter(b"ef".iter().cloned())
+        .collect();
+    assert_eq!(crcs, vec![(skipped + 1, context.crc(b"bcde")),
+                          (skipped + 2, context.crc(b"cdef"))]);
+    let results: Vec<Result<(u64, u32), ()>> = rolling_crc
+        .iter_result(b"e".iter().map(|&b| Ok(b)))
+        .collect();
+    assert_eq!(results, vec![Ok((skipped + 1, context.crc(b"bcde")))]);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_scan_range() {
+    let bytes = test_bytes(0x510e_527f, 100_000);
+    let len = bytes.len() as u64;
+    let mut src = io::Cursor::new(&bytes);
+    for &window_size in &[1, 4, 64] {
+        let context = RollingCRCContext::new(window_size).unwrap();
+        let ranges = [0..len, 0..0, 10..10, 17..70_017, 99_000..len,
+                      len - 3..len + 5, len - 70..len + 100,
+                      len + 10..len + 100];
+        for range in ranges.iter().cloned() {
+            let start = range.start.min(len) as usize;
+            let end = range.end.min(len).max(start as u64) as usize;
+            let expected: Vec<(u64, u32)> =
+                context.rolling_crcs(&bytes[start..end])
+                .map(|(offset, crc)| ((start + offset) as u64, crc))
+                .collect();
+            let mut rolling_crc = RollingCRC::new(&context);
+            let crcs = rolling_crc.scan_range(&mut src, range.clone())
+                .unwrap();
+            assert_eq!(crcs, expected, "{} {:?}", window_size, range);
+        }
+
+        // Rolling continues with absolute offsets.
+        let mut rolling_crc = RollingCRC::new(&context);
+        let crc = rolling_crc.prime_at(&mut src, 1000).unwrap();
+        assert_eq!(crc, context.crc(&bytes[1000..1000 + window_size]));
+        let _ = rolling_crc.scan_range(&mut src, 5..500).unwrap();
+        let crcs: Vec<(u64, u32)> = rolling_crc
+            .iter(bytes[500..600].iter().cloned())
+            .collect();
+        assert_eq!(crcs[0].0, 501 - window_size as u64);
+        assert_eq!(crcs.last().unwrap().1,
+                   context.crc(&bytes[600 - window_size..600]));
+
+        let mut rolling_crc = RollingCRC::new(&context);
+        let offset = len - window_size as u64 + 1;
+        let e = rolling_crc.prime_at(&mut src, offset).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof);
+    }
+
+    let context = RollingCRCContext::checksum_only();
+    let mut rolling_crc = RollingCRC::new(&context);
+    let e = rolling_crc.prime_at(&mut src, 0).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+    let e = rolling_crc.scan_range(&mut src, 0..10).unwrap_err();
+    assert_eq!(e.kind(), io::ErrorKind::InvalidInput);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_rolling_crcs_slice() {
+    let bytes = test_bytes(0x2545_f491, 200);
+    for window_size in 0..20 {
+        let context = RollingCRCContext::build(window_size);
+        let expected: Vec<(usize, u32)> = RollingCRC::new(&context)
+            .iter(bytes.iter().cloned())
+            .map(|(offset, crc)| (offset as usize, crc))
+            .collect();
+        let crcs = context.rolling_crcs(&bytes);
+        assert_eq!(crcs.size_hint().0, expected.len());
+        assert_eq!(crcs.collect::<Vec<_>>(), expected);
+    }
+    let context = RollingCRCContext::new(8).unwrap();
+    assert_eq!(context.rolling_crcs(b"short").next(), None);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_push_warm_up() {
+    let bytes = test_bytes(0x9e37_79b9, 100);
+    for window_size in 0..20 {
+        let context = RollingCRCContext::build(window_size);
+        let mut rolling_crc = RollingCRC::new(&context);
+        for (i, &b) in bytes.iter().enumerate() {
+            let expected = if window_size > 0 && i + 1 >= window_size {
+                Some(calc_crc(&bytes[i + 1 - window_size..=i], &CRC_TABLE))
+            } else {
+                None
+            };
+            assert_eq!(rolling_crc.push(b), expected);
+            let (first, second) = rolling_crc.window();
+            let start = (i + 1).saturating_sub(window_size);
+            if window_size > 0 {
+                assert_eq!([first, second].concat(), &bytes[start..=i]);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_push_window_sizes() {
+    let bytes = test_bytes(0x1234_5678, 1200);
+    for window_size in 1..=257 {
+        let context = RollingCRCContext::new(window_size).unwrap();
+        let expected: Vec<(usize, u32)> =
+            context.rolling_crcs(&bytes).collect();
+        let mut rolling_crc = RollingCRC::new(&context);
+        let mut crcs = Vec::new();
+        for (i, &b) in bytes.iter().enumerate() {
+            if let Some(crc) = rolling_crc.push(b) {
+                crcs.push((i + 1 - window_size, crc));
+            }
+        }
+        assert_eq!(crcs, expected, "window size {}", window_size);
+        let (first, second) = rolling_crc.window();
+        assert_eq!([first, second].concat(),
+                   &bytes[bytes.len() - window_size..]);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_rolling_crc_debug() {
+    let context = RollingCRCContext::new(5).unwrap();
+    let mut roller = RollingCRC::new(&context);
+    roller.prime(b"hel");
+    assert_eq!(format!("{:?}", roller),
+               "RollingCRC { count: 3, primed: false, window: [68 65 6c], \
+                last_open_crc: None, last_crc: None, \
+                context: RollingCRCContext { window_size: 5, .. } }");
+    roller.prime(b"lo world");
+    assert_eq!(format!("{:?}", roller),
+               "RollingCRC { count: 11, primed: true, \
+                window: [77 6f 72 6c 64], \
+                last_open_crc: Some(0xc588eebc), \
+                last_crc: Some(0x3a771143), \
+                context: RollingCRCContext { window_size: 5, .. } }");
+
+    let context = RollingCRCContext::new(40).unwrap();
+    let mut roller = RollingCRC::new(&context);
+    let bytes: Vec<u8> = (0..50).collect();
+    roller.prime(&bytes);
+    let debug = format!("{:?}", roller);
+    assert!(debug.contains("window: [0a 0b 0c 0d 0e 0f 10 11 12 13 14 15 16 \
+                            17 18 19 1a 1b 1c 1d 1e 1f 20 21 22 23 24 25 26 \
+                            27 28 29 ...]"),
+            "{}", debug);
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_big_table() {
+    let bytes = test_bytes(0xdead_beef, 500);
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8).to_ascii_lowercase();
+    }
+    for window_size in 0..40 {
+        for &byte_map in &[None, Some(fold)] {
+            let mut context = RollingCRCContext::build(window_size);
+            context.byte_map = byte_map;
+            let big = context.clone().with_big_table();
+            let expected: Vec<(usize, u32)> =
+                context.rolling_crcs(&bytes).collect();
+            assert_eq!(big.rolling_crcs(&bytes).collect::<Vec<_>>(),
+                       expected);
+            let crcs: Vec<(usize, u32)> = RollingCRC::new(&big)
+                .iter(bytes.iter().cloned())
+                .map(|(offset, crc)| (offset as usize, crc))
+                .collect();
+            assert_eq!(crcs, expected);
+            let mut crcs = Vec::new();
+            let (a, b) = bytes.split_at(window_size / 2 + 7);
+            RollingCRC::new(&big).push_slices(a, b, |offset, crc| {
+                crcs.push((offset as usize, crc));
+            });
+            assert_eq!(crcs, expected);
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_prime() {
+    let bytes = test_bytes(0x0bad_cafe, 300);
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8) & 0x3f;
+    }
+    for window_size in 0..20 {
+        for &byte_map in &[None, Some(fold)] {
+            let mut context = RollingCRCContext::build(window_size);
+            context.byte_map = byte_map;
+            for &split in &[0, 1, window_size, 2 * window_size + 3] {
+                let mut pushed = RollingCRC::new(&context);
+                let mut expected = None;
+                for &b in &bytes[..split] {
+                    expected = pushed.push(b);
+                }
+                let mut primed = RollingCRC::new(&context);
+                assert_eq!(primed.prime(&bytes[..split]), expected);
+                let (a, b) = primed.window();
+                let (c, d) = pushed.window();
+                assert_eq!([a, b].concat(), [c, d].concat());
+                for &b in &bytes[split..] {
+                    assert_eq!(primed.push(b), pushed.push(b));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_byte_map() {
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8).to_ascii_lowercase();
+    }
+    let haystack = b"say HELLO there";
+    let folded = RollingCRCContext::with_byte_map(5, fold).unwrap();
+    let plain = RollingCRCContext::new(5).unwrap();
+
+    let target = folded.crc(b"Hello");
+    let found: Vec<usize> = folded.rolling_crcs(haystack)
+        .filter(|&(_, crc)| crc == target)
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(found, vec![4]);
+    let found: Vec<u64> = RollingCRC::new(&folded)
+        .iter(haystack.iter().cloned())
+        .filter(|&(_, crc)| crc == target)
+        .map(|(index, _)| index)
+        .collect();
+    assert_eq!(found, vec![4]);
+
+    let target = plain.crc(b"Hello");
+    assert!(plain.rolling_crcs(haystack).all(|(_, crc)| crc != target));
+
+    // Mapped rolling CRCs are the plain CRCs of the mapped
+    // bytes.
+    let lower: Vec<u8> = haystack.iter().map(|&b| fold[b as usize]).collect();
+    let expected: Vec<(usize, u32)> = plain.rolling_crcs(&lower).collect();
+    let crcs: Vec<(usize, u32)> = RollingCRC::new(&folded)
+        .iter(haystack.iter().cloned())
+        .map(|(offset, crc)| (offset as usize, crc))
+        .collect();
+    assert_eq!(crcs, expected);
+}
+
+#[cfg(debug_assertions)]
+#[cfg(feature = "alloc")]
+#[test]
+fn test_verify_every() {
+    let bytes: Vec<u8> = (0..100u8).collect();
+    let context = RollingCRCContext::new(7).unwrap();
+    let mut rolling_crc = RollingCRC::new(&context).verify_every(1);
+    for &b in &bytes {
+        let _ = rolling_crc.push(b);
+    }
+    let (first, second) = rolling_crc.window();
+    assert_eq!([first, second].concat(), &bytes[93..]);
+
+    // Corrupt the rolling state and make sure the check
+    // fires on the next verified window.
+    let mut rolling_crc = RollingCRC::new(&context).verify_every(3);
+    for &b in &bytes[..20] {
+        let _ = rolling_crc.push(b);
+    }
+    rolling_crc.last_crc = rolling_crc.last_crc.map(|crc| crc ^ 1);
+    let result = std::panic::catch_unwind(move || {
+        for &b in &bytes[20..] {
+            let _ = rolling_crc.push(b);
+        }
+    });
+    let message = result.unwrap_err();
+    let message = message.downcast_ref::<String>().unwrap();
+    assert!(message.starts_with("rolling CRC verification failed at offset"));
+}
+
+#[test]
+fn test_crc_vectored() {
+    let mut next = test_rng(0x2545_f491u32);
+    let bytes: Vec<u8> = (0..5000).map(|_| next() as u8).collect();
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8).to_ascii_lowercase();
+    }
+    let contexts = [
+        RollingCRCContext::checksum_only(),
+        RollingCRCContext::checksum_only_with_byte_map(fold),
+    ];
+    for context in &contexts {
+        assert_eq!(context.crc_vectored(&[]), context.crc(b""));
+        #[cfg(feature = "std")]
+        assert_eq!(context.crc_io_slices(&[]), context.crc(b""));
+        for _ in 0..100 {
+            let len = next() as usize % bytes.len();
+            let buf = &bytes[..len];
+            // Random cuts, some of them repeated to make
+            // empty segments.
+            let mut cuts: Vec<usize> = (0..next() % 10)
+                .map(|_| next() as usize % (len + 1))
+                .collect();
+            cuts.push(0);
+            cuts.push(len);
+            cuts.sort();
+            let segments: Vec<&[u8]> = cuts.windows(2)
+                .map(|w| &buf[w[0]..w[1]])
+                .collect();
+            assert_eq!(context.crc_vectored(&segments), context.crc(buf));
+            #[cfg(feature = "std")]
+            {
+                let slices: Vec<io::IoSlice> = segments.iter()
+                    .map(|s| io::IoSlice::new(s))
+                    .collect();
+                assert_eq!(context.crc_io_slices(&slices), context.crc(buf));
+            }
+        }
+    }
+}
@@ -0,0 +1,92 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Rolling CRCs of several streams at once.
+
+use super::{RollingCRC, RollingCRCContext};
+#[cfg(test)]
+use super::test_bytes;
+
+/// `N` independent rolling CRCs sharing one context, rolled
+/// in lockstep. Rolling one stream is a chain of dependent
+/// table lookups; rolling several together lets the CPU
+/// overlap the chains.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(3).unwrap();
+/// let mut roller = MultiStreamRoller::<2>::new(&context);
+/// let (a, b) = (b"abcd", b"wxyz");
+/// let mut last = [None; 2];
+/// for i in 0..4 {
+///     last = roller.push_all([a[i], b[i]]);
+/// }
+/// assert_eq!(last, [Some(context.crc(b"bcd")), Some(context.crc(b"xyz"))]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MultiStreamRoller<'a, const N: usize> {
+    /// Rolling CRC of each stream.
+    rolling_crcs: [RollingCRC<'a>; N],
+}
+
+impl<'a, const N: usize> MultiStreamRoller<'a, N> {
+
+    /// Start `N` new rolling CRCs in the given context.
+    pub fn new(context: &'a RollingCRCContext<'a>) -> Self {
+        Self { rolling_crcs: std::array::from_fn(|_| RollingCRC::new(context)) }
+    }
+
+    /// Roll the next byte of each stream, returning the
+    /// result of `RollingCRC::push()` for each.
+    #[inline]
+    pub fn push_all(&mut self, bytes: [u8; N]) -> [Option<u32>; N] {
+        let mut crcs = [None; N];
+        for ((crc, rolling_crc), byte) in crcs.iter_mut()
+            .zip(self.rolling_crcs.iter_mut())
+            .zip(bytes)
+        {
+            *crc = rolling_crc.push(byte);
+        }
+        crcs
+    }
+
+    /// The rolling CRC of each stream.
+    pub fn streams(&self) -> &[RollingCRC<'a>; N] {
+        &self.rolling_crcs
+    }
+}
+
+#[test]
+fn test_multi_stream_roller() {
+    let len = 5000;
+    let streams: Vec<Vec<u8>> = (0..4u32)
+        .map(|seed| {
+            test_bytes(0x2545_f491 ^ seed.wrapping_mul(0x9e37_79b9), len)
+        })
+        .collect();
+    for &window_size in &[0, 1, 7, 64, 1000] {
+        let context = RollingCRCContext::build(window_size);
+        let expected: Vec<Vec<Option<u32>>> = streams.iter()
+            .map(|stream| {
+                let mut rolling_crc = RollingCRC::new(&context);
+                stream.iter().map(|&b| rolling_crc.push(b)).collect()
+            })
+            .collect();
+        let mut roller = MultiStreamRoller::<4>::new(&context);
+        for i in 0..len {
+            let crcs = roller.push_all([
+                streams[0][i],
+                streams[1][i],
+                streams[2][i],
+                streams[3][i],
+            ]);
+            for (s, &crc) in crcs.iter().enumerate() {
+                assert_eq!(crc, expected[s][i]);
+            }
+        }
+    }
+}
@@ -0,0 +1,110 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Multithreaded rolling CRC scans of in-memory data.
+
+use super::RollingCRCContext;
+#[cfg(test)]
+use super::test_bytes;
+
+use std::thread;
+
+/// Compute the rolling CRCs of every window of the buffer
+/// using `threads` threads, calling `sink` with the
+/// positions and CRCs of consecutive runs of windows in
+/// order: the concatenation of the runs is exactly what
+/// `context.rolling_crcs(buf)` would produce.
+///
+/// The window starts are split evenly into one shard per
+/// thread; each shard is scanned over its windows' bytes,
+/// which overlap the next shard's by `window_size - 1`. The
+/// sink is called on the calling thread as each shard, in
+/// order, finishes.
+///
+/// # Panics
+///
+/// Panics if `threads` is 0.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// use std::sync::Mutex;
+///
+/// let context = RollingCRCContext::new(4).unwrap();
+/// let bytes = b"the rain in spain falls mainly on the plain";
+/// let crcs = Mutex::new(Vec::new());
+/// scan_parallel(bytes, &context, 3, |run| {
+///     crcs.lock().unwrap().extend_from_slice(run);
+/// });
+/// let expected: Vec<(usize, u32)> = context.rolling_crcs(bytes).collect();
+/// assert_eq!(crcs.into_inner().unwrap(), expected);
+/// ```
+#[cfg_attr(feature = "tracing", ::tracing::instrument(
+    level = "debug",
+    skip_all,
+    fields(len = buf.len(), window_size = context.window_size(),
+           threads = threads),
+))]
+pub fn scan_parallel<F>(buf: &[u8],
+                        context: &RollingCRCContext,
+                        threads: usize,
+                        sink: F)
+    where F: Fn(&[(usize, u32)]) + Sync
+{
+    assert!(threads > 0, "scan_parallel: threads must be positive");
+    let window_size = context.window_size();
+    if window_size == 0 || buf.len() < window_size {
+        return;
+    }
+    let nwindows = buf.len() - window_size + 1;
+    thread::scope(|scope| {
+        let shards: Vec<_> = (0..threads)
+            .map(|i| {
+                let start = nwindows * i / threads;
+                let end = nwindows * (i + 1) / threads;
+                scope.spawn(move || {
+                    if start == end {
+                        return Vec::new();
+                    }
+                    let bytes = &buf[start..end + window_size - 1];
+                    context.rolling_crcs(bytes)
+                        .map(|(offset, crc)| (start + offset, crc))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        for shard in shards {
+            let crcs = shard.join().expect("scan_parallel: shard panicked");
+            if !crcs.is_empty() {
+                sink(&crcs);
+            }
+        }
+    });
+}
+
+#[test]
+fn test_scan_parallel() {
+    use std::sync::Mutex;
+
+    let bytes = test_bytes(0x5eed_1234, 10_007);
+    for &window_size in &[0, 1, 2, 7, 64, 999, 10_007, 10_008] {
+        let context = RollingCRCContext::build(window_size);
+        for &len in &[0, 1, 63, 1001, 10_007] {
+            let buf = &bytes[..len];
+            let expected: Vec<(usize, u32)> =
+                context.rolling_crcs(buf).collect();
+            for &threads in &[1, 2, 3, 7, 16, 100] {
+                let crcs = Mutex::new(Vec::new());
+                scan_parallel(buf, &context, threads, |run| {
+                    assert!(!run.is_empty());
+                    crcs.lock().unwrap().extend_from_slice(run);
+                });
+                assert_eq!(crcs.into_inner().unwrap(), expected,
+                           "{} {} {}", window_size, len, threads);
+            }
+        }
+    }
+}
@@ -0,0 +1,128 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Bulk CRC-32 using the x86_64 carry-less multiply
+//! instruction, after Gopal *et al*, "Fast CRC Computation
+//! for Generic Polynomials Using PCLMULQDQ Instruction"
+//! (Intel, 2009).
+//!
+//! The buffer is folded 64 bytes at a time into four
+//! 128-bit accumulators, which are then folded into one,
+//! the remaining whole 16-byte blocks folded into that, and
+//! the result reduced to 32 bits. The constants and the
+//! final reduction follow the `crc32fast` crate. Any tail
+//! of fewer than 16 bytes is left to the table-driven
+//! code.
+
+use std::arch::x86_64 as arch;
+
+/// Fold constants: x^(512+32) and x^(512-32) mod P,
+/// bit-reflected, for folding by four blocks.
+const K1: i64 = 0x1_5444_2bd4;
+const K2: i64 = 0x1_c6e4_1596;
+/// x^(128+32) and x^(128-32) mod P, bit-reflected, for
+/// folding by one block.
+const K3: i64 = 0x1_7519_97d0;
+const K4: i64 = 0x0_ccaa_009e;
+/// x^64 mod P, bit-reflected, for reducing 96 bits to 64.
+const K5: i64 = 0x1_63cd_6124;
+/// The polynomial P and the Barrett constant μ,
+/// bit-reflected.
+const P_X: i64 = 0x1_db71_0641;
+const U_PRIME: i64 = 0x1_f701_1641;
+
+lazy_static! {
+    // Checked once: `is_x86_feature_detected!` is cheap
+    // after its first use, but not free.
+    static ref AVAILABLE: bool =
+        is_x86_feature_detected!("pclmulqdq")
+        && is_x86_feature_detected!("sse4.1");
+}
+
+/// Can the kernel run on this CPU?
+#[inline(always)]
+pub(crate) fn available() -> bool {
+    *AVAILABLE
+}
+
+/// Given the current "open" CRC, return the open CRC
+/// including the whole 16-byte blocks of `buf`, along with
+/// the bytes left over. The buffer must be at least 64
+/// bytes long.
+///
+/// # Safety
+///
+/// The CPU must support PCLMULQDQ and SSE4.1: see
+/// `available()`.
+#[target_feature(enable = "pclmulqdq", enable = "sse2", enable = "sse4.1")]
+pub(crate) unsafe fn update(crc: u32, mut buf: &[u8]) -> (u32, &[u8]) {
+    assert!(buf.len() >= 64);
+
+    let mut x3 = load(&mut buf);
+    let mut x2 = load(&mut buf);
+    let mut x1 = load(&mut buf);
+    let mut x0 = load(&mut buf);
+    x3 = arch::_mm_xor_si128(x3, arch::_mm_cvtsi32_si128(crc as i32));
+
+    let k1k2 = arch::_mm_set_epi64x(K2, K1);
+    while buf.len() >= 64 {
+        x3 = fold(x3, load(&mut buf), k1k2);
+        x2 = fold(x2, load(&mut buf), k1k2);
+        x1 = fold(x1, load(&mut buf), k1k2);
+        x0 = fold(x0, load(&mut buf), k1k2);
+    }
+
+    let k3k4 = arch::_mm_set_epi64x(K4, K3);
+    let mut x = fold(x3, x2, k3k4);
+    x = fold(x, x1, k3k4);
+    x = fold(x, x0, k3k4);
+    while buf.len() >= 16 {
+        x = fold(x, load(&mut buf), k3k4);
+    }
+
+    // Reduce 128 bits to 64.
+    let low32 = arch::_mm_set_epi32(0, 0, 0, !0);
+    let x = arch::_mm_xor_si128(
+        arch::_mm_clmulepi64_si128(x, k3k4, 0x10),
+        arch::_mm_srli_si128(x, 8),
+    );
+    let x = arch::_mm_xor_si128(
+        arch::_mm_clmulepi64_si128(
+            arch::_mm_and_si128(x, low32),
+            arch::_mm_set_epi64x(0, K5),
+            0x00,
+        ),
+        arch::_mm_srli_si128(x, 4),
+    );
+
+    // Barrett reduction from 64 bits to 32, bit-reflected:
+    // the result is in the upper half of the low 64 bits.
+    let pu = arch::_mm_set_epi64x(U_PRIME, P_X);
+    let t1 = arch::_mm_clmulepi64_si128(arch::_mm_and_si128(x, low32), pu, 0x10);
+    let t2 = arch::_mm_clmulepi64_si128(arch::_mm_and_si128(t1, low32), pu, 0x00);
+    let crc = arch::_mm_extract_epi32(arch::_mm_xor_si128(x, t2), 1) as u32;
+    (crc, buf)
+}
+
+/// Fold the accumulator `a` forward over the block `b`.
+#[inline]
+#[target_feature(enable = "pclmulqdq", enable = "sse2")]
+unsafe fn fold(a: arch::__m128i, b: arch::__m128i, keys: arch::__m128i)
+               -> arch::__m128i
+{
+    let t1 = arch::_mm_clmulepi64_si128(a, keys, 0x00);
+    let t2 = arch::_mm_clmulepi64_si128(a, keys, 0x11);
+    arch::_mm_xor_si128(arch::_mm_xor_si128(b, t1), t2)
+}
+
+/// Load the next 16 bytes of the buffer, which may be
+/// unaligned, and advance past them.
+#[inline]
+#[target_feature(enable = "sse2")]
+unsafe fn load(buf: &mut &[u8]) -> arch::__m128i {
+    let (block, rest) = buf.split_at(16);
+    *buf = rest;
+    arch::_mm_loadu_si128(block.as_ptr() as *const arch::__m128i)
+}
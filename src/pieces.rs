@@ -0,0 +1,385 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Verification of fixed-size pieces of a stream against
+//! expected CRCs.
+
+use super::{calc_crc, finish_crc, read_block, update_crc, CRC_TABLE,
+            INIT_CRC};
+#[cfg(test)]
+use super::test_bytes;
+
+use std::io::{self, Read, Seek, SeekFrom};
+use std::ops::Range;
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A piece whose CRC is not the expected one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BadPiece {
+    /// Index of the piece.
+    pub index: usize,
+    /// Expected CRC.
+    pub expected: u32,
+    /// Actual CRC.
+    pub actual: u32,
+}
+
+#[cfg(feature = "defmt")]
+impl ::defmt::Format for BadPiece {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "BadPiece {{ index: {=usize}, \
+                            expected: {=u32:#010x}, actual: {=u32:#010x} }}",
+                        self.index, self.expected, self.actual)
+    }
+}
+
+/// How the length of a verified stream compares with the
+/// expected pieces.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "defmt", derive(::defmt::Format))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum PieceLength {
+    /// The stream has data for every piece and no more.
+    Exact,
+    /// The stream ended early, leaving this many pieces
+    /// with no data at all.
+    Short {
+        /// Number of pieces with no data.
+        missing: usize,
+    },
+    /// The stream runs past the end of the last piece taken
+    /// at its full size. The expected length of a short
+    /// last piece is not known, so bytes appended to one
+    /// show up as a bad last piece and are only counted
+    /// here once they fill it.
+    Long {
+        /// Number of bytes after the first `piece_size`
+        /// times the number of pieces.
+        overrun: u64,
+    },
+}
+
+/// The result of `verify_pieces()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct PieceReport {
+    /// Indices of the pieces with the expected CRC.
+    pub good: Vec<usize>,
+    /// Pieces without the expected CRC. A piece cut short
+    /// by the end of the stream appears here unless it is
+    /// the last piece.
+    pub bad: Vec<BadPiece>,
+    /// Whether the stream was the expected length.
+    pub length: PieceLength,
+}
+
+impl PieceReport {
+    /// Did every piece verify, with nothing left over?
+    pub fn is_ok(&self) -> bool {
+        self.bad.is_empty() && self.length == PieceLength::Exact
+    }
+}
+
+/// Verify the bytes of the given reader as consecutive
+/// pieces of `piece_size` bytes against the expected piece
+/// CRCs. The last piece may be short. Only one piece is
+/// held in memory at a time.
+///
+/// # Panics
+///
+/// Panics if `piece_size` is 0.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let data = b"first piece, second piece, last";
+/// let expected = block_crcs(data, 13);
+/// let mut corrupt = data.to_vec();
+/// corrupt[15] = b'X';
+/// let report = verify_pieces(&corrupt[..], 13, &expected).unwrap();
+/// assert_eq!(report.good, vec![0, 2]);
+/// assert_eq!(report.bad[0].index, 1);
+/// assert_eq!(report.length, PieceLength::Exact);
+/// ```
+pub fn verify_pieces<R: Read>(mut reader: R,
+                              piece_size: usize,
+                              expected: &[u32])
+                              -> io::Result<PieceReport>
+{
+    assert!(piece_size > 0, "verify_pieces: piece_size must be positive");
+    let mut buf = vec![0; piece_size];
+    let mut good = Vec::new();
+    let mut bad = Vec::new();
+    for (index, &expected_crc) in expected.iter().enumerate() {
+        let len = read_block(&mut reader, &mut buf)?;
+        if len == 0 {
+            let missing = expected.len() - index;
+            return Ok(PieceReport {
+                good,
+                bad,
+                length: PieceLength::Short { missing },
+            });
+        }
+        let actual = calc_crc(&buf[..len], &CRC_TABLE);
+        if actual == expected_crc {
+            good.push(index);
+        } else {
+            bad.push(BadPiece { index, expected: expected_crc, actual });
+        }
+        if len < piece_size {
+            let missing = expected.len() - index - 1;
+            let length = if missing == 0 {
+                PieceLength::Exact
+            } else {
+                PieceLength::Short { missing }
+            };
+            return Ok(PieceReport { good, bad, length });
+        }
+    }
+    let mut overrun = 0;
+    loop {
+        let len = read_block(&mut reader, &mut buf)?;
+        overrun += len as u64;
+        if len < piece_size {
+            break;
+        }
+    }
+    let length = if overrun == 0 {
+        PieceLength::Exact
+    } else {
+        PieceLength::Long { overrun }
+    };
+    Ok(PieceReport { good, bad, length })
+}
+
+/// A page whose stored CRC is not its actual CRC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BadPage {
+    /// Index of the page.
+    pub index: u64,
+    /// CRC stored in the page.
+    pub expected: u32,
+    /// Actual CRC of the page.
+    pub actual: u32,
+}
+
+#[cfg(feature = "defmt")]
+impl ::defmt::Format for BadPage {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "BadPage {{ index: {=u64}, \
+                            expected: {=u32:#010x}, actual: {=u32:#010x} }}",
+                        self.index, self.expected, self.actual)
+    }
+}
+
+/// A checker for fixed-size pages, such as database pages,
+/// that store their own CRC as a little-endian `u32` in a
+/// field at a fixed position. The field is taken to be zero
+/// when computing the CRC.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let checker = PageChecker::new(16, 0..4);
+/// let mut page = *b"....page content";
+/// let crc = checker.compute(&page);
+/// page[..4].copy_from_slice(&crc.to_le_bytes());
+/// assert_eq!(checker.compute(&page), crc);
+/// assert_eq!(checker.stored(&page), crc);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageChecker {
+    /// Size of a page.
+    page_size: usize,
+    /// Position of the CRC field in a page.
+    crc_field: Range<usize>,
+}
+
+impl PageChecker {
+
+    /// Make a checker for pages of `page_size` bytes with
+    /// the CRC stored in the given byte range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is not four bytes within the
+    /// page.
+    pub fn new(page_size: usize, crc_field: Range<usize>) -> Self {
+        assert!(crc_field.end == crc_field.start + 4,
+                "PageChecker: CRC field must be four bytes");
+        assert!(crc_field.end <= page_size,
+                "PageChecker: CRC field must be within the page");
+        Self { page_size, crc_field }
+    }
+
+    /// Size of a page.
+    pub fn page_size(&self) -> usize {
+        self.page_size
+    }
+
+    /// CRC of the given page, with the CRC field taken as
+    /// zero. A short page is checksummed as far as it goes.
+    pub fn compute(&self, page: &[u8]) -> u32 {
+        let start = self.crc_field.start.min(page.len());
+        let end = self.crc_field.end.min(page.len());
+        let mut crc = INIT_CRC;
+        for &b in &page[..start] {
+            crc = update_crc(crc, &CRC_TABLE, b);
+        }
+        for _ in start..end {
+            crc = update_crc(crc, &CRC_TABLE, 0);
+        }
+        for &b in &page[end..] {
+            crc = update_crc(crc, &CRC_TABLE, b);
+        }
+        finish_crc(crc)
+    }
+
+    /// CRC stored in the given page, or 0 if the page is
+    /// too short to hold it.
+    pub fn stored(&self, page: &[u8]) -> u32 {
+        match page.get(self.crc_field.clone()) {
+            Some(field) => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(field);
+                u32::from_le_bytes(bytes)
+            },
+            None => 0,
+        }
+    }
+
+    /// Check every page of the given file from its start,
+    /// returning the bad pages in order. A partial page at
+    /// the end of the file is always reported as bad: it is
+    /// checked as far as it goes, but even a match is
+    /// suspect.
+    pub fn verify_file<R: Read + Seek>(&self, mut file: R)
+                                       -> io::Result<Vec<BadPage>>
+    {
+        let _ = file.seek(SeekFrom::Start(0))?;
+        let mut buf = vec![0; self.page_size];
+        let mut bad = Vec::new();
+        let mut index = 0;
+        loop {
+            let len = read_block(&mut file, &mut buf)?;
+            if len == 0 {
+                break;
+            }
+            let page = &buf[..len];
+            let (expected, actual) = (self.stored(page), self.compute(page));
+            if expected != actual || len < self.page_size {
+                bad.push(BadPage { index, expected, actual });
+            }
+            if len < self.page_size {
+                break;
+            }
+            index += 1;
+        }
+        Ok(bad)
+    }
+}
+
+#[test]
+fn test_verify_pieces() {
+    let piece_size = 1000;
+    let data = test_bytes(0x7f4a_7c15, 4500);
+    let expected = super::block_crcs(&data, piece_size);
+    assert_eq!(expected.len(), 5);
+
+    let report = verify_pieces(&data[..], piece_size, &expected).unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.good, vec![0, 1, 2, 3, 4]);
+
+    // A corrupted middle piece.
+    let mut corrupt = data.clone();
+    corrupt[2345] ^= 0x10;
+    let report = verify_pieces(&corrupt[..], piece_size, &expected).unwrap();
+    assert_eq!(report.good, vec![0, 1, 3, 4]);
+    assert_eq!(report.bad, vec![BadPiece {
+        index: 2,
+        expected: expected[2],
+        actual: super::block_crcs(&corrupt, piece_size)[2],
+    }]);
+    assert_eq!(report.length, PieceLength::Exact);
+    assert!(!report.is_ok());
+
+    // Truncated within a piece and at a piece boundary.
+    let report = verify_pieces(&data[..2500], piece_size, &expected).unwrap();
+    assert_eq!(report.good, vec![0, 1]);
+    assert_eq!(report.bad.len(), 1);
+    assert_eq!(report.bad[0].index, 2);
+    assert_eq!(report.length, PieceLength::Short { missing: 2 });
+    let report = verify_pieces(&data[..3000], piece_size, &expected).unwrap();
+    assert_eq!(report.good, vec![0, 1, 2]);
+    assert!(report.bad.is_empty());
+    assert_eq!(report.length, PieceLength::Short { missing: 2 });
+    let report = verify_pieces(&b""[..], piece_size, &expected).unwrap();
+    assert_eq!(report.length, PieceLength::Short { missing: 5 });
+
+    // Extra data, within the short last piece and beyond:
+    // 500 of the 2700 bytes fill out the last piece.
+    let mut long = data.clone();
+    long.extend_from_slice(&[0; 2700]);
+    let report = verify_pieces(&long[..], piece_size, &expected).unwrap();
+    assert_eq!(report.good, vec![0, 1, 2, 3]);
+    assert_eq!(report.bad.len(), 1);
+    assert_eq!(report.length, PieceLength::Long { overrun: 2200 });
+    // Extra data after full pieces is all overrun.
+    let mut long = data[..4000].to_vec();
+    long.extend_from_slice(&[0; 2700]);
+    let report =
+        verify_pieces(&long[..], piece_size, &expected[..4]).unwrap();
+    assert_eq!(report.good, vec![0, 1, 2, 3]);
+    assert_eq!(report.length, PieceLength::Long { overrun: 2700 });
+}
+
+#[test]
+fn test_page_checker() {
+    use std::io::Cursor;
+
+    let page_size = 8192;
+    let npages = 6;
+    let make_file = |checker: &PageChecker| -> Vec<u8> {
+        let mut file = test_bytes(0x3c6e_f372, page_size * npages);
+        for page in file.chunks_mut(page_size) {
+            let crc = checker.compute(page);
+            page[checker.crc_field.clone()].copy_from_slice(&crc.to_le_bytes());
+        }
+        file
+    };
+    for field in [0..4, 100..104, page_size - 4..page_size].iter() {
+        let checker = PageChecker::new(page_size, field.clone());
+        let mut file = make_file(&checker);
+        assert!(checker.verify_file(Cursor::new(&file)).unwrap().is_empty());
+
+        // The field itself does not affect the CRC.
+        let page = &file[..page_size];
+        let mut zeroed = page.to_vec();
+        zeroed[field.clone()].copy_from_slice(&[0; 4]);
+        assert_eq!(checker.compute(page), checker.compute(&zeroed));
+        assert_eq!(checker.compute(&zeroed),
+                   calc_crc(&zeroed, &CRC_TABLE));
+
+        let expected = checker.stored(&file[3 * page_size..]);
+        file[3 * page_size + 5000] ^= 1;
+        let actual = checker.compute(&file[3 * page_size..4 * page_size]);
+        let mut cursor = Cursor::new(&file);
+        let _ = cursor.seek(SeekFrom::End(0)).unwrap();
+        assert_eq!(checker.verify_file(cursor).unwrap(),
+                   vec![BadPage { index: 3, expected, actual }]);
+
+        // A partial trailing page.
+        file.truncate(5 * page_size + 1000);
+        let bad = checker.verify_file(Cursor::new(&file)).unwrap();
+        assert_eq!(bad.len(), 2);
+        assert_eq!(bad[1].index, 5);
+    }
+}
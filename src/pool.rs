@@ -0,0 +1,114 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! A per-thread cache of rolling CRC contexts.
+
+use super::RollingCRCContext;
+#[cfg(test)]
+use super::test_bytes;
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Maximum number of contexts cached by each thread for
+/// `with_context()`.
+pub const CONTEXT_POOL_CAPACITY: usize = 8;
+
+thread_local! {
+    /// This thread's cached contexts, most recently used
+    /// first.
+    static CONTEXT_POOL: RefCell<Vec<Rc<RollingCRCContext<'static>>>> =
+        const { RefCell::new(Vec::new()) };
+}
+
+/// Call `f` with a rolling CRC context for the given window
+/// size, returning its result. Contexts are cached per
+/// thread, so repeated calls with the same few window sizes
+/// build each context once per thread and never contend
+/// with other threads. Up to `CONTEXT_POOL_CAPACITY`
+/// contexts are kept; the least recently used is dropped
+/// first.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let crc = with_context(5, |context| context.crc(b"hello"));
+/// assert_eq!(crc, RollingCRCContext::new(5).unwrap().crc(b"hello"));
+/// ```
+pub fn with_context<F, T>(window_size: usize, f: F) -> T
+    where F: FnOnce(&RollingCRCContext) -> T
+{
+    let context = CONTEXT_POOL.with(|pool| {
+        let mut pool = pool.borrow_mut();
+        let context = match pool.iter()
+            .position(|c| c.window_size() == window_size)
+        {
+            Some(i) => {
+                #[cfg(feature = "tracing")]
+                ::tracing::trace!(window_size, "context cache hit");
+                pool.remove(i)
+            },
+            None => {
+                #[cfg(feature = "tracing")]
+                ::tracing::trace!(window_size, "context cache miss");
+                Rc::new(RollingCRCContext::build(window_size))
+            },
+        };
+        pool.insert(0, Rc::clone(&context));
+        pool.truncate(CONTEXT_POOL_CAPACITY);
+        context
+    });
+    // The pool is not borrowed here, so `f` may itself use
+    // the pool.
+    f(&context)
+}
+
+#[test]
+fn test_with_context() {
+    use std::thread;
+
+    let bytes = test_bytes(0xa54f_f53a, 2000);
+    let window_sizes: Vec<usize> = (0..2 * CONTEXT_POOL_CAPACITY)
+        .map(|i| 3 + 7 * i)
+        .collect();
+    let expected: Vec<Vec<(usize, u32)>> = window_sizes.iter()
+        .map(|&w| {
+            RollingCRCContext::new(w).unwrap().rolling_crcs(&bytes).collect()
+        })
+        .collect();
+    thread::scope(|scope| {
+        for t in 0..4 {
+            let (bytes, window_sizes, expected) =
+                (&bytes, &window_sizes, &expected);
+            let _ = scope.spawn(move || {
+                for round in 0..50 {
+                    // A few hot sizes, with occasional others
+                    // to force evictions.
+                    let i = if round % 5 == 4 {
+                        (round + t) % window_sizes.len()
+                    } else {
+                        (round + t) % 3
+                    };
+                    let crcs: Vec<(usize, u32)> =
+                        with_context(window_sizes[i], |context| {
+                            context.rolling_crcs(bytes).collect()
+                        });
+                    assert_eq!(crcs, expected[i]);
+                }
+                CONTEXT_POOL.with(|pool| {
+                    assert!(pool.borrow().len() <= CONTEXT_POOL_CAPACITY);
+                });
+            });
+        }
+    });
+
+    // Nested use.
+    let crc = with_context(4, |outer| {
+        with_context(5, |inner| outer.crc(b"abcd") ^ inner.crc(b"abcde"))
+    });
+    assert_eq!(crc, RollingCRCContext::new(4).unwrap().crc(b"abcd")
+               ^ RollingCRCContext::new(5).unwrap().crc(b"abcde"));
+}
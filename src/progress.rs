@@ -0,0 +1,191 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Progress reporting and statistics for long-running
+//! searches.
+
+use super::{CancelToken, SearchError};
+
+use std::fmt;
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// Progress of a search so far, as passed to a progress
+/// callback.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(::defmt::Format))]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScanProgress {
+    /// Number of input bytes searched.
+    pub bytes: u64,
+    /// Number of candidate windows examined, which depends
+    /// on the search strategy: see `ScanStats::windows`.
+    pub windows: u64,
+    /// Number of matches found.
+    pub matches: u64,
+}
+
+/// Totals for a completed search.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct ScanStats {
+    /// Number of input bytes searched.
+    pub bytes: u64,
+    /// Number of candidate windows examined. This depends
+    /// on the search strategy, so counts are comparable
+    /// only between searches with the same one: with
+    /// `FindStrategy::Rolling` it is every window of the
+    /// input, but with `FindStrategy::Memchr` only the
+    /// windows at occurrences of the anchor byte.
+    pub windows: u64,
+    /// Number of matches found.
+    pub matches: u64,
+    /// Time taken by the search.
+    pub elapsed: Duration,
+}
+
+#[cfg(feature = "defmt")]
+impl ::defmt::Format for ScanStats {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "ScanStats {{ bytes: {=u64}, windows: {=u64}, \
+                            matches: {=u64}, elapsed: {=u64}us }}",
+                        self.bytes, self.windows, self.matches,
+                        self.elapsed.as_micros() as u64)
+    }
+}
+
+/// Bookkeeping for a search: cancellation, progress
+/// reporting and statistics. A monitor with neither a
+/// token nor a callback lets the search run uninterrupted.
+pub(crate) struct ScanMonitor<'a> {
+    /// Token to check for cancellation.
+    token: Option<&'a CancelToken>,
+    /// Progress callback.
+    progress: Option<&'a mut dyn FnMut(ScanProgress)>,
+    /// Number of windows between checkpoints.
+    interval: usize,
+    /// Byte count at the last progress report.
+    reported: u64,
+    /// Progress so far.
+    current: ScanProgress,
+    /// Start time of the search.
+    start: Instant,
+}
+
+impl<'a> fmt::Debug for ScanMonitor<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ScanMonitor")
+            .field("token", &self.token)
+            .field("interval", &self.interval)
+            .field("current", &self.current)
+            .finish()
+    }
+}
+
+impl<'a> ScanMonitor<'a> {
+
+    /// A monitor that does nothing but gather statistics.
+    pub(crate) fn new() -> Self {
+        Self {
+            token: None,
+            progress: None,
+            interval: usize::MAX,
+            reported: 0,
+            current: ScanProgress::default(),
+            start: Instant::now(),
+        }
+    }
+
+    /// A monitor checking the given token every
+    /// `token.interval()` windows.
+    pub(crate) fn with_cancel(token: &'a CancelToken) -> Self {
+        let mut monitor = Self::new();
+        monitor.token = Some(token);
+        monitor.interval = token.interval();
+        monitor
+    }
+
+    /// A monitor reporting progress at most every
+    /// `interval` bytes (every window if 0).
+    pub(crate) fn with_progress(interval: usize,
+                                progress: &'a mut dyn FnMut(ScanProgress))
+                                -> Self
+    {
+        let mut monitor = Self::new();
+        monitor.progress = Some(progress);
+        monitor.interval = interval.max(1);
+        monitor
+    }
+
+    /// Number of windows to examine between checkpoints.
+    pub(crate) fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// Fail if the search has been cancelled.
+    pub(crate) fn check(&self) -> Result<(), SearchError> {
+        match self.token {
+            Some(token) => token.check(),
+            None => Ok(()),
+        }
+    }
+
+    /// Record that the search is complete through `bytes`
+    /// bytes, having examined `windows` more windows and
+    /// found `matches` more matches, and fail if it has been
+    /// cancelled.
+    pub(crate) fn checkpoint(&mut self,
+                             bytes: u64,
+                             windows: usize,
+                             matches: usize)
+                             -> Result<(), SearchError>
+    {
+        self.current.bytes = self.current.bytes.max(bytes);
+        self.current.windows += windows as u64;
+        self.current.matches += matches as u64;
+        if let Some(ref mut progress) = self.progress {
+            let interval = self.interval as u64;
+            if self.current.bytes >= self.reported.saturating_add(interval) {
+                self.reported = self.current.bytes;
+                progress(self.current);
+            }
+        }
+        self.check()
+    }
+
+    /// Number of input bytes searched so far.
+    pub(crate) fn current_bytes(&self) -> u64 {
+        self.current.bytes
+    }
+
+    /// Emit an event summarizing the search so far.
+    #[cfg(feature = "tracing")]
+    pub(crate) fn trace_finished(&self) {
+        ::tracing::debug!(bytes = self.current.bytes,
+                          windows = self.current.windows,
+                          matches = self.current.matches,
+                          "search finished");
+    }
+
+    /// Finish the search after `bytes` bytes, making a
+    /// final progress report if anything has changed since
+    /// the last.
+    pub(crate) fn finish(mut self, bytes: u64) -> ScanStats {
+        self.current.bytes = self.current.bytes.max(bytes);
+        if let Some(progress) = self.progress {
+            if self.current.bytes > self.reported || self.current.bytes == 0 {
+                progress(self.current);
+            }
+        }
+        ScanStats {
+            bytes: self.current.bytes,
+            windows: self.current.windows,
+            matches: self.current.matches,
+            elapsed: self.start.elapsed(),
+        }
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Python bindings, via PyO3. The extension module is
+//! built by `maturin` from `python/pyproject.toml`.
+
+use super::{Finder, RollingCRCContext};
+
+use pyo3::prelude::*;
+
+/// A rolling CRC context for use from Python, exported as
+/// `rolling_crc.RollingCrcContext(window_size)`.
+///
+/// Scans of large buffers release the GIL, so other Python
+/// threads may run meanwhile.
+#[pyclass(name = "RollingCrcContext", module = "rolling_crc", frozen)]
+pub struct PyRollingCrcContext {
+    context: RollingCRCContext<'static>,
+}
+
+#[pymethods]
+impl PyRollingCrcContext {
+
+    /// Make a new context for the given window size.
+    #[new]
+    pub fn new(window_size: usize) -> Self {
+        Self { context: RollingCRCContext::build(window_size) }
+    }
+
+    /// Size of the calculation window.
+    #[getter]
+    pub fn window_size(&self) -> usize {
+        self.context.window_size()
+    }
+
+    /// The CRC of the given bytes.
+    pub fn crc(&self, py: Python, bytes: &[u8]) -> u32 {
+        py.allow_threads(|| self.context.crc(bytes))
+    }
+
+    /// The rolling CRCs of every window of the given bytes,
+    /// as a list of `(offset, crc)` tuples.
+    pub fn scan(&self, py: Python, bytes: &[u8]) -> Vec<(usize, u32)> {
+        py.allow_threads(|| self.context.rolling_crcs(bytes).collect())
+    }
+
+    /// The offsets of all occurrences of `needle` in
+    /// `haystack`, as with `Finder::find_iter()`.
+    pub fn find(&self, py: Python, haystack: &[u8], needle: &[u8])
+                -> Vec<usize>
+    {
+        py.allow_threads(|| Finder::new(needle).find_iter(haystack).collect())
+    }
+
+    fn __repr__(&self) -> String {
+        format!("RollingCrcContext({})", self.context.window_size())
+    }
+}
+
+/// The `rolling_crc` Python module.
+#[pymodule]
+fn rolling_crc(module: &Bound<PyModule>) -> PyResult<()> {
+    module.add_class::<PyRollingCrcContext>()
+}
+
+#[test]
+fn test_python() {
+    pyo3::prepare_freethreaded_python();
+    Python::with_gil(|py| {
+        let context = RollingCRCContext::new(5).unwrap();
+        let py_context = PyRollingCrcContext::new(5);
+        assert_eq!(py_context.window_size(), 5);
+        assert_eq!(py_context.crc(py, b"123456789"), 0xcbf4_3926);
+        let bytes = b"hello world";
+        let expected: Vec<(usize, u32)> =
+            context.rolling_crcs(bytes).collect();
+        assert_eq!(py_context.scan(py, bytes), expected);
+        assert_eq!(py_context.find(py, b"abcabcab", b"cab"), vec![2, 5]);
+
+        // Through Python itself.
+        let module = PyModule::new_bound(py, "rolling_crc").unwrap();
+        rolling_crc(&module).unwrap();
+        let locals = pyo3::types::PyDict::new_bound(py);
+        locals.set_item("rolling_crc", module).unwrap();
+        py.run_bound(
+            "ctx = rolling_crc.RollingCrcContext(5)\n\
+             assert ctx.window_size == 5\n\
+             assert ctx.crc(b'world') == ctx.scan(b'hello world')[-1][1]\n\
+             assert ctx.find(b'hello world', b'o') == [4, 7]\n\
+             assert repr(ctx) == 'RollingCrcContext(5)'\n",
+            None,
+            Some(&locals),
+        ).unwrap();
+    });
+}
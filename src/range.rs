@@ -0,0 +1,146 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! CRCs of arbitrary ranges of a fixed buffer from an index
+//! of prefix CRCs.
+
+// By `combine()`, the CRC of the prefix ending at j is
+// `M^(j-i) P_i ^ C(i..j)`, where P_i is the CRC of the
+// prefix ending at i, C(i..j) the CRC of the range, and M
+// multiplication by x^8 modulo the CRC polynomial. So
+//
+//        C(i..j) == P_j ^ M^(j-i) P_i
+//
+// and each query is one power and one multiply, taking
+// time logarithmic in the range length.
+
+use super::combine::x2n_mod_poly;
+use super::gf2::clmul;
+use super::{finish_crc, RollingCRCContext, INIT_CRC, POLY_CRC};
+#[cfg(test)]
+use super::test_rng;
+
+use alloc::vec::Vec;
+use std::ops::Range;
+
+/// An index of a fixed buffer giving the CRC of any range
+/// of it in time logarithmic in the range length. The index
+/// holds the CRC of every prefix ending at a multiple of
+/// the block size: one `u32` per byte by default. A larger
+/// block size saves memory at the cost of CRCing up to a
+/// block of bytes at each end of a range.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::checksum_only();
+/// let bytes = b"hello world";
+/// let index = RangeCrc::new(&context, bytes);
+/// assert_eq!(index.crc_range(6..11), context.crc(b"world"));
+/// assert_eq!(index.crc_range(3..3), 0);
+/// ```
+#[derive(Debug, Clone)]
+pub struct RangeCrc<'a, 'b> {
+    context: &'a RollingCRCContext<'a>,
+    bytes: &'b [u8],
+    /// Number of bytes between indexed prefixes.
+    block_size: usize,
+    /// Entry k is the CRC of the first `k * block_size`
+    /// bytes.
+    prefixes: Vec<u32>,
+}
+
+impl<'a, 'b> RangeCrc<'a, 'b> {
+
+    /// Index the given buffer, with CRCs in the given
+    /// context, keeping the CRC of every prefix.
+    pub fn new(context: &'a RollingCRCContext<'a>, bytes: &'b [u8]) -> Self {
+        Self::with_block_size(context, bytes, 1)
+    }
+
+    /// Index the given buffer, with CRCs in the given
+    /// context, keeping the CRC of every prefix ending at a
+    /// multiple of `block_size`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is 0.
+    pub fn with_block_size(context: &'a RollingCRCContext<'a>,
+                           bytes: &'b [u8],
+                           block_size: usize)
+                           -> Self
+    {
+        assert!(block_size > 0, "RangeCrc: block size must be positive");
+        let mut prefixes = Vec::with_capacity(bytes.len() / block_size + 1);
+        prefixes.push(0);
+        let mut open = INIT_CRC;
+        for block in bytes.chunks_exact(block_size) {
+            open = context.update_open(open, block);
+            prefixes.push(finish_crc(open));
+        }
+        Self { context, bytes, block_size, prefixes }
+    }
+
+    /// Number of bytes between indexed prefixes.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// CRC of the first `end` bytes.
+    fn prefix_crc(&self, end: usize) -> u32 {
+        let block = end / self.block_size;
+        let start = block * self.block_size;
+        let open = finish_crc(self.prefixes[block]);
+        finish_crc(self.context.update_open(open, &self.bytes[start..end]))
+    }
+
+    /// CRC of the given range of the buffer, as
+    /// `context.crc(&bytes[range])`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the range is out of bounds or decreasing,
+    /// as slicing would.
+    pub fn crc_range(&self, range: Range<usize>) -> u32 {
+        let Range { start, end } = range;
+        assert!(start <= end && end <= self.bytes.len(),
+                "RangeCrc: range {}..{} out of bounds for length {}",
+                start, end, self.bytes.len());
+        let shift = x2n_mod_poly((end - start) as u64, 3);
+        self.prefix_crc(end) ^ clmul(shift, self.prefix_crc(start), POLY_CRC)
+    }
+}
+
+#[test]
+fn test_range_crc() {
+    let mut next = test_rng(0x9e37_79b9u32);
+    let bytes: Vec<u8> = (0..5000).map(|_| next() as u8).collect();
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8).to_ascii_lowercase();
+    }
+    let plain = RollingCRCContext::checksum_only();
+    let mapped = RollingCRCContext::checksum_only_with_byte_map(fold);
+    let empty = RangeCrc::new(&plain, b"");
+    assert_eq!(empty.crc_range(0..0), 0);
+    for context in [plain, mapped].iter() {
+        for &block_size in &[1, 2, 7, 64, 4096, 10_000] {
+            let index = RangeCrc::with_block_size(context, &bytes, block_size);
+            assert_eq!(index.block_size(), block_size);
+            for _ in 0..2000 {
+                let a = next() as usize % (bytes.len() + 1);
+                let b = next() as usize % (bytes.len() + 1);
+                let range = a.min(b)..a.max(b);
+                assert_eq!(index.crc_range(range.clone()),
+                           context.crc(&bytes[range.clone()]),
+                           "{} {:?}", block_size, range);
+            }
+            let len = bytes.len();
+            assert_eq!(index.crc_range(0..len), context.crc(&bytes));
+            assert_eq!(index.crc_range(len..len), 0);
+        }
+    }
+}
@@ -0,0 +1,165 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! A reader that computes rolling CRCs of the bytes read
+//! through it.
+
+use super::{RollingCRC, RollingCRCContext};
+#[cfg(test)]
+use super::{test_bytes, test_rng};
+
+use std::io::{self, Read};
+use std::mem;
+
+/// A reader that passes bytes through from an inner
+/// reader, rolling each byte read through a rolling CRC.
+/// The rolling CRC of each window completed is kept, with
+/// the offset of the window start in the input, until
+/// taken with `take_windows()`: take them regularly when
+/// reading a long stream.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// use std::io::{self, Read};
+///
+/// let context = RollingCRCContext::new(5).unwrap();
+/// let mut reader = RollingCrcReader::new(&b"hello world"[..], &context);
+/// let mut copy = Vec::new();
+/// io::copy(&mut reader, &mut copy).unwrap();
+/// assert_eq!(copy, b"hello world");
+/// assert_eq!(reader.total_read(), 11);
+/// let windows = reader.take_windows();
+/// assert_eq!(windows.len(), 7);
+/// assert_eq!(windows[6], (6, context.crc(b"world")));
+/// ```
+#[derive(Debug)]
+pub struct RollingCrcReader<'a, R> {
+    inner: R,
+    rolling_crc: RollingCRC<'a>,
+    /// Windows completed but not yet taken.
+    windows: Vec<(u64, u32)>,
+    /// Number of bytes read so far.
+    total_read: u64,
+}
+
+impl<'a, R: Read> RollingCrcReader<'a, R> {
+
+    /// Wrap the given reader, computing rolling CRCs in the
+    /// given context.
+    pub fn new(inner: R, context: &'a RollingCRCContext<'a>) -> Self {
+        Self {
+            inner,
+            rolling_crc: RollingCRC::new(context),
+            windows: Vec::new(),
+            total_read: 0,
+        }
+    }
+
+    /// The windows completed since the last call, as
+    /// offsets of window starts in the input and their
+    /// rolling CRCs.
+    pub fn take_windows(&mut self) -> Vec<(u64, u32)> {
+        mem::take(&mut self.windows)
+    }
+
+    /// The windows completed and not yet taken.
+    pub fn windows(&self) -> &[(u64, u32)] {
+        &self.windows
+    }
+
+    /// Number of bytes read through this reader so far.
+    pub fn total_read(&self) -> u64 {
+        self.total_read
+    }
+
+    /// The inner reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// The rolling CRC of the bytes read so far.
+    pub fn rolling_crc(&self) -> &RollingCRC<'a> {
+        &self.rolling_crc
+    }
+
+    /// Unwrap this reader, returning the inner reader and
+    /// the final rolling CRC. Windows not yet taken are
+    /// dropped.
+    pub fn into_inner(self) -> (R, RollingCRC<'a>) {
+        (self.inner, self.rolling_crc)
+    }
+}
+
+impl<'a, R: Read> Read for RollingCrcReader<'a, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        let windows = &mut self.windows;
+        self.rolling_crc.push_each(&buf[..n], |offset, crc| {
+            windows.push((offset, crc));
+        });
+        self.total_read += n as u64;
+        Ok(n)
+    }
+}
+
+#[test]
+fn test_rolling_crc_reader() {
+    /// A reader returning short reads of chaotic lengths,
+    /// and sometimes interrupted.
+    struct Chaotic<R, F> {
+        inner: R,
+        next: F,
+    }
+
+    impl<R: Read, F: FnMut() -> u32> Read for Chaotic<R, F> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let state = (self.next)();
+            if state % 7 == 3 {
+                return Err(io::ErrorKind::Interrupted.into());
+            }
+            let n = (state as usize % 100).max(1).min(buf.len());
+            self.inner.read(&mut buf[..n])
+        }
+    }
+
+    let bytes = test_bytes(0x2545_f491u32, 50_000);
+    for &window_size in &[1, 16, 1000] {
+        let context = RollingCRCContext::new(window_size).unwrap();
+        let expected: Vec<(u64, u32)> = context.rolling_crcs(&bytes)
+            .map(|(offset, crc)| (offset as u64, crc))
+            .collect();
+        let chaotic = Chaotic {
+            inner: io::Cursor::new(&bytes),
+            next: test_rng(0x9e37_79b9),
+        };
+        let mut reader = RollingCrcReader::new(chaotic, &context);
+        // Zero-byte reads pass nothing through.
+        assert_eq!(reader.read(&mut []).unwrap(), 0);
+        let mut copy = Vec::new();
+        let mut windows = Vec::new();
+        let mut buf = [0; 4096];
+        loop {
+            let n = match reader.read(&mut buf) {
+                Ok(0) => break,
+                Ok(n) => n,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted =>
+                    continue,
+                Err(e) => panic!("{}", e),
+            };
+            copy.extend_from_slice(&buf[..n]);
+            windows.extend(reader.take_windows());
+            assert_eq!(reader.total_read(), copy.len() as u64);
+        }
+        assert_eq!(copy, bytes);
+        assert_eq!(windows, expected);
+        assert!(reader.windows().is_empty());
+        let (_, rolling_crc) = reader.into_inner();
+        let (first, second) = rolling_crc.window();
+        assert_eq!([first, second].concat(),
+                   &bytes[bytes.len() - window_size..]);
+    }
+}
@@ -0,0 +1,243 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! CRCs of the delimiter-separated records of a stream,
+//! such as the lines of a log.
+
+use super::{finish_crc, RollingCRCContext, INIT_CRC};
+
+use memchr::memchr;
+
+use std::io::{self, BufRead};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// A record found by `record_crcs()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct RecordCrc {
+    /// Offset of the record in the stream.
+    pub offset: u64,
+    /// Length of the record, including its delimiter if
+    /// delimiters are included.
+    pub len: usize,
+    /// CRC of the record.
+    pub crc: u32,
+}
+
+#[cfg(feature = "defmt")]
+impl ::defmt::Format for RecordCrc {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "RecordCrc {{ offset: {=u64}, len: {=usize}, \
+                            crc: {=u32:#010x} }}",
+                        self.offset, self.len, self.crc)
+    }
+}
+
+/// The CRC of each record of the reader, where records end
+/// with the given delimiter byte, in the given context. The
+/// CRCs are computed as the bytes are read, so a record
+/// of any length needs no allocation.
+///
+/// By default each record excludes its delimiter, and a
+/// final record with no delimiter is reported; see
+/// `RecordCrcs::include_delimiter()` and
+/// `RecordCrcs::unterminated()`. Empty records are
+/// reported with the CRC of no bytes.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::checksum_only();
+/// let lines = b"hello\n\nworld";
+/// let records: Vec<RecordCrc> = record_crcs(&lines[..], b'\n', &context)
+///     .map(Result::unwrap)
+///     .collect();
+/// assert_eq!(records, vec![
+///     RecordCrc { offset: 0, len: 5, crc: context.crc(b"hello") },
+///     RecordCrc { offset: 6, len: 0, crc: 0 },
+///     RecordCrc { offset: 7, len: 5, crc: context.crc(b"world") },
+/// ]);
+/// ```
+pub fn record_crcs<'a, R>(reader: R,
+                          delimiter: u8,
+                          context: &'a RollingCRCContext<'a>)
+                          -> RecordCrcs<'a, R>
+    where R: BufRead
+{
+    RecordCrcs {
+        reader,
+        delimiter,
+        context,
+        include_delimiter: false,
+        unterminated: true,
+        offset: 0,
+        done: false,
+    }
+}
+
+/// An iterator over the CRCs of the records of a stream.
+/// This iterator can be created using `record_crcs()`.
+#[derive(Debug)]
+pub struct RecordCrcs<'a, R> {
+    reader: R,
+    delimiter: u8,
+    context: &'a RollingCRCContext<'a>,
+    /// Include the delimiter in each record?
+    include_delimiter: bool,
+    /// Report a final record with no delimiter?
+    unterminated: bool,
+    /// Stream offset of the next record.
+    offset: u64,
+    /// Has the stream ended or failed?
+    done: bool,
+}
+
+impl<'a, R: BufRead> RecordCrcs<'a, R> {
+
+    /// Include the delimiter ending each record in its
+    /// length and CRC. Default `false`.
+    pub fn include_delimiter(mut self, include: bool) -> Self {
+        self.include_delimiter = include;
+        self
+    }
+
+    /// Report a final record that has no delimiter because
+    /// the stream ended first. Default `true`.
+    pub fn unterminated(mut self, report: bool) -> Self {
+        self.unterminated = report;
+        self
+    }
+}
+
+impl<'a, R: BufRead> Iterator for RecordCrcs<'a, R> {
+    type Item = io::Result<RecordCrc>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let mut open = INIT_CRC;
+        let mut len = 0;
+        let terminated = loop {
+            let (used, found) = {
+                let buf = match self.reader.fill_buf() {
+                    Ok(buf) => buf,
+                    Err(ref e) if e.kind() == io::ErrorKind::Interrupted =>
+                        continue,
+                    Err(e) => {
+                        self.done = true;
+                        return Some(Err(e));
+                    },
+                };
+                if buf.is_empty() {
+                    break false;
+                }
+                match memchr(self.delimiter, buf) {
+                    Some(i) => {
+                        let end = i + self.include_delimiter as usize;
+                        open = self.context.update_open(open, &buf[..end]);
+                        len += end;
+                        (i + 1, true)
+                    },
+                    None => {
+                        open = self.context.update_open(open, buf);
+                        len += buf.len();
+                        (buf.len(), false)
+                    },
+                }
+            };
+            self.reader.consume(used);
+            if found {
+                break true;
+            }
+        };
+        let record = RecordCrc {
+            offset: self.offset,
+            len,
+            crc: finish_crc(open),
+        };
+        if terminated {
+            let delimiter = !self.include_delimiter as usize;
+            self.offset += (len + delimiter) as u64;
+            return Some(Ok(record));
+        }
+        self.done = true;
+        if len == 0 || !self.unterminated {
+            return None;
+        }
+        self.offset += len as u64;
+        Some(Ok(record))
+    }
+}
+
+#[test]
+fn test_record_crcs() {
+    use std::io::BufReader;
+
+    let context = RollingCRCContext::checksum_only();
+    let mut long = vec![b'x'; 100_000];
+    long[50_000] = b'y';
+    let fixtures: [Vec<u8>; 6] = [
+        b"".to_vec(),
+        b"\n".to_vec(),
+        b"one\n\n\nfour\n".to_vec(),
+        b"no trailing newline\n\nlast".to_vec(),
+        [&long[..], b"\n", &long[..], b"\n\n", &long[..]].concat(),
+        b"\n\nx".to_vec(),
+    ];
+    for input in fixtures.iter() {
+        let mut terminated: Vec<&[u8]> = input.split(|&b| b == b'\n')
+            .collect();
+        let last = terminated.pop().unwrap();
+        for &capacity in &[1, 7, 8192] {
+            for &include in &[false, true] {
+                for &unterminated in &[false, true] {
+                    let reader = BufReader::with_capacity(capacity, &input[..]);
+                    let records: Vec<RecordCrc> =
+                        record_crcs(reader, b'\n', &context)
+                        .include_delimiter(include)
+                        .unterminated(unterminated)
+                        .map(Result::unwrap)
+                        .collect();
+                    let mut expected = Vec::new();
+                    let mut offset = 0;
+                    for record in &terminated {
+                        let len = record.len() + include as usize;
+                        let bytes = &input[offset..offset + len];
+                        expected.push(RecordCrc {
+                            offset: offset as u64,
+                            len,
+                            crc: context.crc(bytes),
+                        });
+                        offset += record.len() + 1;
+                    }
+                    if unterminated && !last.is_empty() {
+                        expected.push(RecordCrc {
+                            offset: offset as u64,
+                            len: last.len(),
+                            crc: context.crc(last),
+                        });
+                    }
+                    assert_eq!(records, expected);
+                }
+            }
+        }
+    }
+
+    // Reading stops at the first error.
+    struct Failing;
+    impl io::Read for Failing {
+        fn read(&mut self, _: &mut [u8]) -> io::Result<usize> {
+            Err(io::Error::other("disk on fire"))
+        }
+    }
+    let mut records =
+        record_crcs(BufReader::new(Failing), b'\n', &context);
+    assert!(records.next().unwrap().is_err());
+    assert!(records.next().is_none());
+}
@@ -0,0 +1,156 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Rolling CRCs of the windows of a slice from last to
+//! first.
+
+// Rolling backward, the window loses its last byte and
+// gains a new first byte. As for the reverse strand in
+// `kmer`: with A the linear operator taking an open CRC
+// through one zero byte, T(b) for `crc_table[b]` and I for
+// `INIT_CRC`, dropping the last byte v is undoing its
+// update, `S(U) == A^-1 (S(U ++ v) ^ T(v))`, and prepending
+// a byte c to a message U of length k-1 gives
+//
+//        S(c ++ U) == S(U) ^ A^(k-1) (I ^ A I ^ T(c))
+//
+// The last term depends only on c. A^(k-1) is
+// multiplication by x^(8(k-1)) modulo the CRC polynomial,
+// so the "prepend table" takes 256 carry-less multiplies
+// whatever the window size.
+
+use super::combine::x2n_mod_poly;
+use super::gf2::clmul;
+use super::{finish_crc, unupdate_crc, update_crc, CRCTable,
+            RollingCRCContext, INIT_CRC, INVERSE_CRC_TABLE, POLY_CRC};
+#[cfg(test)]
+use super::test_bytes;
+
+impl<'a> RollingCRCContext<'a> {
+
+    /// An iterator over the rolling CRCs of every window of
+    /// the given slice, as with `rolling_crcs()`, but from
+    /// the last window to the first. Each step rolls
+    /// backward in constant time, so a search for the last
+    /// occurrence of something can stop at the first hit.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let context = RollingCRCContext::new(5).unwrap();
+    /// let bytes = b"hello world";
+    /// let mut crcs: Vec<(usize, u32)> =
+    ///     context.rolling_crcs_rev(bytes).collect();
+    /// assert_eq!(crcs[0], (6, context.crc(b"world")));
+    /// crcs.reverse();
+    /// assert!(crcs.into_iter().eq(context.rolling_crcs(bytes)));
+    /// ```
+    pub fn rolling_crcs_rev<'b>(&'a self, bytes: &'b [u8])
+                                -> RollingCRCRev<'a, 'b>
+    {
+        let window_size = self.window_size;
+        let mut prepend_table = [0; 256];
+        if window_size >= 1 {
+            let shift = x2n_mod_poly(window_size as u64 - 1, 3);
+            let init = INIT_CRC ^ update_crc(INIT_CRC, self.crc_table, 0);
+            for (c, entry) in prepend_table.iter_mut().enumerate() {
+                *entry = clmul(shift, init ^ self.crc_table[c], POLY_CRC);
+            }
+        }
+        let remaining = if window_size == 0 {
+            0
+        } else {
+            (bytes.len() + 1).saturating_sub(window_size)
+        };
+        RollingCRCRev {
+            context: self,
+            bytes,
+            prepend_table,
+            remaining,
+            last_crc: None,
+        }
+    }
+}
+
+/// An iterator over the positions and rolling CRCs of the
+/// windows of a byte slice from last to first. This
+/// iterator can be created using
+/// `RollingCRCContext::rolling_crcs_rev()`.
+#[derive(Debug, Clone)]
+pub struct RollingCRCRev<'a, 'b> {
+    context: &'a RollingCRCContext<'a>,
+    bytes: &'b [u8],
+    /// Contribution of a byte prepended to a message one
+    /// byte shorter than the window.
+    prepend_table: CRCTable,
+    /// Number of windows yet to be reported: the next is
+    /// at position `remaining - 1`.
+    remaining: usize,
+    /// Open CRC of the window last reported.
+    last_crc: Option<u32>,
+}
+
+impl<'a, 'b> Iterator for RollingCRCRev<'a, 'b> {
+    type Item = (usize, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        self.remaining -= 1;
+        let index = self.remaining;
+        let context = self.context;
+        let window_size = context.window_size;
+        let crc = match self.last_crc {
+            None => {
+                let window = &self.bytes[index..index + window_size];
+                finish_crc(context.crc(window))
+            },
+            Some(last_crc) => {
+                let bytes = self.bytes;
+                let roll_out = context.map_byte(bytes[index + window_size]);
+                let roll_in = context.map_byte(bytes[index]);
+                let dropped = unupdate_crc(last_crc,
+                                           context.crc_table,
+                                           &INVERSE_CRC_TABLE,
+                                           roll_out);
+                dropped ^ self.prepend_table[roll_in as usize]
+            },
+        };
+        self.last_crc = Some(crc);
+        Some((index, finish_crc(crc)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+#[test]
+fn test_rolling_crcs_rev() {
+    let bytes = test_bytes(0x510e_527f, 200);
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = (b as u8).to_ascii_lowercase();
+    }
+    for window_size in 0..70 {
+        let plain = RollingCRCContext::build(window_size);
+        let mut mapped = RollingCRCContext::build(window_size);
+        mapped.byte_map = Some(fold);
+        for context in [plain, mapped].iter() {
+            for &len in &[0, 1, window_size, window_size + 1, bytes.len()] {
+                let bytes = &bytes[..len.min(bytes.len())];
+                let mut forward: Vec<(usize, u32)> =
+                    context.rolling_crcs(bytes).collect();
+                forward.reverse();
+                let reverse = context.rolling_crcs_rev(bytes);
+                assert_eq!(reverse.size_hint().0, forward.len());
+                assert_eq!(reverse.collect::<Vec<_>>(), forward,
+                           "{} {}", window_size, len);
+            }
+        }
+    }
+}
@@ -27,6 +27,21 @@
 #[cfg(test)]
 extern crate crc;
 
+use super::{CRC_TABLE, SLICE_TABLES};
+#[cfg(test)]
+use super::test_bytes;
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+use super::pclmul;
+#[cfg(all(target_arch = "aarch64", feature = "std"))]
+use super::armcrc;
+
+#[cfg(feature = "alloc")]
+use alloc::vec::Vec;
+use std::ptr;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
 /// Standard CRC-32 IEEE *et al* polynomial.
 pub const POLY_CRC: u32 =  0xEDB88320;
 
@@ -50,11 +65,62 @@ pub const INIT_CRC: u32 = !0;
 /// possible byte value.
 pub(crate) type CRCTable = [u32; 256];
 
+/// The entry of the table for the given byte.
+///
+/// With the `unsafe-perf` feature the bounds check is
+/// skipped. This is sound because a `u8` index is always
+/// less than the table's fixed length of 256.
+#[inline(always)]
+pub(crate) fn table_entry(table: &CRCTable, index: u8) -> u32 {
+    #[cfg(feature = "unsafe-perf")]
+    {
+        debug_assert!((index as usize) < table.len());
+        // Safety: `index` is at most 255.
+        unsafe { *table.get_unchecked(index as usize) }
+    }
+    #[cfg(not(feature = "unsafe-perf"))]
+    {
+        table[index as usize]
+    }
+}
+
 /// Given the current CRC, return the CRC including the
 /// next character.
 #[inline(always)]
 pub(crate) fn update_crc(crc: u32, crc_table: &CRCTable, c: u8) -> u32 {
-    crc_table[((crc ^ (c as u32)) & 0xff) as usize] ^ (crc >> 8)
+    table_entry(crc_table, (crc ^ (c as u32)) as u8) ^ (crc >> 8)
+}
+
+/// For each possible top byte, the index of the CRC table
+/// entry with that top byte. The CRC-32 table entries have
+/// distinct top bytes.
+pub(crate) type InverseCRCTable = [u8; 256];
+
+/// Build the inverse of the given standard CRC table.
+pub(crate) const fn make_inverse_crc_table(crc_table: &CRCTable)
+                                           -> InverseCRCTable
+{
+    let mut inverse_table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        inverse_table[(crc_table[i] >> 24) as usize] = i as u8;
+        i += 1;
+    }
+    inverse_table
+}
+
+/// Given the CRC including the character `c`, return the
+/// CRC before it: the inverse of `update_crc()`.
+#[inline(always)]
+pub(crate) fn unupdate_crc(crc: u32,
+                           crc_table: &CRCTable,
+                           inverse_table: &InverseCRCTable,
+                           c: u8)
+                           -> u32
+{
+    let i = inverse_table[(crc >> 24) as usize];
+    let high = crc ^ crc_table[i as usize];
+    (high << 8) | (i ^ c) as u32
 }
 
 /// Apply INIT_CRC to the final CRC. This can also be
@@ -64,21 +130,240 @@ pub(crate) fn finish_crc(crc: u32) -> u32 {
     crc ^ INIT_CRC
 }
 
+/// Tables for computing a CRC up to sixteen bytes at a
+/// time. Table `k` gives the contribution of a byte
+/// followed by `k` further bytes; table 0 is the standard
+/// CRC table.
+pub(crate) type SliceTables = [CRCTable; 16];
+
+/// Build the slicing tables from the given standard CRC
+/// table.
+pub(crate) const fn make_slice_tables(crc_table: &CRCTable) -> SliceTables {
+    let mut tables = [[0; 256]; 16];
+    tables[0] = *crc_table;
+    let mut k = 1;
+    while k < 16 {
+        let mut i = 0;
+        while i < 256 {
+            let p = tables[k - 1][i];
+            tables[k][i] = (p >> 8) ^ crc_table[(p & 0xff) as usize];
+            i += 1;
+        }
+        k += 1;
+    }
+    tables
+}
+
+/// Given the current CRC, return the CRC including the
+/// bytes of the buffer, one byte at a time.
+#[inline(always)]
+fn update_crc_bytes(mut crc: u32, crc_table: &CRCTable, buf: &[u8]) -> u32 {
+    for &c in buf {
+        crc = update_crc(crc, crc_table, c);
+    }
+    crc
+}
+
+/// Little-endian word at the given offset of the buffer.
+#[inline(always)]
+fn le_word(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        buf[offset],
+        buf[offset + 1],
+        buf[offset + 2],
+        buf[offset + 3],
+    ])
+}
+
+/// Contribution of a word to a CRC when followed by `k`
+/// further bytes.
+#[inline(always)]
+fn slice_word(tables: &SliceTables, word: u32, k: usize) -> u32 {
+    tables[k + 3][(word & 0xff) as usize]
+        ^ tables[k + 2][((word >> 8) & 0xff) as usize]
+        ^ tables[k + 1][((word >> 16) & 0xff) as usize]
+        ^ tables[k][(word >> 24) as usize]
+}
+
+/// Given the current CRC, return the CRC including the
+/// bytes of the buffer, eight bytes at a time.
+fn update_crc_slice8(mut crc: u32, tables: &SliceTables, buf: &[u8]) -> u32 {
+    let mut blocks = buf.chunks_exact(8);
+    for block in &mut blocks {
+        crc = slice_word(tables, crc ^ le_word(block, 0), 4)
+            ^ slice_word(tables, le_word(block, 4), 0);
+    }
+    update_crc_bytes(crc, &tables[0], blocks.remainder())
+}
+
+/// Given the current CRC, return the CRC including the
+/// bytes of the buffer, sixteen bytes at a time.
+fn update_crc_slice16(mut crc: u32, tables: &SliceTables, buf: &[u8]) -> u32 {
+    let mut blocks = buf.chunks_exact(16);
+    for block in &mut blocks {
+        crc = slice_word(tables, crc ^ le_word(block, 0), 12)
+            ^ slice_word(tables, le_word(block, 4), 8)
+            ^ slice_word(tables, le_word(block, 8), 4)
+            ^ slice_word(tables, le_word(block, 12), 0);
+    }
+    update_crc_slice8(crc, tables, blocks.remainder())
+}
+
+/// Way of computing a non-rolling CRC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CrcKernel {
+    /// One byte at a time.
+    Bytewise,
+    /// Eight bytes at a time.
+    Slice8,
+    /// Sixteen bytes at a time.
+    Slice16,
+    /// Carry-less multiplication on x86_64 CPUs that
+    /// support it.
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
+    Pclmul,
+    /// The CRC32 instructions on AArch64 CPUs that support
+    /// them.
+    #[cfg(all(target_arch = "aarch64", feature = "std"))]
+    Arm,
+    /// The `crc32fast` crate, which computes the standard
+    /// CRC with SIMD where available.
+    #[cfg(feature = "crc32fast")]
+    Crc32Fast,
+}
+
+/// Shortest buffer for which slicing pays for the cache
+/// misses of its extra tables.
+const SLICE8_THRESHOLD: usize = 16;
+
+/// Shortest buffer for which sixteen-byte slicing pays.
+const SLICE16_THRESHOLD: usize = 256;
+
+/// Shortest buffer for which carry-less multiplication
+/// pays.
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+const PCLMUL_THRESHOLD: usize = 128;
+
+impl CrcKernel {
+    /// The fastest kernel for a buffer of the given length.
+    #[inline(always)]
+    pub(crate) fn select(len: usize) -> Self {
+        #[cfg(feature = "crc32fast")]
+        {
+            if INIT_CRC == !0 && len >= SLICE8_THRESHOLD {
+                return CrcKernel::Crc32Fast;
+            }
+        }
+        #[cfg(all(target_arch = "x86_64", feature = "std"))]
+        {
+            if len >= PCLMUL_THRESHOLD && pclmul::available() {
+                return CrcKernel::Pclmul;
+            }
+        }
+        #[cfg(all(target_arch = "aarch64", feature = "std"))]
+        {
+            if len >= SLICE8_THRESHOLD && armcrc::available() {
+                return CrcKernel::Arm;
+            }
+        }
+        if len >= SLICE16_THRESHOLD {
+            CrcKernel::Slice16
+        } else if len >= SLICE8_THRESHOLD {
+            CrcKernel::Slice8
+        } else {
+            CrcKernel::Bytewise
+        }
+    }
+
+    /// Given the current CRC, return the CRC including the
+    /// bytes of the buffer, using the standard CRC table.
+    pub(crate) fn update(self, crc: u32, buf: &[u8]) -> u32 {
+        match self {
+            CrcKernel::Bytewise => update_crc_bytes(crc, &CRC_TABLE, buf),
+            CrcKernel::Slice8 => update_crc_slice8(crc, &SLICE_TABLES, buf),
+            CrcKernel::Slice16 => update_crc_slice16(crc, &SLICE_TABLES, buf),
+            #[cfg(all(target_arch = "x86_64", feature = "std"))]
+            CrcKernel::Pclmul => {
+                assert!(pclmul::available(), "PCLMULQDQ not available");
+                if buf.len() < 64 {
+                    return update_crc_slice8(crc, &SLICE_TABLES, buf);
+                }
+                // Safety: the CPU support was checked above.
+                let (crc, tail) = unsafe { pclmul::update(crc, buf) };
+                update_crc_bytes(crc, &CRC_TABLE, tail)
+            },
+            #[cfg(all(target_arch = "aarch64", feature = "std"))]
+            CrcKernel::Arm => {
+                assert!(armcrc::available(), "CRC32 instructions not available");
+                // Safety: the CPU support was checked above.
+                unsafe { armcrc::update(crc, buf) }
+            },
+            // `crc32fast` takes and returns finished CRCs.
+            #[cfg(feature = "crc32fast")]
+            CrcKernel::Crc32Fast => {
+                let mut hasher =
+                    crc32fast::Hasher::new_with_initial(finish_crc(crc));
+                hasher.update(buf);
+                finish_crc(hasher.finalize())
+            },
+        }
+    }
+}
+
 /// Calculate a standard (non-rolling) CRC of the given
-/// buffer.
+/// buffer. With the standard CRC table, long buffers are
+/// processed several bytes at a time; any other table is
+/// used a byte at a time.
 pub fn calc_crc(buf: &[u8], crc_table: &CRCTable) -> u32 {
-  let mut crc = INIT_CRC;
-  for c in buf {
-      crc = update_crc(crc, crc_table, *c);
-  }
-  finish_crc(crc)
+    finish_crc(update_crc_buf(INIT_CRC, crc_table, buf))
+}
+
+/// Calculate the standard CRC-32 of the given bytes at
+/// compile time, as `calc_crc()` with the standard table
+/// does at run time. This works a bit at a time, so is slow
+/// at run time.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// const TARGET: u32 = crc32_const(b"needle bytes");
+/// let context = RollingCRCContext::checksum_only();
+/// assert_eq!(TARGET, context.crc(b"needle bytes"));
+/// ```
+pub const fn crc32_const(bytes: &[u8]) -> u32 {
+    let mut crc = INIT_CRC;
+    let mut i = 0;
+    while i < bytes.len() {
+        crc ^= bytes[i] as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = (crc >> 1) ^ (POLY_CRC & (crc & 1).wrapping_neg());
+            bit += 1;
+        }
+        i += 1;
+    }
+    !crc
+}
+
+// The standard check value, at compile time.
+const _: () = assert!(crc32_const(b"123456789") == 0xCBF43926);
+
+/// Given the current CRC, return the CRC including the
+/// bytes of the buffer, as fast as `calc_crc()`.
+#[inline]
+pub(crate) fn update_crc_buf(crc: u32, crc_table: &CRCTable, buf: &[u8]) -> u32 {
+    if ptr::eq(crc_table, &CRC_TABLE) {
+        CrcKernel::select(buf.len()).update(crc, buf)
+    } else {
+        update_crc_bytes(crc, crc_table, buf)
+    }
 }
 
 // This construction allows computing the standard
 // (non-running) CRC table with a reasonable amount of
-// work. If table computation is a concern, and
-// INIT_CRC is fixed / know, this table could be
-// built once and compiled into the code.
+// work. The standard table is built at compile time; the
+// rolling tables are built at run time.
 //
 // I haven't analyzed this algorithm and don't understand
 // it, but it seems to work.
@@ -88,7 +373,7 @@ pub fn calc_crc(buf: &[u8], crc_table: &CRCTable) -> u32 {
 /// The "seed" here is only used by the fast running CRC
 /// table computation below: it is normal to pass the hash
 /// polynomial `CRC32_IEEE`.
-pub(crate) fn make_crc_table(crc_table: &mut CRCTable, seed: u32) {
+pub(crate) const fn make_crc_table(crc_table: &mut CRCTable, seed: u32) {
     let mut r = seed;
     crc_table[0] = 0;
     crc_table[128] = seed;
@@ -102,13 +387,26 @@ pub(crate) fn make_crc_table(crc_table: &mut CRCTable, seed: u32) {
 
     i = 2;
     while i < 256 {
-        for j in 1..i {
+        let mut j = 1;
+        while j < i {
             crc_table[i+j] = crc_table[i] ^ crc_table[j];
+            j += 1;
         }
         i <<= 1;
     }
 }
 
+#[test]
+fn test_unupdate_crc() {
+    let inverse_table = make_inverse_crc_table(&CRC_TABLE);
+    for &crc in &[0, INIT_CRC, 0x1234_5678, 0x8000_0001] {
+        for c in 0..=255 {
+            let updated = update_crc(crc, &CRC_TABLE, c);
+            assert_eq!(unupdate_crc(updated, &CRC_TABLE, &inverse_table, c), crc);
+        }
+    }
+}
+
 #[test]
 fn test_fast_crc_table() {
     // Fast CRC table construction
@@ -128,6 +426,134 @@ fn test_fast_crc_table() {
     assert_eq!(&fast_crc_table as &[u32], &crc_table as &[u32]);
 }
 
+#[test]
+fn test_calc_crc_slicing() {
+    let buf = test_bytes(0x9e37_79b9, (1 << 20) + 13);
+    let bytewise = |buf: &[u8], crc_table: &CRCTable| {
+        finish_crc(update_crc_bytes(INIT_CRC, crc_table, buf))
+    };
+
+    for len in 0..1024 {
+        for start in 0..8 {
+            let slice = &buf[start..start + len];
+            assert_eq!(calc_crc(slice, &CRC_TABLE),
+                       bytewise(slice, &CRC_TABLE));
+        }
+    }
+    assert_eq!(calc_crc(&buf, &CRC_TABLE), bytewise(&buf, &CRC_TABLE));
+    assert_eq!(calc_crc(&buf, &CRC_TABLE), crc::crc32::checksum_ieee(&buf));
+    for len in (0..300).chain(Some(buf.len())) {
+        let slice = &buf[..len];
+        assert_eq!(crc32_const(slice), calc_crc(slice, &CRC_TABLE));
+    }
+
+    // Slicing works for other tables too, although only
+    // the standard one gets it.
+    let mut crc_table = [0; 256];
+    make_crc_table(&mut crc_table, 0x1234_5678);
+    let tables = make_slice_tables(&crc_table);
+    for len in (0..1024).chain(Some(buf.len())) {
+        let slice = &buf[..len];
+        let expected = update_crc_bytes(INIT_CRC, &crc_table, slice);
+        assert_eq!(update_crc_slice8(INIT_CRC, &tables, slice), expected);
+        assert_eq!(update_crc_slice16(INIT_CRC, &tables, slice), expected);
+        assert_eq!(calc_crc(slice, &crc_table), finish_crc(expected));
+    }
+}
+
+#[test]
+fn test_crc_kernels() {
+    #[allow(unused_mut)]
+    let mut kernels = vec![
+        CrcKernel::Bytewise,
+        CrcKernel::Slice8,
+        CrcKernel::Slice16,
+        #[cfg(feature = "crc32fast")]
+        CrcKernel::Crc32Fast,
+    ];
+    #[cfg(all(target_arch = "x86_64", feature = "std"))]
+    {
+        if pclmul::available() {
+            kernels.push(CrcKernel::Pclmul);
+        }
+    }
+    #[cfg(all(target_arch = "aarch64", feature = "std"))]
+    {
+        if armcrc::available() {
+            kernels.push(CrcKernel::Arm);
+        }
+    }
+    let buf = test_bytes(0x9e37_79b9, 4096 + 16);
+    for len in 0..=4096 {
+        // Cycle the start through every alignment relative
+        // to the stride, paired with every tail length.
+        let start = (len / 16) % 16;
+        let slice = &buf[start..start + len];
+        let expected = finish_crc(CrcKernel::Bytewise.update(INIT_CRC, slice));
+        for &kernel in &kernels {
+            assert_eq!(finish_crc(kernel.update(INIT_CRC, slice)), expected,
+                       "{:?} {} {}", kernel, start, len);
+        }
+        assert_eq!(calc_crc(slice, &CRC_TABLE), expected);
+    }
+}
+
+#[cfg(all(target_arch = "x86_64", feature = "std"))]
+#[test]
+fn test_pclmul_kernel() {
+    if !pclmul::available() {
+        return;
+    }
+    let buf = test_bytes(0x9e37_79b9, (1 << 16) + 64);
+    let check = |slice: &[u8], init: u32| {
+        assert_eq!(CrcKernel::Pclmul.update(init, slice),
+                   update_crc_bytes(init, &CRC_TABLE, slice),
+                   "{:08x} {}", init, slice.len());
+    };
+    // Every length mod 64 through several fold-by-4
+    // iterations, at every misalignment.
+    for len in 0..=1024 {
+        for start in 0..16 {
+            check(&buf[start..start + len], INIT_CRC);
+        }
+    }
+    for len in (1024..buf.len() - 64).step_by(997) {
+        check(&buf[..len], INIT_CRC);
+        check(&buf[7..7 + len], 0x1234_5678);
+    }
+    check(&buf[3..], INIT_CRC);
+    assert_eq!(calc_crc(&buf, &CRC_TABLE), crc::crc32::checksum_ieee(&buf));
+
+    // Random lengths, offsets and incoming CRCs.
+    let mut rng = super::test_rng(12345);
+    let mut next = |n: usize| rng() as usize % n;
+    for _ in 0..2000 {
+        let start = next(64);
+        let len = next(buf.len() - start);
+        let init = next(1 << 31) as u32;
+        check(&buf[start..start + len], init);
+    }
+}
+
+#[cfg(all(target_arch = "aarch64", feature = "std"))]
+#[test]
+fn test_arm_kernel() {
+    if !armcrc::available() {
+        return;
+    }
+    let buf = test_bytes(0x9e37_79b9, (1 << 16) + 16);
+    for len in (0..=1024).chain((1024..buf.len() - 16).step_by(997)) {
+        for start in 0..8 {
+            let slice = &buf[start..start + len];
+            for &init in &[INIT_CRC, 0x1234_5678] {
+                assert_eq!(CrcKernel::Arm.update(init, slice),
+                           update_crc_bytes(init, &CRC_TABLE, slice));
+            }
+        }
+    }
+    assert_eq!(calc_crc(&buf, &CRC_TABLE), crc::crc32::checksum_ieee(&buf));
+}
+
 // This next bit deserves a careful explanation.
 //
 // For any messages X and Y of the same length,
@@ -156,50 +582,86 @@ fn test_fast_crc_table() {
 // rolling CRC. To "close" it, call `finish_crc()` above
 // on the current CRC.
 
+#[cfg(test)]
+thread_local! {
+    /// Number of byte updates done by
+    /// `make_rolling_crc_table_slow()` on this thread.
+    static SLOW_TABLE_UPDATES: std::cell::Cell<usize> =
+        const { std::cell::Cell::new(0) };
+}
+
+/// Given the current CRC, return the CRC including `n`
+/// zero bytes.
+#[inline(always)]
+fn extend_zeros_slow(mut crc: u32, crc_table: &CRCTable, n: usize) -> u32 {
+    #[cfg(test)]
+    SLOW_TABLE_UPDATES.with(|count| count.set(count.get() + n));
+    for _ in 0..n {
+        crc = update_crc(crc, crc_table, 0);
+    }
+    crc
+}
+
 /// Make a rolling CRC table for the given window size.
 /// This requires first computing the standard CRC table.
-fn make_rolling_crc_table_slow(winsize: usize,
-                               crc_table: &CRCTable,
-                               rolling_crc_table: &mut CRCTable)
+pub(crate) fn make_rolling_crc_table_slow(winsize: usize,
+                                          crc_table: &CRCTable,
+                                          rolling_crc_table: &mut CRCTable)
 {
-    for c in 0..=255 {
-        let mut x = INIT_CRC;
-        let mut y = INIT_CRC;
-        x = update_crc(x, crc_table, c);
-        y = update_crc(y, crc_table, 0);
-        for _ in 0..winsize-1 {
-            x = update_crc(x, &crc_table, 0);
-            y = update_crc(y, &crc_table, 0);
+    // The CRC of the window of zeros does not depend on
+    // the byte rolled out.
+    let y = extend_zeros_slow(INIT_CRC, crc_table, winsize);
+    let entry = |c: usize| {
+        let x = update_crc(INIT_CRC, crc_table, c as u8);
+        extend_zeros_slow(x, crc_table, winsize) ^ y
+    };
+    #[cfg(feature = "rayon")]
+    {
+        if winsize >= PARALLEL_TABLE_THRESHOLD {
+            rolling_crc_table.par_iter_mut()
+                .enumerate()
+                .for_each(|(c, e)| *e = entry(c));
+            return;
         }
-        x = update_crc(x, crc_table, 0);
-        rolling_crc_table[c as usize] = x ^ y;
+    }
+    for (c, e) in rolling_crc_table.iter_mut().enumerate() {
+        *e = entry(c);
     }
 }
 
+/// Smallest number of zero bytes for which table
+/// construction extends the CRCs for each byte value in
+/// parallel.
+#[cfg(feature = "rayon")]
+const PARALLEL_TABLE_THRESHOLD: usize = 1 << 14;
+
 /// Fast rolling CRC table construction algorithm; use only
 /// when INIT_CRC == 0.
 fn make_rolling_crc_table_fast(winsize: usize,
                                crc_table: &CRCTable,
                                rolling_crc_table: &mut CRCTable)
 {
-    assert!(INIT_CRC == 0);
+    assert_eq!(INIT_CRC, 0);
 
     let mut crc = INIT_CRC;
-    crc = update_crc(crc, &crc_table, 128);
+    crc = update_crc(crc, crc_table, 128);
     for _ in 0..winsize {
-        crc = update_crc(crc, &crc_table, 0);
+        crc = update_crc(crc, crc_table, 0);
     }
     crc = finish_crc(crc);
 
     make_crc_table(rolling_crc_table, crc);
 }
 
-/// Make a rolling CRC table for the given window size.
-/// This requires first computing the standard CRC table.
+/// Make a rolling CRC table for the given window size,
+/// which must be positive: the public constructors reject a
+/// zero window size before getting here. This requires
+/// first computing the standard CRC table.
 pub(crate) fn make_rolling_crc_table(winsize: usize,
                                      crc_table: &CRCTable,
                                      rolling_crc_table: &mut CRCTable)
 {
+    debug_assert!(winsize >= 1, "make_rolling_crc_table: zero window size");
     if INIT_CRC == 0 {
         make_rolling_crc_table_fast(winsize, crc_table, rolling_crc_table);
     } else {
@@ -207,6 +669,115 @@ pub(crate) fn make_rolling_crc_table(winsize: usize,
     }
 }
 
+/// Make rolling CRC tables for each of the given window
+/// sizes, which must be in ascending order. The zero-byte
+/// extensions behind each table are carried forward from
+/// one size to the next, so the cost is that of building
+/// just the largest table.
+#[cfg(feature = "alloc")]
+pub(crate) fn make_rolling_crc_tables(winsizes: &[usize],
+                                      crc_table: &CRCTable)
+                                      -> Vec<CRCTable>
+{
+    assert!(winsizes.windows(2).all(|w| w[0] <= w[1]),
+            "make_rolling_crc_tables: window sizes must be sorted");
+    // For each byte c, the open CRC of c followed by the
+    // current number of zero bytes; and the open CRC of that
+    // many zero bytes.
+    let mut xs = [0; 256];
+    for (c, x) in xs.iter_mut().enumerate() {
+        *x = update_crc(INIT_CRC, crc_table, c as u8);
+    }
+    let mut y = INIT_CRC;
+    let mut zeros = 0;
+    let mut tables = Vec::with_capacity(winsizes.len());
+    for &winsize in winsizes {
+        let mut rolling_crc_table = [0; 256];
+        if winsize >= 1 {
+            let n = winsize - zeros;
+            let extend = |x: &mut u32| *x = extend_zeros_slow(*x, crc_table, n);
+            #[cfg(feature = "rayon")]
+            {
+                if n >= PARALLEL_TABLE_THRESHOLD {
+                    xs.par_iter_mut().for_each(extend);
+                } else {
+                    xs.iter_mut().for_each(extend);
+                }
+            }
+            #[cfg(not(feature = "rayon"))]
+            xs.iter_mut().for_each(extend);
+            y = extend_zeros_slow(y, crc_table, n);
+            zeros = winsize;
+            for (entry, &x) in rolling_crc_table.iter_mut().zip(&xs[..]) {
+                *entry = x ^ y;
+            }
+        }
+        tables.push(rolling_crc_table);
+    }
+    tables
+}
+
+#[test]
+fn test_rolling_crc_table_slow() {
+    let winsize = 1000;
+    let mut rolling_crc_table = [0; 256];
+    SLOW_TABLE_UPDATES.with(|count| count.set(0));
+    make_rolling_crc_table_slow(winsize, &CRC_TABLE, &mut rolling_crc_table);
+    // One run of zeros per byte value, plus one shared:
+    // formerly two per byte value.
+    let updates = SLOW_TABLE_UPDATES.with(|count| count.get());
+    assert_eq!(updates, 257 * winsize);
+
+    // Check against the original construction.
+    for c in 0..=255u8 {
+        let mut x = update_crc(INIT_CRC, &CRC_TABLE, c);
+        let mut y = update_crc(INIT_CRC, &CRC_TABLE, 0);
+        for _ in 0..winsize - 1 {
+            x = update_crc(x, &CRC_TABLE, 0);
+            y = update_crc(y, &CRC_TABLE, 0);
+        }
+        x = update_crc(x, &CRC_TABLE, 0);
+        assert_eq!(rolling_crc_table[c as usize], x ^ y);
+    }
+}
+
+#[cfg(feature = "rayon")]
+#[test]
+fn test_parallel_rolling_crc_table() {
+    let winsize = PARALLEL_TABLE_THRESHOLD + 3;
+    let mut parallel = [0; 256];
+    make_rolling_crc_table_slow(winsize, &CRC_TABLE, &mut parallel);
+    let y = (0..winsize).fold(INIT_CRC, |y, _| update_crc(y, &CRC_TABLE, 0));
+    for (c, &entry) in parallel.iter().enumerate() {
+        let x = (0..winsize).fold(update_crc(INIT_CRC, &CRC_TABLE, c as u8),
+                                  |x, _| update_crc(x, &CRC_TABLE, 0));
+        assert_eq!(entry, x ^ y);
+    }
+    let winsizes = [10, winsize, winsize + 1, 3 * winsize];
+    let tables = make_rolling_crc_tables(&winsizes, &CRC_TABLE);
+    assert_eq!(&tables[1][..], &parallel[..]);
+    for (&winsize, table) in winsizes.iter().zip(&tables) {
+        let mut expected = [0; 256];
+        make_rolling_crc_table_slow(winsize, &CRC_TABLE, &mut expected);
+        assert_eq!(&table[..], &expected[..]);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_rolling_crc_tables() {
+    let winsizes = [0, 1, 1, 2, 16, 32, 64, 100, 1000, 4096];
+    let tables = make_rolling_crc_tables(&winsizes, &CRC_TABLE);
+    assert_eq!(tables.len(), winsizes.len());
+    for (&winsize, table) in winsizes.iter().zip(&tables) {
+        let mut expected = [0; 256];
+        if winsize >= 1 {
+            make_rolling_crc_table(winsize, &CRC_TABLE, &mut expected);
+        }
+        assert_eq!(&table[..], &expected[..], "window size {}", winsize);
+    }
+}
+
 #[test]
 fn test_rolling_crc_table() {
     // Make the base CRC table.
@@ -235,9 +806,7 @@ fn test_rolling_crc_table() {
 
         let test_size = 2 * winsize;
         // Make a buffer of "random" values.
-        let buffer: Vec<u8> = (0..winsize+test_size)
-            .map(|i| ((11 + i*31 + i/17) & 0xff) as u8)
-            .collect();
+        let buffer = test_bytes(0x2545_f491, winsize+test_size);
 
         // Calculate the CRC of the tail of the buffer using
         // the rolling hash and check for agreement.
@@ -0,0 +1,136 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! The RustCrypto `digest` traits for whole-message CRCs,
+//! so that the CRC can be used by code generic over hash
+//! functions.
+
+use super::{Crc32Digest, RollingCRCContext};
+#[cfg(test)]
+use super::test_bytes;
+
+use digest_traits::consts::U4;
+use digest_traits::{FixedOutput, FixedOutputReset, HashMarker, Output,
+                    OutputSizeUser, Reset, Update};
+
+lazy_static! {
+    /// Context for the standard CRC, with no byte map.
+    static ref STANDARD_CONTEXT: RollingCRCContext<'static> =
+        RollingCRCContext::checksum_only();
+}
+
+/// The standard CRC-32 as a RustCrypto hash function. The
+/// output is the finished CRC in big-endian byte order,
+/// the order in which it is usually printed.
+///
+/// This is not a cryptographic hash: it implements
+/// `HashMarker` only so that it can be used through
+/// `digest::Digest`.
+///
+/// # Examples
+///
+/// ```
+/// # extern crate digest;
+/// # extern crate rolling_crc;
+/// # use rolling_crc::*;
+/// use digest::Digest;
+///
+/// # fn main() {
+/// let output = Crc32Hasher::digest(b"hello world");
+/// assert_eq!(output[..], 0x0d4a1185u32.to_be_bytes());
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Crc32Hasher {
+    /// The CRC in progress.
+    inner: Crc32Digest<'static>,
+}
+
+impl Crc32Hasher {
+
+    /// Start a new CRC.
+    pub fn new() -> Self {
+        Self { inner: Crc32Digest::new(&STANDARD_CONTEXT) }
+    }
+}
+
+impl Default for Crc32Hasher {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HashMarker for Crc32Hasher {}
+
+impl Update for Crc32Hasher {
+    fn update(&mut self, data: &[u8]) {
+        self.inner.update(data);
+    }
+}
+
+impl OutputSizeUser for Crc32Hasher {
+    type OutputSize = U4;
+}
+
+impl FixedOutput for Crc32Hasher {
+    fn finalize_into(self, out: &mut Output<Self>) {
+        out.copy_from_slice(&self.inner.finalize().to_be_bytes());
+    }
+}
+
+impl FixedOutputReset for Crc32Hasher {
+    fn finalize_into_reset(&mut self, out: &mut Output<Self>) {
+        out.copy_from_slice(&self.inner.finalize_reset().to_be_bytes());
+    }
+}
+
+impl Reset for Crc32Hasher {
+    fn reset(&mut self) {
+        let _ = self.inner.finalize_reset();
+    }
+}
+
+#[test]
+fn test_crc32_hasher() {
+    use super::{calc_crc, CRC_TABLE};
+    use digest_traits::Digest;
+
+    fn hash_pieces<D: Digest>(pieces: &[&[u8]]) -> Vec<u8> {
+        let mut hasher = D::new();
+        for piece in pieces {
+            hasher.update(piece);
+        }
+        hasher.finalize().to_vec()
+    }
+
+    fn hash_twice<D: Digest + FixedOutputReset>(a: &[u8], b: &[u8])
+                                                -> (Vec<u8>, Vec<u8>)
+    {
+        let mut hasher = D::new();
+        Digest::update(&mut hasher, a);
+        let first = hasher.finalize_reset().to_vec();
+        Digest::update(&mut hasher, b);
+        (first, hasher.finalize().to_vec())
+    }
+
+    let bytes = test_bytes(0x9e37_79b9, 10_000);
+    let expected = |buf: &[u8]| {
+        calc_crc(buf, &CRC_TABLE).to_be_bytes().to_vec()
+    };
+
+    assert_eq!(<Crc32Hasher as Digest>::output_size(), 4);
+    assert_eq!(hash_pieces::<Crc32Hasher>(&[]), expected(b""));
+    assert_eq!(hash_pieces::<Crc32Hasher>(&[&bytes]), expected(&bytes));
+    let (a, b) = bytes.split_at(3333);
+    assert_eq!(hash_pieces::<Crc32Hasher>(&[a, b"", b]), expected(&bytes));
+    assert_eq!(hash_twice::<Crc32Hasher>(a, b), (expected(a), expected(b)));
+    assert_eq!(Crc32Hasher::digest(&bytes).to_vec(), expected(&bytes));
+
+    let mut hasher = Crc32Hasher::new();
+    Digest::update(&mut hasher, a);
+    Reset::reset(&mut hasher);
+    Digest::update(&mut hasher, b);
+    assert_eq!(hasher.finalize().to_vec(), expected(b));
+}
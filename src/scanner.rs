@@ -0,0 +1,104 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Incremental scanning of streams delivered in chunks.
+
+use super::{RollingCRC, RollingCRCContext};
+#[cfg(test)]
+use super::test_rng;
+
+/// A rolling CRC scan of a stream that arrives in chunks of
+/// arbitrary size, such as reads from standard input or a
+/// socket. The scanner keeps the rolling state and the
+/// stream position between chunks, so the windows reported
+/// are exactly those of a single scan of the concatenated
+/// chunks, with absolute stream offsets.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(5).unwrap();
+/// let mut scanner = ChunkScanner::new(&context);
+/// let mut crcs = Vec::new();
+/// for chunk in [&b"hello w"[..], b"", b"orld"].iter() {
+///     scanner.feed(chunk, |offset, crc| crcs.push((offset, crc)));
+/// }
+/// assert_eq!(scanner.finish(), 11);
+/// assert_eq!(crcs[6], (6, context.crc(b"world")));
+/// ```
+#[derive(Debug, Clone)]
+pub struct ChunkScanner<'a> {
+    /// Rolling state carried across chunks.
+    rolling_crc: RollingCRC<'a>,
+    /// Number of bytes fed so far.
+    position: u64,
+}
+
+impl<'a> ChunkScanner<'a> {
+
+    /// Start a new scan in the given context.
+    pub fn new(context: &'a RollingCRCContext<'a>) -> Self {
+        Self { rolling_crc: RollingCRC::new(context), position: 0 }
+    }
+
+    /// Scan the next chunk of the stream, calling `sink`
+    /// with the stream offset and CRC of each window ending
+    /// in this chunk, in order. Empty chunks are allowed.
+    pub fn feed<F>(&mut self, chunk: &[u8], mut sink: F)
+        where F: FnMut(u64, u32)
+    {
+        let window_size = self.rolling_crc.context.window_size as u64;
+        for &byte in chunk {
+            self.position += 1;
+            if let Some(crc) = self.rolling_crc.push(byte) {
+                sink(self.position - window_size, crc);
+            }
+        }
+    }
+
+    /// Number of stream bytes fed so far.
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// End the scan, returning the total length of the
+    /// stream.
+    pub fn finish(self) -> u64 {
+        self.position
+    }
+}
+
+#[test]
+fn test_chunk_scanner() {
+    let mut random = test_rng(0x9e37_79b9u32);
+    let bytes: Vec<u8> = (0..3000).map(|_| random() as u8).collect();
+    for window_size in 0..40 {
+        let context = RollingCRCContext::build(window_size);
+        let expected: Vec<(u64, u32)> = context.rolling_crcs(&bytes)
+            .map(|(offset, crc)| (offset as u64, crc))
+            .collect();
+        for _ in 0..5 {
+            let mut scanner = ChunkScanner::new(&context);
+            let mut crcs = Vec::new();
+            let mut start = 0;
+            while start < bytes.len() {
+                // Chunks of 0 to about twice the window size,
+                // including plenty of empty ones.
+                let len = (random() as usize % (2 * window_size + 3))
+                    .saturating_sub(1)
+                    .min(bytes.len() - start);
+                scanner.feed(&bytes[start..start + len], |offset, crc| {
+                    crcs.push((offset, crc));
+                });
+                start += len;
+                assert_eq!(scanner.position(), start as u64);
+            }
+            scanner.feed(b"", |_, _| panic!("window from empty chunk"));
+            assert_eq!(scanner.finish(), bytes.len() as u64);
+            assert_eq!(crcs, expected);
+        }
+    }
+}
@@ -0,0 +1,185 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! A run-time self test of the tables and rolling
+//! machinery.
+
+use super::combine::x2n_mod_poly;
+use super::gf2::clmul;
+use super::{test_vectors, update_crc, RollingCRCBuf, RollingCRCContext,
+            CRC_TABLE, INIT_CRC, POLY_CRC};
+
+use std::error;
+use std::fmt;
+
+/// Failure found by `self_test()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SelfTestError {
+    /// The CRC table differs from the classic bitwise
+    /// construction.
+    CrcTable {
+        /// Index of the first wrong entry.
+        index: usize,
+    },
+    /// A rolling CRC table differs from its derivation by
+    /// polynomial arithmetic.
+    RollingTable {
+        /// Window size of the table.
+        window_size: usize,
+        /// Index of the first wrong entry.
+        index: usize,
+    },
+    /// A rolling CRC differs from the direct CRC of its
+    /// window.
+    Rolling {
+        /// Window size of the rolling CRC.
+        window_size: usize,
+        /// Offset of the window.
+        offset: usize,
+    },
+    /// A CRC variant gives the wrong CRC of the catalog
+    /// check message `"123456789"`, or a whole-message
+    /// known answer in `test_vectors` is wrong.
+    CheckValue {
+        /// Catalog name of the variant, or name of the
+        /// vector.
+        name: &'static str,
+        /// Known CRC.
+        expected: u32,
+        /// CRC computed.
+        got: u32,
+    },
+}
+
+impl fmt::Display for SelfTestError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SelfTestError::CrcTable { index } =>
+                write!(f, "CRC table entry {} is wrong", index),
+            SelfTestError::RollingTable { window_size, index } =>
+                write!(f, "rolling CRC table entry {} is wrong \
+                           for window size {}",
+                       index, window_size),
+            SelfTestError::Rolling { window_size, offset } =>
+                write!(f, "rolling CRC at offset {} is wrong \
+                           for window size {}",
+                       offset, window_size),
+            SelfTestError::CheckValue { name, expected, got } =>
+                write!(f, "{} check value is {:08x}, expected {:08x}",
+                       name, got, expected),
+        }
+    }
+}
+
+impl error::Error for SelfTestError {}
+
+/// Window sizes whose rolling CRC tables are checked.
+const TABLE_WINDOW_SIZES: [usize; 12] =
+    [1, 2, 3, 4, 7, 8, 16, 31, 64, 255, 1000, 4096];
+
+/// Window sizes whose rolling CRCs are checked against
+/// direct CRCs.
+const ROLLING_WINDOW_SIZES: [usize; 6] = [1, 2, 5, 16, 64, 257];
+
+/// Length of the pseudo-random test data.
+const DATA_LEN: usize = 2048;
+
+/// Check the workings of this crate on the current
+/// platform: the CRC table against the classic bitwise
+/// construction, rolling CRC tables for a spread of window
+/// sizes against polynomial arithmetic, rolling CRCs of
+/// pseudo-random data against direct CRCs, and the known
+/// answers in `test_vectors`. This takes a few
+/// milliseconds and allocates nothing.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// assert_eq!(self_test(), Ok(()));
+/// ```
+pub fn self_test() -> Result<(), SelfTestError> {
+    check_crc_table()?;
+    check_rolling_tables()?;
+    check_rolling()?;
+    test_vectors::verify_all()
+}
+
+fn check_crc_table() -> Result<(), SelfTestError> {
+    for (i, &entry) in CRC_TABLE.iter().enumerate() {
+        let mut r = i as u32;
+        for _ in 0..8 {
+            r = (r >> 1) ^ (POLY_CRC & (r & 1).wrapping_neg());
+        }
+        if entry != r {
+            return Err(SelfTestError::CrcTable { index: i });
+        }
+    }
+    Ok(())
+}
+
+// Rolling out byte c from a w-byte window XORs in
+// `A^w (T(c) ^ I ^ A I)`, where A takes an open CRC through
+// a zero byte, T(c) is `CRC_TABLE[c]` and I is `INIT_CRC`.
+// A^w is multiplication by x^(8w) modulo the CRC
+// polynomial, which gives each entry independently of the
+// table construction, whichever way that goes.
+fn check_rolling_tables() -> Result<(), SelfTestError> {
+    let init = INIT_CRC ^ update_crc(INIT_CRC, &CRC_TABLE, 0);
+    for &window_size in &TABLE_WINDOW_SIZES {
+        let context = RollingCRCContext::new(window_size).unwrap();
+        let shift = x2n_mod_poly(window_size as u64, 3);
+        let wrong = context.rolling_crc_table.iter()
+            .zip(&CRC_TABLE[..])
+            .position(|(&entry, &t)| {
+                entry != clmul(shift, t ^ init, POLY_CRC)
+            });
+        if let Some(index) = wrong {
+            return Err(SelfTestError::RollingTable { window_size, index });
+        }
+    }
+    Ok(())
+}
+
+fn check_rolling() -> Result<(), SelfTestError> {
+    let mut data = [0; DATA_LEN];
+    let mut state = 0x9e37_79b9u32;
+    for b in data.iter_mut() {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        *b = state as u8;
+    }
+    let mut buffer = [0; 257];
+    for &window_size in &ROLLING_WINDOW_SIZES {
+        let context = RollingCRCContext::new(window_size).unwrap();
+        let mut roller = RollingCRCBuf::with_buffer(&context, &mut buffer);
+        let mut crcs = context.rolling_crcs(&data);
+        for (i, &b) in data.iter().enumerate() {
+            let pushed = roller.push(b);
+            if i + 1 < window_size {
+                continue;
+            }
+            let offset = i + 1 - window_size;
+            let direct = context.crc(&data[offset..=i]);
+            if pushed != Some(direct) || crcs.next() != Some((offset, direct)) {
+                return Err(SelfTestError::Rolling { window_size, offset });
+            }
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_self_test() {
+    assert_eq!(self_test(), Ok(()));
+    let error = SelfTestError::CheckValue {
+        name: "CRC-32/ISO-HDLC",
+        expected: 0xcbf4_3926,
+        got: 0,
+    };
+    assert_eq!(error.to_string(),
+               "CRC-32/ISO-HDLC check value is 00000000, expected cbf43926");
+}
@@ -0,0 +1,538 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! rsync-style file signatures.
+
+use super::{calc_crc, RollingCRCContext, CRC_TABLE};
+#[cfg(test)]
+use super::test_bytes;
+
+use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+use std::io::{self, Read, Write};
+
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
+
+/// CRCs of the consecutive `block_size`-byte blocks of the
+/// buffer. The last block may be short. The CRC of each
+/// full block is the rolling CRC of that window for window
+/// size `block_size`.
+///
+/// # Panics
+///
+/// Panics if `block_size` is 0.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let context = RollingCRCContext::new(4).unwrap();
+/// let crcs = block_crcs(b"abcdefghij", 4);
+/// assert_eq!(crcs, vec![context.crc(b"abcd"),
+///                       context.crc(b"efgh"),
+///                       context.crc(b"ij")]);
+/// ```
+pub fn block_crcs(buf: &[u8], block_size: usize) -> Vec<u32> {
+    assert!(block_size > 0, "block_crcs: block_size must be positive");
+    buf.chunks(block_size)
+        .map(|block| calc_crc(block, &CRC_TABLE))
+        .collect()
+}
+
+/// Weak and strong hashes of one block of a `Signature`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub struct BlockSignature {
+    /// CRC of the block, as computed by a rolling CRC with
+    /// the block size as window size.
+    pub weak: u32,
+    /// Strong hash of the block, as computed by the
+    /// caller-supplied hash function.
+    pub strong: [u8; 16],
+}
+
+#[cfg(feature = "defmt")]
+impl ::defmt::Format for BlockSignature {
+    fn format(&self, f: ::defmt::Formatter) {
+        ::defmt::write!(f, "BlockSignature {{ weak: {=u32:#010x}, \
+                            strong: {=[u8]:02x} }}",
+                        self.weak, &self.strong[..])
+    }
+}
+
+/// The signature of a file for rsync-style remote
+/// synchronization: the weak (rolling CRC) and strong
+/// hashes of each of its consecutive fixed-size blocks. The
+/// last block may be short.
+///
+/// With the `serde` feature enabled, signatures can be
+/// serialized for transmission. Deserializing checks that
+/// the block size is positive and that the number of
+/// blocks agrees with the length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "RawSignature"))]
+pub struct Signature {
+    /// Size of the blocks.
+    block_size: usize,
+    /// Length of the file.
+    len: u64,
+    /// Hashes of the blocks, in order.
+    blocks: Vec<BlockSignature>,
+}
+
+/// A `Signature` as deserialized, before its parts are
+/// checked against each other.
+#[cfg(feature = "serde")]
+#[derive(Deserialize)]
+struct RawSignature {
+    block_size: usize,
+    len: u64,
+    blocks: Vec<BlockSignature>,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<RawSignature> for Signature {
+    type Error = &'static str;
+
+    fn try_from(raw: RawSignature) -> Result<Self, Self::Error> {
+        let signature = Signature {
+            block_size: raw.block_size,
+            len: raw.len,
+            blocks: raw.blocks,
+        };
+        signature.check()?;
+        Ok(signature)
+    }
+}
+
+impl Signature {
+
+    /// Compute the signature of the bytes of the given
+    /// reader, using blocks of `block_size` bytes and the
+    /// given strong hash function. Only one block is held
+    /// in memory at a time.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `block_size` is 0.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// // A real application would use a cryptographic hash.
+    /// let strong = |block: &[u8]| {
+    ///     let mut hash = [0; 16];
+    ///     hash[0] = block.len() as u8;
+    ///     hash
+    /// };
+    /// let data = b"some file contents";
+    /// let signature = Signature::generate(&data[..], 8, strong).unwrap();
+    /// assert_eq!(signature.block_size(), 8);
+    /// assert_eq!(signature.blocks().len(), 3);
+    /// assert_eq!(signature.blocks()[2].strong[0], 2);
+    /// ```
+    pub fn generate<R, S>(mut reader: R, block_size: usize, mut strong: S)
+                          -> io::Result<Signature>
+        where R: Read, S: FnMut(&[u8]) -> [u8; 16]
+    {
+        assert!(block_size > 0, "Signature: block_size must be positive");
+        let mut buf = vec![0; block_size];
+        let mut blocks = Vec::new();
+        let mut total = 0;
+        loop {
+            let len = read_block(&mut reader, &mut buf)?;
+            if len == 0 {
+                break;
+            }
+            total += len as u64;
+            let block = &buf[..len];
+            blocks.push(BlockSignature {
+                weak: calc_crc(block, &CRC_TABLE),
+                strong: strong(block),
+            });
+            if len < block_size {
+                break;
+            }
+        }
+        Ok(Signature { block_size, len: total, blocks })
+    }
+
+    /// Size of the blocks.
+    pub fn block_size(&self) -> usize {
+        self.block_size
+    }
+
+    /// Hashes of the blocks, in order.
+    pub fn blocks(&self) -> &[BlockSignature] {
+        &self.blocks
+    }
+
+    /// Compute a delta from the file with this signature to
+    /// the bytes of the given reader: a sequence of
+    /// operations that rebuilds the new bytes from the old
+    /// file with `apply()`. A window of the block size is
+    /// rolled over the new bytes; windows whose CRC is the
+    /// weak hash of an old block are confirmed with the
+    /// strong hash function, which must be the one used to
+    /// make the signature. The short last block of the old
+    /// file, if any, can only match at the end of the new
+    /// bytes. The new bytes are read into memory. Fails with
+    /// `InvalidData` if the signature is inconsistent.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// # let strong = |block: &[u8]| {
+    /// #     let mut hash = [0; 16];
+    /// #     for (i, &b) in block.iter().enumerate() {
+    /// #         hash[i % 16] ^= b;
+    /// #     }
+    /// #     hash
+    /// # };
+    /// let old = b"0123456789abcdefghij";
+    /// let new = b"abcdeXX0123456789";
+    /// let signature = Signature::generate(&old[..], 5, strong).unwrap();
+    /// let ops = signature.delta(&new[..], strong).unwrap();
+    /// assert_eq!(ops, vec![
+    ///     Op::Copy { old_block_index: 2 },
+    ///     Op::Literal { data: b"XX".to_vec() },
+    ///     Op::Copy { old_block_index: 0 },
+    ///     Op::Copy { old_block_index: 1 },
+    /// ]);
+    /// let mut rebuilt = Vec::new();
+    /// signature.apply(&ops, old, &mut rebuilt).unwrap();
+    /// assert_eq!(&rebuilt[..], &new[..]);
+    /// ```
+    pub fn delta<R, S>(&self, mut new: R, mut strong: S) -> io::Result<Vec<Op>>
+        where R: Read, S: FnMut(&[u8]) -> [u8; 16]
+    {
+        self.check_data()?;
+        let mut data = Vec::new();
+        let _ = new.read_to_end(&mut data)?;
+        let block_size = self.block_size;
+        let mut index: HashMap<u32, Vec<usize>> = HashMap::new();
+        for (i, block) in self.blocks.iter().enumerate() {
+            if Some(i) != self.short_block() {
+                index.entry(block.weak).or_default().push(i);
+            }
+        }
+        let context = RollingCRCContext::build(block_size);
+        let mut ops = Vec::new();
+        // Start of the pending literal.
+        let mut literal = 0;
+        // Start of the current rolling scan.
+        let mut start = 0;
+        'scan: loop {
+            for (offset, crc) in context.rolling_crcs(&data[start..]) {
+                let offset = start + offset;
+                let candidates = match index.get(&crc) {
+                    Some(candidates) => candidates,
+                    None => continue,
+                };
+                let hash = strong(&data[offset..offset + block_size]);
+                let found = candidates.iter()
+                    .find(|&&i| self.blocks[i].strong == hash);
+                if let Some(&i) = found {
+                    push_literal(&mut ops, &data[literal..offset]);
+                    ops.push(Op::Copy { old_block_index: i });
+                    literal = offset + block_size;
+                    start = literal;
+                    continue 'scan;
+                }
+            }
+            break;
+        }
+        // Try the short last block against the end.
+        if let Some(last) = self.short_block() {
+            let short_len = self.short_len();
+            if data.len() - literal >= short_len {
+                let tail = &data[data.len() - short_len..];
+                let block = &self.blocks[last];
+                if calc_crc(tail, &CRC_TABLE) == block.weak
+                    && strong(tail) == block.strong
+                {
+                    let end = data.len() - short_len;
+                    push_literal(&mut ops, &data[literal..end]);
+                    ops.push(Op::Copy { old_block_index: last });
+                    return Ok(ops);
+                }
+            }
+        }
+        push_literal(&mut ops, &data[literal..]);
+        Ok(ops)
+    }
+
+    /// Rebuild new bytes from the old file with this
+    /// signature and a delta computed by `delta()`, writing
+    /// them to `out`. Fails with `InvalidData` if the
+    /// signature is inconsistent or the delta refers to a
+    /// block the old file does not have.
+    pub fn apply<W: Write>(&self, ops: &[Op], old: &[u8], mut out: W)
+                           -> io::Result<()>
+    {
+        self.check_data()?;
+        for op in ops {
+            match *op {
+                Op::Copy { old_block_index } => {
+                    let start = old_block_index.saturating_mul(self.block_size);
+                    if start >= old.len() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            format!("delta copies missing block {}",
+                                    old_block_index),
+                        ));
+                    }
+                    let end = old.len().min(start + self.block_size);
+                    out.write_all(&old[start..end])?;
+                },
+                Op::Literal { ref data } => out.write_all(data)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Length of the file with this signature.
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Is the file with this signature empty?
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Check that the block size is positive and that the
+    /// number of blocks agrees with the length, as they do
+    /// for a generated signature.
+    fn check(&self) -> Result<(), &'static str> {
+        if self.block_size == 0 {
+            return Err("signature block size is 0");
+        }
+        let nblocks = self.len.div_ceil(self.block_size as u64);
+        if self.blocks.len() as u64 != nblocks {
+            return Err("signature block count does not match its length");
+        }
+        Ok(())
+    }
+
+    /// `check()` as an I/O error.
+    fn check_data(&self) -> io::Result<()> {
+        self.check()
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Length of the short last block, or 0 if there is
+    /// none.
+    fn short_len(&self) -> usize {
+        (self.len % self.block_size as u64) as usize
+    }
+
+    /// Index of the short last block, if any.
+    fn short_block(&self) -> Option<usize> {
+        if self.short_len() > 0 {
+            Some(self.blocks.len() - 1)
+        } else {
+            None
+        }
+    }
+}
+
+/// One step of a delta between files. This can be computed
+/// using `Signature::delta()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(Serialize, Deserialize))]
+pub enum Op {
+    /// Copy the given block of the old file.
+    Copy {
+        /// Index of the block in the signature.
+        old_block_index: usize,
+    },
+    /// Insert new bytes.
+    Literal {
+        /// The bytes to insert.
+        data: Vec<u8>,
+    },
+}
+
+/// Add a literal for the given bytes to the delta, if
+/// there are any.
+fn push_literal(ops: &mut Vec<Op>, data: &[u8]) {
+    if !data.is_empty() {
+        ops.push(Op::Literal { data: data.to_vec() });
+    }
+}
+
+/// Fill as much of the buffer as the reader allows,
+/// returning the number of bytes read. This is short only
+/// at end of stream.
+pub(crate) fn read_block<R: Read>(reader: &mut R, buf: &mut [u8])
+                                  -> io::Result<usize>
+{
+    let mut len = 0;
+    while len < buf.len() {
+        match reader.read(&mut buf[len..]) {
+            Ok(0) => break,
+            Ok(n) => len += n,
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => (),
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(len)
+}
+
+/// A toy 128-bit hash standing in for a strong hash in
+/// tests.
+#[cfg(test)]
+pub(crate) fn test_strong_hash(block: &[u8]) -> [u8; 16] {
+    let mut hash = [0; 16];
+    let mut state = 0xcbf2_9ce4_8422_2325u64;
+    for (i, &b) in block.iter().enumerate() {
+        state ^= b as u64;
+        state = state.wrapping_mul(0x100_0000_01b3);
+        hash[i % 16] ^= (state >> 32) as u8;
+    }
+    hash[..8].copy_from_slice(&state.to_le_bytes());
+    hash
+}
+
+#[test]
+fn test_signature() {
+    use super::RollingCRCContext;
+
+    // Deliver a few bytes at a time, so that blocks span
+    // reads.
+    struct Dribble<'a>(&'a [u8]);
+
+    impl<'a> Read for Dribble<'a> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = buf.len().min(self.0.len()).min(7);
+            buf[..n].copy_from_slice(&self.0[..n]);
+            self.0 = &self.0[n..];
+            Ok(n)
+        }
+    }
+
+    let data = test_bytes(0x1234_5678, 10_000);
+    for &block_size in &[1, 64, 1000, 1024, 10_000, 20_000] {
+        let signature =
+            Signature::generate(Dribble(&data), block_size, test_strong_hash)
+            .unwrap();
+        assert_eq!(signature.block_size(), block_size);
+        assert_eq!(signature.len(), data.len() as u64);
+        let weak: Vec<u32> = signature.blocks().iter().map(|b| b.weak).collect();
+        assert_eq!(weak, block_crcs(&data, block_size));
+        for (block, sig) in data.chunks(block_size).zip(signature.blocks()) {
+            assert_eq!(sig.strong, test_strong_hash(block));
+        }
+
+        // Full-block weak hashes are rolling CRCs.
+        let context = RollingCRCContext::new(block_size).unwrap();
+        for (offset, crc) in context.rolling_crcs(&data) {
+            if offset % block_size == 0 {
+                assert_eq!(crc, weak[offset / block_size]);
+            }
+        }
+    }
+    let empty = Signature::generate(&b""[..], 16, test_strong_hash).unwrap();
+    assert!(empty.blocks().is_empty());
+    assert!(empty.is_empty());
+}
+
+#[test]
+fn test_delta() {
+    let block_size = 512;
+    let nblocks = 64;
+    let old = test_bytes(0xdead_beef, nblocks * block_size + 100);
+    let block = |i: usize| &old[i * block_size..(i + 1) * block_size];
+    let signature = Signature::generate(&old[..], block_size, test_strong_hash)
+        .unwrap();
+
+    // Insertion, deletion and reordering, keeping the
+    // short tail block at the end.
+    let mut new = Vec::new();
+    for i in 0..10 {
+        new.extend_from_slice(block(i));
+    }
+    new.extend_from_slice(b"inserted text that is not in the old file");
+    new.extend_from_slice(&old[10 * block_size..20 * block_size + 37]);
+    for i in (30..nblocks).rev() {
+        new.extend_from_slice(block(i));
+    }
+    for i in 21..25 {
+        new.extend_from_slice(block(i));
+    }
+    new.extend_from_slice(&old[nblocks * block_size..]);
+
+    let ops = signature.delta(&new[..], test_strong_hash).unwrap();
+    let mut rebuilt = Vec::new();
+    signature.apply(&ops, &old, &mut rebuilt).unwrap();
+    assert_eq!(rebuilt, new);
+    let literal: usize = ops.iter()
+        .map(|op| match *op {
+            Op::Literal { ref data } => data.len(),
+            Op::Copy { .. } => 0,
+        })
+        .sum();
+    // The inserted text and the partial block.
+    assert_eq!(literal, 41 + 37);
+    assert_eq!(ops.last(), Some(&Op::Copy { old_block_index: nblocks }));
+
+    // Unrelated, empty and identical new files.
+    for new in [&b"nothing in common"[..], b"", &old[..]].iter() {
+        let ops = signature.delta(*new, test_strong_hash).unwrap();
+        let mut rebuilt = Vec::new();
+        signature.apply(&ops, &old, &mut rebuilt).unwrap();
+        assert_eq!(&rebuilt[..], *new);
+    }
+
+    let bad = [Op::Copy { old_block_index: nblocks + 1 }];
+    assert!(signature.apply(&bad, &old, &mut Vec::new()).is_err());
+
+    // Inconsistent signatures, as might arrive from a peer,
+    // are errors rather than panics.
+    let block = BlockSignature { weak: 0, strong: [0; 16] };
+    let malformed = [
+        Signature { block_size: 0, len: 5, blocks: vec![] },
+        Signature { block_size: 4, len: 5, blocks: vec![] },
+        Signature { block_size: 4, len: 4, blocks: vec![block; 2] },
+    ];
+    for signature in malformed.iter() {
+        let error = signature.delta(&new[..], test_strong_hash).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = signature.apply(&[], &old, &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_signature_serde() {
+    let data = b"serialize this signature, please";
+    let signature = Signature::generate(&data[..], 5, test_strong_hash)
+        .unwrap();
+    let json = serde_json::to_string(&signature).unwrap();
+    let decoded: Signature = serde_json::from_str(&json).unwrap();
+    assert_eq!(decoded, signature);
+
+    // Inconsistent signatures are rejected.
+    let block = r#"{"weak":0,"strong":[0,0,0,0,0,0,0,0,0,0,0,0,0,0,0,0]}"#;
+    let bad = [
+        r#"{"block_size":0,"len":5,"blocks":[]}"#.to_string(),
+        r#"{"block_size":4,"len":5,"blocks":[]}"#.to_string(),
+        format!(r#"{{"block_size":4,"len":4,"blocks":[{0},{0}]}}"#, block),
+    ];
+    for json in bad.iter() {
+        assert!(serde_json::from_str::<Signature>(json).is_err(), "{}", json);
+    }
+    let good = format!(r#"{{"block_size":4,"len":5,"blocks":[{0},{0}]}}"#,
+                       block);
+    let decoded: Signature = serde_json::from_str(&good).unwrap();
+    assert_eq!(decoded.blocks().len(), 2);
+}
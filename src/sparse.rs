@@ -0,0 +1,195 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Whole-file CRCs of sparse files, such as disk images,
+//! that skip over holes and runs of zeros rather than
+//! feeding every zero byte through the CRC.
+
+use super::{append_zeros, finish_crc, read_block, update_crc_buf,
+            CRC_TABLE, INIT_CRC};
+#[cfg(test)]
+use super::test_bytes;
+
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// Size of the buffer used to read data regions.
+const SPARSE_BUF_SIZE: usize = 1 << 16;
+
+/// Size of the blocks of a read buffer checked for zeros.
+/// An all-zero block is skipped over with `append_zeros()`.
+const ZERO_BLOCK_SIZE: usize = 4096;
+
+/// CRC of the file at the given path, as `calc_crc()` of
+/// its contents, computed without reading its holes.
+///
+/// Where the platform supports `SEEK_DATA` and `SEEK_HOLE`
+/// (Linux and Android), only the data regions of the file
+/// are read. Elsewhere, or where the filesystem does not
+/// support them, the whole file is read. Either way, blocks
+/// of zeros in the data read are detected, and the CRC is
+/// advanced over holes and zero blocks in time logarithmic
+/// in their length.
+pub fn crc_file_sparse(path: &Path) -> io::Result<u32> {
+    let mut file = File::open(path)?;
+    let len = file.metadata()?.len();
+    let mut buf = vec![0; SPARSE_BUF_SIZE];
+    let mut open = INIT_CRC;
+    let mut pos = 0;
+    while pos < len {
+        let (start, end) = match data_region(&file, pos, len) {
+            Some(region) => region,
+            None => break,
+        };
+        open = append_zeros(open, start - pos);
+        let _ = file.seek(SeekFrom::Start(start))?;
+        let mut region = (&mut file).take(end - start);
+        let (crc, nread) = crc_zero_runs(&mut region, open, &mut buf)?;
+        open = crc;
+        pos = start + nread;
+        if nread < end - start {
+            // The file was truncated under us.
+            return Ok(finish_crc(open));
+        }
+    }
+    Ok(finish_crc(append_zeros(open, len - pos)))
+}
+
+/// The next region of data in the file at or after `pos`,
+/// clipped to `len`, or `None` if the rest of the file is
+/// a hole. If the file's holes cannot be found, the rest of
+/// the file is treated as data.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+fn data_region(file: &File, pos: u64, len: u64) -> Option<(u64, u64)> {
+    use std::convert::TryFrom;
+    use std::os::unix::io::AsRawFd;
+
+    let fd = file.as_raw_fd();
+    let offset = match libc::off_t::try_from(pos) {
+        Ok(offset) => offset,
+        Err(_) => return Some((pos, len)),
+    };
+    let start = unsafe { libc::lseek(fd, offset, libc::SEEK_DATA) };
+    if start < 0 {
+        return match io::Error::last_os_error().raw_os_error() {
+            Some(libc::ENXIO) => None,
+            _ => Some((pos, len)),
+        };
+    }
+    let end = unsafe { libc::lseek(fd, start, libc::SEEK_HOLE) };
+    if end < 0 {
+        return Some((pos, len));
+    }
+    let (start, end) = (start as u64, end as u64);
+    if start >= len {
+        None
+    } else {
+        Some((start, end.min(len)))
+    }
+}
+
+/// The next region of data in the file at or after `pos`:
+/// without `SEEK_DATA`, the rest of the file.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+fn data_region(_file: &File, pos: u64, len: u64) -> Option<(u64, u64)> {
+    Some((pos, len))
+}
+
+/// Advance the given open CRC over the bytes of the
+/// reader, skipping over blocks of zeros. Returns the new
+/// open CRC and the number of bytes read.
+fn crc_zero_runs<R: Read>(reader: &mut R, mut open: u32, buf: &mut [u8])
+                          -> io::Result<(u32, u64)>
+{
+    let mut total = 0;
+    let mut zeros = 0;
+    loop {
+        let len = read_block(reader, buf)?;
+        for block in buf[..len].chunks(ZERO_BLOCK_SIZE) {
+            if block.iter().all(|&b| b == 0) {
+                zeros += block.len() as u64;
+            } else {
+                open = append_zeros(open, zeros);
+                zeros = 0;
+                open = update_crc_buf(open, &CRC_TABLE, block);
+            }
+        }
+        total += len as u64;
+        if len < buf.len() {
+            break;
+        }
+    }
+    Ok((append_zeros(open, zeros), total))
+}
+
+#[cfg(test)]
+fn test_sparse_contents() -> Vec<(u64, Vec<u8>)> {
+    vec![
+        (0, b"boot sector".to_vec()),
+        (5000, vec![0xff; 3]),
+        (1 << 20, test_bytes(0x1234_5678, 10_000)),
+        (3 << 20, vec![1; 4096]),
+        ((3 << 20) + 8192, b"end of data".to_vec()),
+    ]
+}
+
+#[test]
+fn test_crc_zero_runs() {
+    use super::calc_crc;
+    use std::io::Cursor;
+
+    let mut image = vec![0; 5 << 20];
+    for (offset, data) in test_sparse_contents() {
+        let offset = offset as usize;
+        image[offset..offset + data.len()].copy_from_slice(&data);
+    }
+    for &len in &[0, 1, 4095, 4096, 4097, 5000, 5003, 1 << 20, image.len()] {
+        for &buf_size in &[ZERO_BLOCK_SIZE, 1000, SPARSE_BUF_SIZE] {
+            let mut buf = vec![0; buf_size];
+            let mut cursor = Cursor::new(&image[..len]);
+            let (open, nread) =
+                crc_zero_runs(&mut cursor, INIT_CRC, &mut buf).unwrap();
+            assert_eq!(nread, len as u64);
+            assert_eq!(finish_crc(open), calc_crc(&image[..len], &CRC_TABLE),
+                       "{} {}", len, buf_size);
+        }
+    }
+}
+
+#[test]
+fn test_crc_file_sparse() {
+    use super::calc_crc;
+    use std::io::Write;
+
+    for &len in &[0, 100, 5 << 20] {
+        // Holes made by seeking past the end and by
+        // extending the file.
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        let mut image = vec![0; len];
+        for (offset, data) in test_sparse_contents() {
+            let end = (offset as usize + data.len()).min(len);
+            if offset as usize >= end {
+                continue;
+            }
+            let data = &data[..end - offset as usize];
+            let _ = file.seek(SeekFrom::Start(offset)).unwrap();
+            file.write_all(data).unwrap();
+            image[offset as usize..end].copy_from_slice(data);
+        }
+        file.as_file().set_len(len as u64).unwrap();
+        file.flush().unwrap();
+        assert_eq!(crc_file_sparse(file.path()).unwrap(),
+                   calc_crc(&image, &CRC_TABLE), "{}", len);
+    }
+
+    // Zeros that are written rather than holes.
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    let mut image = vec![0; 100_000];
+    image[99_999] = 1;
+    file.write_all(&image).unwrap();
+    assert_eq!(crc_file_sparse(file.path()).unwrap(),
+               calc_crc(&image, &CRC_TABLE));
+}
@@ -0,0 +1,155 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Canonical known answers for this crate's CRCs, for
+//! ports, fuzz harnesses and `self_test()` to check
+//! against. Unlike the generated `vectors` (with the
+//! `vectors` feature), these are fixed, small, and
+//! available without allocation.
+
+use super::{RollingCRCBuf, RollingCRCContext, SelfTestError};
+
+/// A whole-message known answer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrcVector {
+    /// Short description of the vector.
+    pub name: &'static str,
+    /// Message.
+    pub input: &'static [u8],
+    /// Finished CRC of the message.
+    pub crc: u32,
+}
+
+/// A rolling known answer: the CRC of every window of the
+/// input, in order of window start.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RollingVector {
+    /// Size of the calculation window.
+    pub window_size: usize,
+    /// Message.
+    pub input: &'static [u8],
+    /// Finished CRC of each window.
+    pub crcs: &'static [u32],
+}
+
+/// The catalog check message.
+pub const CHECK_INPUT: &[u8] = b"123456789";
+
+/// CRC of `CHECK_INPUT`: the catalog check value of
+/// CRC-32/ISO-HDLC.
+pub const CHECK_CRC: u32 = 0xcbf4_3926;
+
+/// CRC of `"hello world"`.
+pub const HELLO_WORLD_CRC: u32 = 0x0d4a_1185;
+
+/// The catalog check values of the built-in CRC variants,
+/// by catalog name: the CRC of `CHECK_INPUT` for each.
+pub static CATALOG: &[(&str, u32)] = &[("CRC-32/ISO-HDLC", CHECK_CRC)];
+
+/// Whole-message known answers.
+pub static CRC_VECTORS: &[CrcVector] = &[
+    CrcVector { name: "empty", input: b"", crc: 0 },
+    CrcVector { name: "check", input: CHECK_INPUT, crc: CHECK_CRC },
+    CrcVector {
+        name: "hello world",
+        input: b"hello world",
+        crc: HELLO_WORLD_CRC,
+    },
+];
+
+/// Rolling known answers.
+pub static ROLLING_VECTORS: &[RollingVector] = &[
+    RollingVector {
+        window_size: 5,
+        input: b"hello world",
+        crcs: &[
+            0x3610_a686, 0x7ec6_227f, 0xf37e_69ca, 0x7c9b_5f1c,
+            0x70a2_a112, 0xeb8d_fa26, 0x3a77_1143,
+        ],
+    },
+    RollingVector {
+        window_size: 4,
+        input: CHECK_INPUT,
+        crcs: &[
+            0x9be3_e0a3, 0xb0d2_832b, 0x8d33_9230, 0x4d0c_a3eb,
+            0x7e52_5607, 0x9dba_bf87,
+        ],
+    },
+];
+
+/// Largest window size in `ROLLING_VECTORS`.
+const MAX_WINDOW_SIZE: usize = 5;
+
+/// Check this crate against every known answer here: the
+/// catalog, the whole-message vectors, and the rolling
+/// vectors both over slices and byte by byte. This
+/// allocates nothing.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// assert_eq!(test_vectors::verify_all(), Ok(()));
+/// ```
+pub fn verify_all() -> Result<(), SelfTestError> {
+    let context = RollingCRCContext::checksum_only();
+    for &(name, expected) in CATALOG {
+        let got = context.crc(CHECK_INPUT);
+        if got != expected {
+            return Err(SelfTestError::CheckValue { name, expected, got });
+        }
+    }
+    for vector in CRC_VECTORS {
+        let got = context.crc(vector.input);
+        if got != vector.crc {
+            return Err(SelfTestError::CheckValue {
+                name: vector.name,
+                expected: vector.crc,
+                got,
+            });
+        }
+    }
+    let mut buffer = [0; MAX_WINDOW_SIZE];
+    for vector in ROLLING_VECTORS {
+        let window_size = vector.window_size;
+        let mismatch = |offset| {
+            Err(SelfTestError::Rolling { window_size, offset })
+        };
+        let context = RollingCRCContext::build(window_size);
+        let mut crcs = context.rolling_crcs(vector.input);
+        for (offset, &expected) in vector.crcs.iter().enumerate() {
+            if crcs.next() != Some((offset, expected)) {
+                return mismatch(offset);
+            }
+        }
+        if crcs.next().is_some() {
+            return mismatch(vector.crcs.len());
+        }
+        let mut roller = RollingCRCBuf::with_buffer(&context, &mut buffer);
+        let mut expected = vector.crcs.iter();
+        for (i, &b) in vector.input.iter().enumerate() {
+            if let Some(crc) = roller.push(b) {
+                let offset = i + 1 - window_size;
+                if expected.next() != Some(&crc) {
+                    return mismatch(offset);
+                }
+            }
+        }
+        if expected.next().is_some() {
+            return mismatch(vector.input.len());
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_verify_all() {
+    assert_eq!(verify_all(), Ok(()));
+    for vector in ROLLING_VECTORS {
+        assert!(vector.window_size <= MAX_WINDOW_SIZE);
+        assert_eq!(vector.crcs.len(),
+                   vector.input.len() - vector.window_size + 1);
+    }
+}
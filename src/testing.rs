@@ -0,0 +1,229 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Invariant-checking harness for the rolling CRC state
+//! machine, shared by the unit tests and the fuzz targets
+//! in `fuzz/`.
+
+use super::{RollingCRC, RollingCRCContext};
+#[cfg(test)]
+use super::test_rng;
+
+use alloc::vec::Vec;
+
+#[cfg(feature = "arbitrary")]
+use arbitrary::Arbitrary;
+
+/// One step of a `RollingCRCScript`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
+pub enum RollingCRCOp {
+    /// Push one byte.
+    Push(u8),
+    /// Push each of the bytes in turn.
+    PushSlice(Vec<u8>),
+    /// Carry on with a clone of the rolling CRC.
+    Clone,
+    /// Start over with a fresh rolling CRC.
+    Reset,
+    /// Roll the bytes through with `RollingCRC::prime()`.
+    Prime(Vec<u8>),
+    /// Roll the bytes through with `RollingCRC::iter()`.
+    Iter(Vec<u8>),
+}
+
+/// A script of operations on a rolling CRC with the given
+/// window size, to be run by `check_rolling_crc_script()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(Arbitrary))]
+pub struct RollingCRCScript {
+    /// Size of the calculation window.
+    pub window_size: u8,
+    /// Operations to perform, in order.
+    pub ops: Vec<RollingCRCOp>,
+}
+
+/// Check a `RollingCRC` against a model of its window.
+struct Checker<'a> {
+    context: &'a RollingCRCContext<'a>,
+    roller: RollingCRC<'a>,
+    /// Every byte pushed since the last reset.
+    seen: Vec<u8>,
+}
+
+impl<'a> Checker<'a> {
+
+    /// The CRC the rolling CRC should report now.
+    fn expected(&self) -> Option<u32> {
+        let window_size = self.context.window_size();
+        if window_size == 0 || self.seen.len() < window_size {
+            return None;
+        }
+        let start = self.seen.len() - window_size;
+        Some(self.context.crc(&self.seen[start..]))
+    }
+
+    /// Check that the window holds the latest bytes.
+    fn check_window(&self) {
+        let window_size = self.context.window_size();
+        let (first, second) = self.roller.window();
+        let window: Vec<u8> =
+            first.iter().chain(second).cloned().collect();
+        let start = if window_size == 0 {
+            self.seen.len()
+        } else {
+            self.seen.len().saturating_sub(window_size)
+        };
+        assert_eq!(window, &self.seen[start..], "window mismatch");
+    }
+
+    fn push(&mut self, byte: u8) -> Option<u32> {
+        let crc = self.roller.push(byte);
+        self.seen.push(byte);
+        assert_eq!(crc, self.expected(),
+                   "push mismatch at {}", self.seen.len());
+        crc
+    }
+
+    fn run(&mut self, op: &RollingCRCOp) {
+        match *op {
+            RollingCRCOp::Push(byte) => {
+                self.push(byte);
+            },
+            RollingCRCOp::PushSlice(ref bytes) => {
+                for &b in bytes {
+                    self.push(b);
+                }
+            },
+            RollingCRCOp::Clone => {
+                self.roller = self.roller.clone();
+            },
+            RollingCRCOp::Reset => {
+                self.roller = RollingCRC::new(self.context);
+                self.seen.clear();
+            },
+            RollingCRCOp::Prime(ref bytes) => {
+                let crc = self.roller.prime(bytes);
+                self.seen.extend_from_slice(bytes);
+                let expected = if bytes.is_empty() {
+                    None
+                } else {
+                    self.expected()
+                };
+                assert_eq!(crc, expected, "prime mismatch");
+            },
+            RollingCRCOp::Iter(ref bytes) => {
+                let roller = self.roller.clone();
+                let crcs: Vec<(u64, u32)> =
+                    roller.iter(bytes.iter().cloned()).collect();
+                let window_size = self.context.window_size();
+                let mut expected = Vec::new();
+                for &b in bytes {
+                    if let Some(crc) = self.push(b) {
+                        let start = self.seen.len() - window_size;
+                        expected.push((start as u64, crc));
+                    }
+                }
+                assert_eq!(crcs, expected, "iter mismatch");
+            },
+        }
+        self.check_window();
+    }
+}
+
+/// Run the script, checking after every operation that the
+/// rolling CRC agrees with a direct CRC of its window as
+/// tracked separately.
+///
+/// # Panics
+///
+/// Panics if any check fails.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// check_rolling_crc_script(&RollingCRCScript {
+///     window_size: 3,
+///     ops: vec![
+///         RollingCRCOp::PushSlice(b"hello".to_vec()),
+///         RollingCRCOp::Clone,
+///         RollingCRCOp::Iter(b" world".to_vec()),
+///     ],
+/// });
+/// ```
+pub fn check_rolling_crc_script(script: &RollingCRCScript) {
+    let context = RollingCRCContext::build(usize::from(script.window_size));
+    let mut checker = Checker {
+        context: &context,
+        roller: RollingCRC::new(&context),
+        seen: Vec::new(),
+    };
+    for op in &script.ops {
+        checker.run(op);
+    }
+}
+
+/// Check that `RollingCRC::iter()` over the bytes agrees
+/// with the slice fast path,
+/// `RollingCRCContext::rolling_crcs()`.
+///
+/// # Panics
+///
+/// Panics if they differ.
+pub fn check_iter_matches_slice(window_size: usize, bytes: &[u8]) {
+    let context = RollingCRCContext::build(window_size);
+    let from_iter: Vec<(u64, u32)> = RollingCRC::new(&context)
+        .iter(bytes.iter().cloned())
+        .collect();
+    let from_slice: Vec<(u64, u32)> = context.rolling_crcs(bytes)
+        .map(|(offset, crc)| (offset as u64, crc))
+        .collect();
+    assert_eq!(from_iter, from_slice);
+}
+
+#[test]
+fn test_rolling_crc_scripts() {
+    let mut next = test_rng(0x2545_f491u32);
+    for _ in 0..200 {
+        let window_size = (next() % 20) as u8;
+        let mut ops = Vec::new();
+        for _ in 0..20 {
+            let len = (next() % 40) as usize;
+            let bytes: Vec<u8> = (0..len).map(|_| next() as u8).collect();
+            let op = match next() % 6 {
+                0 => RollingCRCOp::Push(next() as u8),
+                1 => RollingCRCOp::PushSlice(bytes),
+                2 => RollingCRCOp::Clone,
+                3 => RollingCRCOp::Reset,
+                4 => RollingCRCOp::Prime(bytes),
+                _ => RollingCRCOp::Iter(bytes),
+            };
+            ops.push(op);
+        }
+        check_rolling_crc_script(&RollingCRCScript { window_size, ops });
+    }
+
+    let bytes: Vec<u8> = (0..500).map(|_| next() as u8).collect();
+    for window_size in 0..70 {
+        check_iter_matches_slice(window_size, &bytes);
+        check_iter_matches_slice(window_size, &bytes[..window_size / 2]);
+    }
+}
+
+#[cfg(feature = "arbitrary")]
+#[test]
+fn test_arbitrary_script() {
+    use arbitrary::Unstructured;
+
+    let data = super::test_bytes(0x9e37_79b9u32, 4096);
+    let mut input = Unstructured::new(&data);
+    while let Ok(script) = RollingCRCScript::arbitrary(&mut input) {
+        check_rolling_crc_script(&script);
+        if input.is_empty() {
+            break;
+        }
+    }
+}
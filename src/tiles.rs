@@ -0,0 +1,290 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Rolling CRCs of the rectangular tiles of a row-major
+//! byte image.
+
+// The CRC of a tile is the CRC of its rows concatenated.
+// By `combine()`, appending a w-byte row with CRC c to a
+// message with CRC t gives the CRC `M t ^ c`, where M is
+// multiplication by x^(8w) modulo the CRC polynomial. So
+// the CRC of the h-row tile whose rows have CRCs
+// c_y ... c_(y+h-1) is
+//
+//        T_y == M^(h-1) c_y ^ M^(h-2) c_(y+1) ^ ... ^ c_(y+h-1)
+//
+// and moving the tile down a row gives
+//
+//        T_(y+1) == M T_y ^ c_(y+h) ^ M^h c_y
+//
+// The row CRCs c come from rolling along each row. M and
+// M^h are fixed linear maps, so each is tabulated a byte of
+// its argument at a time, as for a CRC table.
+
+use super::combine::x2n_mod_poly;
+use super::gf2::clmul;
+use super::{RollingCRCContext, WindowSizeError, POLY_CRC};
+#[cfg(test)]
+use super::test_bytes;
+
+use alloc::vec::Vec;
+
+/// Table for multiplying a CRC by a fixed polynomial: entry
+/// `[i][b]` is the product with `b << 8 * i`.
+type MulTable = [[u32; 256]; 4];
+
+/// Make the table for multiplying by `p`.
+fn make_mul_table(p: u32) -> MulTable {
+    let mut table = [[0; 256]; 4];
+    for (i, row) in table.iter_mut().enumerate() {
+        for (b, entry) in row.iter_mut().enumerate() {
+            *entry = clmul(p, (b as u32) << (8 * i), POLY_CRC);
+        }
+    }
+    table
+}
+
+/// Multiply a CRC using a table from `make_mul_table()`.
+#[inline(always)]
+fn mul(table: &MulTable, crc: u32) -> u32 {
+    table[0][(crc & 0xff) as usize]
+        ^ table[1][((crc >> 8) & 0xff) as usize]
+        ^ table[2][((crc >> 16) & 0xff) as usize]
+        ^ table[3][(crc >> 24) as usize]
+}
+
+/// Data needed for rolling CRC calculation over the tiles
+/// of a row-major byte image.
+#[derive(Debug, Clone)]
+pub struct RollingCRCContext2D {
+    /// Context for rolling along a row.
+    context: RollingCRCContext<'static>,
+    /// Height of a tile in rows.
+    height: usize,
+    /// Length of an image row in bytes.
+    row_stride: usize,
+    /// Multiplication by x^(8 * width).
+    row_shift: MulTable,
+    /// Multiplication by x^(8 * width * height).
+    tile_shift: MulTable,
+}
+
+impl RollingCRCContext2D {
+
+    /// Make a context for tiles `width` bytes wide and
+    /// `height` rows high, in images whose rows are
+    /// `row_stride` bytes long.
+    ///
+    /// # Errors
+    ///
+    /// A width or height of 0 is an error.
+    pub fn new(width: usize, height: usize, row_stride: usize)
+               -> Result<Self, WindowSizeError>
+    {
+        if height == 0 {
+            return Err(WindowSizeError);
+        }
+        let context = RollingCRCContext::new(width)?;
+        let (width, tile_len) = (width as u64, (width * height) as u64);
+        Ok(Self {
+            context,
+            height,
+            row_stride,
+            row_shift: make_mul_table(x2n_mod_poly(width, 3)),
+            tile_shift: make_mul_table(x2n_mod_poly(tile_len, 3)),
+        })
+    }
+
+    /// Width of a tile in bytes.
+    pub fn width(&self) -> usize {
+        self.context.window_size()
+    }
+
+    /// Height of a tile in rows.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Length of an image row in bytes.
+    pub fn row_stride(&self) -> usize {
+        self.row_stride
+    }
+
+    /// An iterator over the CRCs of every tile of the
+    /// image, with the column and row of its top left
+    /// corner, by rows of tiles from the top and then by
+    /// columns from the left. The CRC of a tile is the CRC
+    /// of its rows concatenated. Bytes past the last whole
+    /// row of the image are ignored.
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// # use rolling_crc::*;
+    /// let image = b"abcd\
+    ///               efgh\
+    ///               ijkl";
+    /// let context = RollingCRCContext2D::new(2, 2, 4).unwrap();
+    /// let crcs: Vec<(usize, usize, u32)> = context.tile_crcs(image).collect();
+    /// assert_eq!(crcs.len(), 3 * 2);
+    /// let bytes = RollingCRCContext::checksum_only();
+    /// assert_eq!(crcs[0], (0, 0, bytes.crc(b"abef")));
+    /// assert_eq!(crcs[5], (2, 1, bytes.crc(b"ghkl")));
+    /// ```
+    pub fn tile_crcs<'c, 'i>(&'c self, image: &'i [u8]) -> TileCrcs<'c, 'i> {
+        let width = self.width();
+        let columns = (self.row_stride + 1).saturating_sub(width);
+        let rows = image.len().checked_div(self.row_stride).unwrap_or(0);
+        let mut tiles = TileCrcs {
+            context: self,
+            image,
+            columns,
+            rows,
+            row_crcs: Vec::new(),
+            tile_crcs: Vec::new(),
+            oldest: 0,
+            x: 0,
+            y: 0,
+        };
+        if columns == 0 || rows < self.height {
+            tiles.y = rows;
+            return tiles;
+        }
+        tiles.row_crcs = vec![0; columns * self.height];
+        tiles.tile_crcs = vec![0; columns];
+        for row in 0..self.height {
+            let start = row * columns;
+            tiles.row_crcs(row, start);
+            for x in 0..columns {
+                let row_crc = tiles.row_crcs[start + x];
+                let tile_crc = &mut tiles.tile_crcs[x];
+                *tile_crc = mul(&self.row_shift, *tile_crc) ^ row_crc;
+            }
+        }
+        tiles
+    }
+}
+
+/// An iterator over the positions and CRCs of the tiles of
+/// an image. This iterator can be created using
+/// `RollingCRCContext2D::tile_crcs()`.
+#[derive(Debug, Clone)]
+pub struct TileCrcs<'c, 'i> {
+    context: &'c RollingCRCContext2D,
+    image: &'i [u8],
+    /// Number of tile columns.
+    columns: usize,
+    /// Number of whole image rows.
+    rows: usize,
+    /// CRCs of the row windows of the current tile rows,
+    /// `columns` per image row, as a ring of `height` rows.
+    row_crcs: Vec<u32>,
+    /// CRC of the tile in each column of the current tile
+    /// row.
+    tile_crcs: Vec<u32>,
+    /// Index in the ring of the top row of the current
+    /// tiles.
+    oldest: usize,
+    /// Column of the next tile.
+    x: usize,
+    /// Row of the next tile.
+    y: usize,
+}
+
+impl<'c, 'i> TileCrcs<'c, 'i> {
+
+    /// Store the CRCs of the row windows of the given image
+    /// row at the given start in `row_crcs`.
+    fn row_crcs(&mut self, row: usize, start: usize) {
+        let stride = self.context.row_stride;
+        let bytes = &self.image[row * stride..(row + 1) * stride];
+        let row_crcs = &mut self.row_crcs[start..start + self.columns];
+        for (x, crc) in self.context.context.rolling_crcs(bytes) {
+            row_crcs[x] = crc;
+        }
+    }
+
+    /// Move the tiles down one row: roll the image row
+    /// below them in and their top row out.
+    fn next_row(&mut self) {
+        let context = self.context;
+        let columns = self.columns;
+        let start = self.oldest * columns;
+        let old_crcs = &self.row_crcs[start..start + columns];
+        for (tile_crc, &old_crc) in self.tile_crcs.iter_mut().zip(old_crcs) {
+            *tile_crc = mul(&context.row_shift, *tile_crc)
+                ^ mul(&context.tile_shift, old_crc);
+        }
+        self.row_crcs(self.y + context.height, start);
+        let new_crcs = &self.row_crcs[start..start + columns];
+        for (tile_crc, &new_crc) in self.tile_crcs.iter_mut().zip(new_crcs) {
+            *tile_crc ^= new_crc;
+        }
+        self.oldest = (self.oldest + 1) % context.height;
+        self.y += 1;
+        self.x = 0;
+    }
+}
+
+impl<'c, 'i> Iterator for TileCrcs<'c, 'i> {
+    type Item = (usize, usize, u32);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let height = self.context.height;
+        if self.y + height > self.rows {
+            return None;
+        }
+        if self.x == self.columns {
+            if self.y + height == self.rows {
+                self.y += 1;
+                return None;
+            }
+            self.next_row();
+        }
+        let x = self.x;
+        self.x += 1;
+        Some((x, self.y, self.tile_crcs[x]))
+    }
+}
+
+#[test]
+fn test_tile_crcs() {
+    let bytes = RollingCRCContext::checksum_only();
+    let image = test_bytes(0x0bad_cafe, 1000);
+    for &row_stride in &[1, 2, 5, 16, 33] {
+        for width in 1..7 {
+            for height in 1..7 {
+                let context =
+                    RollingCRCContext2D::new(width, height, row_stride)
+                    .unwrap();
+                for &len in &[0, row_stride * 3 + 1, 200, image.len()] {
+                    let image = &image[..len];
+                    let rows = len / row_stride;
+                    let mut expected = Vec::new();
+                    for y in 0..(rows + 1).saturating_sub(height) {
+                        for x in 0..(row_stride + 1).saturating_sub(width) {
+                            let tile: Vec<u8> = (y..y + height)
+                                .flat_map(|row| {
+                                    let start = row * row_stride + x;
+                                    image[start..start + width].to_vec()
+                                })
+                                .collect();
+                            expected.push((x, y, bytes.crc(&tile)));
+                        }
+                    }
+                    let mut tiles = context.tile_crcs(image);
+                    assert_eq!(tiles.by_ref().collect::<Vec<_>>(), expected,
+                               "{} {} {} {}",
+                               row_stride, width, height, len);
+                    assert_eq!(tiles.next(), None);
+                }
+            }
+        }
+    }
+    assert!(RollingCRCContext2D::new(0, 3, 10).is_err());
+    assert!(RollingCRCContext2D::new(3, 0, 10).is_err());
+    let context = RollingCRCContext2D::new(3, 3, 0).unwrap();
+    assert_eq!(context.tile_crcs(&image).next(), None);
+}
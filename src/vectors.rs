@@ -0,0 +1,282 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Reference test vectors for ports of the rolling CRC to
+//! other languages.
+//!
+//! `generate_vectors()` deterministically produces a set of
+//! vectors from a seed; `verify_vectors()` checks a vector
+//! file against this crate. The golden file
+//! `tests/vectors.json` holds the vectors for
+//! `DEFAULT_VECTOR_SEED`, and a test checks that they still
+//! regenerate exactly.
+//!
+//! # Format
+//!
+//! A vector file is a JSON object:
+//!
+//! ```text
+//! {"format":1,"algorithm":"CRC-32/ISO-HDLC","seed":1,"vectors":[
+//! {"window_size":4,"input":"68656c6c","crcs":[[0,478544099]]},
+//! ...
+//! ]}
+//! ```
+//!
+//! * `format`: version of this format, currently 1.
+//! * `algorithm`: catalog name of the CRC.
+//! * `seed`: seed the vectors were generated from.
+//! * `vectors`: the vectors, each with
+//!   * `window_size`: size of the rolling window, at
+//!     least 1;
+//!   * `input`: the input bytes, in lowercase hex;
+//!   * `crcs`: an `[offset, crc]` pair for every window
+//!     of the input, in order, where `offset` is the
+//!     position of the window start and `crc` is the CRC
+//!     of the window as an unsigned number. An input
+//!     shorter than the window has none.
+//!
+//! Each vector is on a line of its own, so that changes
+//! show up well in diffs.
+
+use super::RollingCRCContext;
+
+use serde::{Deserialize, Serialize};
+use std::error;
+use std::fmt;
+
+/// Version of the vector file format.
+pub const VECTOR_FORMAT: u32 = 1;
+
+/// Seed of the vectors in `tests/vectors.json`.
+pub const DEFAULT_VECTOR_SEED: u64 = 1;
+
+/// Catalog name of the CRC of the vectors.
+const ALGORITHM: &str = "CRC-32/ISO-HDLC";
+
+/// Window sizes of the generated vectors.
+const WINDOW_SIZES: [usize; 10] = [1, 2, 3, 4, 5, 8, 16, 31, 64, 100];
+
+/// Input lengths of the generated vectors for each window
+/// size, with `None` standing for a random length.
+const LENGTHS: [Option<usize>; 4] = [Some(0), Some(3), None, None];
+
+/// A set of test vectors, as stored in a vector file.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VectorSet {
+    /// Version of the vector file format.
+    pub format: u32,
+    /// Catalog name of the CRC.
+    pub algorithm: String,
+    /// Seed the vectors were generated from.
+    pub seed: u64,
+    /// The vectors.
+    pub vectors: Vec<TestVector>,
+}
+
+/// A test vector: the rolling CRCs of an input.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TestVector {
+    /// Size of the rolling window.
+    pub window_size: usize,
+    /// Input bytes, serialized as lowercase hex.
+    #[serde(with = "hex_bytes")]
+    pub input: Vec<u8>,
+    /// Offset and CRC of every window of the input.
+    pub crcs: Vec<(u64, u32)>,
+}
+
+impl TestVector {
+
+    /// The vector for the given window size and input.
+    pub fn new(window_size: usize, input: Vec<u8>) -> Self {
+        let context = RollingCRCContext::new(window_size).unwrap();
+        let crcs = context.rolling_crcs(&input)
+            .map(|(offset, crc)| (offset as u64, crc))
+            .collect();
+        TestVector { window_size, input, crcs }
+    }
+}
+
+impl VectorSet {
+
+    /// The vector file contents for this set: JSON with
+    /// each vector on a line of its own.
+    pub fn to_json(&self) -> String {
+        let mut json = format!(
+            "{{\"format\":{},\"algorithm\":{},\"seed\":{},\"vectors\":[\n",
+            self.format,
+            serde_json::to_string(&self.algorithm).unwrap(),
+            self.seed,
+        );
+        for (i, vector) in self.vectors.iter().enumerate() {
+            if i > 0 {
+                json.push_str(",\n");
+            }
+            json.push_str(&serde_json::to_string(vector).unwrap());
+        }
+        json.push_str("\n]}\n");
+        json
+    }
+}
+
+/// Serialization of byte strings as lowercase hex.
+mod hex_bytes {
+    use serde::de::{self, Deserialize, Deserializer};
+    use serde::ser::Serializer;
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S)
+                                    -> Result<S::Ok, S::Error>
+    {
+        let hex: String = bytes.iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        serializer.serialize_str(&hex)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Vec<u8>, D::Error>
+        where D: Deserializer<'de>
+    {
+        let hex = String::deserialize(deserializer)?;
+        if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(de::Error::invalid_value(de::Unexpected::Str(&hex),
+                                                &"a hex byte string"));
+        }
+        Ok((0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).unwrap())
+            .collect())
+    }
+}
+
+/// Generate the test vectors for the given seed: for each
+/// of a spread of window sizes, an empty input, a short
+/// input, and inputs of pseudo-random length up to a few
+/// windows, all of pseudo-random bytes from an xorshift64
+/// generator.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::vectors::*;
+/// let vectors = generate_vectors(DEFAULT_VECTOR_SEED);
+/// assert_eq!(verify_vectors(&vectors.to_json()),
+///            Ok(vectors.vectors.len()));
+/// ```
+pub fn generate_vectors(seed: u64) -> VectorSet {
+    // Xorshift64 has a fixed point at zero.
+    let mut state = seed | 1;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    let mut vectors = Vec::new();
+    for &window_size in &WINDOW_SIZES {
+        for &len in &LENGTHS {
+            let len = len.unwrap_or_else(|| {
+                (next() % (3 * window_size as u64 + 20)) as usize
+            });
+            let input = (0..len).map(|_| next() as u8).collect();
+            vectors.push(TestVector::new(window_size, input));
+        }
+    }
+    VectorSet {
+        format: VECTOR_FORMAT,
+        algorithm: ALGORITHM.to_string(),
+        seed,
+        vectors,
+    }
+}
+
+/// Failure of `verify_vectors()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VectorError {
+    /// The file is not a valid vector file.
+    Parse(String),
+    /// The file is of an unsupported format version or CRC
+    /// algorithm.
+    Unsupported,
+    /// The rolling CRCs of a vector are not those of this
+    /// crate.
+    Mismatch {
+        /// Index of the vector.
+        index: usize,
+    },
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            VectorError::Parse(ref message) =>
+                write!(f, "bad vector file: {}", message),
+            VectorError::Unsupported =>
+                write!(f, "unsupported vector format or algorithm"),
+            VectorError::Mismatch { index } =>
+                write!(f, "vector {} does not match", index),
+        }
+    }
+}
+
+impl error::Error for VectorError {}
+
+/// Check the vectors of a vector file against this crate,
+/// returning the number of vectors checked.
+pub fn verify_vectors(json: &str) -> Result<usize, VectorError> {
+    let set: VectorSet = serde_json::from_str(json)
+        .map_err(|e| VectorError::Parse(e.to_string()))?;
+    if set.format != VECTOR_FORMAT || set.algorithm != ALGORITHM {
+        return Err(VectorError::Unsupported);
+    }
+    for (index, vector) in set.vectors.iter().enumerate() {
+        if vector.window_size == 0 {
+            return Err(VectorError::Parse("window size 0".to_string()));
+        }
+        let expected = TestVector::new(vector.window_size,
+                                       vector.input.clone());
+        if vector.crcs != expected.crcs {
+            return Err(VectorError::Mismatch { index });
+        }
+    }
+    Ok(set.vectors.len())
+}
+
+/// Set `UPDATE_VECTORS` in the environment to regenerate
+/// the golden file instead.
+#[test]
+fn test_golden_vectors() {
+    let golden = include_str!("../tests/vectors.json");
+    let vectors = generate_vectors(DEFAULT_VECTOR_SEED);
+    if std::env::var_os("UPDATE_VECTORS").is_some() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/vectors.json");
+        std::fs::write(path, vectors.to_json()).unwrap();
+        return;
+    }
+    assert_eq!(vectors.to_json(), golden,
+               "tests/vectors.json is out of date");
+    assert_eq!(serde_json::from_str::<VectorSet>(golden).unwrap(), vectors);
+    assert_eq!(verify_vectors(golden), Ok(vectors.vectors.len()));
+}
+
+#[test]
+fn test_verify_vectors() {
+    let mut vectors = generate_vectors(7);
+    assert_ne!(vectors, generate_vectors(8));
+    assert_eq!(verify_vectors(&vectors.to_json()),
+               Ok(vectors.vectors.len()));
+
+    vectors.vectors[5].crcs[0].1 ^= 1;
+    assert_eq!(verify_vectors(&vectors.to_json()),
+               Err(VectorError::Mismatch { index: 5 }));
+    vectors.algorithm = "CRC-32C".to_string();
+    assert_eq!(verify_vectors(&vectors.to_json()),
+               Err(VectorError::Unsupported));
+    match verify_vectors("{\"format\":1}") {
+        Err(VectorError::Parse(_)) => (),
+        r => panic!("unexpected {:?}", r),
+    }
+    let bad_hex = r#"{"window_size":1,"input":"6","crcs":[]}"#;
+    assert!(serde_json::from_str::<TestVector>(bad_hex).is_err());
+}
@@ -0,0 +1,138 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! JavaScript bindings for WebAssembly, via `wasm-bindgen`.
+
+use super::{RollingCRC, RollingCRCContext};
+
+use wasm_bindgen::prelude::*;
+
+use std::mem::ManuallyDrop;
+
+/// A rolling CRC for use from JavaScript, owning its
+/// context. Exported to JavaScript as `WasmRollingCrc`.
+///
+/// Pushing a byte at a time crosses from JavaScript into
+/// WebAssembly for every byte; `scan()` takes a whole
+/// `Uint8Array` in one call and should be preferred for
+/// bulk data.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// let mut roller = WasmRollingCrc::new(5);
+/// let bytes = b"hello world";
+/// let last = bytes.iter().fold(None, |_, &b| roller.push(b));
+/// assert_eq!(last, Some(roller.crc_of(b"world")));
+/// assert_eq!(&roller.scan(bytes)[12..], &[6, last.unwrap()]);
+/// ```
+#[wasm_bindgen]
+pub struct WasmRollingCrc {
+    /// The rolling CRC, borrowing `*context`. Dropped by
+    /// hand, before the context is freed.
+    roller: ManuallyDrop<RollingCRC<'static>>,
+    /// The context, from `Box::into_raw()`: it is only ever
+    /// borrowed shared, so the borrow held by `roller`
+    /// stays valid however `self` moves. Freed on drop.
+    context: *mut RollingCRCContext<'static>,
+}
+
+#[wasm_bindgen]
+impl WasmRollingCrc {
+
+    /// Start a new rolling CRC with the given window size.
+    #[wasm_bindgen(constructor)]
+    pub fn new(window_size: usize) -> Self {
+        let context =
+            Box::into_raw(Box::new(RollingCRCContext::build(window_size)));
+        // The context is never mutated and is freed only
+        // after the roller is dropped.
+        let borrowed: &'static RollingCRCContext<'static> =
+            unsafe { &*context };
+        let roller = ManuallyDrop::new(RollingCRC::new(borrowed));
+        Self { roller, context }
+    }
+
+    /// The owned context.
+    fn context(&self) -> &RollingCRCContext<'static> {
+        unsafe { &*self.context }
+    }
+
+    /// Size of the calculation window.
+    #[wasm_bindgen(getter, js_name = windowSize)]
+    pub fn window_size(&self) -> usize {
+        self.context().window_size()
+    }
+
+    /// Roll a byte through this rolling CRC, returning the
+    /// CRC of the window it completes, or `undefined`
+    /// until the window has filled.
+    pub fn push(&mut self, byte: u8) -> Option<u32> {
+        self.roller.push(byte)
+    }
+
+    /// The CRC of the given bytes.
+    #[wasm_bindgen(js_name = crcOf)]
+    pub fn crc_of(&self, bytes: &[u8]) -> u32 {
+        self.context().crc(bytes)
+    }
+
+    /// The rolling CRCs of every window of the given bytes,
+    /// flattened into a `Uint32Array` of offset and CRC
+    /// pairs: `[offset0, crc0, offset1, crc1, ...]`. This
+    /// does not affect the state of `push()`.
+    pub fn scan(&self, bytes: &[u8]) -> Vec<u32> {
+        let mut result = Vec::with_capacity(2 * bytes.len());
+        for (offset, crc) in self.context().rolling_crcs(bytes) {
+            result.push(offset as u32);
+            result.push(crc);
+        }
+        result
+    }
+}
+
+impl Drop for WasmRollingCrc {
+    fn drop(&mut self) {
+        unsafe {
+            ManuallyDrop::drop(&mut self.roller);
+            drop(Box::from_raw(self.context));
+        }
+    }
+}
+
+// Natively a plain test; under wasm32-unknown-unknown run
+// by `wasm-bindgen-test`, so that the WebAssembly build is
+// checked against fixed values and the native code paths.
+#[cfg(test)]
+#[cfg_attr(not(target_arch = "wasm32"), test)]
+#[cfg_attr(target_arch = "wasm32", wasm_bindgen_test::wasm_bindgen_test)]
+fn test_wasm_rolling_crc() {
+    let vectors: [&[u8]; 4] = [
+        b"",
+        b"abc",
+        b"hello world",
+        b"the quick brown fox jumps over the lazy dog",
+    ];
+    // The CRC-32 check value.
+    assert_eq!(WasmRollingCrc::new(0).crc_of(b"123456789"), 0xcbf4_3926);
+    for window_size in 0..8 {
+        let context = RollingCRCContext::build(window_size);
+        for bytes in &vectors {
+            let mut roller = WasmRollingCrc::new(window_size);
+            assert_eq!(roller.window_size(), window_size);
+            assert_eq!(roller.crc_of(bytes), context.crc(bytes));
+            let mut native = RollingCRC::new(&context);
+            for &b in bytes.iter() {
+                assert_eq!(roller.push(b), native.push(b));
+            }
+            let expected: Vec<u32> = context
+                .rolling_crcs(bytes)
+                .flat_map(|(offset, crc)| vec![offset as u32, crc])
+                .collect();
+            assert_eq!(roller.scan(bytes), expected);
+        }
+    }
+}
@@ -0,0 +1,148 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! A writer that computes rolling CRCs of the bytes
+//! written through it.
+
+use super::{RollingCRC, RollingCRCContext};
+#[cfg(test)]
+use super::test_bytes;
+
+use std::fmt;
+use std::io::{self, Write};
+
+/// A writer that passes bytes through to an inner writer,
+/// rolling each byte accepted by the inner writer through
+/// a rolling CRC. The rolling CRC of each window completed
+/// is passed to a callback along with the offset of the
+/// window start in the output.
+///
+/// # Examples
+///
+/// ```
+/// # use rolling_crc::*;
+/// use std::io;
+///
+/// let context = RollingCRCContext::new(5).unwrap();
+/// let mut crcs = Vec::new();
+/// let mut writer = RollingCrcWriter::new(
+///     Vec::new(),
+///     &context,
+///     |offset, crc| crcs.push((offset, crc)),
+/// );
+/// io::copy(&mut &b"hello world"[..], &mut writer).unwrap();
+/// let (output, _) = writer.into_inner();
+/// assert_eq!(output, b"hello world");
+/// assert_eq!(crcs.len(), 7);
+/// assert_eq!(crcs[6], (6, context.crc(b"world")));
+/// ```
+pub struct RollingCrcWriter<'a, W, F> {
+    inner: W,
+    rolling_crc: RollingCRC<'a>,
+    on_window: F,
+}
+
+impl<'a, W, F> RollingCrcWriter<'a, W, F>
+    where W: Write, F: FnMut(u64, u32)
+{
+
+    /// Wrap the given writer, computing rolling CRCs in the
+    /// given context and passing each to `on_window`.
+    pub fn new(inner: W, context: &'a RollingCRCContext<'a>, on_window: F)
+               -> Self
+    {
+        Self { inner, rolling_crc: RollingCRC::new(context), on_window }
+    }
+
+    /// The inner writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// The rolling CRC of the bytes written so far.
+    pub fn rolling_crc(&self) -> &RollingCRC<'a> {
+        &self.rolling_crc
+    }
+
+    /// Unwrap this writer, returning the inner writer and
+    /// the final rolling CRC.
+    pub fn into_inner(self) -> (W, RollingCRC<'a>) {
+        (self.inner, self.rolling_crc)
+    }
+}
+
+impl<'a, W, F> Write for RollingCrcWriter<'a, W, F>
+    where W: Write, F: FnMut(u64, u32)
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.rolling_crc.push_each(&buf[..n], &mut self.on_window);
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<'a, W: fmt::Debug, F> fmt::Debug for RollingCrcWriter<'a, W, F> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("RollingCrcWriter")
+            .field("inner", &self.inner)
+            .field("rolling_crc", &self.rolling_crc)
+            .finish_non_exhaustive()
+    }
+}
+
+#[test]
+fn test_rolling_crc_writer() {
+    /// A writer accepting at most 7 bytes at a time.
+    struct Short(Vec<u8>);
+
+    impl Write for Short {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            let n = buf.len().min(7);
+            self.0.extend_from_slice(&buf[..n]);
+            Ok(n)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let bytes = test_bytes(0x9e37_79b9u32, 100_000);
+    for &window_size in &[1, 16, 1000] {
+        let context = RollingCRCContext::new(window_size).unwrap();
+        let expected: Vec<(u64, u32)> = context.rolling_crcs(&bytes)
+            .map(|(offset, crc)| (offset as u64, crc))
+            .collect();
+
+        let mut crcs = Vec::new();
+        let mut writer = RollingCrcWriter::new(
+            Vec::new(),
+            &context,
+            |offset, crc| crcs.push((offset, crc)),
+        );
+        io::copy(&mut &bytes[..], &mut writer).unwrap();
+        writer.flush().unwrap();
+        let (output, rolling_crc) = writer.into_inner();
+        assert_eq!(output, bytes);
+        assert_eq!(crcs, expected);
+        let (first, second) = rolling_crc.window();
+        assert_eq!([first, second].concat(),
+                   &bytes[bytes.len() - window_size..]);
+
+        let mut crcs = Vec::new();
+        let mut writer = RollingCrcWriter::new(
+            Short(Vec::new()),
+            &context,
+            |offset, crc| crcs.push((offset, crc)),
+        );
+        writer.write_all(&bytes).unwrap();
+        assert_eq!(writer.get_ref().0, bytes);
+        assert_eq!(crcs, expected);
+    }
+}
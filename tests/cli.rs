@@ -0,0 +1,135 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Run the `rcrc` tool on the fixtures in `tests/fixtures`.
+
+#![cfg(feature = "cli")]
+
+extern crate assert_cmd;
+extern crate rolling_crc;
+
+use assert_cmd::Command;
+use rolling_crc::*;
+
+use std::fmt::Write;
+use std::fs;
+
+const HELLO: &str = "tests/fixtures/hello.txt";
+const WORLD: &str = "tests/fixtures/world.txt";
+const RANDOM: &str = "tests/fixtures/random.bin";
+
+/// Run `rcrc` with the given arguments, checking that it
+/// succeeds, and return its output.
+fn rcrc(args: &[&str]) -> String {
+    let output = Command::cargo_bin("rcrc").unwrap()
+        .args(args)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8(output).unwrap()
+}
+
+/// Run `rcrc` with the given arguments and standard input,
+/// checking that it succeeds, and return its output.
+fn rcrc_stdin(args: &[&str], input: &[u8]) -> String {
+    let output = Command::cargo_bin("rcrc").unwrap()
+        .args(args)
+        .write_stdin(input)
+        .assert()
+        .success()
+        .get_output()
+        .stdout
+        .clone();
+    String::from_utf8(output).unwrap()
+}
+
+#[test]
+fn test_cli_crc() {
+    assert_eq!(rcrc(&["crc", HELLO, WORLD]),
+               format!("3797c962  {}\n3a771143  {}\n", HELLO, WORLD));
+}
+
+#[test]
+fn test_cli_scan() {
+    let output = rcrc(&["scan", "-w", "5", HELLO]);
+    assert!(output.starts_with("0 3610a686\n1 7ec6227f\n"));
+    let bytes = fs::read(RANDOM).unwrap();
+    let context = RollingCRCContext::new(64).unwrap();
+    let mut expected = String::new();
+    for (offset, crc) in context.rolling_crcs(&bytes) {
+        writeln!(expected, "{} {:08x}", offset, crc).unwrap();
+    }
+    assert_eq!(rcrc(&["scan", "-w", "64", RANDOM]), expected);
+
+    let mut expected = String::new();
+    for (i, crc) in block_crcs(&bytes, 4096).into_iter().enumerate() {
+        writeln!(expected, "{} {:08x}", i * 4096, crc).unwrap();
+    }
+    assert_eq!(rcrc(&["scan", "--blocks", "-w", "4096", RANDOM]), expected);
+}
+
+#[test]
+fn test_cli_find() {
+    assert_eq!(rcrc(&["find", WORLD, HELLO, RANDOM]),
+               format!("{}: 6\n{}: 16\n", HELLO, HELLO));
+}
+
+#[test]
+fn test_cli_chunk() {
+    let bytes = fs::read(RANDOM).unwrap();
+    let config = ChunkerConfig::new(1024).window_size(32).mask(0x3ff);
+    let chunker = Chunker::new(config);
+    let mut expected = String::new();
+    let mut total = 0;
+    for chunk in chunker.chunk_read(&bytes[..]) {
+        let chunk = chunk.unwrap();
+        writeln!(expected, "{} {} {:08x}",
+                 chunk.offset, chunk.len, chunk.crc).unwrap();
+        total += chunk.len;
+    }
+    assert_eq!(total, bytes.len() as u64);
+    assert_eq!(rcrc(&["chunk", "-w", "32", "--mask", "0x3ff", RANDOM]),
+               expected);
+    assert_eq!(rcrc(&["chunk", "-w", "32", "--mask", "1023", RANDOM]),
+               expected);
+}
+
+#[test]
+fn test_cli_stdin() {
+    let hello = fs::read(HELLO).unwrap();
+    assert_eq!(rcrc_stdin(&["crc", "-"], &hello), "3797c962  -\n");
+    assert_eq!(rcrc_stdin(&["find", WORLD, "-"], &hello), "-: 6\n-: 16\n");
+    let bytes = fs::read(RANDOM).unwrap();
+    let commands: [&[&str]; 3] = [
+        &["scan", "-w", "64"],
+        &["scan", "--blocks", "-w", "4096"],
+        &["chunk", "-w", "32", "--mask", "0x3ff"],
+    ];
+    for args in &commands {
+        let mut with_file = args.to_vec();
+        with_file.push(RANDOM);
+        let mut with_stdin = args.to_vec();
+        with_stdin.push("-");
+        assert_eq!(rcrc_stdin(&with_stdin, &bytes), rcrc(&with_file));
+    }
+}
+
+#[test]
+fn test_cli_errors() {
+    Command::cargo_bin("rcrc").unwrap()
+        .args(["crc", "tests/fixtures/missing"])
+        .assert()
+        .failure();
+    Command::cargo_bin("rcrc").unwrap()
+        .args(["scan", "--blocks", "-w", "0", HELLO])
+        .assert()
+        .failure();
+    Command::cargo_bin("rcrc").unwrap()
+        .args(["chunk", "--mask", "0xzz", HELLO])
+        .assert()
+        .failure();
+}
@@ -0,0 +1,92 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Log through the `defmt::Format` implementations with a
+//! global logger that just counts the encoded bytes, so
+//! that the implementations keep compiling and linking.
+
+#![cfg(feature = "defmt")]
+#![no_std]
+
+extern crate defmt;
+extern crate rolling_crc;
+
+use core::sync::atomic::{AtomicUsize, Ordering};
+use rolling_crc::*;
+
+static ENCODED: AtomicUsize = AtomicUsize::new(0);
+
+#[defmt::global_logger]
+struct CountingLogger;
+
+unsafe impl defmt::Logger for CountingLogger {
+    fn acquire() {}
+
+    unsafe fn flush() {}
+
+    unsafe fn release() {}
+
+    unsafe fn write(bytes: &[u8]) {
+        ENCODED.fetch_add(bytes.len(), Ordering::SeqCst);
+    }
+}
+
+defmt::timestamp!("{=u32}", 0);
+
+#[test]
+fn test_defmt_format() {
+    let context = RollingCRCContext::new(4).unwrap();
+    let mut fixed = RollingCRCFixed::<4>::new(&context);
+    for &b in b"hello" {
+        fixed.push(b);
+    }
+    let mut digest = Crc32Digest::new(&context);
+    digest.update(b"hello");
+
+    let before = ENCODED.load(Ordering::SeqCst);
+    defmt::println!("{} {} {} {}", context, fixed, digest, OpenCrc::new());
+    let after = ENCODED.load(Ordering::SeqCst);
+    assert!(after > before);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn test_defmt_format_std() {
+    let context = RollingCRCContext::new(4).unwrap();
+    let mut rolling = RollingCRC::new(&context);
+    defmt::println!("{}", rolling);
+    rolling.prime(b"hello");
+    let chunk = Chunk { offset: 0, len: 5, crc: context.crc(b"hello") };
+    let hit = DupHit {
+        source_id: 1,
+        offset: 2,
+        prior_source: 0,
+        prior_offset: 3,
+    };
+    let anchor = AnchorMatch { a_off: 0, b_off: 4, len: 16 };
+
+    let before = ENCODED.load(Ordering::SeqCst);
+    defmt::println!("{} {} {} {} {}",
+                    rolling, chunk, ChunkBoundary::from(chunk), hit, anchor);
+    let after = ENCODED.load(Ordering::SeqCst);
+    assert!(after > before);
+
+    let recent = RecentHit { current_offset: 9, earlier_offset: 2 };
+    let piece = BadPiece { index: 1, expected: 5, actual: 6 };
+    let page = BadPage { index: 3, expected: 7, actual: 8 };
+    let length = PieceLength::Long { overrun: 9 };
+    let entry = FingerprintEntry { offset: 4, crc: context.crc(b"hell") };
+    let follow = FollowMatch { offset: 10, signature: 0 };
+    let record = RecordCrc { offset: 0, len: 5, crc: context.crc(b"hello") };
+    let block = BlockSignature { weak: 1, strong: [2; 16] };
+    let progress = ScanProgress { bytes: 100, windows: 96, matches: 1 };
+    let stats = ScanStats::default();
+
+    let before = ENCODED.load(Ordering::SeqCst);
+    defmt::println!("{} {} {} {} {}", recent, piece, page, length, entry);
+    defmt::println!("{} {} {} {} {}", follow, record, block, progress, stats);
+    let after = ENCODED.load(Ordering::SeqCst);
+    assert!(after > before);
+}
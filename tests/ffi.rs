@@ -0,0 +1,25 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Run the C smoke test of the C API, compiled against
+//! `include/rolling_crc.h` by the build script.
+
+#![cfg(feature = "ffi")]
+
+extern crate rolling_crc;
+
+use std::os::raw::c_int;
+
+#[link(name = "rcrc_smoke", kind = "static")]
+extern "C" {
+    fn rcrc_smoke_test() -> c_int;
+}
+
+#[test]
+fn test_c_smoke() {
+    // Make sure the library, and so the C API, is linked.
+    let _ = rolling_crc::ffi::RCRC_OK;
+    assert_eq!(unsafe { rcrc_smoke_test() }, 0);
+}
@@ -0,0 +1,47 @@
+/*
+ * Copyright © 2018 Bart Massey
+ * [This program is licensed under the "MIT License"]
+ * Please see the file LICENSE in the source
+ * distribution of this software for license terms.
+ */
+
+/* Smoke test of the C API, run by tests/ffi.rs. Returns 0
+ * on success, or the number of the failed check. */
+
+#include <string.h>
+
+#include "rolling_crc.h"
+
+int rcrc_smoke_test(void) {
+    const char *text = "hello world";
+    size_t len = strlen(text);
+    const uint8_t *bytes = (const uint8_t *) text;
+    uint32_t crc = 0, window_crc = 0;
+    size_t i;
+    int status;
+
+    RcrcContext *ctx = rcrc_context_new(5);
+    if (!ctx)
+        return 1;
+    if (rcrc_crc(ctx, bytes, len, &crc) != RCRC_OK || crc != 0x0d4a1185)
+        return 2;
+    if (rcrc_crc(ctx, NULL, 1, &crc) != RCRC_NULL_POINTER)
+        return 3;
+
+    RcrcRoller *roller = rcrc_roller_new(ctx);
+    if (!roller)
+        return 4;
+    for (i = 0; i < len; i++) {
+        status = rcrc_push(roller, bytes[i], &crc);
+        if (status != (i >= 4))
+            return 5;
+    }
+    if (rcrc_crc(ctx, bytes + len - 5, 5, &window_crc) != RCRC_OK
+        || crc != window_crc)
+        return 6;
+
+    rcrc_roller_free(roller);
+    rcrc_context_free(ctx);
+    rcrc_context_free(NULL);
+    return 0;
+}
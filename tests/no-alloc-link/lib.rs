@@ -0,0 +1,45 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Link check for the allocation-free core: see
+//! `Cargo.toml`.
+
+#![no_std]
+
+extern crate rolling_crc;
+
+use core::panic::PanicInfo;
+use core::slice;
+use rolling_crc::*;
+
+#[panic_handler]
+fn panic(_info: &PanicInfo) -> ! {
+    loop {}
+}
+
+/// XOR of the CRCs of every 16-byte window of the given
+/// bytes, together with their whole-message CRC.
+///
+/// # Safety
+///
+/// `bytes` must point to `len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn no_alloc_link_check(bytes: *const u8, len: usize)
+                                             -> u32
+{
+    let bytes = slice::from_raw_parts(bytes, len);
+    let context = RollingCRCContext::new(16).unwrap();
+    let mut rolling_crc = RollingCRCFixed::<16>::new(&context);
+    let mut result = context.crc(bytes);
+    for &b in bytes {
+        result ^= rolling_crc.push(b).unwrap_or(0);
+    }
+    let mut buffer = [0; 16];
+    let mut buffered = RollingCRCBuf::with_buffer(&context, &mut buffer);
+    for &b in bytes {
+        result ^= buffered.push(b).unwrap_or(0);
+    }
+    result
+}
@@ -0,0 +1,78 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! The allocation-free core, checked with an allocator
+//! that counts the allocations made by each thread.
+
+extern crate rolling_crc;
+
+use rolling_crc::*;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+
+struct CountingAlloc;
+
+thread_local! {
+    static ALLOCATIONS: Cell<usize> = const { Cell::new(0) };
+}
+
+unsafe impl GlobalAlloc for CountingAlloc {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let _ = ALLOCATIONS.try_with(|n| n.set(n.get() + 1));
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAlloc = CountingAlloc;
+
+/// Number of allocations made by this thread while running
+/// the given function.
+fn allocations<F: FnOnce()>(f: F) -> usize {
+    let before = ALLOCATIONS.with(|n| n.get());
+    f();
+    ALLOCATIONS.with(|n| n.get()) - before
+}
+
+#[test]
+fn test_no_alloc() {
+    let bytes = [0x5a; 10_000];
+    let mut fold = [0; 256];
+    for (b, f) in fold.iter_mut().enumerate() {
+        *f = b as u8 & 0x7f;
+    }
+    let mut buffer = [0; 100];
+    let mut total = 0u32;
+    let n = allocations(|| {
+        let context = RollingCRCContext::new(32).unwrap();
+        let mut fixed = RollingCRCFixed::<32>::new(&context);
+        for &b in &bytes[..] {
+            total ^= fixed.push(b).unwrap_or(0);
+        }
+        let mapped = RollingCRCContext::with_byte_map(48, fold).unwrap();
+        let mut buffered = RollingCRCBuf::with_buffer(&mapped, &mut buffer);
+        for &b in &bytes[..] {
+            total ^= buffered.push(b).unwrap_or(0);
+        }
+        for (_, crc) in context.rolling_crcs(&bytes) {
+            total ^= crc;
+        }
+        total ^= context.crc(&bytes);
+        total ^= context.crc_vectored(&[&bytes[..10], &bytes[10..]]);
+        let mut digest = Crc32Digest::new(&context);
+        digest.update(&bytes);
+        total ^= combine(digest.finalize(), context.crc(b"x"), 1);
+        let mut data = *b"forge me....";
+        context.forge_at(&mut data, 8, 0x1234_5678).unwrap();
+        total ^= context.crc(&data);
+    });
+    assert_eq!(n, 0);
+    assert!(total != 0);
+}
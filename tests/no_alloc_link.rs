@@ -0,0 +1,31 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Build the `no_std` static library in
+//! `tests/no-alloc-link`, which links the allocation-free
+//! core without a global allocator.
+
+use std::env;
+use std::path::Path;
+use std::process::Command;
+
+#[test]
+fn test_no_alloc_link() {
+    let root = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let cargo = env::var_os("CARGO").unwrap_or_else(|| "cargo".into());
+    // A target directory of its own, so that the build does
+    // not wait on the lock held by this test's own build.
+    let output = Command::new(cargo)
+        .arg("build")
+        .arg("--manifest-path")
+        .arg(root.join("tests/no-alloc-link/Cargo.toml"))
+        .arg("--target-dir")
+        .arg(Path::new(env!("CARGO_TARGET_TMPDIR")).join("no-alloc-link"))
+        .output()
+        .unwrap();
+    assert!(output.status.success(),
+            "no-alloc-link failed to build:\n{}",
+            String::from_utf8_lossy(&output.stderr));
+}
@@ -0,0 +1,47 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! The core rolling CRC used from a `no_std` crate with
+//! `alloc`. Run with `--no-default-features --features
+//! alloc` to check that the core builds without `std`.
+
+#![cfg(feature = "alloc")]
+#![no_std]
+
+extern crate alloc;
+extern crate rolling_crc;
+
+use alloc::vec::Vec;
+use rolling_crc::*;
+
+#[test]
+fn test_no_std_push() {
+    let bytes = b"the quick brown fox jumps over the lazy dog";
+    let context = RollingCRCContext::new(8).unwrap();
+    let mut rolling_crc = RollingCRC::new(&context);
+    for (i, &b) in bytes.iter().enumerate() {
+        let crc = rolling_crc.push(b);
+        if i + 1 < 8 {
+            assert_eq!(crc, None);
+        } else {
+            assert_eq!(crc, Some(context.crc(&bytes[i + 1 - 8..=i])));
+        }
+    }
+}
+
+#[test]
+fn test_no_std_iter() {
+    let bytes: Vec<u8> = (0..1000u32).map(|i| (i * 37 + i / 7) as u8).collect();
+    let context = RollingCRCContext::new(16).unwrap();
+    let crcs: Vec<(usize, u32)> = context.rolling_crcs(&bytes).collect();
+    assert_eq!(crcs.len(), bytes.len() - 15);
+    for &(i, crc) in &crcs {
+        assert_eq!(crc, context.crc(&bytes[i..i + 16]));
+    }
+    let rolling_crc = RollingCRC::new(&context);
+    let mapped: Vec<(u64, u32)> =
+        rolling_crc.iter(bytes.iter().cloned()).collect();
+    assert!(mapped.iter().map(|&(i, crc)| (i as usize, crc)).eq(crcs));
+}
@@ -0,0 +1,70 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Property tests of the rolling and combining arithmetic.
+
+#![cfg(feature = "alloc")]
+
+extern crate proptest;
+extern crate rolling_crc;
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use rolling_crc::*;
+
+proptest! {
+    #[test]
+    fn rolling_equals_direct(bytes in vec(any::<u8>(), 0..600),
+                             window_size in 0..80usize)
+    {
+        let context = match RollingCRCContext::new(window_size) {
+            Ok(context) => context,
+            Err(_) => RollingCRCContext::checksum_only(),
+        };
+        let mut roller = RollingCRC::new(&context);
+        let mut rolled = context.rolling_crcs(&bytes);
+        for (i, &b) in bytes.iter().enumerate() {
+            let crc = roller.push(b);
+            if window_size == 0 || i + 1 < window_size {
+                prop_assert_eq!(crc, None);
+                continue;
+            }
+            let start = i + 1 - window_size;
+            let direct = context.crc(&bytes[start..=i]);
+            prop_assert_eq!(crc, Some(direct));
+            prop_assert_eq!(rolled.next(), Some((start, direct)));
+        }
+        prop_assert_eq!(rolled.next(), None);
+    }
+
+    #[test]
+    fn combine_associative(a in vec(any::<u8>(), 0..100),
+                           b in vec(any::<u8>(), 0..100),
+                           c in vec(any::<u8>(), 0..100))
+    {
+        let context = RollingCRCContext::checksum_only();
+        let (crc_a, crc_b, crc_c) =
+            (context.crc(&a), context.crc(&b), context.crc(&c));
+        let (len_b, len_c) = (b.len() as u64, c.len() as u64);
+        let left = combine(combine(crc_a, crc_b, len_b), crc_c, len_c);
+        let crc_bc = combine(crc_b, crc_c, len_c);
+        let right = combine(crc_a, crc_bc, len_b + len_c);
+        prop_assert_eq!(left, right);
+        let whole: Vec<u8> = [&a[..], &b[..], &c[..]].concat();
+        prop_assert_eq!(left, context.crc(&whole));
+    }
+
+    #[test]
+    fn strip_prefix_inverts_combine(
+        prefix in vec(any::<u8>(), 0..100),
+        suffix in vec(any::<u8>(), 0..100))
+    {
+        let context = RollingCRCContext::checksum_only();
+        let len = suffix.len() as u64;
+        let whole = combine(context.crc(&prefix), context.crc(&suffix), len);
+        prop_assert_eq!(context.strip_prefix(whole, &prefix, len),
+                        context.crc(&suffix));
+    }
+}
@@ -0,0 +1,75 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Round trips of the result types through `serde_json`,
+//! pinning down their wire format.
+
+#![cfg(feature = "serde")]
+
+extern crate rolling_crc;
+extern crate serde;
+extern crate serde_json;
+
+use rolling_crc::*;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt::Debug;
+use std::time::Duration;
+
+/// Check that `value` serializes to `json` and back.
+fn round_trip<T>(value: T, json: &str)
+    where T: Serialize + DeserializeOwned + PartialEq + Debug
+{
+    assert_eq!(serde_json::to_string(&value).unwrap(), json);
+    assert_eq!(serde_json::from_str::<T>(json).unwrap(), value);
+}
+
+#[test]
+fn test_serde_wire_format() {
+    round_trip(Chunk { offset: 10, len: 20, crc: 0xcbf4_3926 },
+               r#"{"offset":10,"len":20,"crc":3421780262}"#);
+    round_trip(ChunkBoundary { offset: 10, len: 20, crc: 1 },
+               r#"{"offset":10,"len":20,"crc":1}"#);
+    round_trip(DupHit {
+                   source_id: 1,
+                   offset: 2,
+                   prior_source: 3,
+                   prior_offset: 4,
+               },
+               concat!(r#"{"source_id":1,"offset":2,"#,
+                       r#""prior_source":3,"prior_offset":4}"#));
+    round_trip(RecentHit { current_offset: 9, earlier_offset: 1 },
+               r#"{"current_offset":9,"earlier_offset":1}"#);
+    round_trip(AnchorMatch { a_off: 1, b_off: 2, len: 3 },
+               r#"{"a_off":1,"b_off":2,"len":3}"#);
+    round_trip(DuplicateGroup { crc: 7, runs: vec![0..1, 5..8] },
+               concat!(r#"{"crc":7,"runs":[{"start":0,"end":1},"#,
+                       r#"{"start":5,"end":8}]}"#));
+    round_trip(MaskStats { expected_chunk_size: 4096.0, chi_squared: 0.5 },
+               r#"{"expected_chunk_size":4096.0,"chi_squared":0.5}"#);
+    round_trip(FingerprintEntry { offset: 100, crc: 42 },
+               r#"{"offset":100,"crc":42}"#);
+    round_trip(ScanProgress { bytes: 1, windows: 2, matches: 3 },
+               r#"{"bytes":1,"windows":2,"matches":3}"#);
+    round_trip(ScanStats {
+                   bytes: 1,
+                   windows: 2,
+                   matches: 3,
+                   elapsed: Duration::from_millis(1500),
+               },
+               concat!(r#"{"bytes":1,"windows":2,"matches":3,"#,
+                       r#""elapsed":{"secs":1,"nanos":500000000}}"#));
+    round_trip(BadPage { index: 3, expected: 1, actual: 2 },
+               r#"{"index":3,"expected":1,"actual":2}"#);
+    round_trip(PieceReport {
+                   good: vec![0, 2],
+                   bad: vec![BadPiece { index: 1, expected: 5, actual: 6 }],
+                   length: PieceLength::Short { missing: 2 },
+               },
+               concat!(r#"{"good":[0,2],"bad":[{"index":1,"expected":5,"#,
+                       r#""actual":6}],"length":{"Short":{"missing":2}}}"#));
+    round_trip(PieceLength::Exact, r#""Exact""#);
+    round_trip(PieceLength::Long { overrun: 9 }, r#"{"Long":{"overrun":9}}"#);
+}
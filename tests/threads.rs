@@ -0,0 +1,85 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Check that contexts and rolling states may be shared
+//! and moved between threads.
+
+#![cfg(feature = "std")]
+
+extern crate rolling_crc;
+
+use rolling_crc::*;
+
+use std::thread;
+
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn test_send_sync() {
+    assert_send_sync::<RollingCRCContext<'static>>();
+    assert_send_sync::<RollingCRC<'static>>();
+    assert_send_sync::<RollingCRCFixed<'static, 16>>();
+    assert_send_sync::<RollingCRCBuf<'static, Vec<u8>>>();
+    assert_send_sync::<RollingCRCSlice<'static, 'static>>();
+    assert_send_sync::<Crc32Digest<'static>>();
+    assert_send_sync::<Finder>();
+    assert_send_sync::<Chunker>();
+    assert_send_sync::<CrcSet>();
+    assert_send_sync::<WindowSizeError>();
+}
+
+#[test]
+fn test_shared_context() {
+    let mut state = 0x9e37_79b9u32;
+    let bytes: Vec<u8> = (0..100_000)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        })
+        .collect();
+    let window_size = 48;
+    let context = RollingCRCContext::new(window_size).unwrap();
+    let expected: Vec<(usize, u32)> = context.rolling_crcs(&bytes).collect();
+
+    // Scan shards concurrently with the one context.
+    let nwindows = expected.len();
+    let threads = 8;
+    let crcs: Vec<(usize, u32)> = thread::scope(|scope| {
+        let shards: Vec<_> = (0..threads)
+            .map(|i| {
+                let start = nwindows * i / threads;
+                let end = nwindows * (i + 1) / threads;
+                let context = &context;
+                let bytes = &bytes[start..end + window_size - 1];
+                scope.spawn(move || {
+                    context.rolling_crcs(bytes)
+                        .map(|(offset, crc)| (start + offset, crc))
+                        .collect::<Vec<_>>()
+                })
+            })
+            .collect();
+        shards.into_iter()
+            .flat_map(|shard| shard.join().unwrap())
+            .collect()
+    });
+    assert_eq!(crcs, expected);
+
+    // Hand one rolling CRC from thread to thread.
+    let mut roller = RollingCRC::new(&context);
+    let mut last = None;
+    for chunk in bytes.chunks(10_007) {
+        let (moved, crc) = thread::scope(|scope| {
+            scope.spawn(move || {
+                let crc = roller.prime(chunk);
+                (roller, crc)
+            }).join().unwrap()
+        });
+        roller = moved;
+        last = crc.or(last);
+    }
+    assert_eq!(last, expected.last().map(|&(_, crc)| crc));
+}
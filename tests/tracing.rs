@@ -0,0 +1,180 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Check the spans and events emitted with the `tracing`
+//! feature. Run with `cargo test --features tracing`.
+
+#![cfg(feature = "tracing")]
+
+extern crate rolling_crc;
+extern crate tempfile;
+extern crate tracing;
+extern crate tracing_subscriber;
+
+use rolling_crc::*;
+
+use std::fmt;
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Subscriber};
+use tracing_subscriber::layer::{Context, Layer, SubscriberExt};
+use tracing_subscriber::registry::Registry;
+
+/// A span or event seen by the collector: its name (the
+/// message, for an event) and its fields as
+/// `name=value` strings.
+#[derive(Debug, Clone, Default)]
+struct Record {
+    name: String,
+    fields: Vec<String>,
+}
+
+impl Record {
+    fn field(&self, name: &str) -> Option<&str> {
+        let prefix = format!("{}=", name);
+        self.fields.iter()
+            .find(|f| f.starts_with(&prefix))
+            .map(|f| &f[prefix.len()..])
+    }
+}
+
+impl Visit for Record {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.name = format!("{:?}", value);
+        } else {
+            self.fields.push(format!("{}={:?}", field.name(), value));
+        }
+    }
+}
+
+/// A layer collecting every span and event.
+#[derive(Clone, Default)]
+struct Collector {
+    spans: Arc<Mutex<Vec<Record>>>,
+    events: Arc<Mutex<Vec<Record>>>,
+}
+
+impl Collector {
+    fn spans(&self, name: &str) -> Vec<Record> {
+        self.spans.lock().unwrap().iter()
+            .filter(|r| r.name == name)
+            .cloned()
+            .collect()
+    }
+
+    fn events(&self, name: &str) -> Vec<Record> {
+        self.events.lock().unwrap().iter()
+            .filter(|r| r.name == name)
+            .cloned()
+            .collect()
+    }
+}
+
+impl<S: Subscriber> Layer<S> for Collector {
+    fn on_new_span(&self, attrs: &Attributes, _id: &Id, _ctx: Context<S>) {
+        let mut record = Record {
+            name: attrs.metadata().name().to_string(),
+            fields: Vec::new(),
+        };
+        attrs.record(&mut record);
+        self.spans.lock().unwrap().push(record);
+    }
+
+    fn on_event(&self, event: &Event, _ctx: Context<S>) {
+        let mut record = Record::default();
+        event.record(&mut record);
+        self.events.lock().unwrap().push(record);
+    }
+}
+
+/// Run `f` with a collector as this thread's subscriber,
+/// returning the collector.
+fn collect<F: FnOnce()>(f: F) -> Collector {
+    let collector = Collector::default();
+    let subscriber = Registry::default().with(collector.clone());
+    tracing::subscriber::with_default(subscriber, f);
+    collector
+}
+
+#[test]
+fn test_table_spans() {
+    let collector = collect(|| {
+        RollingCRCContext::new(37).unwrap();
+        RollingCRCContext::with_window_sizes(&[5, 9]).unwrap();
+    });
+    let spans = collector.spans("rolling_table");
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].field("window_size"), Some("37"));
+    let spans = collector.spans("rolling_tables");
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].field("count"), Some("2"));
+    assert_eq!(spans[0].field("max_window_size"), Some("9"));
+    let built = collector.events("built rolling CRC table");
+    assert_eq!(built.len(), 1);
+    assert!(built[0].field("elapsed").is_some());
+    assert_eq!(collector.events("built rolling CRC tables").len(), 1);
+}
+
+#[test]
+fn test_cache_events() {
+    // A window size no other test uses, so that the first
+    // call misses.
+    let collector = collect(|| {
+        with_context(4093, |_| ());
+        with_context(4093, |_| ());
+    });
+    let misses = collector.events("context cache miss");
+    assert_eq!(misses.len(), 1);
+    assert_eq!(misses[0].field("window_size"), Some("4093"));
+    assert_eq!(collector.events("context cache hit").len(), 1);
+    assert_eq!(collector.spans("rolling_table").len(), 1);
+}
+
+#[test]
+fn test_search_spans() {
+    let haystack = b"abc".repeat(400);
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&haystack).unwrap();
+    let collector = collect(|| {
+        let found = find_in_file(file.path(), b"cab").unwrap();
+        assert_eq!(found.len(), 399);
+        let finder = Finder::new(b"cab");
+        finder.find_reader(&haystack[..]).unwrap();
+        finder.find_all_with_progress(&haystack, 0, |_| ());
+    });
+
+    let spans = collector.spans("find_in_file");
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].field("needle_len"), Some("3"));
+    assert!(spans[0].field("path").is_some());
+    assert!(!collector.spans("find_reader").is_empty());
+    let spans = collector.spans("find_all");
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].field("len"), Some("1200"));
+
+    let finished = collector.events("search finished");
+    assert!(finished.len() >= 3);
+    for event in &finished {
+        assert_eq!(event.field("bytes"), Some("1200"));
+        assert_eq!(event.field("matches"), Some("399"));
+    }
+}
+
+#[test]
+fn test_parallel_span() {
+    let context = RollingCRCContext::new(4).unwrap();
+    let collector = collect(|| {
+        scan_parallel(b"hello world", &context, 2, |_| ());
+    });
+    let spans = collector.spans("scan_parallel");
+    assert_eq!(spans.len(), 1);
+    assert_eq!(spans[0].field("len"), Some("11"));
+    assert_eq!(spans[0].field("window_size"), Some("4"));
+    assert_eq!(spans[0].field("threads"), Some("2"));
+}
@@ -0,0 +1,117 @@
+// Copyright © 2018 Bart Massey
+// [This program is licensed under the "MIT License"]
+// Please see the file LICENSE in the source
+// distribution of this software for license terms.
+
+//! Differential tests against zlib itself, to catch CRC
+//! convention mistakes (reflection, initial value, final
+//! XOR) that two Rust implementations might share. Run with
+//! `cargo test --features zlib-tests`.
+
+#![cfg(feature = "zlib-tests")]
+
+extern crate libz_sys;
+extern crate rolling_crc;
+
+use libz_sys::{uInt, uLong, z_off_t};
+use rolling_crc::*;
+
+/// zlib's CRC of `bytes` continued from the finished CRC
+/// `crc`.
+fn zlib_crc(crc: u32, bytes: &[u8]) -> u32 {
+    let crc = unsafe {
+        libz_sys::crc32(uLong::from(crc), bytes.as_ptr(), bytes.len() as uInt)
+    };
+    crc as u32
+}
+
+/// zlib's CRC of the concatenation of messages with CRCs
+/// `crc_a` and `crc_b`, the second of length `len_b`.
+fn zlib_combine(crc_a: u32, crc_b: u32, len_b: u64) -> u32 {
+    let crc = unsafe {
+        libz_sys::crc32_combine(uLong::from(crc_a),
+                                uLong::from(crc_b),
+                                len_b as z_off_t)
+    };
+    crc as u32
+}
+
+/// A pseudo-random number generator for test data.
+struct XorShift(u32);
+
+impl XorShift {
+    fn next(&mut self) -> u32 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 17;
+        self.0 ^= self.0 << 5;
+        self.0
+    }
+
+    fn bytes(&mut self, len: usize) -> Vec<u8> {
+        (0..len).map(|_| self.next() as u8).collect()
+    }
+}
+
+#[test]
+fn test_zlib_crc() {
+    let context = RollingCRCContext::checksum_only();
+    let mut rng = XorShift(0x9e37_79b9);
+    assert_eq!(context.crc(b""), zlib_crc(0, b""));
+    for _ in 0..500 {
+        let len = match rng.next() % 4 {
+            0 => rng.next() % 16,
+            1 => rng.next() % 256,
+            2 => rng.next() % 4096,
+            _ => rng.next() % 100_000,
+        } as usize;
+        let bytes = rng.bytes(len);
+        assert_eq!(context.crc(&bytes), zlib_crc(0, &bytes), "length {}", len);
+    }
+}
+
+#[test]
+fn test_zlib_combine() {
+    let context = RollingCRCContext::checksum_only();
+    let mut rng = XorShift(0x2545_f491);
+    for _ in 0..500 {
+        let crc_a = rng.next();
+        let crc_b = rng.next();
+        let len_b = match rng.next() % 3 {
+            0 => u64::from(rng.next() % 64),
+            1 => u64::from(rng.next()),
+            _ => (u64::from(rng.next()) << 8) | u64::from(rng.next() % 256),
+        };
+        assert_eq!(combine(crc_a, crc_b, len_b),
+                   zlib_combine(crc_a, crc_b, len_b),
+                   "length {}", len_b);
+    }
+    for _ in 0..100 {
+        let (len_a, len_b) = (rng.next() % 1000, rng.next() % 1000);
+        let a = rng.bytes(len_a as usize);
+        let b = rng.bytes(len_b as usize);
+        let (crc_a, crc_b) = (context.crc(&a), context.crc(&b));
+        let len_b = b.len() as u64;
+        assert_eq!(combine(crc_a, crc_b, len_b),
+                   zlib_combine(crc_a, crc_b, len_b));
+    }
+}
+
+#[test]
+fn test_zlib_resume() {
+    let context = RollingCRCContext::checksum_only();
+    let mut rng = XorShift(0x1234_5678);
+    for _ in 0..500 {
+        let crc = rng.next();
+        let len = rng.next() % 2000;
+        let more = rng.bytes(len as usize);
+        assert_eq!(context.resume(crc, &more), zlib_crc(crc, &more));
+    }
+    let whole = rng.bytes(10_000);
+    let mut zlib = 0;
+    let mut digest = Crc32Digest::new(&context);
+    for piece in whole.chunks(777) {
+        zlib = zlib_crc(zlib, piece);
+        digest.update(piece);
+        assert_eq!(digest.finalize(), zlib);
+    }
+}